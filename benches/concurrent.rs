@@ -0,0 +1,82 @@
+// NOTE: run this benchmark with `cargo bench --bench concurrent 2> /dev/null`
+// and then open `target/criterion/report/index.html`.
+//
+// The logger configuration is selected using the `CONCURRENT_CONFIG`
+// environment variable, to compare the configurations run, for example:
+// ```
+// cargo bench --bench concurrent -- --save-baseline locked 2> /dev/null
+// CONCURRENT_CONFIG=unlocked cargo bench --bench concurrent -- --baseline locked 2> /dev/null
+// ```
+// Supported configurations:
+//  * `locked` (default): write each record while holding the `Stderr` lock.
+//  * `unlocked`: see `Config::with_unlocked_stderr`.
+//  * `batch`: see `Config::with_batch`.
+
+use std::sync::{Barrier, Once};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use log::info;
+use std_logger::BatchPolicy;
+
+/// Number of threads logging concurrently.
+const THREADS: [u64; 3] = [1, 8, 64];
+
+/// Logs records from multiple threads at the same time, all writing to
+/// standard error.
+fn concurrent(c: &mut Criterion) {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        let config = std_logger::Config::logfmt().with_call_location(false);
+        match std::env::var("CONCURRENT_CONFIG").as_deref() {
+            Ok("locked") | Err(_) => config.init(),
+            Ok("unlocked") => config.with_unlocked_stderr(true).init(),
+            Ok("batch") => config
+                .with_batch(BatchPolicy::new(64, Duration::from_millis(10)))
+                .init(),
+            Ok(config) => panic!("unknown configuration: {}", config),
+        }
+    });
+
+    let mut group = c.benchmark_group("concurrent");
+    for threads in THREADS {
+        group.throughput(Throughput::Elements(threads));
+        group.bench_with_input(
+            BenchmarkId::new("threads", threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let barrier = Barrier::new(threads as usize);
+                    thread::scope(|s| {
+                        let threads: Vec<_> = (0..threads)
+                            .map(|n| {
+                                let barrier = &barrier;
+                                s.spawn(move || {
+                                    barrier.wait();
+                                    let start = Instant::now();
+                                    for i in 0..iters {
+                                        info!(thread = n, iteration = i; "concurrent record");
+                                    }
+                                    // Include writing the remaining batched records.
+                                    log::logger().flush();
+                                    start.elapsed()
+                                })
+                            })
+                            .collect();
+                        // The slowest thread determines the time it took.
+                        threads
+                            .into_iter()
+                            .map(|t| t.join().unwrap())
+                            .max()
+                            .unwrap_or(Duration::ZERO)
+                    })
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, concurrent);
+criterion_main!(benches);
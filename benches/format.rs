@@ -0,0 +1,99 @@
+// NOTE: run this benchmark with `cargo bench --bench format` and then open
+// `target/criterion/report/index.html`.
+
+use std::io::IoSlice;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use log::{kv, Level, Record};
+
+/// Formats a typical request record, which fits in the inline buffer.
+fn request(c: &mut Criterion) {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("method", &"GET"),
+        ("path", &"/api/v1/users/123"),
+        ("status_code", &200),
+        ("body_size", &1024),
+        ("response_time", &0.0123),
+        ("user_agent", &"Mozilla/5.0 (X11; Linux x86_64)"),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("request handled"))
+        .level(Level::Info)
+        .target("request")
+        .module_path(Some("app::http"))
+        .key_values(kvs)
+        .build();
+    bench_record(c, "request", &record);
+}
+
+/// Formats a record with only a message, for which formatting the timestamp is
+/// most of the work.
+fn tiny(c: &mut Criterion) {
+    let record = Record::builder()
+        .args(format_args!("hi"))
+        .level(Level::Info)
+        .target("app")
+        .module_path(Some("app"))
+        .build();
+    bench_record(c, "tiny", &record);
+}
+
+/// Formats a record with ten key-values of different types.
+fn key_values(c: &mut Criterion) {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("user_id", &123),
+        ("user_name", &"Thomas"),
+        ("is_admin", &false),
+        ("ratio", &0.75),
+        ("items", &10_000),
+        ("region", &"eu-west-1"),
+        ("retry", &true),
+        ("attempt", &3),
+        ("query", &"SELECT * FROM users WHERE id = $1"),
+        ("elapsed", &-12),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("processed user"))
+        .level(Level::Info)
+        .target("app")
+        .module_path(Some("app::users"))
+        .key_values(kvs)
+        .build();
+    bench_record(c, "key_values", &record);
+}
+
+/// Formats a record that is larger than the inline buffer.
+fn large(c: &mut Criterion) {
+    let value = "a".repeat(4096);
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("value", &&*value)];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("large record"))
+        .level(Level::Info)
+        .target("app")
+        .module_path(Some("app"))
+        .key_values(kvs)
+        .build();
+    bench_record(c, "large", &record);
+}
+
+fn bench_record(c: &mut Criterion, name: &str, record: &Record) {
+    let len = std_logger::_format_with(record, false, total_len);
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Bytes(len as u64));
+    for (format, json) in [("logfmt", false), ("json", true)] {
+        group.bench_with_input(BenchmarkId::from_parameter(format), &json, |b, json| {
+            b.iter(|| std_logger::_format_with(black_box(record), *json, total_len))
+        });
+    }
+    group.finish();
+}
+
+fn total_len(bufs: &[IoSlice<'_>]) -> usize {
+    bufs.iter().map(|buf| black_box(buf).len()).sum()
+}
+
+criterion_group!(format, tiny, key_values, request, large);
+criterion_main!(format);
@@ -0,0 +1,48 @@
+// NOTE: run this benchmark with `cargo bench --bench parser` and then open
+// `target/criterion/report/index.html`.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+/// Size of the generated corpus.
+const CORPUS_SIZE: usize = 100 * 1024 * 1024;
+
+/// Parses a 100 MB corpus of logfmt records.
+fn parse(c: &mut Criterion) {
+    let corpus = corpus();
+    let mut group = c.benchmark_group("parser");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+    group.bench_function("100MB", |b| {
+        b.iter(|| {
+            let mut n = 0;
+            for record in std_logger_parser::parse(&*corpus) {
+                let _ = record.expect("failed to parse record");
+                n += 1;
+            }
+            n
+        })
+    });
+    group.finish();
+}
+
+/// Generates a corpus of a mix of typical records.
+fn corpus() -> Vec<u8> {
+    const RECORDS: [&str; 4] = [
+        "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Hello world\" target=\"app\" module=\"app\"\n",
+        "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Request handled\" target=\"request\" module=\"app::http\" method=\"GET\" path=\"/api/v1/users/123\" status_code=200 body_size=1024 response_time=0.0123\n",
+        "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"WARN\" msg=\"Slow query: \\\"SELECT * FROM users\\\"\" target=\"db\" module=\"app::db\" took_ms=1200 retry=true\n",
+        "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"DEBUG\" msg=\"with file\" target=\"app\" module=\"app\" user_id=123 file=\"src/main.rs:12\"\n",
+    ];
+
+    let mut corpus = Vec::with_capacity(CORPUS_SIZE + 256);
+    for record in RECORDS.iter().cycle() {
+        if corpus.len() >= CORPUS_SIZE {
+            break;
+        }
+        corpus.extend_from_slice(record.as_bytes());
+    }
+    corpus
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -10,7 +10,7 @@ const MSG: &[u8] = b"2020-01-06T12:00:09.514249Z [REQUEST] simple: url = `/not_f
 fn via_std_lib(c: &mut Criterion) {
     c.bench_function("via_std_lib", |b| {
         b.iter(|| {
-            stdout().write(MSG).expect("write error");
+            stdout().write_all(MSG).expect("write error");
         })
     });
 }
@@ -0,0 +1,286 @@
+//! Pretty-prints logfmt logs created by std-logger, see [`USAGE`].
+
+use std::env;
+use std::fs::File;
+use std::io::{self, IsTerminal, Read, Write};
+use std::process::ExitCode;
+
+use log::LevelFilter;
+use std_logger_parser::{ParseErrorKind, ParserOptions};
+
+mod print;
+mod time;
+#[cfg(feature = "tui")]
+mod tui;
+
+use print::{Columns, Printer, Theme};
+use time::TimeFormat;
+
+const USAGE: &str = "Usage: std-logger-cat [OPTIONS] [FILE]...
+
+Pretty-prints logfmt logs created by std-logger. Reads from standard in if no
+FILE, or `-`, is given. Lines that can't be parsed are printed as is.
+
+Options:
+  -l, --level <LEVEL>  Only show records with LEVEL or a higher severity, one
+                       of error, warn, info, debug or trace (default: trace).
+  -k, --keys <KEYS>    Only show the key-values with the comma separated KEYS.
+  -p, --priority <KEYS>
+                       Show the key-values with the comma separated KEYS
+                       first, in the given order, followed by the other
+                       key-values sorted by key.
+  -a, --align          Align the target, message and values into columns.
+  -t, --time <FORMAT>  Format of the timestamps: utc (as logged), local (in
+                       the local time zone, e.g. `14:03:05.123`) or relative
+                       (to now, e.g. `2m ago`) (default: utc).
+  -s, --search <KEY[=VALUE]>
+                       Only show records that have KEY, with VALUE if given.
+                       Can be used multiple times to search for all of them.
+  -f, --follow         Keep reading when reaching the end of the FILE,
+                       similar to `tail -f`.
+      --tui            Show the records in an interactive viewer, which
+                       follows new records and allows changing the level
+                       and searches, and showing a record's details. Press
+                       `q` to quit. Requires the `tui` feature.
+      --color <WHEN>   Color the output: auto, always or never (default: auto).
+      --colors <SPEC>  Colors to use, overwrites `LOG_COLORS`, see below.
+  -h, --help           Print this help message.
+
+Colors:
+  The colors can be set using `--colors` or the `LOG_COLORS` environment
+  variable, using a colon separated list of themes and NAME=SGR pairs, e.g.
+  `color-blind:error=1;35:key=36`. The themes are `default` and `color-blind`,
+  which doesn't rely on distinguishing red and green. NAME is a level (error,
+  warn, info, debug or trace), `key` or `dim` (timestamp, target and file) and
+  SGR the ANSI SGR parameters to use, e.g. `1;31` for bold red.
+";
+
+fn main() -> ExitCode {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("std-logger-cat: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.help {
+        print!("{USAGE}");
+        return ExitCode::SUCCESS;
+    }
+
+    match run(args) {
+        Ok(()) => ExitCode::SUCCESS,
+        // Output is closed, e.g. when piping into `head`.
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("std-logger-cat: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Parsed command line arguments.
+#[derive(Debug)]
+struct Args {
+    files: Vec<String>,
+    level: LevelFilter,
+    keys: Option<Vec<String>>,
+    priority: Vec<String>,
+    align: bool,
+    time: TimeFormat,
+    search: Vec<(String, Option<String>)>,
+    follow: bool,
+    color: Color,
+    theme: Option<Theme>,
+    tui: bool,
+    help: bool,
+}
+
+/// When to color the output.
+#[derive(Debug)]
+enum Color {
+    /// Only if standard out is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl Args {
+    fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Args, String> {
+        let mut parsed = Args {
+            files: Vec::new(),
+            level: LevelFilter::Trace,
+            keys: None,
+            priority: Vec::new(),
+            align: false,
+            time: TimeFormat::Utc,
+            search: Vec::new(),
+            follow: false,
+            color: Color::Auto,
+            theme: None,
+            tui: false,
+            help: false,
+        };
+        while let Some(arg) = args.next() {
+            // Support both `--level warn` and `--level=warn`.
+            let (arg, value) = match arg.split_once('=') {
+                Some((arg, value)) if arg.starts_with("--") => {
+                    (arg.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg, None),
+            };
+            match arg.as_str() {
+                "-h" | "--help" => parsed.help = true,
+                "-f" | "--follow" => parsed.follow = true,
+                "-a" | "--align" => parsed.align = true,
+                "--tui" => parsed.tui = true,
+                "-l" | "--level" => {
+                    let value = option_value(&arg, value, &mut args)?;
+                    parsed.level = value
+                        .parse()
+                        .map_err(|_| format!("invalid level `{value}`"))?;
+                }
+                "-k" | "--keys" => {
+                    let value = option_value(&arg, value, &mut args)?;
+                    let keys = parsed.keys.get_or_insert_with(Vec::new);
+                    keys.extend(value.split(',').map(str::to_owned));
+                }
+                "-t" | "--time" => {
+                    parsed.time = match option_value(&arg, value, &mut args)?.as_str() {
+                        "utc" => TimeFormat::Utc,
+                        "local" => TimeFormat::Local,
+                        "relative" => TimeFormat::Relative,
+                        value => return Err(format!("invalid time format `{value}`")),
+                    };
+                }
+                "-p" | "--priority" => {
+                    let value = option_value(&arg, value, &mut args)?;
+                    parsed.priority.extend(value.split(',').map(str::to_owned));
+                }
+                "-s" | "--search" => {
+                    let value = option_value(&arg, value, &mut args)?;
+                    parsed.search.push(parse_search(&value));
+                }
+                "--color" => {
+                    parsed.color = match option_value(&arg, value, &mut args)?.as_str() {
+                        "auto" => Color::Auto,
+                        "always" => Color::Always,
+                        "never" => Color::Never,
+                        value => return Err(format!("invalid color `{value}`")),
+                    };
+                }
+                "--colors" => {
+                    let value = option_value(&arg, value, &mut args)?;
+                    parsed.theme = Some(Theme::parse(&value)?);
+                }
+                "--" => parsed.files.extend(args.by_ref()),
+                "-" => parsed.files.push(arg),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{arg}`")),
+                _ => parsed.files.push(arg),
+            }
+        }
+        if parsed.follow && parsed.files.len() > 1 {
+            return Err("`--follow` can only be used with a single file".to_owned());
+        }
+        Ok(parsed)
+    }
+}
+
+/// Returns the value for option `arg`, either passed as `--arg=value` or as
+/// the next argument.
+fn option_value<I: Iterator<Item = String>>(
+    arg: &str,
+    value: Option<String>,
+    args: &mut I,
+) -> Result<String, String> {
+    value
+        .or_else(|| args.next())
+        .ok_or_else(|| format!("missing value for `{arg}`"))
+}
+
+/// Parse a search in the form `KEY[=VALUE]`.
+fn parse_search(search: &str) -> (String, Option<String>) {
+    match search.split_once('=') {
+        Some((key, value)) => (key.to_owned(), Some(value.to_owned())),
+        None => (search.to_owned(), None),
+    }
+}
+
+fn run(args: Args) -> io::Result<()> {
+    let color = match args.color {
+        Color::Auto => io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none(),
+        Color::Always => true,
+        Color::Never => false,
+    };
+    let theme = match (args.theme, env::var("LOG_COLORS")) {
+        (Some(theme), _) => theme,
+        (None, Ok(spec)) => Theme::parse(&spec).map_err(|err| {
+            io::Error::new(io::ErrorKind::InvalidInput, format!("`LOG_COLORS`: {err}"))
+        })?,
+        (None, Err(_)) => Theme::default(),
+    };
+    let mut printer = Printer {
+        color: color.then_some(theme),
+        level: args.level,
+        keys: args.keys,
+        priority: args.priority,
+        align: args.align.then(Columns::default),
+        time: args.time,
+        search: args.search,
+    };
+    let options = ParserOptions::new().follow(args.follow);
+    if args.tui {
+        return run_tui(printer, options, args.files);
+    }
+    // NOTE: standard out is line buffered and the printer writes an entire
+    // record at a time.
+    let mut output = io::stdout().lock();
+
+    if args.files.is_empty() {
+        return print(&mut printer, options, io::stdin().lock(), &mut output);
+    }
+    for path in args.files {
+        if path == "-" {
+            print(&mut printer, options, io::stdin().lock(), &mut output)?;
+        } else {
+            let file = File::open(&path).map_err(|err| {
+                io::Error::new(err.kind(), format!("failed to open `{path}`: {err}"))
+            })?;
+            print(&mut printer, options, file, &mut output)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(feature = "tui")]
+fn run_tui(printer: Printer, options: ParserOptions, files: Vec<String>) -> io::Result<()> {
+    tui::run(printer, options, files)
+}
+
+#[cfg(not(feature = "tui"))]
+fn run_tui(_: Printer, _: ParserOptions, _: Vec<String>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "`--tui` requires the `tui` feature",
+    ))
+}
+
+/// Print all records in `input`.
+fn print<R: Read, W: Write>(
+    printer: &mut Printer,
+    options: ParserOptions,
+    input: R,
+    output: &mut W,
+) -> io::Result<()> {
+    for record in options.parse(input) {
+        match record {
+            Ok(record) => printer.print(output, &record)?,
+            Err(err) => match (err.line, err.kind) {
+                (_, ParseErrorKind::Io(err)) => return Err(err),
+                (Some(line), _) => printer.print_invalid(output, &line)?,
+                (None, _) => {}
+            },
+        }
+    }
+    Ok(())
+}
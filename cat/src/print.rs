@@ -0,0 +1,341 @@
+//! Pretty-printing of records.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+use std::time::SystemTime;
+
+use log::{Level, LevelFilter};
+use std_logger_parser::{format_timestamp, Record, Value};
+
+use crate::time::TimeFormat;
+
+const RESET: &str = "\x1b[0m";
+
+/// Fields longer than this many characters are not aligned, to not add a lot
+/// of spaces to all following records.
+const MAX_ALIGN: usize = 40;
+
+/// Colors used by the [`Printer`] and the viewer (`--tui`), as ANSI SGR
+/// parameters, e.g. `1;31`.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Theme {
+    error: String,
+    warn: String,
+    info: String,
+    debug: String,
+    trace: String,
+    /// Keys of the key-values.
+    pub(crate) key: String,
+    /// Timestamp, target and file.
+    pub(crate) dim: String,
+}
+
+impl Theme {
+    /// Default theme, using red, yellow, green, blue and magenta for the
+    /// levels.
+    pub(crate) fn default() -> Theme {
+        Theme::from_sgr(["1;31", "33", "32", "34", "35", "36", "2"])
+    }
+
+    /// Theme that doesn't rely on distinguishing red and green, using colors
+    /// from the Okabe-Ito palette: (bold) vermillion, orange, blue, sky blue
+    /// and grey for the levels.
+    pub(crate) fn color_blind() -> Theme {
+        Theme::from_sgr([
+            "1;38;5;166",
+            "38;5;214",
+            "38;5;32",
+            "38;5;117",
+            "38;5;245",
+            "38;5;75",
+            "2",
+        ])
+    }
+
+    /// Create a theme from the SGR parameters, e.g. `1;31`, for the error,
+    /// warn, info, debug and trace levels, the keys and dimmed text.
+    fn from_sgr(sgr: [&str; 7]) -> Theme {
+        let [error, warn, info, debug, trace, key, dim] = sgr.map(str::to_owned);
+        Theme {
+            error,
+            warn,
+            info,
+            debug,
+            trace,
+            key,
+            dim,
+        }
+    }
+
+    /// Parse a theme, as used by the `--colors` option and `LOG_COLORS`
+    /// environment variable, e.g. `color-blind:error=1;35:key=36`.
+    ///
+    /// The specification is a colon separated list of themes (`default` or
+    /// `color-blind`) and `NAME=SGR` pairs, where `NAME` is a level (`error`,
+    /// `warn`, `info`, `debug` or `trace`), `key` or `dim` and `SGR` the ANSI
+    /// SGR parameters to use. Later items overwrite earlier ones.
+    pub(crate) fn parse(spec: &str) -> Result<Theme, String> {
+        let mut theme = Theme::default();
+        for item in spec.split(':').filter(|item| !item.is_empty()) {
+            let (name, sgr) = match item.split_once('=') {
+                Some(pair) => pair,
+                None => {
+                    theme = match item {
+                        "default" => Theme::default(),
+                        "color-blind" => Theme::color_blind(),
+                        _ => return Err(format!("unknown color theme `{item}`")),
+                    };
+                    continue;
+                }
+            };
+            if sgr.is_empty() || !sgr.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                return Err(format!("invalid color `{sgr}` for `{name}`"));
+            }
+            let color = match name {
+                "error" => &mut theme.error,
+                "warn" => &mut theme.warn,
+                "info" => &mut theme.info,
+                "debug" => &mut theme.debug,
+                "trace" => &mut theme.trace,
+                "key" => &mut theme.key,
+                "dim" => &mut theme.dim,
+                _ => return Err(format!("unknown color name `{name}`")),
+            };
+            *color = sgr.to_owned();
+        }
+        Ok(theme)
+    }
+
+    pub(crate) fn level(&self, level: Level) -> &str {
+        match level {
+            Level::Error => &self.error,
+            Level::Warn => &self.warn,
+            Level::Info => &self.info,
+            Level::Debug => &self.debug,
+            Level::Trace => &self.trace,
+        }
+    }
+}
+
+/// Prints records in a human readable format, e.g.
+/// `2021-02-23T13:15:48.624447Z INFO  my_app: Hello world user="Thomas" id=123`.
+///
+/// Values containing new lines, e.g. backtraces, are printed on the lines
+/// following the record.
+#[derive(Debug)]
+pub(crate) struct Printer {
+    /// Colors to use, if the output is colored.
+    pub(crate) color: Option<Theme>,
+    /// Only print records with this level, or a higher severity.
+    pub(crate) level: LevelFilter,
+    /// Only print these key-values, in the given order, if set.
+    pub(crate) keys: Option<Vec<String>>,
+    /// Print these key-values first, in the given order, before the other
+    /// key-values (sorted by key).
+    pub(crate) priority: Vec<String>,
+    /// Align the fields into columns, if set.
+    pub(crate) align: Option<Columns>,
+    /// How to print the timestamp of the records.
+    pub(crate) time: TimeFormat,
+    /// Only print records that have all these keys, with the value if set.
+    pub(crate) search: Vec<(String, Option<String>)>,
+}
+
+/// Widths of the columns seen so far, see [`Printer::align`].
+#[derive(Debug, Default)]
+pub(crate) struct Columns {
+    target: usize,
+    msg: usize,
+    /// Key -> width of the value.
+    values: HashMap<String, usize>,
+}
+
+impl Printer {
+    /// Print `record` to `output`, unless it's filtered.
+    pub(crate) fn print<W: Write>(&mut self, output: &mut W, record: &Record) -> io::Result<()> {
+        if !self.shows(record) {
+            return Ok(());
+        }
+
+        let mut buf = String::with_capacity(256);
+        if let Some(timestamp) = record.timestamp {
+            let timestamp = self.time.format(timestamp, SystemTime::now());
+            self.paint(&mut buf, |theme| &theme.dim, &timestamp);
+            buf.push(' ');
+        }
+        self.paint(
+            &mut buf,
+            |theme| theme.level(record.level),
+            &format!("{:<5}", record.level),
+        );
+        buf.push(' ');
+        if !record.target.is_empty() {
+            self.paint(&mut buf, |theme| &theme.dim, &record.target);
+            buf.push_str(": ");
+            let padding = self.padding(|c| &mut c.target, &record.target);
+            pad(&mut buf, padding);
+        }
+        buf.push_str(&record.msg);
+        // Padding is only added before the next field, to not end the line
+        // with spaces.
+        let mut padding = self.padding(|c| &mut c.msg, &record.msg);
+
+        let mut multi_line = Vec::new();
+        let mut value_buf = String::new();
+        for (key, value) in self.key_values(record) {
+            if let Value::String(value) = value {
+                if value.contains('\n') {
+                    multi_line.push((key, value));
+                    continue;
+                }
+            }
+            pad(&mut buf, padding);
+            buf.push(' ');
+            self.paint(&mut buf, |theme| &theme.key, key);
+            buf.push('=');
+            value_buf.clear();
+            write_value(&mut value_buf, value);
+            buf.push_str(&value_buf);
+            padding = self.padding(|c| c.values.entry(key.to_owned()).or_default(), &value_buf);
+        }
+
+        if let Some((file, line)) = &record.file {
+            pad(&mut buf, padding);
+            buf.push(' ');
+            self.paint(&mut buf, |theme| &theme.dim, &format!("({file}:{line})"));
+        }
+        buf.push('\n');
+
+        for (key, value) in multi_line {
+            buf.push_str("  ");
+            self.paint(&mut buf, |theme| &theme.key, key);
+            buf.push_str(":\n");
+            for line in value.lines() {
+                buf.push_str("    ");
+                buf.push_str(line);
+                buf.push('\n');
+            }
+        }
+        output.write_all(buf.as_bytes())
+    }
+
+    /// Print a `line` that couldn't be parsed as is.
+    pub(crate) fn print_invalid<W: Write>(&self, output: &mut W, line: &[u8]) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(line.len() + 1);
+        buf.extend_from_slice(line);
+        buf.push(b'\n');
+        output.write_all(&buf)
+    }
+
+    /// Returns `true` if `record` passes the level filter and matches all
+    /// searches, i.e. if it's printed.
+    pub(crate) fn shows(&self, record: &Record) -> bool {
+        record.level <= self.level && self.matches(record)
+    }
+
+    /// Returns `true` if `record` matches all searches. Searching for `msg` or
+    /// `target` matches the message or target of the record.
+    fn matches(&self, record: &Record) -> bool {
+        self.search.iter().all(|(key, want)| {
+            let got = match key.as_str() {
+                "msg" => Cow::Borrowed(&*record.msg),
+                "target" => Cow::Borrowed(&*record.target),
+                key => match record.key_values.get(key) {
+                    Some(Value::String(value)) => Cow::Borrowed(value.as_str()),
+                    Some(value) => {
+                        let mut buf = String::new();
+                        write_value(&mut buf, value);
+                        Cow::Owned(buf)
+                    }
+                    None => return false,
+                },
+            };
+            want.as_ref().is_none_or(|want| *want == *got)
+        })
+    }
+
+    /// Returns the key-values of `record` to print.
+    fn key_values<'r>(&self, record: &'r Record) -> Vec<(&'r str, &'r Value)> {
+        match &self.keys {
+            Some(keys) => keys
+                .iter()
+                .filter_map(|key| record.key_values.get_key_value(key.as_str()))
+                .map(|(key, value)| (key.as_str(), value))
+                .collect(),
+            None => {
+                let mut key_values = record
+                    .key_values
+                    .iter()
+                    .map(|(key, value)| (key.as_str(), value))
+                    .collect::<Vec<_>>();
+                // Keys with a priority first, in the order of `priority`.
+                let priority = |key: &str| {
+                    let index = self.priority.iter().position(|k| k == key);
+                    index.unwrap_or(self.priority.len())
+                };
+                key_values.sort_unstable_by_key(|(key, _)| (priority(key), *key));
+                key_values
+            }
+        }
+    }
+
+    /// Returns the number of spaces to add after `text` to align it with the
+    /// column returned by `column`, if enabled.
+    fn padding<F>(&mut self, column: F, text: &str) -> usize
+    where
+        F: FnOnce(&mut Columns) -> &mut usize,
+    {
+        let Some(columns) = &mut self.align else {
+            return 0;
+        };
+        let width = column(columns);
+        let len = text.chars().count();
+        if len <= MAX_ALIGN && len > *width {
+            *width = len;
+        }
+        width.saturating_sub(len)
+    }
+
+    /// Write `text` to `buf`, colored using the color returned by `color` if
+    /// enabled.
+    fn paint<F>(&self, buf: &mut String, color: F, text: &str)
+    where
+        F: FnOnce(&Theme) -> &str,
+    {
+        if let Some(theme) = &self.color {
+            let _ = write!(buf, "\x1b[{}m{text}{RESET}", color(theme));
+        } else {
+            buf.push_str(text);
+        }
+    }
+}
+
+/// Write `n` spaces to `buf`.
+fn pad(buf: &mut String, n: usize) {
+    buf.extend(std::iter::repeat_n(' ', n));
+}
+
+/// Write `value`, quoting strings if they're empty or contain a space, quote,
+/// `=` or control character.
+pub(crate) fn write_value(buf: &mut String, value: &Value) {
+    let _ = match value {
+        Value::Null => Ok(()),
+        Value::Bool(b) => write!(buf, "{b}"),
+        Value::Int(i) => write!(buf, "{i}"),
+        Value::Float(f) => write!(buf, "{f}"),
+        Value::String(s) if needs_quotes(s) => write!(buf, "{s:?}"),
+        Value::String(s) => write!(buf, "{s}"),
+        Value::Timestamp(timestamp) => write!(buf, "{}", format_timestamp(*timestamp)),
+        Value::Duration(duration) => write!(buf, "{duration:?}"),
+        Value::Bytes(bytes) => write!(buf, "{bytes}B"),
+    };
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == ' ' || c == '"' || c == '=' || c.is_control())
+}
@@ -0,0 +1,115 @@
+//! Formatting of the timestamp of records, see [`TimeFormat`].
+
+use std::time::{Duration, SystemTime};
+
+use std_logger_parser::format_timestamp;
+
+/// How the timestamp of a record is printed.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum TimeFormat {
+    /// RFC 3339 in UTC, as logged, e.g. `2021-02-23T13:15:48.624447Z`.
+    Utc,
+    /// Local time, e.g. `14:15:48.624`, including the date if it's not today,
+    /// e.g. `2021-02-23 14:15:48.624`.
+    Local,
+    /// Relative to now, e.g. `2m ago`.
+    Relative,
+}
+
+impl TimeFormat {
+    /// Format `timestamp`, using `now` as the current time.
+    pub(crate) fn format(self, timestamp: SystemTime, now: SystemTime) -> String {
+        match self {
+            TimeFormat::Utc => format_timestamp(timestamp),
+            TimeFormat::Local => format_local(timestamp, now),
+            TimeFormat::Relative => format_relative(timestamp, now),
+        }
+    }
+}
+
+/// Format `timestamp` in local time, falling back to UTC if the local time
+/// can't be determined.
+fn format_local(timestamp: SystemTime, now: SystemTime) -> String {
+    let (Some(time), Some(today)) = (local_time(timestamp), local_time(now)) else {
+        return format_timestamp(timestamp);
+    };
+    let millis = unix_time(timestamp).subsec_millis();
+    let time_of_day = format!(
+        "{:02}:{:02}:{:02}.{millis:03}",
+        time.hour, time.minute, time.second
+    );
+    if (time.year, time.month, time.day) == (today.year, today.month, today.day) {
+        time_of_day
+    } else {
+        format!(
+            "{:04}-{:02}-{:02} {time_of_day}",
+            time.year, time.month, time.day
+        )
+    }
+}
+
+/// Format `timestamp` relative to `now`, using the largest fitting unit, e.g.
+/// `2m ago` or `in 5s` for timestamps in the future.
+fn format_relative(timestamp: SystemTime, now: SystemTime) -> String {
+    let (elapsed, future) = match now.duration_since(timestamp) {
+        Ok(elapsed) => (elapsed, false),
+        Err(err) => (err.duration(), true),
+    };
+    let secs = elapsed.as_secs();
+    let amount = match secs {
+        0 => return "now".to_owned(),
+        1..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86_399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86_400),
+    };
+    if future {
+        format!("in {amount}")
+    } else {
+        format!("{amount} ago")
+    }
+}
+
+/// Returns the time since the Unix epoch, zero for times before it.
+fn unix_time(timestamp: SystemTime) -> Duration {
+    timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+}
+
+/// Date and time in the local time zone.
+#[derive(Debug)]
+struct LocalTime {
+    year: i32,
+    month: i32,
+    day: i32,
+    hour: i32,
+    minute: i32,
+    second: i32,
+}
+
+/// Returns `timestamp` in the local time zone, set using the `TZ` environment
+/// variable or the system's configuration.
+#[cfg(unix)]
+fn local_time(timestamp: SystemTime) -> Option<LocalTime> {
+    let secs = libc::time_t::try_from(unix_time(timestamp).as_secs()).ok()?;
+    // SAFETY: all zeros is a valid `tm` and `localtime_r` only writes to it.
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&secs, &mut tm) }.is_null() {
+        return None;
+    }
+    Some(LocalTime {
+        year: tm.tm_year + 1900,
+        month: tm.tm_mon + 1,
+        day: tm.tm_mday,
+        hour: tm.tm_hour,
+        minute: tm.tm_min,
+        second: tm.tm_sec,
+    })
+}
+
+/// The local time zone is only supported on Unix.
+#[cfg(not(unix))]
+fn local_time(_: SystemTime) -> Option<LocalTime> {
+    None
+}
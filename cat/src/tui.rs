@@ -0,0 +1,462 @@
+//! Interactive terminal viewer, see the `--tui` option.
+//!
+//! The input is parsed on a separate thread, which sends the records to the
+//! viewer, so that following a file (`--follow`) or a slow standard in doesn't
+//! block the interface. Filtering and formatting is done by the [`Printer`].
+
+use std::fs::File;
+use std::io::{self, IsTerminal, Read};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use log::LevelFilter;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use std_logger_parser::{format_timestamp, ParseErrorKind, ParserOptions, Record, Value};
+
+use crate::parse_search;
+use crate::print::{write_value, Printer, Theme};
+
+const HELP: &str = "q quit  j/k move  g/G top/bottom  f follow  1-5 level  / search  enter details";
+
+/// Line of the input.
+enum Entry {
+    Record(Record),
+    /// Line that couldn't be parsed.
+    Invalid(String),
+}
+
+/// Message from the reading thread.
+enum Message {
+    Entry(Entry),
+    /// Reading the input failed.
+    Error(String),
+}
+
+/// State of the viewer.
+struct App {
+    /// Printer used to format the entries, without colors.
+    printer: Printer,
+    /// Colors to use, taken from `printer`.
+    theme: Option<Theme>,
+    entries: Vec<Entry>,
+    /// Indices into `entries` of the entries shown, based on the filters of
+    /// `printer`.
+    shown: Vec<usize>,
+    list: ListState,
+    /// Keep the last record selected when new records are read.
+    follow: bool,
+    /// Search being typed, if any.
+    search_input: Option<String>,
+    /// Show the details of the selected record.
+    details: bool,
+    error: Option<String>,
+}
+
+/// Run the viewer on the terminal, reading the records from `files` (or
+/// standard in if empty).
+///
+/// The colors of `printer` are applied as styles of the viewer, rather than
+/// writing the escape codes.
+pub(crate) fn run(
+    mut printer: Printer,
+    options: ParserOptions,
+    files: Vec<String>,
+) -> io::Result<()> {
+    if !io::stdout().is_terminal() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "`--tui` requires standard out to be a terminal",
+        ));
+    }
+    let (sender, receiver) = mpsc::channel();
+    let _ = thread::Builder::new()
+        .name("reader".into())
+        .spawn(move || read(options, files, &sender))?;
+
+    let mut terminal = ratatui::try_init()?;
+    let theme = printer.color.take();
+    let mut app = App {
+        printer,
+        theme,
+        entries: Vec::new(),
+        shown: Vec::new(),
+        list: ListState::default(),
+        follow: true,
+        search_input: None,
+        details: false,
+        error: None,
+    };
+    let result = app.run(&mut terminal, &receiver);
+    ratatui::restore();
+    result
+}
+
+/// Read all entries from `files`, or standard in if empty, sending them to
+/// the viewer.
+fn read(options: ParserOptions, files: Vec<String>, sender: &Sender<Message>) {
+    if files.is_empty() {
+        read_entries(options, io::stdin().lock(), sender);
+        return;
+    }
+    for path in files {
+        if path == "-" {
+            read_entries(options, io::stdin().lock(), sender);
+            continue;
+        }
+        match File::open(&path) {
+            Ok(file) => read_entries(options, file, sender),
+            Err(err) => {
+                let _ = sender.send(Message::Error(format!("failed to open `{path}`: {err}")));
+                return;
+            }
+        }
+    }
+}
+
+fn read_entries<R: Read>(options: ParserOptions, input: R, sender: &Sender<Message>) {
+    for record in options.parse(input) {
+        let message = match record {
+            Ok(record) => Message::Entry(Entry::Record(record)),
+            Err(err) => match (err.line, err.kind) {
+                (_, ParseErrorKind::Io(err)) => Message::Error(err.to_string()),
+                (Some(line), _) => {
+                    Message::Entry(Entry::Invalid(String::from_utf8_lossy(&line).into_owned()))
+                }
+                (None, _) => continue,
+            },
+        };
+        if sender.send(message).is_err() {
+            // Viewer is closed.
+            return;
+        }
+    }
+}
+
+impl App {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        receiver: &Receiver<Message>,
+    ) -> io::Result<()> {
+        loop {
+            self.receive(receiver);
+            let _ = terminal.draw(|frame| self.draw(frame))?;
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !self.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Add the entries read so far.
+    fn receive(&mut self, receiver: &Receiver<Message>) {
+        loop {
+            match receiver.try_recv() {
+                Ok(Message::Entry(entry)) => {
+                    if self.shows(&entry) {
+                        self.shown.push(self.entries.len());
+                    }
+                    self.entries.push(entry);
+                }
+                Ok(Message::Error(err)) => self.error = Some(err),
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => break,
+            }
+        }
+        if self.follow && !self.shown.is_empty() {
+            self.list.select(Some(self.shown.len() - 1));
+        }
+    }
+
+    /// Returns `true` if `entry` passes the filters. Invalid lines are only
+    /// shown if not searching.
+    fn shows(&self, entry: &Entry) -> bool {
+        match entry {
+            Entry::Record(record) => self.printer.shows(record),
+            Entry::Invalid(_) => self.printer.search.is_empty(),
+        }
+    }
+
+    /// Apply the changed filters to all entries, keeping the selected entry
+    /// selected if it's still shown.
+    fn refilter(&mut self) {
+        let selected = self
+            .list
+            .selected()
+            .and_then(|i| self.shown.get(i).copied());
+        self.shown = (0..self.entries.len())
+            .filter(|i| self.shows(&self.entries[*i]))
+            .collect();
+        let index = match selected {
+            Some(selected) => self.shown.partition_point(|i| *i < selected),
+            None => 0,
+        };
+        self.select(index);
+    }
+
+    /// Select the entry at `index` in `shown`, or the last one if it's out of
+    /// bounds.
+    fn select(&mut self, index: usize) {
+        if self.shown.is_empty() {
+            self.list.select(None);
+        } else {
+            self.list.select(Some(index.min(self.shown.len() - 1)));
+        }
+    }
+
+    /// Returns `false` if the viewer should be closed.
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
+            return false;
+        }
+        if let Some(input) = &mut self.search_input {
+            match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    let _ = input.pop();
+                }
+                KeyCode::Enter => {
+                    self.printer.search = input.split_whitespace().map(parse_search).collect();
+                    self.search_input = None;
+                    self.refilter();
+                }
+                KeyCode::Esc => self.search_input = None,
+                _ => {}
+            }
+            return true;
+        }
+        if self.details {
+            if matches!(key.code, KeyCode::Enter | KeyCode::Esc | KeyCode::Char('q')) {
+                self.details = false;
+            }
+            return true;
+        }
+
+        let selected = self.list.selected().unwrap_or(0);
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Down | KeyCode::Char('j') => self.move_to(selected.saturating_add(1)),
+            KeyCode::Up | KeyCode::Char('k') => self.move_to(selected.saturating_sub(1)),
+            KeyCode::PageDown => self.move_to(selected.saturating_add(20)),
+            KeyCode::PageUp => self.move_to(selected.saturating_sub(20)),
+            KeyCode::Home | KeyCode::Char('g') => self.move_to(0),
+            KeyCode::End | KeyCode::Char('G') => {
+                self.follow = true;
+                self.select(usize::MAX);
+            }
+            KeyCode::Char('f') => {
+                self.follow = !self.follow;
+                if self.follow {
+                    self.select(usize::MAX);
+                }
+            }
+            KeyCode::Char(c @ '1'..='5') => {
+                self.printer.level = match c {
+                    '1' => LevelFilter::Error,
+                    '2' => LevelFilter::Warn,
+                    '3' => LevelFilter::Info,
+                    '4' => LevelFilter::Debug,
+                    _ => LevelFilter::Trace,
+                };
+                self.refilter();
+            }
+            KeyCode::Char('/') => {
+                let search = self.printer.search.iter().map(|(key, value)| match value {
+                    Some(value) => format!("{key}={value}"),
+                    None => key.clone(),
+                });
+                self.search_input = Some(search.collect::<Vec<_>>().join(" "));
+            }
+            KeyCode::Enter => self.details = self.list.selected().is_some(),
+            _ => {}
+        }
+        true
+    }
+
+    /// Select the entry at `index`, stopping following.
+    fn move_to(&mut self, index: usize) {
+        self.follow = false;
+        self.select(index);
+    }
+
+    /// Returns the formatted `entry`, including the multi-line values.
+    fn format(&mut self, index: usize) -> String {
+        match &self.entries[index] {
+            Entry::Record(record) => {
+                let mut output = Vec::new();
+                let _ = self.printer.print(&mut output, record);
+                String::from_utf8_lossy(&output).into_owned()
+            }
+            Entry::Invalid(line) => line.clone(),
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [list_area, status_area] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        // Only format the entries that fit on the screen.
+        let height = usize::from(list_area.height);
+        let selected = self.list.selected().unwrap_or(0);
+        let offset = self
+            .list
+            .offset()
+            .min(selected)
+            .max((selected + 1).saturating_sub(height));
+        let end = self.shown.len().min(offset + height);
+        let items = (offset..end)
+            .map(|i| {
+                let index = self.shown[i];
+                let text = self.format(index);
+                let line = text.lines().next().unwrap_or_default().to_owned();
+                let style = match &self.entries[index] {
+                    Entry::Record(record) => self.style(|theme| theme.level(record.level)),
+                    Entry::Invalid(_) => self.style(|theme| &theme.dim),
+                };
+                ListItem::new(Line::styled(line, style))
+            })
+            .collect::<Vec<_>>();
+        let list = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        let mut state =
+            ListState::default().with_selected(self.list.selected().map(|s| s - offset));
+        frame.render_stateful_widget(list, list_area, &mut state);
+        *self.list.offset_mut() = offset;
+
+        let status = match (&self.search_input, &self.error) {
+            (Some(input), _) => format!("search (KEY[=VALUE] ...): {input}"),
+            (None, Some(err)) => format!("error: {err}"),
+            (None, None) => format!(
+                "{}/{} records | level: {} | search: {} | {} | {HELP}",
+                self.shown.len(),
+                self.entries.len(),
+                self.printer.level,
+                self.printer.search.len(),
+                if self.follow { "following" } else { "paused" },
+            ),
+        };
+        let status = Span::styled(status, Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_widget(Paragraph::new(Line::from(status)), status_area);
+
+        if self.details {
+            if let Some(index) = self
+                .list
+                .selected()
+                .and_then(|i| self.shown.get(i).copied())
+            {
+                self.draw_details(frame, list_area, index);
+            }
+        }
+    }
+
+    /// Draw the details of the entry at `index`: all fields and key-values on
+    /// separate lines, including multi-line values such as backtraces.
+    fn draw_details(&mut self, frame: &mut Frame, area: Rect, index: usize) {
+        let mut lines = Vec::new();
+        let key_style = self.style(|theme| &theme.key);
+        match &self.entries[index] {
+            Entry::Record(record) => {
+                let mut field = |name: &str, value: String| {
+                    let name = Span::styled(format!("{name}: "), key_style);
+                    let mut value = value.lines().map(str::to_owned);
+                    let first = value.next().unwrap_or_default();
+                    lines.push(Line::from(vec![name, Span::raw(first)]));
+                    lines.extend(value.map(|line| Line::raw(format!("    {line}"))));
+                };
+                if let Some(timestamp) = record.timestamp {
+                    field("ts", format_timestamp(timestamp));
+                }
+                field("lvl", record.level.to_string());
+                field("msg", record.msg.to_string());
+                field("target", record.target.to_string());
+                if let Some(module) = &record.module {
+                    field("module", module.to_string());
+                }
+                if let Some((file, line)) = &record.file {
+                    field("file", format!("{file}:{line}"));
+                }
+                let mut key_values = record.key_values.iter().collect::<Vec<_>>();
+                key_values.sort_unstable_by_key(|(key, _)| &***key);
+                for (key, value) in key_values {
+                    // Show strings as is, so multi-line values are readable.
+                    let mut buf = String::new();
+                    match value {
+                        Value::String(s) => buf.push_str(s),
+                        value => write_value(&mut buf, value),
+                    }
+                    field(key, buf);
+                }
+            }
+            Entry::Invalid(line) => lines.push(Line::raw(line.clone())),
+        }
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .title(" details (esc to close) ");
+        let details = Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: false });
+        frame.render_widget(Clear, area);
+        frame.render_widget(details, area);
+    }
+
+    /// Returns the style for the color selected by `color`, if colors are
+    /// enabled.
+    fn style<F>(&self, color: F) -> Style
+    where
+        F: FnOnce(&Theme) -> &str,
+    {
+        self.theme
+            .as_ref()
+            .map_or_else(Style::new, |theme| sgr_style(color(theme)))
+    }
+}
+
+/// Returns the style for the ANSI SGR parameters `sgr`, e.g. `1;31`, see
+/// [`Theme`].
+fn sgr_style(sgr: &str) -> Style {
+    let mut style = Style::new();
+    // Empty or too large parameters are treated as reset.
+    let mut params = sgr.split(';').map(|param| param.parse().unwrap_or(0));
+    while let Some(param) = params.next() {
+        style = match param {
+            0 => Style::new(),
+            1 => style.add_modifier(Modifier::BOLD),
+            2 => style.add_modifier(Modifier::DIM),
+            3 => style.add_modifier(Modifier::ITALIC),
+            4 => style.add_modifier(Modifier::UNDERLINED),
+            5 => style.add_modifier(Modifier::SLOW_BLINK),
+            7 => style.add_modifier(Modifier::REVERSED),
+            9 => style.add_modifier(Modifier::CROSSED_OUT),
+            30..=37 => style.fg(Color::Indexed(param - 30)),
+            40..=47 => style.bg(Color::Indexed(param - 40)),
+            90..=97 => style.fg(Color::Indexed(param - 90 + 8)),
+            100..=107 => style.bg(Color::Indexed(param - 100 + 8)),
+            // 256 colors (`38;5;N`) or true colors (`38;2;R;G;B`).
+            38 | 48 => {
+                let color = match params.next() {
+                    Some(5) => params.next().map(Color::Indexed),
+                    Some(2) => match (params.next(), params.next(), params.next()) {
+                        (Some(r), Some(g), Some(b)) => Some(Color::Rgb(r, g, b)),
+                        _ => None,
+                    },
+                    _ => None,
+                };
+                match color {
+                    Some(color) if param == 38 => style.fg(color),
+                    Some(color) => style.bg(color),
+                    None => style,
+                }
+            }
+            _ => style,
+        };
+    }
+    style
+}
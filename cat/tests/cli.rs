@@ -0,0 +1,252 @@
+//! Tests running the `std-logger-cat` binary.
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+
+const LOGS: &str = "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Hello world\" target=\"my_app\" module=\"my_app\" user=\"Thomas de Zeeuw\" id=123 admin=false
+ts=\"2021-02-23T13:15:49.000001Z\" lvl=\"ERROR\" msg=\"thread 'main' panicked at 'oops'\" target=\"panic\" module=\"\" backtrace=\"   0: std::backtrace::Backtrace::create
+   1: my_app::main\" file=\"src/main.rs:12\"
+lvl=LOUD msg=\"invalid level\"
+lvl=\"DEBUG\" msg=\"Debug message\" target=\"my_app\" module=\"my_app\" key=\"\"
+";
+
+/// Run `std-logger-cat` with `args`, writing `input` to standard in.
+fn run(args: &[&str], input: &str) -> (bool, String) {
+    run_with_env(args, &[], input)
+}
+
+/// Same as [`run`], setting the environment variables in `env`.
+fn run_with_env(args: &[&str], env: &[(&str, &str)], input: &str) -> (bool, String) {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_std-logger-cat"));
+    let _ = cmd.env_remove("LOG_COLORS").envs(env.iter().copied());
+    let mut child = cmd
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start std-logger-cat");
+    // The binary doesn't read the input if the arguments are invalid.
+    match child.stdin.take().unwrap().write_all(input.as_bytes()) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {}
+        Err(err) => panic!("failed to write input: {err}"),
+    }
+    let output = child.wait_with_output().unwrap();
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn pretty_print() {
+    let (success, got) = run(&["--color", "never"], LOGS);
+    assert!(success);
+    let want = "2021-02-23T13:15:48.624447Z INFO  my_app: Hello world admin=false id=123 user=\"Thomas de Zeeuw\"
+2021-02-23T13:15:49.000001Z ERROR panic: thread 'main' panicked at 'oops' (src/main.rs:12)
+  backtrace:
+       0: std::backtrace::Backtrace::create
+       1: my_app::main
+lvl=LOUD msg=\"invalid level\"
+DEBUG my_app: Debug message key=\"\"
+";
+    assert_eq!(got, want);
+}
+
+#[test]
+fn color() {
+    let input = "lvl=\"WARN\" msg=\"Hello\" target=\"my_app\" module=\"\" key=value\n";
+    let (success, got) = run(&["--color=always"], input);
+    assert!(success);
+    let want = "\x1b[33mWARN \x1b[0m \x1b[2mmy_app\x1b[0m: Hello \x1b[36mkey\x1b[0m=value\n";
+    assert_eq!(got, want);
+}
+
+#[test]
+fn color_themes() {
+    let input = "lvl=\"ERROR\" msg=\"Hello\" target=\"my_app\" module=\"\" key=value\n";
+    let tests = [
+        (
+            &["--color=always", "--colors", "color-blind"][..],
+            None,
+            "\x1b[1;38;5;166mERROR\x1b[0m \x1b[2mmy_app\x1b[0m: Hello \x1b[38;5;75mkey\x1b[0m=value\n",
+        ),
+        (
+            &["--color=always"],
+            Some("color-blind:error=1;35:dim=90"),
+            "\x1b[1;35mERROR\x1b[0m \x1b[90mmy_app\x1b[0m: Hello \x1b[38;5;75mkey\x1b[0m=value\n",
+        ),
+        // The option overwrites the environment variable.
+        (
+            &["--color=always", "--colors=key=4"],
+            Some("color-blind"),
+            "\x1b[1;31mERROR\x1b[0m \x1b[2mmy_app\x1b[0m: Hello \x1b[4mkey\x1b[0m=value\n",
+        ),
+        // Not used if the output is not colored.
+        (
+            &["--color=never"],
+            Some("color-blind"),
+            "ERROR my_app: Hello key=value\n",
+        ),
+    ];
+    for (args, colors, want) in tests {
+        let env = colors.map(|colors| ("LOG_COLORS", colors));
+        let (success, got) = run_with_env(args, env.as_slice(), input);
+        assert!(success, "{args:?} {colors:?}");
+        assert_eq!(got, want, "{args:?} {colors:?}");
+    }
+
+    for (args, colors) in [
+        (&["--colors", "rainbow"][..], None),
+        (&["--colors", "error=red"], None),
+        (&["--colors", "fatal=31"], None),
+        (&["--color=always"], Some("error=")),
+    ] {
+        let env = colors.map(|colors| ("LOG_COLORS", colors));
+        let (success, _) = run_with_env(args, env.as_slice(), input);
+        assert!(!success, "{args:?} {colors:?}");
+    }
+}
+
+#[test]
+fn align() {
+    let input = "lvl=\"INFO\" msg=\"GET /\" target=\"request\" status=200 took=\"1.2ms\" user=\"a\"
+lvl=\"INFO\" msg=\"POST /users\" target=\"request\" status=201 took=\"12.5ms\" user=\"b\"
+lvl=\"WARN\" msg=\"slow\" target=\"db\" took=\"1s\" file=\"src/db.rs:12\"
+lvl=\"INFO\" msg=\"GET /\" target=\"request\" status=200 took=\"1.2ms\" user=\"a\"
+";
+    let (success, got) = run(&["--color", "never", "--align", "-p", "took,status"], input);
+    assert!(success);
+    // Columns only grow, the first record isn't aligned with later ones.
+    let want = "INFO  request: GET / took=1.2ms status=200 user=a
+INFO  request: POST /users took=12.5ms status=201 user=b
+WARN  db:      slow        took=1s     (src/db.rs:12)
+INFO  request: GET /       took=1.2ms  status=200 user=a
+";
+    assert_eq!(got, want);
+}
+
+#[test]
+fn time_format() {
+    let input = "ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Hello\" target=\"app\"\n";
+    let tests: [(&[&str], &str); 3] = [
+        (&[], "2021-02-23T13:15:48.624447Z INFO  app: Hello\n"),
+        (
+            &["--time", "utc"],
+            "2021-02-23T13:15:48.624447Z INFO  app: Hello\n",
+        ),
+        // Not today, so the date is included.
+        (
+            &["-t", "local"],
+            "2021-02-23 15:15:48.624 INFO  app: Hello\n",
+        ),
+    ];
+    for (args, want) in tests {
+        let args = [&["--color", "never"], args].concat();
+        let (success, got) = run_with_env(&args, &[("TZ", "XYZ-2")], input);
+        assert!(success, "{args:?}");
+        assert_eq!(got, want, "{args:?}");
+    }
+
+    let (success, got) = run(&["--color", "never", "--time=relative"], input);
+    assert!(success);
+    assert!(got.ends_with("d ago INFO  app: Hello\n"), "{got}");
+
+    let (success, _) = run(&["--time", "yesterday"], input);
+    assert!(!success);
+}
+
+#[test]
+fn filter_level() {
+    let (success, got) = run(&["--color", "never", "-l", "warn"], LOGS);
+    assert!(success);
+    let want =
+        "2021-02-23T13:15:49.000001Z ERROR panic: thread 'main' panicked at 'oops' (src/main.rs:12)
+  backtrace:
+       0: std::backtrace::Backtrace::create
+       1: my_app::main
+lvl=LOUD msg=\"invalid level\"
+";
+    assert_eq!(got, want);
+}
+
+#[test]
+fn select_keys() {
+    let (success, got) = run(
+        &["--color", "never", "--keys", "user,missing", "-k", "id"],
+        LOGS,
+    );
+    assert!(success);
+    let want =
+        "2021-02-23T13:15:48.624447Z INFO  my_app: Hello world user=\"Thomas de Zeeuw\" id=123
+2021-02-23T13:15:49.000001Z ERROR panic: thread 'main' panicked at 'oops' (src/main.rs:12)
+lvl=LOUD msg=\"invalid level\"
+DEBUG my_app: Debug message
+";
+    assert_eq!(got, want);
+}
+
+#[test]
+fn search() {
+    let tests: &[(&[&str], &str)] = &[
+        (
+            &["--search", "id=123"],
+            "2021-02-23T13:15:48.624447Z INFO  my_app: Hello world admin=false id=123 user=\"Thomas de Zeeuw\"\n",
+        ),
+        (
+            &["-s", "user=Thomas de Zeeuw", "--search=admin"],
+            "2021-02-23T13:15:48.624447Z INFO  my_app: Hello world admin=false id=123 user=\"Thomas de Zeeuw\"\n",
+        ),
+        (
+            &["-s", "target=my_app", "-s", "key="],
+            "DEBUG my_app: Debug message key=\"\"\n",
+        ),
+        (&["-s", "id=124"], ""),
+        (&["-s", "id", "-s", "key"], ""),
+    ];
+    // Don't include the invalid line.
+    let logs = LOGS.replace("lvl=LOUD msg=\"invalid level\"\n", "");
+    for (args, want) in tests {
+        let args = [&["--color", "never"], *args].concat();
+        let (success, got) = run(&args, &logs);
+        assert!(success);
+        assert_eq!(got, *want, "args: {args:?}");
+    }
+}
+
+#[test]
+fn invalid_arguments() {
+    for args in [
+        &["--level", "loud"][..],
+        &["--unknown"],
+        &["--keys"],
+        &["--search"],
+        &["-f", "a", "b"],
+    ] {
+        let (success, got) = run(args, "");
+        assert!(!success, "args: {args:?}");
+        assert!(got.is_empty());
+    }
+
+    let (success, got) = run(&["--help"], "");
+    assert!(success);
+    assert!(got.starts_with("Usage: std-logger-cat"));
+}
+
+#[test]
+fn tui() {
+    // Standard out isn't a terminal in the tests.
+    let output = Command::new(env!("CARGO_BIN_EXE_std-logger-cat"))
+        .arg("--tui")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let got = String::from_utf8(output.stderr).unwrap();
+    #[cfg(feature = "tui")]
+    let want = "std-logger-cat: `--tui` requires standard out to be a terminal\n";
+    #[cfg(not(feature = "tui"))]
+    let want = "std-logger-cat: `--tui` requires the `tui` feature\n";
+    assert_eq!(got, want);
+}
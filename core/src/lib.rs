@@ -0,0 +1,483 @@
+//! The logfmt formatting of [std-logger], without depending on the standard
+//! library (only on [`alloc`]).
+//!
+//! std-logger formats records using thread local buffers and writes them to
+//! standard out or error, neither of which are available on embedded targets.
+//! This crate contains the formatting itself, writing into a buffer provided
+//! by the caller (see [`Output`]), so that embedded applications can use the
+//! exact same format and send the records over their own transport, e.g. a
+//! serial port or RTT.
+//!
+//! The timestamp is not written as there is no (portable) clock without the
+//! standard library. If needed the caller can write it before the record,
+//! using the `ts="YYYY-MM-DDTHH:MM:SS.MICROSZ" ` format of std-logger.
+//!
+//! [std-logger]: https://crates.io/crates/std-logger
+//!
+//! # Examples
+//!
+//! ```
+//! use log::{Level, Record};
+//! use std_logger_core::{logfmt, Options};
+//!
+//! let mut buf = Vec::new();
+//! let kvs = [("sensor", 3)];
+//! let record = Record::builder()
+//!     .args(format_args!("reading too high"))
+//!     .level(Level::Warn)
+//!     .target("firmware")
+//!     .module_path_static(Some("firmware::sensors"))
+//!     .key_values(&kvs)
+//!     .build();
+//! logfmt::format(&mut buf, &record, &Options::new(false));
+//! assert_eq!(buf, b"lvl=\"WARN\" msg=\"reading too high\" target=\"firmware\" module=\"firmware::sensors\" sensor=3\n");
+//! ```
+
+#![no_std]
+#![warn(missing_debug_implementations, missing_docs, unused_results)]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+use log::{LevelFilter, Record};
+
+pub mod logfmt;
+// Not part of the API, used by the other formats of std-logger.
+#[cfg(feature = "serde")]
+#[doc(hidden)]
+pub mod structured;
+
+/// Target for logging panics.
+pub const PANIC_TARGET: &str = "panic";
+
+/// Buffer the formatted records are written into.
+///
+/// Implemented for `Vec<u8>`, other (growable) buffers can implement it to
+/// format into their own storage.
+pub trait Output {
+    /// Append `bytes` to the buffer.
+    fn extend_from_slice(&mut self, bytes: &[u8]);
+
+    /// Append a single byte to the buffer.
+    fn push(&mut self, b: u8) {
+        self.extend_from_slice(&[b]);
+    }
+
+    /// Remove the last byte from the buffer and return it.
+    fn pop(&mut self) -> Option<u8>;
+
+    /// Remove the byte at `index`, moving all bytes after it one to the front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    fn remove(&mut self, index: usize) -> u8;
+
+    /// Shorten the buffer to `len` bytes, does nothing if the buffer is
+    /// already shorter.
+    fn truncate(&mut self, len: usize) {
+        while self.as_bytes().len() > len {
+            let _ = self.pop();
+        }
+    }
+
+    /// Returns the bytes written so far.
+    fn as_bytes(&self) -> &[u8];
+}
+
+impl Output for Vec<u8> {
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        Vec::extend_from_slice(self, bytes);
+    }
+
+    fn push(&mut self, b: u8) {
+        Vec::push(self, b);
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        Vec::pop(self)
+    }
+
+    fn remove(&mut self, index: usize) -> u8 {
+        Vec::remove(self, index)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        Vec::truncate(self, len);
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+/// Formatting options.
+#[derive(Debug)]
+pub struct Options {
+    /// Add the file and line.
+    pub add_loc: bool,
+    /// Write the line as separate key, e.g. `file="src/main.rs" line=12`
+    /// rather than `file="src/main.rs:12"`, if supported by the format.
+    pub separate_line: bool,
+    /// Format key-value pairs with the value `true` as only the key, if
+    /// supported by the format.
+    pub bare_keys: bool,
+    /// Format the message as the last field, if supported by the format.
+    pub msg_last: bool,
+    /// Don't escape the message and string values. Only use this if the
+    /// strings never contain characters that need escaping, i.e. quotes,
+    /// backslashes or control characters.
+    pub raw: bool,
+    /// Don't escape new lines in the message and values, if supported by the
+    /// format.
+    pub multi_line: bool,
+    /// Maximum size of the message and values, longer strings are truncated
+    /// and `truncated=true` is added to the record.
+    pub max_size: usize,
+    /// Type hints for the key-values, see [`TypeHint`].
+    pub type_hints: Box<[(Box<str>, TypeHint)]>,
+    /// How to format structured values, see [`StructuredValues`].
+    pub structured: StructuredValues,
+    /// Built-in fields in the order they're written, with their names and the
+    /// levels of the records they're written for, if supported by the format.
+    /// Fields not included are not written. Empty means all fields using their
+    /// default names and order, see [`Field`].
+    pub fields: Box<[(Field, Box<str>, LevelFilter)]>,
+}
+
+impl Options {
+    /// Options without any key-value formatting options.
+    pub fn new(add_loc: bool) -> Options {
+        Options {
+            add_loc,
+            separate_line: false,
+            bare_keys: false,
+            msg_last: false,
+            raw: false,
+            multi_line: false,
+            max_size: usize::MAX,
+            type_hints: Box::new([]),
+            structured: StructuredValues::Display,
+            fields: Box::new([]),
+        }
+    }
+
+    /// Returns `true` if the message and string values of `record` should be
+    /// written without escaping them. Panics are never logged in raw mode as
+    /// they can contain anything, e.g. backtraces.
+    pub fn raw(&self, record: &Record) -> bool {
+        self.raw && record.target() != PANIC_TARGET
+    }
+
+    /// Returns the type hint for `key`, if any.
+    pub fn type_hint(&self, key: &str) -> Option<TypeHint> {
+        self.type_hints
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, hint)| *hint)
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options::new(false)
+    }
+}
+
+/// Type hint for the value of a key-value pair.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum TypeHint {
+    /// Always format the value as a string, even if it's a number or boolean,
+    /// e.g. `user_id="123"`.
+    String,
+    /// Format the value as a number if it is one when formatted, even if it's
+    /// passed as a string, e.g. `took_ms=12` instead of `took_ms="12"`.
+    ///
+    /// Values that are not a valid JSON number, such as `007`, are still
+    /// formatted as a string.
+    Number,
+    /// Format the value as a number of milliseconds if it's a duration, e.g.
+    /// `took_ms=1.5` for a [`Duration`] of 1.5 milliseconds.
+    ///
+    /// Durations are recognised by their `Debug` output, e.g. `1.5ms`, so log
+    /// a [`Duration`] using `key:? = duration` (or std-logger's
+    /// `value::duration`). Other values are formatted as
+    /// with [`TypeHint::Number`], i.e. numbers are assumed to already be in
+    /// milliseconds.
+    ///
+    /// [`Duration`]: core::time::Duration
+    Duration,
+}
+
+/// Built-in field of a record, see [`Options::fields`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Field {
+    /// Timestamp, `ts` by default.
+    Timestamp,
+    /// Log level, `lvl` by default.
+    Level,
+    /// Message, `msg` by default.
+    Message,
+    /// Target, `target` by default.
+    Target,
+    /// Module path, `module` by default.
+    Module,
+    /// The key-values of the record, doesn't have a name.
+    KeyValues,
+    /// File and line, `file` by default. Only written if [`Options::add_loc`]
+    /// is `true`.
+    File,
+}
+
+impl Field {
+    /// All fields in the default order.
+    pub const ALL: [Field; 7] = [
+        Field::Timestamp,
+        Field::Level,
+        Field::Message,
+        Field::Target,
+        Field::Module,
+        Field::KeyValues,
+        Field::File,
+    ];
+
+    /// Returns the default name of the field, e.g. `lvl`.
+    pub const fn default_name(self) -> &'static str {
+        match self {
+            Field::Timestamp => "ts",
+            Field::Level => "lvl",
+            Field::Message => "msg",
+            Field::Target => "target",
+            Field::Module => "module",
+            Field::KeyValues => "",
+            Field::File => "file",
+        }
+    }
+}
+
+/// How to format structured values, e.g. maps, sequences and nested structs,
+/// that implement `serde::Serialize`.
+///
+/// Only `Display` is supported without the *serde* feature.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum StructuredValues {
+    /// Format the value as a string using its `Display` implementation, the
+    /// default.
+    #[default]
+    Display,
+    /// Flatten the value into multiple key-value pairs with dotted keys, e.g.
+    /// `user.id=7 user.name="bob"`. Items in sequences use their index as key,
+    /// e.g. `tags.0="a" tags.1="b"`.
+    Flatten,
+    /// Format the value as JSON, e.g. `user="{\"id\":7,\"name\":\"bob\"}"`
+    /// using logfmt or `"user":{"id":7,"name":"bob"}` using JSON.
+    Json,
+}
+
+/// [`fmt::Write`] implementation that writes strings as is, without escaping
+/// them, used in raw mode.
+///
+/// # Panics
+///
+/// If debug assertions are enabled this panics if a string contains a
+/// character that would otherwise be escaped, i.e. a quote, backslash or
+/// control character.
+// Not part of the API, used by the other formats of std-logger.
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct RawBuf<'b, O>(pub &'b mut O);
+
+impl<'b, O: Output> fmt::Write for RawBuf<'b, O> {
+    #[inline]
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        debug_assert!(
+            !string
+                .bytes()
+                .any(|b| b == b'"' || b == b'\\' || b < b' ' || b == 0x7F),
+            "std-logger: string logged in raw mode contains characters that need escaping: {string:?}",
+        );
+        self.0.extend_from_slice(string.as_bytes());
+        Ok(())
+    }
+}
+
+/// Remaining size of the message and string values of a record, see
+/// [`Options::max_size`].
+// Not part of the API, used by the other formats of std-logger.
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct Limit {
+    remaining: usize,
+    /// Set if a string was truncated.
+    pub truncated: bool,
+}
+
+impl Limit {
+    /// Create a new limit based on [`Options::max_size`].
+    pub const fn new(options: &Options) -> Limit {
+        Limit {
+            remaining: options.max_size,
+            truncated: false,
+        }
+    }
+}
+
+/// [`fmt::Write`] implementation that truncates the strings written to the
+/// `inner` writer once the [`Limit`] is reached.
+// Not part of the API, used by the other formats of std-logger.
+#[doc(hidden)]
+#[allow(missing_debug_implementations)]
+pub struct Truncate<'l, W> {
+    /// Writer to write the (truncated) strings to.
+    pub inner: W,
+    /// Remaining size.
+    pub limit: &'l mut Limit,
+}
+
+impl<'l, W: fmt::Write> fmt::Write for Truncate<'l, W> {
+    #[inline]
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        if string.len() <= self.limit.remaining {
+            self.limit.remaining -= string.len();
+            return self.inner.write_str(string);
+        }
+
+        let mut end = self.limit.remaining;
+        while !string.is_char_boundary(end) {
+            end -= 1;
+        }
+        self.limit.remaining = 0;
+        self.limit.truncated = true;
+        self.inner.write_str(&string[..end])
+    }
+}
+
+/// Returns `true` if `key` is empty or contains a space, `=`, `"`, `\\` or
+/// control character, i.e. if the key must be quoted in logfmt.
+// Not part of the API, used by std-logger-parser.
+#[doc(hidden)]
+pub fn key_needs_quotes(key: &str) -> bool {
+    key.is_empty()
+        || key
+            .bytes()
+            .any(|b| b <= b' ' || b == b'=' || b == b'"' || b == b'\\' || b == 0x7F)
+}
+
+/// Returns `true` if `value` is a valid JSON number, e.g. `-1.5e3`.
+// Not part of the API, used by the other formats of std-logger.
+#[doc(hidden)]
+pub fn is_number(value: &[u8]) -> bool {
+    fn digits(value: &[u8]) -> (&[u8], usize) {
+        let n = value.iter().take_while(|b| b.is_ascii_digit()).count();
+        (&value[n..], n)
+    }
+
+    let value = value.strip_prefix(b"-").unwrap_or(value);
+    let value = match value {
+        [b'0', rest @ ..] => rest,
+        _ => match digits(value) {
+            (_, 0) => return false,
+            (rest, _) => rest,
+        },
+    };
+    let value = match value.strip_prefix(b".") {
+        Some(fraction) => match digits(fraction) {
+            (_, 0) => return false,
+            (rest, _) => rest,
+        },
+        None => value,
+    };
+    let value = match value
+        .strip_prefix(b"e")
+        .or_else(|| value.strip_prefix(b"E"))
+    {
+        Some(exponent) => {
+            let exponent = match exponent {
+                [b'+' | b'-', rest @ ..] => rest,
+                _ => exponent,
+            };
+            match digits(exponent) {
+                (_, 0) => return false,
+                (rest, _) => rest,
+            }
+        }
+        None => value,
+    };
+    value.is_empty()
+}
+
+/// Maximum length of the output of [`duration_millis`].
+#[doc(hidden)]
+pub const MAX_MILLIS: usize = 48;
+
+/// Returns `value`, a [`Duration`] formatted using `Debug` (e.g. `1.5s` or
+/// `250µs`), as a number of milliseconds formatted into `buf` (e.g. `1500` or
+/// `0.25`). Returns `None` if `value` isn't a duration.
+///
+/// [`Duration`]: core::time::Duration
+// Not part of the API, used by the other formats of std-logger.
+#[doc(hidden)]
+pub fn duration_millis<'b>(value: &[u8], buf: &'b mut [u8; MAX_MILLIS]) -> Option<&'b [u8]> {
+    let int_len = value.iter().take_while(|b| b.is_ascii_digit()).count();
+    let (int, rest) = value.split_at(int_len);
+    let (fraction, unit) = match rest.strip_prefix(b".") {
+        Some(rest) => match rest.iter().take_while(|b| b.is_ascii_digit()).count() {
+            0 => return None,
+            n => rest.split_at(n),
+        },
+        None => (&rest[..0], rest),
+    };
+    // `Duration` uses at most 20 digits for the integer and 9 for the fraction.
+    if int.is_empty() || int.len() > 20 || fraction.len() > 9 {
+        return None;
+    }
+    let unit_nanos: u128 = match unit {
+        b"s" => 1_000_000_000,
+        b"ms" => 1_000_000,
+        b"\xC2\xB5s" /* µs */ | b"us" => 1_000,
+        b"ns" => 1,
+        _ => return None,
+    };
+    let parse = |digits: &[u8]| {
+        digits
+            .iter()
+            .fold(0_u128, |n, d| n * 10 + u128::from(d - b'0'))
+    };
+    let nanos =
+        parse(int) * unit_nanos + parse(fraction) * unit_nanos / 10_u128.pow(fraction.len() as u32);
+
+    // Write the milliseconds backwards, starting with the fraction.
+    let mut end = buf.len();
+    let mut fraction = nanos % 1_000_000;
+    if fraction != 0 {
+        // Skip the trailing zeros of the fraction.
+        let mut trailing = true;
+        for _ in 0..6 {
+            let digit = (fraction % 10) as u8;
+            fraction /= 10;
+            trailing = trailing && digit == 0;
+            if !trailing {
+                end -= 1;
+                buf[end] = b'0' + digit;
+            }
+        }
+        end -= 1;
+        buf[end] = b'.';
+    }
+    let mut millis = nanos / 1_000_000;
+    loop {
+        end -= 1;
+        buf[end] = b'0' + (millis % 10) as u8;
+        millis /= 10;
+        if millis == 0 {
+            break;
+        }
+    }
+    Some(&buf[end..])
+}
@@ -0,0 +1,479 @@
+//! Logfmt following <https://www.brandur.org/logfmt>.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use log::kv::{VisitSource, VisitValue};
+use log::{kv, Record};
+
+use crate::{
+    duration_millis, is_number, key_needs_quotes, Field, Limit, Options, Output, RawBuf, Truncate,
+    TypeHint, MAX_MILLIS,
+};
+#[cfg(feature = "serde")]
+use crate::{structured, StructuredValues};
+
+/// Format `record` using logfmt, appending it to `buf`.
+///
+/// This writes the same format as std-logger, without the timestamp, e.g.
+/// `lvl="INFO" msg="some message" target="request" module="stored::http"`
+/// followed by the key-values, the optional file and a line end.
+///
+/// If [`Options::fields`] is set the fields are written in that order, using
+/// their names and levels (the timestamp is never written).
+pub fn format<O: Output>(buf: &mut O, record: &Record, options: &Options) {
+    let kvs = None::<(&str, &str)>;
+    if !options.fields.is_empty() {
+        format_fields(buf, record, kvs, options, None);
+        return;
+    }
+
+    let mut limit = Limit::new(options);
+    let raw = options.raw(record);
+    if options.msg_last {
+        // The message is formatted first to apply the `limit` the same way as
+        // when it's formatted as first field.
+        let mut msg = Vec::new();
+        write_msg(&mut msg, record.args(), raw, options.multi_line, &mut limit);
+        write_fields(buf, record);
+        buf.push(b'"');
+        write_key_values(buf, record.key_values(), kvs, options, raw, &mut limit);
+        if options.add_loc {
+            write_file(buf, record, options);
+            let _ = buf.pop(); // Line end.
+        }
+        buf.extend_from_slice(b" msg=\"");
+        buf.extend_from_slice(&msg);
+        buf.extend_from_slice(b"\"\n");
+        return;
+    }
+
+    buf.extend_from_slice(b"lvl=\"");
+    buf.extend_from_slice(record.level().as_str().as_bytes());
+    buf.extend_from_slice(b"\" msg=\"");
+    write_msg(buf, record.args(), raw, options.multi_line, &mut limit);
+    buf.extend_from_slice(b"\" target=\"");
+    buf.extend_from_slice(record.target().as_bytes());
+    buf.extend_from_slice(b"\" module=\"");
+    buf.extend_from_slice(record.module_path().unwrap_or("").as_bytes());
+    buf.push(b'"');
+    write_key_values(buf, record.key_values(), kvs, options, raw, &mut limit);
+    if options.add_loc {
+        write_file(buf, record, options);
+    } else {
+        buf.push(b'\n');
+    }
+}
+
+/// Format `record` using the fields in [`Options::fields`], appending it to
+/// `buf`. Fields are skipped if their level filter doesn't enable the level of
+/// `record`. The `timestamp` is written if the fields include it and it's not
+/// `None`.
+// Not part of the API, used by std-logger.
+#[doc(hidden)]
+pub fn format_fields<O: Output, Kvs: kv::Source>(
+    buf: &mut O,
+    record: &Record,
+    kvs: Kvs,
+    options: &Options,
+    timestamp: Option<&[u8]>,
+) {
+    let mut limit = Limit::new(options);
+    let raw = options.raw(record);
+    // The message is formatted first to apply the `limit` the same way as in
+    // the default order.
+    let mut msg = Vec::new();
+    write_msg(&mut msg, record.args(), raw, options.multi_line, &mut limit);
+
+    let start = buf.as_bytes().len();
+    for (field, name, level) in options.fields.iter() {
+        if record.level() > *level {
+            continue;
+        }
+        let value = match field {
+            Field::Timestamp => match timestamp {
+                Some(timestamp) => timestamp,
+                None => continue,
+            },
+            Field::Level => record.level().as_str().as_bytes(),
+            Field::Message => &msg,
+            Field::Target => record.target().as_bytes(),
+            Field::Module => record.module_path().unwrap_or("").as_bytes(),
+            Field::KeyValues => {
+                write_key_values(buf, record.key_values(), &kvs, options, raw, &mut limit);
+                continue;
+            }
+            Field::File if options.add_loc => {
+                buf.push(b' ');
+                buf.extend_from_slice(name.as_bytes());
+                buf.extend_from_slice(b"=\"");
+                buf.extend_from_slice(record.file().unwrap_or("??").as_bytes());
+                if options.separate_line {
+                    write_line_key(buf, record.line().unwrap_or(0));
+                } else {
+                    write_line(buf, record.line().unwrap_or(0));
+                }
+                let _ = buf.pop(); // Line end.
+                continue;
+            }
+            Field::File => continue,
+        };
+        buf.push(b' ');
+        buf.extend_from_slice(name.as_bytes());
+        buf.extend_from_slice(b"=\"");
+        buf.extend_from_slice(value);
+        buf.push(b'"');
+    }
+    // Remove the space before the first field.
+    if buf.as_bytes().get(start) == Some(&b' ') {
+        let _ = buf.remove(start);
+    }
+    buf.push(b'\n');
+}
+
+/// Writes the level, target and module for the message last format, without
+/// the closing quote.
+fn write_fields<O: Output>(buf: &mut O, record: &Record) {
+    buf.extend_from_slice(b"lvl=\"");
+    buf.extend_from_slice(record.level().as_str().as_bytes());
+    buf.extend_from_slice(b"\" target=\"");
+    buf.extend_from_slice(record.target().as_bytes());
+    buf.extend_from_slice(b"\" module=\"");
+    buf.extend_from_slice(record.module_path().unwrap_or("").as_bytes());
+}
+
+/// Writes the file and line, e.g. ` file="some_file:123"` (or
+/// ` file="some_file" line=123`, see [`Options::separate_line`]), and a line
+/// end.
+fn write_file<O: Output>(buf: &mut O, record: &Record, options: &Options) {
+    buf.extend_from_slice(b" file=\"");
+    buf.extend_from_slice(record.file().unwrap_or("??").as_bytes());
+    if options.separate_line {
+        write_line_key(buf, record.line().unwrap_or(0));
+    } else {
+        write_line(buf, record.line().unwrap_or(0));
+    }
+}
+
+/// Writes the (escaped) message.
+// Not part of the API, used by std-logger.
+#[doc(hidden)]
+#[inline]
+pub fn write_msg<O: Output>(
+    buf: &mut O,
+    args: &fmt::Arguments,
+    raw: bool,
+    multi_line: bool,
+    limit: &mut Limit,
+) {
+    if raw {
+        let inner = RawBuf(buf);
+        Truncate { inner, limit }.write_fmt(*args)
+    } else if multi_line {
+        let inner = MultiLineBuf(buf);
+        Truncate { inner, limit }.write_fmt(*args)
+    } else if let Some(msg) = args.as_str() {
+        let inner = Buf(buf);
+        Truncate { inner, limit }.write_str(msg)
+    } else {
+        let inner = Buf(buf);
+        Truncate { inner, limit }.write_fmt(*args)
+    }
+    .unwrap_or_else(|_| unreachable!());
+}
+
+/// Writes the key-values of `kvs1` and `kvs2`, each prefixed with a space,
+/// followed by `truncated=true` if any string was truncated.
+// Not part of the API, used by std-logger.
+#[doc(hidden)]
+#[inline]
+pub fn write_key_values<O: Output, Kvs: kv::Source>(
+    buf: &mut O,
+    kvs1: &dyn kv::Source,
+    kvs2: Kvs,
+    options: &Options,
+    raw: bool,
+    limit: &mut Limit,
+) {
+    let mut visitor = KeyValueVisitor {
+        buf,
+        options,
+        raw,
+        multi_line: options.multi_line,
+        limit,
+        hint: None,
+    };
+    kvs1.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
+    kvs2.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
+    if visitor.limit.truncated {
+        buf.extend_from_slice(b" truncated=true");
+    }
+}
+
+/// Writes the `line` number of the file, e.g. `:123"`, and a line end.
+// Not part of the API, used by std-logger.
+#[doc(hidden)]
+#[inline]
+pub fn write_line<O: Output>(buf: &mut O, line: u32) {
+    buf.push(b':');
+    let mut itoa = itoa::Buffer::new();
+    buf.extend_from_slice(itoa.format(line).as_bytes());
+    buf.extend_from_slice(b"\"\n");
+}
+
+/// Writes the `line` number of the file as separate key, e.g. `" line=123`,
+/// and a line end.
+// Not part of the API, used by std-logger.
+#[doc(hidden)]
+#[inline]
+pub fn write_line_key<O: Output>(buf: &mut O, line: u32) {
+    buf.extend_from_slice(b"\" line=");
+    let mut itoa = itoa::Buffer::new();
+    buf.extend_from_slice(itoa.format(line).as_bytes());
+    buf.push(b'\n');
+}
+
+/// Formats key value pairs in the following format: `key="value"`. For example:
+/// `user_name="Thomas" user_id=123 is_admin=true`
+///
+/// Null values, e.g. `Option::None`, are formatted without a value, e.g.
+/// `user_email=`.
+///
+/// Keys that contain spaces, quotes, `=` or control characters are quoted, e.g.
+/// `"user name"="Thomas"`.
+///
+/// If [`Options::bare_keys`] is `true` pairs with the value `true` are
+/// formatted as a bare key, e.g. `is_admin` instead of `is_admin=true`.
+///
+/// Values of keys with a [`TypeHint`] are formatted according to the hint.
+struct KeyValueVisitor<'b, O> {
+    buf: &'b mut O,
+    options: &'b Options,
+    /// Write string values without escaping them, see [`RawBuf`].
+    raw: bool,
+    /// Write string values without escaping new lines, see [`MultiLineBuf`].
+    multi_line: bool,
+    /// Limit for the string values, see [`Truncate`].
+    limit: &'b mut Limit,
+    /// Type hint for the value currently being formatted.
+    hint: Option<TypeHint>,
+}
+
+impl<'b, O: Output> KeyValueVisitor<'b, O> {
+    /// Writes a number or boolean `value`, quoting it if the value should be
+    /// formatted as a string.
+    fn write_unquoted(&mut self, value: &[u8]) {
+        if self.hint == Some(TypeHint::String) {
+            self.buf.push(b'\"');
+            self.buf.extend_from_slice(value);
+            self.buf.push(b'\"');
+        } else {
+            self.buf.extend_from_slice(value);
+        }
+    }
+
+    /// Removes the quotes around the string value starting at `start` if the
+    /// value should be, and can be, formatted as a number. Durations are
+    /// replaced by their milliseconds, see [`TypeHint::Duration`].
+    fn unquote_number(&mut self, start: usize) {
+        let bytes = self.buf.as_bytes();
+        let value = &bytes[start + 1..bytes.len() - 1];
+        let mut buf = [0; MAX_MILLIS];
+        if self.hint == Some(TypeHint::Duration) {
+            if let Some(millis) = duration_millis(value, &mut buf) {
+                self.buf.truncate(start);
+                self.buf.extend_from_slice(millis);
+                return;
+            }
+        }
+        if matches!(self.hint, Some(TypeHint::Number | TypeHint::Duration)) && is_number(value) {
+            let _ = self.buf.pop();
+            let _ = self.buf.remove(start);
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'b, O: Output> KeyValueVisitor<'b, O> {
+    /// Writes the structured `value` as flattened key-value pairs or as JSON
+    /// string, see [`StructuredValues`].
+    fn visit_structured(&mut self, key: &str, value: &structured::Node) -> Result<(), kv::Error> {
+        let mut result = Ok(());
+        match self.options.structured {
+            StructuredValues::Flatten => value.flatten(key, &mut |key, value| {
+                if result.is_ok() {
+                    result = VisitSource::visit_pair(self, kv::Key::from(key), value);
+                }
+            }),
+            _ => {
+                let json = value.to_json();
+                result = VisitSource::visit_pair(self, kv::Key::from(key), kv::Value::from(&*json));
+            }
+        }
+        result
+    }
+}
+
+impl<'b, 'kvs, O: Output> VisitSource<'kvs> for KeyValueVisitor<'b, O> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        #[cfg(feature = "serde")]
+        if self.options.structured != StructuredValues::Display {
+            if let Some(node) = structured::to_node(&value) {
+                return self.visit_structured(key.as_str(), &node);
+            }
+        }
+
+        self.buf.push(b' ');
+        let key = key.as_str();
+        if key_needs_quotes(key) {
+            self.buf.push(b'\"');
+            let _ = Buf(self.buf).write_str(key);
+            self.buf.push(b'\"');
+        } else {
+            self.buf.extend_from_slice(key.as_bytes());
+        }
+        self.hint = self.options.type_hint(key);
+        if self.options.bare_keys
+            && self.hint != Some(TypeHint::String)
+            && value.to_bool() == Some(true)
+        {
+            return Ok(());
+        }
+        self.buf.push(b'=');
+        value.visit(self)
+    }
+}
+
+impl<'b, 'v, O: Output> VisitValue<'v> for KeyValueVisitor<'b, O> {
+    fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
+        let start = self.buf.as_bytes().len();
+        self.buf.push(b'\"');
+        let limit = &mut *self.limit;
+        if self.raw {
+            let inner = RawBuf(self.buf);
+            Truncate { inner, limit }.write_fmt(format_args!("{value}"))
+        } else if self.multi_line {
+            let inner = MultiLineBuf(self.buf);
+            Truncate { inner, limit }.write_fmt(format_args!("{value}"))
+        } else {
+            let inner = Buf(self.buf);
+            Truncate { inner, limit }.write_fmt(format_args!("{value}"))
+        }
+        .unwrap_or_else(|_| unreachable!());
+        self.buf.push(b'\"');
+        self.unquote_number(start);
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        // Absent value, e.g. `Option::None`, formatted as `key=`.
+        Ok(())
+    }
+
+    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+        let mut itoa = itoa::Buffer::new();
+        self.write_unquoted(itoa.format(value).as_bytes());
+        Ok(())
+    }
+
+    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+        let mut itoa = itoa::Buffer::new();
+        self.write_unquoted(itoa.format(value).as_bytes());
+        Ok(())
+    }
+
+    fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
+        let mut itoa = itoa::Buffer::new();
+        self.write_unquoted(itoa.format(value).as_bytes());
+        Ok(())
+    }
+
+    fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
+        let mut itoa = itoa::Buffer::new();
+        self.write_unquoted(itoa.format(value).as_bytes());
+        Ok(())
+    }
+
+    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+        let mut ryu = ryu::Buffer::new();
+        self.write_unquoted(ryu.format(value).as_bytes());
+        Ok(())
+    }
+
+    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+        self.write_unquoted(if value { b"true" } else { b"false" });
+        Ok(())
+    }
+
+    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+        let start = self.buf.as_bytes().len();
+        self.buf.push(b'\"');
+        let limit = &mut *self.limit;
+        let _ = if self.raw {
+            let inner = RawBuf(self.buf);
+            Truncate { inner, limit }.write_str(value)
+        } else if self.multi_line {
+            let inner = MultiLineBuf(self.buf);
+            Truncate { inner, limit }.write_str(value)
+        } else {
+            let inner = Buf(self.buf);
+            Truncate { inner, limit }.write_str(value)
+        };
+        self.buf.push(b'\"');
+        self.unquote_number(start);
+        Ok(())
+    }
+}
+
+/// [`fmt::Write`] implementation that writes escaped quotes, backslashes and
+/// new lines.
+struct Buf<'b, O>(&'b mut O);
+
+impl<'b, O: Output> fmt::Write for Buf<'b, O> {
+    #[inline]
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for c in string.chars() {
+            let _ = self.write_char(c);
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn write_char(&mut self, c: char) -> fmt::Result {
+        // See RFC 8259, section 7
+        // <https://datatracker.ietf.org/doc/html/rfc8259#section-7>.
+        let mut bytes = [0; 8];
+        let bytes: &[u8] = match c {
+            // Quotation mark.
+            '"' => b"\\\"",
+            // Reverse solidus.
+            '\\' => b"\\\\",
+            // Line feed.
+            '\u{000A}' => b"\\n",
+            // Carriage return.
+            '\u{000D}' => b"\\r",
+            // Tab.
+            '\u{0009}' => b"\\t",
+            _ => c.encode_utf8(&mut bytes).as_bytes(),
+        };
+        self.0.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// [`fmt::Write`] implementation that escapes only quotes and backslashes,
+/// writing new lines as is, see [`Options::multi_line`].
+struct MultiLineBuf<'b, O>(&'b mut O);
+
+impl<'b, O: Output> fmt::Write for MultiLineBuf<'b, O> {
+    #[inline]
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        for &b in string.as_bytes() {
+            if b == b'"' || b == b'\\' {
+                self.0.push(b'\\');
+            }
+            self.0.push(b);
+        }
+        Ok(())
+    }
+}
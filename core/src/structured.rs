@@ -0,0 +1,507 @@
+//! Formatting of structured values, e.g. maps and sequences, using serde, see
+//! [`StructuredValues`].
+//!
+//! [`StructuredValues`]: crate::StructuredValues
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+
+use log::kv;
+use serde::ser::{self, Serialize};
+
+/// Structured value.
+#[derive(Debug, PartialEq)]
+pub enum Node {
+    /// Absent value, e.g. `Option::None`.
+    Null,
+    /// Boolean.
+    Bool(bool),
+    /// Signed integer.
+    I64(i64),
+    /// Unsigned integer.
+    U64(u64),
+    /// Floating point number.
+    F64(f64),
+    /// String.
+    Str(String),
+    /// Sequence, e.g. a `Vec`.
+    Seq(Vec<Node>),
+    /// Map, e.g. a struct or `HashMap`.
+    Map(Vec<(String, Node)>),
+}
+
+/// Returns the structured `value` if it's a sequence or map, `None` otherwise.
+///
+/// Strings, numbers and booleans are checked first, so only values that are
+/// (possibly) structured are serialised.
+pub fn to_node(value: &kv::Value) -> Option<Node> {
+    if value.to_borrowed_str().is_some()
+        || value.to_i64().is_some()
+        || value.to_u64().is_some()
+        || value.to_f64().is_some()
+        || value.to_bool().is_some()
+    {
+        return None;
+    }
+    match value.serialize(Serializer) {
+        Ok(node @ (Node::Seq(_) | Node::Map(_))) => Some(node),
+        Ok(_) | Err(_) => None,
+    }
+}
+
+impl Node {
+    /// Calls `f` for all scalar values, with their dotted key starting with
+    /// `prefix`, e.g. `user.id` for the `id` field in the `user` map and
+    /// `tags.0` for the first item in the `tags` sequence.
+    pub fn flatten(&self, prefix: &str, f: &mut dyn FnMut(&str, kv::Value)) {
+        match self {
+            Node::Null => f(prefix, kv::Value::null()),
+            Node::Bool(value) => f(prefix, kv::Value::from(*value)),
+            Node::I64(value) => f(prefix, kv::Value::from(*value)),
+            Node::U64(value) => f(prefix, kv::Value::from(*value)),
+            Node::F64(value) => f(prefix, kv::Value::from(*value)),
+            Node::Str(value) => f(prefix, kv::Value::from(&**value)),
+            Node::Seq(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let mut itoa = itoa::Buffer::new();
+                    item.flatten(&join(prefix, itoa.format(i)), f);
+                }
+            }
+            Node::Map(entries) => {
+                for (key, value) in entries {
+                    value.flatten(&join(prefix, key), f);
+                }
+            }
+        }
+    }
+
+    /// Formats the value as JSON.
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        self.write_json(&mut json);
+        json
+    }
+
+    fn write_json(&self, json: &mut String) {
+        match self {
+            Node::Null => json.push_str("null"),
+            Node::Bool(value) => json.push_str(if *value { "true" } else { "false" }),
+            Node::I64(value) => json.push_str(itoa::Buffer::new().format(*value)),
+            Node::U64(value) => json.push_str(itoa::Buffer::new().format(*value)),
+            Node::F64(value) if value.is_finite() => {
+                json.push_str(ryu::Buffer::new().format_finite(*value));
+            }
+            // JSON doesn't support infinity or NaN.
+            Node::F64(_) => json.push_str("null"),
+            Node::Str(value) => write_json_str(json, value),
+            Node::Seq(items) => {
+                json.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        json.push(',');
+                    }
+                    item.write_json(json);
+                }
+                json.push(']');
+            }
+            Node::Map(entries) => {
+                json.push('{');
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        json.push(',');
+                    }
+                    write_json_str(json, key);
+                    json.push(':');
+                    value.write_json(json);
+                }
+                json.push('}');
+            }
+        }
+    }
+}
+
+/// Returns `prefix.key`.
+fn join(prefix: &str, key: &str) -> String {
+    let mut path = String::with_capacity(prefix.len() + 1 + key.len());
+    path.push_str(prefix);
+    path.push('.');
+    path.push_str(key);
+    path
+}
+
+/// Writes `value` as quoted and escaped JSON string.
+fn write_json_str(json: &mut String, value: &str) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+/// Error returned by [`Serializer`], only used for unsupported values.
+#[derive(Debug)]
+pub struct Error;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("unsupported value")
+    }
+}
+
+impl ser::StdError for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(_: T) -> Error {
+        Error
+    }
+}
+
+/// [`ser::Serializer`] that creates a [`Node`].
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Node;
+    type Error = Error;
+    type SerializeSeq = Compound;
+    type SerializeTuple = Compound;
+    type SerializeTupleStruct = Compound;
+    type SerializeTupleVariant = Compound;
+    type SerializeMap = Compound;
+    type SerializeStruct = Compound;
+    type SerializeStructVariant = Compound;
+
+    fn serialize_bool(self, v: bool) -> Result<Node, Error> {
+        Ok(Node::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Node, Error> {
+        Ok(Node::I64(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Node, Error> {
+        Ok(Node::I64(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Node, Error> {
+        Ok(Node::I64(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Node, Error> {
+        Ok(Node::I64(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Node, Error> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Node, Error> {
+        Ok(Node::U64(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Node, Error> {
+        Ok(Node::U64(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Node, Error> {
+        Ok(Node::U64(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Node, Error> {
+        Ok(Node::U64(v))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Node, Error> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Node, Error> {
+        Ok(Node::F64(v.into()))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Node, Error> {
+        Ok(Node::F64(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Node, Error> {
+        Ok(Node::Str(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Node, Error> {
+        Ok(Node::Str(v.into()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Node, Error> {
+        Ok(Node::Seq(
+            v.iter().map(|b| Node::U64((*b).into())).collect(),
+        ))
+    }
+
+    fn serialize_none(self) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Node, Error> {
+        Ok(Node::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Node, Error> {
+        Ok(Node::Str(variant.into()))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Node, Error> {
+        Ok(Node::Map(alloc::vec![(
+            variant.into(),
+            value.serialize(self)?
+        )]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::seq(len.unwrap_or(0), None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::seq(len, None))
+    }
+
+    fn serialize_tuple_struct(self, _: &'static str, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::seq(len, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::seq(len, Some(variant)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound, Error> {
+        Ok(Compound::map(len.unwrap_or(0), None))
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Compound, Error> {
+        Ok(Compound::map(len, None))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound, Error> {
+        Ok(Compound::map(len, Some(variant)))
+    }
+
+    fn collect_str<T: ?Sized + fmt::Display>(self, value: &T) -> Result<Node, Error> {
+        Ok(Node::Str(value.to_string()))
+    }
+}
+
+/// Sequence or map being serialised.
+struct Compound {
+    items: Vec<Node>,
+    entries: Vec<(String, Node)>,
+    is_map: bool,
+    /// Key of the enum variant, if any.
+    variant: Option<&'static str>,
+    /// Key of the map entry being serialised.
+    key: Option<String>,
+}
+
+impl Compound {
+    fn seq(len: usize, variant: Option<&'static str>) -> Compound {
+        Compound {
+            items: Vec::with_capacity(len),
+            entries: Vec::new(),
+            is_map: false,
+            variant,
+            key: None,
+        }
+    }
+
+    fn map(len: usize, variant: Option<&'static str>) -> Compound {
+        Compound {
+            items: Vec::new(),
+            entries: Vec::with_capacity(len),
+            is_map: true,
+            variant,
+            key: None,
+        }
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        let node = if self.is_map {
+            Node::Map(self.entries)
+        } else {
+            Node::Seq(self.items)
+        };
+        Ok(match self.variant {
+            Some(variant) => Node::Map(alloc::vec![(variant.into(), node)]),
+            None => node,
+        })
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.items.push(value.serialize(Serializer)?);
+        Ok(())
+    }
+
+    fn insert<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.entries
+            .push((key.into(), value.serialize(Serializer)?));
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTuple for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleStruct for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeTupleVariant for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeMap for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.key = Some(match key.serialize(Serializer)? {
+            Node::Str(key) => key,
+            Node::Bool(key) => key.to_string(),
+            Node::I64(key) => key.to_string(),
+            Node::U64(key) => key.to_string(),
+            Node::F64(key) => key.to_string(),
+            Node::Null | Node::Seq(_) | Node::Map(_) => return Err(Error),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().ok_or(Error)?;
+        self.insert(&key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStruct for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
+
+impl ser::SerializeStructVariant for Compound {
+    type Ok = Node;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Node, Error> {
+        Compound::end(self)
+    }
+}
@@ -0,0 +1,125 @@
+use log::{Level, Record};
+use std_logger_core::{logfmt, Options, TypeHint};
+
+fn format(record: &Record, options: &Options) -> String {
+    let mut buf = Vec::new();
+    logfmt::format(&mut buf, record, options);
+    String::from_utf8(buf).unwrap()
+}
+
+#[test]
+fn format_record() {
+    let kvs: &[(&str, &dyn log::kv::ToValue)] = &[
+        ("user", &"Thomas \"T\""),
+        ("id", &123),
+        ("admin", &true),
+        ("user name", &1.5),
+    ];
+    let record = Record::builder()
+        .args(format_args!("some\nmessage"))
+        .level(Level::Info)
+        .target("request")
+        .module_path_static(Some("stored::http"))
+        .file_static(Some("src/http.rs"))
+        .line(Some(42))
+        .key_values(&kvs)
+        .build();
+
+    let tests = [
+        (
+            Options::new(false),
+            "lvl=\"INFO\" msg=\"some\\nmessage\" target=\"request\" module=\"stored::http\" user=\"Thomas \\\"T\\\"\" id=123 admin=true \"user name\"=1.5\n",
+        ),
+        (
+            Options::new(true),
+            "lvl=\"INFO\" msg=\"some\\nmessage\" target=\"request\" module=\"stored::http\" user=\"Thomas \\\"T\\\"\" id=123 admin=true \"user name\"=1.5 file=\"src/http.rs:42\"\n",
+        ),
+        (
+            Options {
+                msg_last: true,
+                bare_keys: true,
+                ..Options::new(true)
+            },
+            "lvl=\"INFO\" target=\"request\" module=\"stored::http\" user=\"Thomas \\\"T\\\"\" id=123 admin \"user name\"=1.5 file=\"src/http.rs:42\" msg=\"some\\nmessage\"\n",
+        ),
+        (
+            Options {
+                multi_line: true,
+                type_hints: vec![("id".into(), TypeHint::String)].into_boxed_slice(),
+                ..Options::new(false)
+            },
+            "lvl=\"INFO\" msg=\"some\nmessage\" target=\"request\" module=\"stored::http\" user=\"Thomas \\\"T\\\"\" id=\"123\" admin=true \"user name\"=1.5\n",
+        ),
+    ];
+    for (options, want) in tests {
+        assert_eq!(format(&record, &options), want, "{options:?}");
+    }
+}
+
+#[test]
+fn format_duration_type_hint() {
+    use std::time::Duration;
+
+    let tests = [
+        (Duration::from_nanos(100), "0.0001"),
+        (Duration::from_nanos(12_500), "0.0125"),
+        (Duration::from_millis(250), "250"),
+        (Duration::from_millis(1500), "1500"),
+        (
+            Duration::new(u64::MAX, 999_999_999),
+            "18446744073709551615999.999999",
+        ),
+    ];
+    let options = Options {
+        type_hints: vec![("took".into(), TypeHint::Duration)].into_boxed_slice(),
+        ..Options::new(false)
+    };
+    for (duration, want) in tests {
+        let kvs: &[(&str, log::kv::Value)] = &[("took", log::kv::Value::from_debug(&duration))];
+        let record = Record::builder()
+            .args(format_args!("msg"))
+            .target("target")
+            .key_values(&kvs)
+            .build();
+        let want = format!("lvl=\"INFO\" msg=\"msg\" target=\"target\" module=\"\" took={want}\n");
+        assert_eq!(format(&record, &options), want, "{duration:?}");
+    }
+
+    // Not durations.
+    let kvs: &[(&str, &dyn log::kv::ToValue)] = &[("took", &"12"), ("took", &"soon")];
+    let record = Record::builder()
+        .args(format_args!("msg"))
+        .target("target")
+        .key_values(&kvs)
+        .build();
+    let want = "lvl=\"INFO\" msg=\"msg\" target=\"target\" module=\"\" took=12 took=\"soon\"\n";
+    assert_eq!(format(&record, &options), want);
+}
+
+#[test]
+fn format_max_size() {
+    let kvs = [("key", "value")];
+    let record = Record::builder()
+        .args(format_args!("message"))
+        .level(Level::Warn)
+        .target("target")
+        .key_values(&kvs)
+        .build();
+
+    // The message is truncated first, also if it's formatted last.
+    let options = Options {
+        max_size: 9,
+        ..Options::new(false)
+    };
+    let want =
+        "lvl=\"WARN\" msg=\"message\" target=\"target\" module=\"\" key=\"va\" truncated=true\n";
+    assert_eq!(format(&record, &options), want);
+    let options = Options {
+        max_size: 9,
+        msg_last: true,
+        ..Options::new(false)
+    };
+    let want =
+        "lvl=\"WARN\" target=\"target\" module=\"\" key=\"va\" truncated=true msg=\"message\"\n";
+    assert_eq!(format(&record, &options), want);
+}
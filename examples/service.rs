@@ -0,0 +1,38 @@
+#[cfg(feature = "service")]
+fn main() {
+    use log::info;
+    use std_logger::service;
+
+    // Initialize the logger, adding the request id to all records logged while
+    // handling a request.
+    service::init();
+
+    info!("service started");
+
+    // Fake the handling of a few requests. In a real service `service::handle`
+    // would be called by the HTTP server for each request.
+    for path in ["/", "/missing", "/panic"] {
+        let _ = service::handle("GET", path, |_| http_handler(path));
+    }
+}
+
+#[cfg(feature = "service")]
+fn http_handler(path: &str) -> u16 {
+    use log::warn;
+
+    match path {
+        "/" => 200,
+        // This panic is logged with the request id and the request is logged
+        // with status code 500.
+        "/panic" => panic!("oops"),
+        _ => {
+            warn!(path = path; "page not found");
+            404
+        }
+    }
+}
+
+#[cfg(not(feature = "service"))]
+fn main() {
+    panic!("enable the `service` feature to run this example");
+}
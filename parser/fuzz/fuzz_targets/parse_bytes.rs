@@ -0,0 +1,68 @@
+//! Parses random bytes, read in chunks of random sizes to test the buffer
+//! handling of the parser.
+//!
+//! Run using `cargo +nightly fuzz run parse_bytes` from the `parser` directory.
+
+#![no_main]
+
+use std::io::{self, Read};
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std_logger_parser::ParserOptions;
+
+#[derive(Debug, Arbitrary)]
+struct Input<'a> {
+    /// Sizes of the reads, cycled.
+    chunks: Vec<u8>,
+    interleaved: bool,
+    timestamp_values: bool,
+    unit_values: bool,
+    max_error_line: u8,
+    input: &'a [u8],
+}
+
+/// Reader that returns the input in chunks.
+struct Chunked<'a> {
+    input: &'a [u8],
+    chunks: &'a [u8],
+    n: usize,
+}
+
+impl<'a> Read for Chunked<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = match self.chunks.get(self.n % self.chunks.len().max(1)) {
+            Some(size) => usize::from(*size).max(1),
+            None => buf.len(),
+        };
+        self.n += 1;
+        let n = chunk.min(buf.len()).min(self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Ok(n)
+    }
+}
+
+fuzz_target!(|input: Input<'_>| {
+    let options = ParserOptions::new()
+        .interleaved(input.interleaved)
+        .timestamp_values(input.timestamp_values)
+        .unit_values(input.unit_values)
+        .max_error_line(input.max_error_line.into());
+    let reader = Chunked {
+        input: input.input,
+        chunks: &input.chunks,
+        n: 0,
+    };
+    let mut parser = options.parse(reader);
+    while let Some(result) = parser.next() {
+        if let Ok(record) = result {
+            // Writing a parsed record and parsing it again must succeed.
+            let mut output = Vec::new();
+            record.write_logfmt(&mut output).unwrap();
+            let reparsed = std_logger_parser::parse(&*output).next().unwrap();
+            assert!(reparsed.is_ok(), "{:?}: {:?}", record, reparsed);
+        }
+        let _ = parser.last_raw();
+    }
+});
@@ -0,0 +1,117 @@
+//! Parses structured mutations of valid logfmt records, e.g. values with
+//! (unbalanced) quotes, escapes and new lines.
+//!
+//! Run using `cargo +nightly fuzz run parse_logfmt` from the `parser`
+//! directory.
+
+#![no_main]
+
+use arbitrary::Arbitrary;
+use libfuzzer_sys::fuzz_target;
+use std_logger_parser::ParserOptions;
+
+#[derive(Debug, Arbitrary)]
+struct Record<'a> {
+    fields: Vec<Field<'a>>,
+    /// Line ending, `\n` if `None`.
+    line_end: Option<&'a str>,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Field<'a> {
+    Timestamp(&'a str),
+    Level(&'a str),
+    Message(&'a str),
+    Target(&'a str),
+    Module(&'a str),
+    File(&'a str, u32),
+    Line(u32),
+    KeyValue(Key<'a>, Value<'a>),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Key<'a> {
+    Naked(&'a str),
+    Quoted(&'a str),
+}
+
+#[derive(Debug, Arbitrary)]
+enum Value<'a> {
+    Quoted(&'a str),
+    Naked(&'a str),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    /// Bare key, without a value.
+    None,
+}
+
+impl<'a> Record<'a> {
+    fn write(&self, buf: &mut Vec<u8>) {
+        for field in &self.fields {
+            match field {
+                Field::Timestamp(ts) => write_quoted(buf, "ts", ts),
+                Field::Level(lvl) => write_quoted(buf, "lvl", lvl),
+                Field::Message(msg) => write_quoted(buf, "msg", msg),
+                Field::Target(target) => write_quoted(buf, "target", target),
+                Field::Module(module) => write_quoted(buf, "module", module),
+                Field::File(file, line) => write_quoted(buf, "file", &format!("{}:{}", file, line)),
+                Field::Line(line) => buf.extend_from_slice(format!("line={} ", line).as_bytes()),
+                Field::KeyValue(key, value) => {
+                    match key {
+                        Key::Naked(key) => buf.extend_from_slice(key.as_bytes()),
+                        Key::Quoted(key) => {
+                            buf.push(b'"');
+                            buf.extend_from_slice(key.as_bytes());
+                            buf.push(b'"');
+                        }
+                    }
+                    match value {
+                        Value::Quoted(value) => {
+                            buf.extend_from_slice(b"=\"");
+                            buf.extend_from_slice(value.as_bytes());
+                            buf.push(b'"');
+                        }
+                        Value::Naked(value) => {
+                            buf.push(b'=');
+                            buf.extend_from_slice(value.as_bytes());
+                        }
+                        Value::Bool(value) => {
+                            buf.extend_from_slice(format!("={}", value).as_bytes())
+                        }
+                        Value::Int(value) => {
+                            buf.extend_from_slice(format!("={}", value).as_bytes())
+                        }
+                        Value::Float(value) => {
+                            buf.extend_from_slice(format!("={}", value).as_bytes())
+                        }
+                        Value::None => {}
+                    }
+                    buf.push(b' ');
+                }
+            }
+        }
+        buf.extend_from_slice(self.line_end.unwrap_or("\n").as_bytes());
+    }
+}
+
+fn write_quoted(buf: &mut Vec<u8>, key: &str, value: &str) {
+    buf.extend_from_slice(key.as_bytes());
+    buf.extend_from_slice(b"=\"");
+    buf.extend_from_slice(value.as_bytes());
+    buf.extend_from_slice(b"\" ");
+}
+
+fuzz_target!(|input: (Vec<Record<'_>>, bool, bool)| {
+    let (records, interleaved, strict) = input;
+    let mut logs = Vec::new();
+    for record in &records {
+        record.write(&mut logs);
+    }
+    let options = ParserOptions::new()
+        .interleaved(interleaved)
+        .strict(strict)
+        .timestamp_values(true)
+        .unit_values(true);
+    for _ in options.parse(&*logs) {}
+});
@@ -0,0 +1,263 @@
+//! Streaming aggregation of records, see [`Aggregator`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+
+use crate::export::field;
+use crate::series::{bucket_index, bucket_start};
+use crate::{Column, Record, Value};
+
+/// Aggregates records one at a time.
+///
+/// This is implemented by [`CountBy`], [`Percentiles`], [`ErrorRate`] and
+/// [`TimeSeries`], which allows multiple aggregations to be done while parsing
+/// the logs only once.
+///
+/// [`TimeSeries`]: crate::TimeSeries
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use std_logger_parser::{parse, Aggregator, Column, CountBy, ErrorRate, Percentiles};
+///
+/// let logs = /* Open some log file, anything that implements `io::Read`. */
+/// #    b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"request\" target=\"request\" status=200 response_time=12\n" as &[u8];
+///
+/// let mut status = CountBy::new(Column::Key("status".into()));
+/// let mut response_time = Percentiles::new("response_time");
+/// let mut errors = ErrorRate::new(Duration::from_secs(60));
+/// let mut aggregators: [&mut dyn Aggregator; 3] = [&mut status, &mut response_time, &mut errors];
+/// for record in parse(logs) {
+///     let record = record.expect("failed to parse record");
+///     for aggregator in aggregators.iter_mut() {
+///         aggregator.add(&record);
+///     }
+/// }
+///
+/// for (status, count) in status.counts() {
+///     println!("status {}: {} requests", status, count);
+/// }
+/// println!("p99 response time: {:?}", response_time.percentile(99.0));
+/// for (time, rate) in errors.points() {
+///     println!("{:?}: {:.1}% errors", time, rate * 100.0);
+/// }
+/// ```
+pub trait Aggregator {
+    /// Add `record` to the aggregation.
+    fn add(&mut self, record: &Record);
+}
+
+/// Counts the records grouped by the value of a column, e.g. the number of
+/// requests per status code.
+///
+/// Values are compared as strings, formatted as in [`write_csv`]. Records
+/// without a value for the column are counted separately, see
+/// [`CountBy::missing`].
+///
+/// [`write_csv`]: crate::write_csv
+#[derive(Debug)]
+pub struct CountBy {
+    column: Column,
+    counts: HashMap<String, u64>,
+    missing: u64,
+}
+
+impl CountBy {
+    /// Count the records grouped by the value of `column`.
+    pub fn new(column: Column) -> CountBy {
+        CountBy {
+            column,
+            counts: HashMap::new(),
+            missing: 0,
+        }
+    }
+
+    /// Add `record` to the count of its value.
+    pub fn add(&mut self, record: &Record) {
+        match field(record, &self.column) {
+            Some(value) => match self.counts.get_mut(&*value) {
+                Some(count) => *count += 1,
+                None => {
+                    let _ = self.counts.insert(value.into_owned(), 1);
+                }
+            },
+            None => self.missing += 1,
+        }
+    }
+
+    /// Returns the values and the number of records with that value, the
+    /// most common value first.
+    pub fn counts(&self) -> Vec<(&str, u64)> {
+        let mut counts = self
+            .counts
+            .iter()
+            .map(|(value, count)| (value.as_str(), *count))
+            .collect::<Vec<_>>();
+        counts.sort_unstable_by(|(a_value, a), (b_value, b)| b.cmp(a).then(a_value.cmp(b_value)));
+        counts
+    }
+
+    /// Returns the number of records without a value for the column.
+    pub const fn missing(&self) -> u64 {
+        self.missing
+    }
+}
+
+impl Aggregator for CountBy {
+    fn add(&mut self, record: &Record) {
+        CountBy::add(self, record);
+    }
+}
+
+/// Calculates percentiles of the values of a key, e.g. the 99th percentile of
+/// the response time.
+///
+/// Integers and floats are used as is, durations (see
+/// [`ParserOptions::unit_values`]) are converted to seconds. Records without
+/// such a value for the key are ignored.
+///
+/// The percentiles are exact, which means all values are kept in memory.
+///
+/// [`ParserOptions::unit_values`]: crate::ParserOptions::unit_values
+#[derive(Debug)]
+pub struct Percentiles {
+    key: String,
+    values: Vec<f64>,
+    /// Whether or not `values` is sorted.
+    sorted: bool,
+}
+
+impl Percentiles {
+    /// Calculate the percentiles of the values of `key`.
+    pub fn new(key: &str) -> Percentiles {
+        Percentiles {
+            key: key.to_owned(),
+            values: Vec::new(),
+            sorted: true,
+        }
+    }
+
+    /// Add the value of `record`, if any.
+    pub fn add(&mut self, record: &Record) {
+        let value = match record.key_values.get(self.key.as_str()) {
+            Some(Value::Int(value)) => *value as f64,
+            Some(Value::Float(value)) if !value.is_nan() => *value,
+            Some(Value::Duration(value)) => value.as_secs_f64(),
+            _ => return,
+        };
+        self.values.push(value);
+        self.sorted = false;
+    }
+
+    /// Returns the number of values.
+    pub fn count(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns the `percentile`th percentile, between `0.0` (the minimum) and
+    /// `100.0` (the maximum), using the nearest-rank method. Returns `None` if
+    /// no values were added.
+    ///
+    /// # Panics
+    ///
+    /// This panics if `percentile` is not between `0.0` and `100.0`.
+    pub fn percentile(&mut self, percentile: f64) -> Option<f64> {
+        assert!(
+            (0.0..=100.0).contains(&percentile),
+            "percentile must be between 0 and 100"
+        );
+        if self.values.is_empty() {
+            return None;
+        }
+        if !self.sorted {
+            self.values.sort_unstable_by(f64::total_cmp);
+            self.sorted = true;
+        }
+        let rank = (percentile / 100.0 * self.values.len() as f64).ceil() as usize;
+        Some(self.values[rank.saturating_sub(1)])
+    }
+}
+
+impl Aggregator for Percentiles {
+    fn add(&mut self, record: &Record) {
+        Percentiles::add(self, record);
+    }
+}
+
+/// Calculates the fraction of error records over time.
+///
+/// The records are grouped into buckets of a fixed size based on their
+/// timestamp, like [`TimeSeries`]. Records without a timestamp are ignored.
+///
+/// [`TimeSeries`]: crate::TimeSeries
+#[derive(Debug)]
+pub struct ErrorRate {
+    bucket: Duration,
+    level: Level,
+    /// Bucket index -> (errors, total).
+    buckets: BTreeMap<u128, (u64, u64)>,
+}
+
+impl ErrorRate {
+    /// Create a new error rate using buckets of `bucket` size. Defaults to
+    /// counting error records, see [`ErrorRate::level`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if `bucket` is zero.
+    pub fn new(bucket: Duration) -> ErrorRate {
+        assert!(!bucket.is_zero(), "bucket size of error rate can't be zero");
+        ErrorRate {
+            bucket,
+            level: Level::Error,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Set the level of records that are considered errors, records with a
+    /// more severe level are also considered errors. For example
+    /// [`Level::Warn`] counts both warning and error records.
+    pub fn level(mut self, level: Level) -> ErrorRate {
+        self.level = level;
+        self
+    }
+
+    /// Add `record` to the bucket of its timestamp.
+    pub fn add(&mut self, record: &Record) {
+        let index = match bucket_index(record, self.bucket) {
+            Some(index) => index,
+            None => return,
+        };
+        let (errors, total) = self.buckets.entry(index).or_insert((0, 0));
+        if record.level <= self.level {
+            *errors += 1;
+        }
+        *total += 1;
+    }
+
+    /// Returns the error rate, between `0.0` and `1.0`, per bucket, ordered by
+    /// time.
+    ///
+    /// Each point is the start time of the bucket and the fraction of the
+    /// records in it that are errors. Buckets without records are not
+    /// included.
+    pub fn points(&self) -> Vec<(SystemTime, f64)> {
+        self.buckets
+            .iter()
+            .map(|(index, (errors, total))| {
+                let rate = *errors as f64 / *total as f64;
+                (bucket_start(*index, self.bucket), rate)
+            })
+            .collect()
+    }
+}
+
+impl Aggregator for ErrorRate {
+    fn add(&mut self, record: &Record) {
+        ErrorRate::add(self, record);
+    }
+}
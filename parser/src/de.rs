@@ -0,0 +1,180 @@
+//! Deserialising a [`Record`] into a user defined type, see
+//! [`Record::deserialize_into`].
+
+use std::time::{Duration, SystemTime};
+
+use serde::de::value::{Error, MapDeserializer};
+use serde::de::{Deserialize, Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+
+use crate::{Record, Value};
+
+impl Record {
+    /// Deserialise the record into `T`.
+    ///
+    /// The record is deserialised as a map containing the key-value pairs, as
+    /// well as the following reserved fields (if present in the record):
+    ///  * `ts`: the timestamp, deserialises into a [`SystemTime`],
+    ///  * `lvl`: the log level, e.g. `INFO`,
+    ///  * `msg`: the message,
+    ///  * `target`: the target,
+    ///  * `module`: the module,
+    ///  * `file`: the file and line number, e.g. `src/main.rs:12`.
+    ///
+    /// Requires the *serde* feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use serde::Deserialize;
+    /// use std_logger_parser::parse;
+    ///
+    /// #[derive(Deserialize)]
+    /// struct Request {
+    ///     msg: String,
+    ///     method: String,
+    ///     status: u16,
+    ///     user: Option<String>,
+    /// }
+    ///
+    /// let logs: &[u8] = b"lvl=\"INFO\" msg=\"request\" target=\"request\" method=\"GET\" status=200\n";
+    /// for record in parse(logs) {
+    ///     let record = record.expect("failed to parse record");
+    ///     let request: Request = record.deserialize_into().expect("invalid request log");
+    ///     assert_eq!(request.msg, "request");
+    ///     assert_eq!(request.method, "GET");
+    ///     assert_eq!(request.status, 200);
+    ///     assert_eq!(request.user, None);
+    /// }
+    /// ```
+    pub fn deserialize_into<'de, T>(&'de self) -> Result<T, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        let timestamp = self.timestamp.map(|ts| ("ts", Field::Timestamp(ts)));
+        let module = self.module.as_deref().map(|m| ("module", Field::Str(m)));
+        let file = self
+            .file
+            .as_ref()
+            .map(|(file, line)| ("file", Field::String(format!("{}:{}", file, line))));
+        let key_values = self
+            .key_values
+            .iter()
+            .map(|(key, value)| (key.as_str(), Field::Value(value)));
+        let fields = timestamp
+            .into_iter()
+            .chain(Some(("lvl", Field::Str(self.level.as_str()))))
+            .chain(Some(("msg", Field::Str(&self.msg))))
+            .chain(Some(("target", Field::Str(&self.target))))
+            .chain(module)
+            .chain(file)
+            .chain(key_values);
+        T::deserialize(MapDeserializer::new(fields))
+    }
+}
+
+/// Deserialiser for a single field of a [`Record`].
+enum Field<'de> {
+    Str(&'de str),
+    String(String),
+    Timestamp(SystemTime),
+    Value(&'de Value),
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Field<'de> {
+    type Deserializer = Field<'de>;
+
+    fn into_deserializer(self) -> Field<'de> {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Field<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Field::Str(value) => visitor.visit_borrowed_str(value),
+            Field::String(value) => visitor.visit_string(value),
+            Field::Timestamp(timestamp) => deserialize_timestamp(timestamp, visitor),
+            Field::Value(value) => match value {
+                Value::Null => visitor.visit_unit(),
+                Value::Bool(b) => visitor.visit_bool(*b),
+                Value::Int(i) => visitor.visit_i64(*i),
+                Value::Float(f) => visitor.visit_f64(*f),
+                Value::String(s) => visitor.visit_borrowed_str(s),
+                Value::Timestamp(timestamp) => deserialize_timestamp(*timestamp, visitor),
+                Value::Duration(duration) => deserialize_duration(*duration, visitor),
+                Value::Bytes(bytes) => visitor.visit_u64(*bytes),
+            },
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Field::Value(Value::Null) => visitor.visit_none(),
+            field => visitor.visit_some(field),
+        }
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        // Support unit variants, e.g. `lvl` into a `log::Level`.
+        match self {
+            Field::Str(value) => visitor.visit_enum(value.into_deserializer()),
+            Field::Value(Value::String(value)) => {
+                visitor.visit_enum(value.as_str().into_deserializer())
+            }
+            Field::String(value) => visitor.visit_enum(value.into_deserializer()),
+            field => field.deserialize_any(visitor),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Deserialises `timestamp` in the same way as the serde implementation of
+/// [`SystemTime`] serialises it.
+fn deserialize_timestamp<'de, V>(timestamp: SystemTime, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let elapsed = timestamp
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let fields = [
+        ("secs_since_epoch", elapsed.as_secs()),
+        ("nanos_since_epoch", u64::from(elapsed.subsec_nanos())),
+    ];
+    visitor.visit_map(MapDeserializer::new(fields.iter().copied()))
+}
+
+/// Deserialises `duration` in the same way as the serde implementation of
+/// [`Duration`] serialises it.
+fn deserialize_duration<'de, V>(duration: Duration, visitor: V) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    let fields = [
+        ("secs", duration.as_secs()),
+        ("nanos", u64::from(duration.subsec_nanos())),
+    ];
+    visitor.visit_map(MapDeserializer::new(fields.iter().copied()))
+}
@@ -0,0 +1,415 @@
+//! Exporting records as table, e.g. to load them into a dataframe, see
+//! [`write_csv`] and, with the *arrow* and *parquet* features, `to_arrow` and
+//! `write_parquet`.
+
+use std::borrow::{Borrow, Cow};
+use std::convert::Infallible;
+use std::io::{self, Write};
+use std::str::FromStr;
+
+use crate::{write_timestamp, Record, Value};
+
+/// Column of an exported table, see [`write_csv`].
+///
+/// Can be parsed from a string: `ts`, `lvl`, `msg`, `target`, `module`, `file`
+/// and `line` are parsed as the record's fields (using the same keys as
+/// std-logger), anything else as [`Column::Key`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Column {
+    /// [`Record::timestamp`].
+    Timestamp,
+    /// [`Record::level`].
+    Level,
+    /// [`Record::msg`].
+    Message,
+    /// [`Record::target`].
+    Target,
+    /// [`Record::module`].
+    Module,
+    /// File of [`Record::file`].
+    File,
+    /// Line of [`Record::file`].
+    Line,
+    /// Value in [`Record::key_values`].
+    Key(String),
+}
+
+impl Column {
+    /// Name of the column, as used in the header.
+    pub fn name(&self) -> &str {
+        match self {
+            Column::Timestamp => "ts",
+            Column::Level => "lvl",
+            Column::Message => "msg",
+            Column::Target => "target",
+            Column::Module => "module",
+            Column::File => "file",
+            Column::Line => "line",
+            Column::Key(key) => key,
+        }
+    }
+}
+
+impl FromStr for Column {
+    /// This can always return [`Column::Key`].
+    type Err = Infallible;
+
+    fn from_str(column: &str) -> Result<Self, Self::Err> {
+        Ok(match column {
+            "ts" => Column::Timestamp,
+            "lvl" => Column::Level,
+            "msg" => Column::Message,
+            "target" => Column::Target,
+            "module" => Column::Module,
+            "file" => Column::File,
+            "line" => Column::Line,
+            key => Column::Key(key.to_owned()),
+        })
+    }
+}
+
+/// Returns the value of `column` as string, or `None` if the record doesn't
+/// have a value for it.
+pub(crate) fn field<'r>(record: &'r Record, column: &Column) -> Option<Cow<'r, str>> {
+    match column {
+        Column::Timestamp => record.timestamp.map(|ts| {
+            let mut buf = Vec::with_capacity(30);
+            write_timestamp(&mut buf, ts);
+            Cow::Owned(String::from_utf8(buf).unwrap())
+        }),
+        Column::Level => Some(Cow::Borrowed(record.level.as_str())),
+        Column::Message => Some(Cow::Borrowed(&record.msg)),
+        Column::Target => Some(Cow::Borrowed(&record.target)),
+        Column::Module => record.module.as_deref().map(Cow::Borrowed),
+        Column::File => record.file.as_ref().map(|(file, _)| Cow::Borrowed(&**file)),
+        Column::Line => record
+            .file
+            .as_ref()
+            .map(|(_, line)| line.to_string().into()),
+        Column::Key(key) => match record.key_values.get(key.as_str())? {
+            Value::Null => None,
+            Value::Bool(b) => Some(Cow::Borrowed(if *b { "true" } else { "false" })),
+            Value::Int(i) => Some(i.to_string().into()),
+            // NOTE: using `Debug` for the same reasons as `write_logfmt`.
+            Value::Float(f) => Some(format!("{f:?}").into()),
+            Value::String(value) => Some(Cow::Borrowed(value)),
+            Value::Timestamp(ts) => {
+                let mut buf = Vec::with_capacity(30);
+                write_timestamp(&mut buf, *ts);
+                Some(Cow::Owned(String::from_utf8(buf).unwrap()))
+            }
+            Value::Duration(duration) => Some(format!("{duration:?}").into()),
+            Value::Bytes(bytes) => Some(format!("{bytes}B").into()),
+        },
+    }
+}
+
+/// Write `records` as CSV, with a header containing the names of `columns`.
+///
+/// Each record is written as a single row containing the values of
+/// `columns`, missing values (and [`Value::Null`]) are written as empty
+/// fields. Values are formatted as in [`Record::write_logfmt`], without
+/// quotes, e.g. timestamps as `2021-02-23T13:15:48.624447Z` and durations as
+/// `1.5ms`. Fields containing a comma, quote or new line are quoted, following
+/// RFC 4180, and rows end with `\n`.
+///
+/// # Examples
+///
+/// ```
+/// use std_logger_parser::{parse, write_csv, Column};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"GET /\" target=\"request\" status=200
+/// ts=\"2021-02-23T13:15:49.000000Z\" lvl=\"INFO\" msg=\"POST /users, batch\" target=\"request\"
+/// ";
+///
+/// let columns = ["ts", "msg", "status"].map(|c| c.parse::<Column>().unwrap());
+/// let records = parse(logs).map(|record| record.expect("failed to parse record"));
+/// let mut output = Vec::new();
+/// write_csv(&mut output, &columns, records)?;
+/// assert_eq!(
+///     output,
+///     b"ts,msg,status
+/// 2021-02-23T13:15:48.624447Z,GET /,200
+/// 2021-02-23T13:15:49.000000Z,\"POST /users, batch\",
+/// "
+/// );
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_csv<W, I, R>(mut writer: W, columns: &[Column], records: I) -> io::Result<()>
+where
+    W: Write,
+    I: IntoIterator<Item = R>,
+    R: Borrow<Record>,
+{
+    let mut buf = Vec::with_capacity(256);
+    write_csv_row(&mut buf, columns.iter().map(|c| Some(c.name())));
+    writer.write_all(&buf)?;
+    for record in records {
+        let record = record.borrow();
+        buf.clear();
+        let fields = columns
+            .iter()
+            .map(|column| field(record, column))
+            .collect::<Vec<_>>();
+        write_csv_row(&mut buf, fields.iter().map(Option::as_deref));
+        writer.write_all(&buf)?;
+    }
+    Ok(())
+}
+
+/// Write a single CSV row containing `fields`.
+fn write_csv_row<'a, F>(buf: &mut Vec<u8>, fields: F)
+where
+    F: Iterator<Item = Option<&'a str>>,
+{
+    for (i, field) in fields.enumerate() {
+        if i != 0 {
+            buf.push(b',');
+        }
+        let field = field.unwrap_or("");
+        if field.contains(&[',', '"', '\n', '\r'][..]) {
+            buf.push(b'"');
+            buf.extend_from_slice(field.replace('"', "\"\"").as_bytes());
+            buf.push(b'"');
+        } else {
+            buf.extend_from_slice(field.as_bytes());
+        }
+    }
+    buf.push(b'\n');
+}
+
+#[cfg(feature = "arrow")]
+mod arrow {
+    use std::borrow::Borrow;
+    use std::convert::TryFrom;
+    use std::iter::FromIterator;
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    use arrow_array::{
+        ArrayRef, BooleanArray, DurationNanosecondArray, Float64Array, Int64Array, RecordBatch,
+        StringArray, TimestampNanosecondArray, UInt32Array, UInt64Array,
+    };
+    use arrow_schema::{ArrowError, DataType, Field, Schema, TimeUnit};
+
+    use super::{field, Column};
+    use crate::{Record, Value};
+
+    /// Convert `records` into an Arrow [`RecordBatch`] containing `columns`.
+    ///
+    /// The record's fields use the following types:
+    /// * [`Column::Timestamp`]: nanosecond timestamp in UTC.
+    /// * [`Column::Level`], [`Column::Message`] and [`Column::Target`]: string.
+    /// * [`Column::Module`] and [`Column::File`]: nullable string.
+    /// * [`Column::Line`]: nullable `u32`.
+    ///
+    /// The type of a [`Column::Key`] is based on the values of all records:
+    /// if all values are integers the column uses `i64`, if all values are
+    /// numbers `f64`, and similarly for booleans, timestamps, durations
+    /// (nanoseconds) and byte sizes (`u64`). Otherwise the values are
+    /// converted to strings as in [`write_csv`]. Missing values and
+    /// [`Value::Null`] are null.
+    ///
+    /// [`write_csv`]: super::write_csv
+    ///
+    /// # Errors
+    ///
+    /// This returns an error if `columns` is empty.
+    pub fn to_arrow<I, R>(columns: &[Column], records: I) -> Result<RecordBatch, ArrowError>
+    where
+        I: IntoIterator<Item = R>,
+        R: Borrow<Record>,
+    {
+        let records = records.into_iter().collect::<Vec<R>>();
+        let records = records.iter().map(Borrow::borrow).collect::<Vec<&Record>>();
+        let mut fields = Vec::with_capacity(columns.len());
+        let mut arrays = Vec::with_capacity(columns.len());
+        for column in columns {
+            let (array, nullable) = to_array(column, &records);
+            fields.push(Field::new(
+                column.name(),
+                array.data_type().clone(),
+                nullable,
+            ));
+            arrays.push(array);
+        }
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+    }
+
+    /// Returns the values of `column` as array and whether or not the column
+    /// is nullable.
+    fn to_array(column: &Column, records: &[&Record]) -> (ArrayRef, bool) {
+        let strings = || -> ArrayRef {
+            let values = records
+                .iter()
+                .map(|record| field(record, column))
+                .collect::<Vec<_>>();
+            Arc::new(StringArray::from_iter(values))
+        };
+        match column {
+            Column::Timestamp => {
+                let values = records.iter().map(|r| r.timestamp.map(timestamp_nanos));
+                let array = TimestampNanosecondArray::from_iter(values).with_timezone("UTC");
+                (Arc::new(array), true)
+            }
+            Column::Level | Column::Message | Column::Target => (strings(), false),
+            Column::Module | Column::File => (strings(), true),
+            Column::Line => {
+                let values = records.iter().map(|r| r.file.as_ref().map(|(_, l)| *l));
+                (Arc::new(UInt32Array::from_iter(values)), true)
+            }
+            Column::Key(key) => {
+                let values = records
+                    .iter()
+                    .map(|r| match r.key_values.get(key.as_str()) {
+                        Some(Value::Null) | None => None,
+                        Some(value) => Some(value),
+                    })
+                    .collect::<Vec<_>>();
+                let array: ArrayRef = match key_type(values.iter().flatten().copied()) {
+                    Some(DataType::Boolean) => {
+                        Arc::new(BooleanArray::from_iter(values.iter().map(|v| match v {
+                            Some(Value::Bool(b)) => Some(*b),
+                            _ => None,
+                        })))
+                    }
+                    Some(DataType::Int64) => {
+                        Arc::new(Int64Array::from_iter(values.iter().map(|v| match v {
+                            Some(Value::Int(i)) => Some(*i),
+                            _ => None,
+                        })))
+                    }
+                    Some(DataType::Float64) => {
+                        Arc::new(Float64Array::from_iter(values.iter().map(|v| match v {
+                            Some(Value::Int(i)) => Some(*i as f64),
+                            Some(Value::Float(f)) => Some(*f),
+                            _ => None,
+                        })))
+                    }
+                    Some(DataType::Timestamp(..)) => Arc::new(
+                        TimestampNanosecondArray::from_iter(values.iter().map(|v| match v {
+                            Some(Value::Timestamp(ts)) => Some(timestamp_nanos(*ts)),
+                            _ => None,
+                        }))
+                        .with_timezone("UTC"),
+                    ),
+                    Some(DataType::Duration(..)) => Arc::new(DurationNanosecondArray::from_iter(
+                        values.iter().map(|v| match v {
+                            Some(Value::Duration(d)) => i64::try_from(d.as_nanos()).ok(),
+                            _ => None,
+                        }),
+                    )),
+                    Some(DataType::UInt64) => {
+                        Arc::new(UInt64Array::from_iter(values.iter().map(|v| match v {
+                            Some(Value::Bytes(b)) => Some(*b),
+                            _ => None,
+                        })))
+                    }
+                    _ => strings(),
+                };
+                (array, true)
+            }
+        }
+    }
+
+    /// Returns the type of a key column with `values`, or `None` for strings.
+    fn key_type<'a, I>(values: I) -> Option<DataType>
+    where
+        I: Iterator<Item = &'a Value>,
+    {
+        let mut data_type = None;
+        for value in values {
+            let value_type = match value {
+                Value::Bool(_) => DataType::Boolean,
+                Value::Int(_) => DataType::Int64,
+                Value::Float(_) => DataType::Float64,
+                Value::Timestamp(_) => DataType::Timestamp(TimeUnit::Nanosecond, None),
+                Value::Duration(_) => DataType::Duration(TimeUnit::Nanosecond),
+                Value::Bytes(_) => DataType::UInt64,
+                Value::Null | Value::String(_) => return None,
+            };
+            data_type = match (data_type, value_type) {
+                (None, value_type) => Some(value_type),
+                (Some(a), b) if a == b => Some(a),
+                (
+                    Some(DataType::Int64 | DataType::Float64),
+                    DataType::Int64 | DataType::Float64,
+                ) => Some(DataType::Float64),
+                (Some(_), _) => return None,
+            };
+        }
+        data_type
+    }
+
+    /// Returns the number of nanoseconds since the Unix epoch.
+    fn timestamp_nanos(timestamp: SystemTime) -> i64 {
+        match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.as_nanos() as i64,
+            Err(err) => -(err.duration().as_nanos() as i64),
+        }
+    }
+}
+
+#[cfg(feature = "arrow")]
+pub use arrow::to_arrow;
+
+/// Write `records` as a Parquet file containing `columns`, using the types as
+/// described in [`to_arrow`]. Parquet doesn't support durations, so these are
+/// written as number of nanoseconds (`i64`) instead.
+///
+/// The file is written using a single row group, see the [parquet] crate to
+/// write larger files.
+///
+/// [parquet]: https://docs.rs/parquet
+#[cfg(feature = "parquet")]
+pub fn write_parquet<W, I, R>(
+    writer: W,
+    columns: &[Column],
+    records: I,
+) -> Result<(), parquet::errors::ParquetError>
+where
+    W: Write + Send,
+    I: IntoIterator<Item = R>,
+    R: Borrow<Record>,
+{
+    let batch = durations_as_nanos(to_arrow(columns, records)?)?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(writer, batch.schema(), None)?;
+    writer.write(&batch)?;
+    writer.close().map(|_| ())
+}
+
+/// Replace the duration columns in `batch` with the number of nanoseconds.
+#[cfg(feature = "parquet")]
+fn durations_as_nanos(
+    batch: arrow_array::RecordBatch,
+) -> Result<arrow_array::RecordBatch, arrow_schema::ArrowError> {
+    use std::sync::Arc;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::DurationNanosecondType;
+    use arrow_array::{Array, ArrayRef, Int64Array};
+    use arrow_schema::{DataType, Field, Schema};
+
+    let schema = batch.schema();
+    let mut fields = Vec::with_capacity(schema.fields().len());
+    let mut arrays = Vec::with_capacity(schema.fields().len());
+    for (field, array) in schema.fields().iter().zip(batch.columns()) {
+        if let DataType::Duration(_) = field.data_type() {
+            let durations = array.as_primitive::<DurationNanosecondType>();
+            let nanos = Int64Array::new(durations.values().clone(), durations.nulls().cloned());
+            fields.push(Arc::new(Field::new(
+                field.name(),
+                DataType::Int64,
+                field.is_nullable(),
+            )));
+            arrays.push(Arc::new(nanos) as ArrayRef);
+        } else {
+            fields.push(field.clone());
+            arrays.push(array.clone());
+        }
+    }
+    arrow_array::RecordBatch::try_new(Arc::new(Schema::new(fields)), arrays)
+}
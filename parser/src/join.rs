@@ -0,0 +1,217 @@
+//! Correlating records from two streams by a shared key, see [`Join`].
+
+use std::collections::HashMap;
+use std::iter::Fuse;
+use std::mem;
+use std::time::{Duration, SystemTime};
+
+use crate::{ParseError, Record, Value};
+
+/// Correlates the records of two streams by the value of a shared key, e.g. a
+/// `request_id`.
+///
+/// This can be used to see the request record (from standard out) of a request
+/// together with the error records (from standard error) logged while handling
+/// the request. Records with the same value for the key are collected into a
+/// [`JoinGroup`].
+///
+/// The streams are consumed in order of the timestamps of the records, so both
+/// are expected to be (mostly) ordered by time, as log files are. A group is
+/// returned once no record for the key was seen for the [`window`], based on
+/// the timestamps of the records (not the wall-clock time), or when both
+/// streams end. This keeps the memory usage bounded to the records in the
+/// window. Records without the key, or with a value that isn't a string,
+/// integer or boolean, are ignored.
+///
+/// Parse errors of either stream are returned as is.
+///
+/// [`window`]: Join::window
+///
+/// # Examples
+///
+/// ```
+/// use std_logger_parser::{parse, Join};
+///
+/// let requests = /* Open the request logs (standard out). */
+/// #    b"ts=\"2021-02-23T13:15:48.000000Z\" lvl=\"INFO\" msg=\"GET /\" target=\"request\" request_id=\"1\"\n" as &[u8];
+/// let errors = /* Open the error logs (standard error). */
+/// #    b"ts=\"2021-02-23T13:15:47.000000Z\" lvl=\"ERROR\" msg=\"oops\" request_id=\"1\"\n" as &[u8];
+///
+/// for group in Join::new("request_id", parse(requests), parse(errors)) {
+///     let group = group.expect("failed to parse record");
+///     for request in &group.left {
+///         println!("{}: {} ({} errors)", group.key, request.msg, group.right.len());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Join<L, R>
+where
+    L: Iterator,
+    R: Iterator,
+{
+    key: String,
+    window: Duration,
+    left: Fuse<L>,
+    right: Fuse<R>,
+    /// Next record of `left` and `right`, if already read.
+    left_next: Option<Record>,
+    right_next: Option<Record>,
+    /// Latest timestamp seen.
+    watermark: Option<SystemTime>,
+    /// Key value -> group, including the timestamp of the last record.
+    groups: HashMap<String, (JoinGroup, Option<SystemTime>)>,
+    /// Completed groups, ready to be returned.
+    ready: Vec<JoinGroup>,
+}
+
+/// Records with the same key value, see [`Join`].
+#[derive(Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct JoinGroup {
+    /// Value of the key, integers and booleans are converted to a string.
+    pub key: String,
+    /// Records from the left (first) stream, ordered as read.
+    pub left: Vec<Record>,
+    /// Records from the right (second) stream, ordered as read.
+    pub right: Vec<Record>,
+}
+
+impl<L, R> Join<L, R>
+where
+    L: Iterator<Item = Result<Record, ParseError>>,
+    R: Iterator<Item = Result<Record, ParseError>>,
+{
+    /// Join the records of `left` and `right` by the value of `key`. Defaults
+    /// to a window of one minute.
+    pub fn new<IL, IR>(key: &str, left: IL, right: IR) -> Join<L, R>
+    where
+        IL: IntoIterator<IntoIter = L>,
+        IR: IntoIterator<IntoIter = R>,
+    {
+        Join {
+            key: key.to_owned(),
+            window: Duration::from_secs(60),
+            left: left.into_iter().fuse(),
+            right: right.into_iter().fuse(),
+            left_next: None,
+            right_next: None,
+            watermark: None,
+            groups: HashMap::new(),
+            ready: Vec::new(),
+        }
+    }
+
+    /// Set the time after the last record of a key before the group is
+    /// considered complete.
+    ///
+    /// This should be larger than the time it takes to handle a request,
+    /// including the difference in time between the error and request records.
+    pub fn window(mut self, window: Duration) -> Join<L, R> {
+        self.window = window;
+        self
+    }
+
+    /// Add `record` to its group, if it has the key.
+    fn add(&mut self, record: Record, left: bool) {
+        let key = match record.key_values.get(self.key.as_str()) {
+            Some(Value::String(value)) => value.clone(),
+            Some(Value::Int(value)) => value.to_string(),
+            Some(Value::Bool(value)) => value.to_string(),
+            _ => return,
+        };
+        let timestamp = record.timestamp;
+        if timestamp > self.watermark {
+            self.watermark = timestamp;
+        }
+        let (group, last) = self.groups.entry(key).or_insert_with_key(|key| {
+            let group = JoinGroup {
+                key: key.clone(),
+                left: Vec::new(),
+                right: Vec::new(),
+            };
+            (group, timestamp)
+        });
+        if left {
+            group.left.push(record);
+        } else {
+            group.right.push(record);
+        }
+        if timestamp > *last {
+            *last = timestamp;
+        }
+    }
+
+    /// Move the groups that are complete to `ready`, or all groups if `all` is
+    /// `true`.
+    fn complete(&mut self, all: bool) {
+        let deadline = match self.watermark.and_then(|ts| ts.checked_sub(self.window)) {
+            Some(deadline) => Some(deadline),
+            None if all => None,
+            None => return,
+        };
+        let mut completed = Vec::new();
+        self.groups.retain(|_, (group, last)| {
+            let complete = all || *last < deadline;
+            if complete {
+                completed.push((*last, mem::take(group)));
+            }
+            !complete
+        });
+        // Return the oldest groups first, `ready` is used as a stack.
+        completed.sort_by(|(a, _), (b, _)| b.cmp(a));
+        self.ready
+            .extend(completed.into_iter().map(|(_, group)| group));
+    }
+}
+
+impl<L, R> Iterator for Join<L, R>
+where
+    L: Iterator<Item = Result<Record, ParseError>>,
+    R: Iterator<Item = Result<Record, ParseError>>,
+{
+    type Item = Result<JoinGroup, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(group) = self.ready.pop() {
+                return Some(Ok(group));
+            }
+
+            if self.left_next.is_none() {
+                match self.left.next() {
+                    Some(Ok(record)) => self.left_next = Some(record),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {}
+                }
+            }
+            if self.right_next.is_none() {
+                match self.right.next() {
+                    Some(Ok(record)) => self.right_next = Some(record),
+                    Some(Err(err)) => return Some(Err(err)),
+                    None => {}
+                }
+            }
+
+            // Add the oldest record first.
+            let left = match (&self.left_next, &self.right_next) {
+                (Some(left), Some(right)) => left.timestamp <= right.timestamp,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => {
+                    self.complete(true);
+                    return self.ready.pop().map(Ok);
+                }
+            };
+            let record = if left {
+                self.left_next.take()
+            } else {
+                self.right_next.take()
+            };
+            if let Some(record) = record {
+                self.add(record, left);
+                self.complete(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,462 @@
+//! Parsing of JSON records (NDJSON), as created by the JSON and Google Cloud
+//! Platform (gcloud) formats of std-logger, and writing records as JSON, see
+//! [`Record::write_json`].
+
+use std::borrow::Cow;
+use std::io::{self, Write};
+use std::str;
+use std::time::SystemTime;
+
+use log::Level;
+
+use crate::{
+    parse_optional_types, parse_timestamp, write_timestamp, FieldErrors, ParseErrorKind,
+    ParserOptions, Record, RecordError, RecordRef, ValueRef,
+};
+
+/// How timestamps are represented by [`Record::write_json`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum JsonTimestamp {
+    /// RFC 3339 string, in the same format as std-logger, e.g.
+    /// `"2021-02-23T13:15:48.624447Z"`.
+    Rfc3339,
+    /// Number of seconds since the Unix epoch, including the fraction, e.g.
+    /// `1614086148.624447`.
+    UnixSecs,
+    /// Number of milliseconds since the Unix epoch, e.g. `1614086148624`.
+    UnixMillis,
+    /// Number of nanoseconds since the Unix epoch, e.g.
+    /// `1614086148624447000`.
+    UnixNanos,
+}
+
+impl Record {
+    /// Write the record as a single JSON object (a JSON Lines line), including
+    /// the line ending, in the same format as the JSON format of std-logger.
+    ///
+    /// The fields are always written in the same order: `timestamp` (if
+    /// any), `level`, `message`, `target`, `module`, the key-value pairs sorted
+    /// by key and finally `file` and `line` (if any). Timestamps, both of the
+    /// record and [`Value::Timestamp`] values, are written using the
+    /// representation in `timestamps`. Durations and byte sizes are written as
+    /// strings in the same format as [`Record::write_logfmt`], e.g. `"1.5ms"`
+    /// and `"1024B"`, and floats that can't be represented in JSON (`NaN` and
+    /// infinity) as `null`.
+    ///
+    /// The line is written using a single call to [`Write::write_all`].
+    ///
+    /// [`Value::Timestamp`]: crate::Value::Timestamp
+    ///
+    /// # Examples
+    ///
+    /// Converting logfmt logs into JSON Lines.
+    ///
+    /// ```
+    /// use std_logger_parser::{parse, JsonTimestamp};
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Hello\" target=\"main\" user=\"Bob\" id=123\n";
+    ///
+    /// let mut output = Vec::new();
+    /// for record in parse(logs) {
+    ///     let record = record.expect("failed to parse record");
+    ///     record.write_json(&mut output, JsonTimestamp::UnixMillis)?;
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     br#"{"timestamp":1614086148624,"level":"INFO","message":"Hello","target":"main","module":"","id":123,"user":"Bob"}
+    /// "#
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_json<W: Write>(&self, mut writer: W, timestamps: JsonTimestamp) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(256);
+        buf.push(b'{');
+        if let Some(timestamp) = self.timestamp {
+            buf.extend_from_slice(b"\"timestamp\":");
+            write_json_timestamp(&mut buf, timestamp, timestamps);
+            buf.push(b',');
+        }
+        buf.extend_from_slice(b"\"level\":\"");
+        buf.extend_from_slice(self.level.as_str().as_bytes());
+        buf.extend_from_slice(b"\",\"message\":");
+        write_json_string(&mut buf, &self.msg);
+        buf.extend_from_slice(b",\"target\":");
+        write_json_string(&mut buf, &self.target);
+        buf.extend_from_slice(b",\"module\":");
+        write_json_string(&mut buf, self.module.as_deref().unwrap_or(""));
+
+        let mut key_values = self.key_values.iter().collect::<Vec<_>>();
+        key_values.sort_unstable_by_key(|(key, _)| *key);
+        for (key, value) in key_values {
+            buf.push(b',');
+            write_json_string(&mut buf, key);
+            buf.push(b':');
+            match value {
+                crate::Value::Null => buf.extend_from_slice(b"null"),
+                crate::Value::Bool(b) => {
+                    buf.extend_from_slice(if *b { b"true" } else { b"false" });
+                }
+                crate::Value::Int(i) => buf.extend_from_slice(i.to_string().as_bytes()),
+                crate::Value::Float(f) if f.is_finite() => {
+                    buf.extend_from_slice(format!("{f:?}").as_bytes());
+                }
+                crate::Value::Float(_) => buf.extend_from_slice(b"null"),
+                crate::Value::String(value) => write_json_string(&mut buf, value),
+                crate::Value::Timestamp(timestamp) => {
+                    write_json_timestamp(&mut buf, *timestamp, timestamps);
+                }
+                crate::Value::Duration(duration) => {
+                    buf.extend_from_slice(format!("\"{duration:?}\"").as_bytes());
+                }
+                crate::Value::Bytes(bytes) => {
+                    buf.extend_from_slice(format!("\"{bytes}B\"").as_bytes());
+                }
+            }
+        }
+
+        if let Some((file, line)) = &self.file {
+            buf.extend_from_slice(b",\"file\":");
+            write_json_string(&mut buf, file);
+            buf.extend_from_slice(format!(",\"line\":\"{line}\"").as_bytes());
+        }
+        buf.extend_from_slice(b"}\n");
+        writer.write_all(&buf)
+    }
+}
+
+/// Writes `timestamp` using the `representation`.
+fn write_json_timestamp(buf: &mut Vec<u8>, timestamp: SystemTime, representation: JsonTimestamp) {
+    let nanos = match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_nanos() as i128,
+        Err(err) => -(err.duration().as_nanos() as i128),
+    };
+    let value = match representation {
+        JsonTimestamp::Rfc3339 => {
+            buf.push(b'"');
+            write_timestamp(buf, timestamp);
+            buf.push(b'"');
+            return;
+        }
+        JsonTimestamp::UnixSecs => {
+            let sign = if nanos < 0 { "-" } else { "" };
+            let (secs, nanos) = (nanos.abs() / 1_000_000_000, nanos.abs() % 1_000_000_000);
+            if nanos % 1000 == 0 {
+                format!("{sign}{secs}.{:06}", nanos / 1000)
+            } else {
+                format!("{sign}{secs}.{nanos:09}")
+            }
+        }
+        JsonTimestamp::UnixMillis => nanos.div_euclid(1_000_000).to_string(),
+        JsonTimestamp::UnixNanos => nanos.to_string(),
+    };
+    buf.extend_from_slice(value.as_bytes());
+}
+
+/// Writes `value` as JSON string, escaping it according to RFC 8259, section
+/// 7.
+fn write_json_string(buf: &mut Vec<u8>, value: &str) {
+    buf.push(b'"');
+    for c in value.chars() {
+        match c {
+            '"' => buf.extend_from_slice(b"\\\""),
+            '\\' => buf.extend_from_slice(b"\\\\"),
+            '\n' => buf.extend_from_slice(b"\\n"),
+            '\r' => buf.extend_from_slice(b"\\r"),
+            '\t' => buf.extend_from_slice(b"\\t"),
+            c if c.is_control() => {
+                buf.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes());
+            }
+            c => buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    buf.push(b'"');
+}
+
+/// A JSON value.
+enum Value<'a> {
+    Null,
+    Bool(bool),
+    /// Unparsed number.
+    Number(&'a str),
+    String(Cow<'a, str>),
+    /// Unparsed object or array.
+    Raw(&'a str),
+}
+
+/// Returns `true` if `input` looks like a JSON record, i.e. it starts with `{`.
+pub(crate) fn is_json(input: &[u8]) -> bool {
+    input.first() == Some(&b'{')
+}
+
+/// Parses a single JSON object `input` into a record.
+///
+/// Supports the fields of both the JSON and gcloud formats, e.g. `level` and
+/// `severity` for the log level. Numbers are parsed as [`ValueRef::Int`] or
+/// [`ValueRef::Float`], strings are never parsed as another type (except for
+/// the types enabled in the options, e.g. timestamps using
+/// [`ParserOptions::timestamp_values`]) and objects and arrays are returned as
+/// a string containing the JSON.
+pub(crate) fn parse_record<'a>(
+    input: &'a [u8],
+    options: &ParserOptions,
+) -> Result<RecordRef<'a>, RecordError> {
+    let input = str::from_utf8(input).map_err(|_| ParseErrorKind::InvalidValue)?;
+    let mut record = RecordRef::empty();
+    let mut errors = FieldErrors::default();
+    let mut file = None;
+    let mut line = None;
+    let result = parse_object(input, &mut |key, value| {
+        match (&*key, value) {
+            ("timestamp", Value::String(timestamp)) => {
+                record.timestamp = errors.check(&key, parse_timestamp(timestamp.as_bytes()));
+            }
+            ("level" | "severity", Value::String(level)) => {
+                if let Some(level) = errors.check(&key, parse_level(&level)) {
+                    record.level = level;
+                }
+            }
+            ("message", Value::String(msg)) => record.msg = msg,
+            ("target", Value::String(target)) => record.target = target,
+            ("module", Value::String(module)) => {
+                if !module.is_empty() {
+                    record.module = Some(module);
+                }
+            }
+            ("file", Value::String(f)) => file = Some(f),
+            ("line", value) => line = errors.check(&key, parse_line(value)),
+            // Used by the gcloud format, e.g.
+            // `"sourceLocation":{"file":"src/main.rs","line":"12"}`.
+            ("sourceLocation", Value::Raw(object)) => {
+                let rest = parse_object(object, &mut |key, value| {
+                    match (&*key, value) {
+                        ("file", Value::String(f)) => file = Some(f),
+                        ("line", value) => line = errors.check(&key, parse_line(value)),
+                        _ => {}
+                    }
+                    Ok(())
+                })?;
+                if !eat_whitespace(rest).is_empty() {
+                    return Err(ParseErrorKind::InvalidJson);
+                }
+            }
+            (_, value) => record.key_values.push((key, value.into_value(options))),
+        }
+        Ok(())
+    });
+    match result {
+        Ok(rest) if eat_whitespace(rest).is_empty() => {}
+        Ok(_) => return Err(errors.stop(ParseErrorKind::InvalidJson)),
+        Err(kind) => return Err(errors.stop(kind)),
+    }
+    if let Some(file) = file {
+        record.file = Some((file, line.unwrap_or(0)));
+    }
+    errors.finish(record)
+}
+
+impl<'a> Value<'a> {
+    fn into_value(self, options: &ParserOptions) -> ValueRef<'a> {
+        match self {
+            Value::Null => ValueRef::Null,
+            Value::Bool(b) => ValueRef::Bool(b),
+            Value::Number(n) => {
+                if let Ok(i) = n.parse() {
+                    ValueRef::Int(i)
+                } else if let Ok(f) = n.parse() {
+                    ValueRef::Float(f)
+                } else {
+                    ValueRef::String(Cow::Borrowed(n))
+                }
+            }
+            Value::String(s) => parse_optional_types(&s, options).unwrap_or(ValueRef::String(s)),
+            Value::Raw(raw) => ValueRef::String(Cow::Borrowed(raw)),
+        }
+    }
+}
+
+/// Parses the log level, supporting both the levels used by the [`log`] crate
+/// and the severities used by gcloud.
+fn parse_level(level: &str) -> Result<Level, ParseErrorKind> {
+    match level {
+        // See <https://cloud.google.com/logging/docs/reference/v2/rest/v2/LogEntry#logseverity>.
+        "DEFAULT" | "NOTICE" => Ok(Level::Info),
+        "WARNING" => Ok(Level::Warn),
+        "CRITICAL" | "ALERT" | "EMERGENCY" => Ok(Level::Error),
+        level => level.parse().map_err(|_| ParseErrorKind::InvalidLevel),
+    }
+}
+
+/// Parses the line number, either a string (as std-logger does) or a number.
+fn parse_line(value: Value<'_>) -> Result<u32, ParseErrorKind> {
+    let line = match &value {
+        Value::String(line) => line,
+        Value::Number(line) => *line,
+        _ => return Err(ParseErrorKind::InvalidFile),
+    };
+    line.parse().map_err(|_| ParseErrorKind::InvalidFile)
+}
+
+/// Parses a JSON object, calling `f` for each field. Returns the remaining
+/// input.
+fn parse_object<'a, F>(input: &'a str, f: &mut F) -> Result<&'a str, ParseErrorKind>
+where
+    F: FnMut(Cow<'a, str>, Value<'a>) -> Result<(), ParseErrorKind>,
+{
+    let input = eat_whitespace(input)
+        .strip_prefix('{')
+        .ok_or(ParseErrorKind::InvalidJson)?;
+    if let Some(rest) = eat_whitespace(input).strip_prefix('}') {
+        return Ok(rest);
+    }
+
+    let mut input = input;
+    loop {
+        let (rest, key) = parse_string(eat_whitespace(input))?;
+        let rest = eat_whitespace(rest)
+            .strip_prefix(':')
+            .ok_or(ParseErrorKind::InvalidJson)?;
+        let (rest, value) = parse_value(eat_whitespace(rest))?;
+        f(key, value)?;
+
+        let rest = eat_whitespace(rest);
+        if let Some(rest) = rest.strip_prefix(',') {
+            input = rest;
+        } else {
+            return rest.strip_prefix('}').ok_or(ParseErrorKind::InvalidJson);
+        }
+    }
+}
+
+/// Parses a single JSON value, returning the remaining input.
+fn parse_value(input: &str) -> Result<(&str, Value<'_>), ParseErrorKind> {
+    match input.as_bytes().first() {
+        Some(b'"') => parse_string(input).map(|(rest, s)| (rest, Value::String(s))),
+        Some(b'{' | b'[') => {
+            let end = nested_end(input)?;
+            Ok((&input[end..], Value::Raw(&input[..end])))
+        }
+        Some(b'-' | b'0'..=b'9') => {
+            let end = input
+                .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+                .unwrap_or(input.len());
+            Ok((&input[end..], Value::Number(&input[..end])))
+        }
+        _ => {
+            if let Some(rest) = input.strip_prefix("true") {
+                Ok((rest, Value::Bool(true)))
+            } else if let Some(rest) = input.strip_prefix("false") {
+                Ok((rest, Value::Bool(false)))
+            } else if let Some(rest) = input.strip_prefix("null") {
+                Ok((rest, Value::Null))
+            } else {
+                Err(ParseErrorKind::InvalidJson)
+            }
+        }
+    }
+}
+
+/// Parses a JSON string, returning the remaining input.
+fn parse_string(input: &str) -> Result<(&str, Cow<'_, str>), ParseErrorKind> {
+    let input = input.strip_prefix('"').ok_or(ParseErrorKind::InvalidJson)?;
+    let mut escaped = false;
+    for (i, b) in input.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Ok((&input[i + 1..], unescape(&input[..i])?)),
+            _ => {}
+        }
+    }
+    Err(ParseErrorKind::InvalidJson)
+}
+
+/// Returns the length of the object or array at the start of `input`.
+fn nested_end(input: &str) -> Result<usize, ParseErrorKind> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, b) in input.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            _ if in_string => {}
+            b'{' | b'[' => depth += 1,
+            b'}' | b']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok(i + 1);
+                }
+            }
+            _ => {}
+        }
+    }
+    Err(ParseErrorKind::InvalidJson)
+}
+
+/// Unescapes a JSON string, see RFC 8259, section 7
+/// <https://datatracker.ietf.org/doc/html/rfc8259#section-7>.
+fn unescape(value: &str) -> Result<Cow<'_, str>, ParseErrorKind> {
+    if !value.contains('\\') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{0008}',
+            Some('f') => '\u{000C}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = parse_hex(&mut chars)?;
+                let c = if (0xD800..0xDC00).contains(&high) {
+                    // Surrogate pair, e.g. `😀`.
+                    if chars.next() != Some('\\') || chars.next() != Some('u') {
+                        return Err(ParseErrorKind::InvalidJson);
+                    }
+                    let low = parse_hex(&mut chars)?;
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(ParseErrorKind::InvalidJson);
+                    }
+                    char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                } else {
+                    char::from_u32(high)
+                };
+                c.ok_or(ParseErrorKind::InvalidJson)?
+            }
+            _ => return Err(ParseErrorKind::InvalidJson),
+        };
+        unescaped.push(c);
+    }
+    Ok(Cow::Owned(unescaped))
+}
+
+/// Parses the four hexadecimal digits of a `\uXXXX` escape.
+fn parse_hex(chars: &mut str::Chars<'_>) -> Result<u32, ParseErrorKind> {
+    let rest = chars.as_str();
+    match rest.get(..4) {
+        Some(hex) if hex.bytes().all(|b| b.is_ascii_hexdigit()) => {
+            *chars = rest[4..].chars();
+            u32::from_str_radix(hex, 16).map_err(|_| ParseErrorKind::InvalidJson)
+        }
+        _ => Err(ParseErrorKind::InvalidJson),
+    }
+}
+
+/// Removes all JSON whitespace at the start of `input`.
+fn eat_whitespace(input: &str) -> &str {
+    input.trim_start_matches([' ', '\t', '\n', '\r'])
+}
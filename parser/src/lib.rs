@@ -1,30 +1,415 @@
 //! See the [`Parser`] type.
 
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::convert::Infallible;
 use std::fmt;
-use std::io::{self, Read};
+#[cfg(feature = "tokio")]
+use std::future::Future;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+#[cfg(feature = "tokio")]
+use std::pin::Pin;
 use std::str::{self, FromStr};
+#[cfg(feature = "tokio")]
+use std::task::{self, Poll};
+use std::thread;
 use std::time::{Duration, SystemTime};
 
+#[cfg(feature = "tokio")]
+use futures_core::Stream;
 use log::Level;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std_logger_core::key_needs_quotes;
+#[cfg(feature = "tokio")]
+use tokio::io::{AsyncRead, ReadBuf};
+
+mod aggregate;
+#[cfg(feature = "serde")]
+mod de;
+mod export;
+mod join;
+mod json;
+mod merge;
+mod series;
+mod units;
+
+pub use aggregate::{Aggregator, CountBy, ErrorRate, Percentiles};
+#[cfg(feature = "arrow")]
+pub use export::to_arrow;
+#[cfg(feature = "parquet")]
+pub use export::write_parquet;
+pub use export::{write_csv, Column};
+pub use join::{Join, JoinGroup};
+pub use json::JsonTimestamp;
+pub use merge::Merged;
+pub use series::{Aggregate, TimeSeries};
 
 /// Create a new [`Parser`].
 pub fn parse<R>(reader: R) -> Parser<R>
 where
     R: Read,
 {
-    Parser {
-        parsed: 0,
-        reader,
-        buf: Vec::with_capacity(4096),
-        needs_read: true,
-        hit_eof: false,
+    Parser::new(reader, ParserOptions::new())
+}
+
+/// Parse the records of multiple `readers`, returning them merged in
+/// timestamp order (key `ts`).
+///
+/// Each reader is expected to be ordered by time, as log files are, e.g. the
+/// logs of multiple replicas of a service. Each reader is read on its own
+/// thread, which reads ahead a few records, so a reader that blocks doesn't
+/// block reading the other readers. Records without a timestamp are returned
+/// as soon as they're read. Parse errors are returned as is, use
+/// [`Merged::last_source`] to determine the reader of a record or error.
+///
+/// To return the records in timestamp order the next record of all readers
+/// is needed, a reader that blocks is waited for up to one second, see
+/// [`Merged::max_wait`].
+///
+/// # Examples
+///
+/// ```
+/// use std_logger_parser::parse_merged;
+///
+/// let replica1 = /* Open the logs of the first replica. */
+/// #    b"ts=\"2021-02-23T13:15:48.000000Z\" lvl=\"INFO\" msg=\"second\"\n" as &[u8];
+/// let replica2 = /* Open the logs of the second replica. */
+/// #    b"ts=\"2021-02-23T13:15:47.000000Z\" lvl=\"INFO\" msg=\"first\"\n" as &[u8];
+///
+/// let mut records = parse_merged(vec![replica1, replica2]);
+/// while let Some(record) = records.next() {
+///     let record = record.expect("failed to parse record");
+///     let replica = records.last_source().unwrap() + 1;
+///     println!("replica {}: {}", replica, record.msg);
+/// }
+/// ```
+pub fn parse_merged<R>(readers: Vec<R>) -> Merged
+where
+    R: Read + Send + 'static,
+{
+    Merged::new(readers.into_iter().map(parse).collect())
+}
+
+/// Options for the [`Parser`].
+///
+/// # Examples
+///
+/// Follow a log file, similar to `tail -f`.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use std::time::Duration;
+///
+/// use std_logger_parser::ParserOptions;
+///
+/// # fn main() -> Result<(), std_logger_parser::ParseError> {
+/// let logs = File::open("/var/log/my_app.log").expect("failed to open logs");
+///
+/// let options = ParserOptions::new()
+///     .follow(true)
+///     .poll_interval(Duration::from_millis(100));
+/// for record in options.parse(logs) {
+///     let record = record?;
+///
+///     println!("parsed a record: {:?}", record);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ParserOptions {
+    follow: bool,
+    poll_interval: Duration,
+    timestamp_values: bool,
+    unit_values: bool,
+    quoted_strings: bool,
+    resync_key: &'static str,
+    key_aliases: &'static [(&'static str, &'static str)],
+    strict: bool,
+    interleaved: bool,
+    max_error_line: usize,
+}
+
+impl ParserOptions {
+    /// Create the default options.
+    pub const fn new() -> ParserOptions {
+        ParserOptions {
+            follow: false,
+            poll_interval: Duration::from_secs(1),
+            timestamp_values: false,
+            unit_values: false,
+            quoted_strings: false,
+            resync_key: "ts",
+            key_aliases: &[],
+            strict: false,
+            interleaved: false,
+            max_error_line: 1024,
+        }
+    }
+
+    /// Keep following the input after reaching the end of it, similar to
+    /// `tail -f`. Defaults to `false`.
+    ///
+    /// If enabled the parser will not stop when it hits the end of the input,
+    /// instead it will wait [`poll_interval`] and try to read more. This means
+    /// the [`Iterator`] implementation of [`Parser`] will never return `None`
+    /// and that a record without a line ending is only returned once the line
+    /// ending is written.
+    ///
+    /// The [`Stream`] implementation of [`AsyncParser`] (see
+    /// [`ParserOptions::parse_async`]) waits using a Tokio timer, which
+    /// requires the Tokio runtime to have the time driver enabled.
+    ///
+    /// [`poll_interval`]: ParserOptions::poll_interval
+    /// [`Stream`]: futures_core::Stream
+    pub const fn follow(mut self, follow: bool) -> ParserOptions {
+        self.follow = follow;
+        self
+    }
+
+    /// Time to wait before reading again after hitting the end of the input
+    /// when [following] the input. Defaults to one second.
+    ///
+    /// [following]: ParserOptions::follow
+    pub const fn poll_interval(mut self, poll_interval: Duration) -> ParserOptions {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Parse values of key-value pairs that look like a RFC 3339 timestamp,
+    /// e.g. `expires_at="2021-02-23T13:15:48.624447Z"`, into a
+    /// [`Value::Timestamp`]. Defaults to `false`.
+    ///
+    /// Note that this doesn't apply to the [`FromStr`] implementation of
+    /// [`Value`].
+    pub const fn timestamp_values(mut self, enable: bool) -> ParserOptions {
+        self.timestamp_values = enable;
+        self
+    }
+
+    /// Parse values of key-value pairs that look like a duration, e.g.
+    /// `response_time=15ms` or `took=2.5s`, into a [`Value::Duration`] and
+    /// values that look like a byte size, e.g. `size=1.5MB` or `buf=4KiB`, into
+    /// a [`Value::Bytes`]. Defaults to `false`.
+    ///
+    /// Durations support the units `ns`, `us` (or `µs`), `ms`, `s`, `m` and
+    /// `h`, including combinations such as `1h30m`. Byte sizes support the
+    /// units `B`, `kB`, `MB`, `GB`, `TB` (powers of 1000) and `KiB`, `MiB`,
+    /// `GiB`, `TiB` (powers of 1024).
+    ///
+    /// Note that this doesn't apply to the [`FromStr`] implementation of
+    /// [`Value`].
+    pub const fn unit_values(mut self, enable: bool) -> ParserOptions {
+        self.unit_values = enable;
+        self
+    }
+
+    /// Parse quoted values of key-value pairs, e.g. `user_id="00123"`, as
+    /// [`Value::String`], rather than attempting to parse them as a boolean or
+    /// number. Defaults to `false`.
+    ///
+    /// std-logger always quotes strings (and never numbers or booleans), so
+    /// this preserves the type of values (and e.g. leading zeros in IDs) when
+    /// parsing logs created by it. See also the `TypeHint` type of std-logger.
+    pub const fn quoted_strings(mut self, enable: bool) -> ParserOptions {
+        self.quoted_strings = enable;
+        self
+    }
+
+    /// Key that starts a record, used to recover from an error in a multi-line
+    /// record. Defaults to `ts`.
+    ///
+    /// If a record spanning multiple lines, e.g. because of an unbalanced quote
+    /// in a multi-line value, can't be parsed the parser continues with the
+    /// next line starting with this key (e.g. `ts="..."`), rather than
+    /// skipping the entire (possibly incorrectly detected) record.
+    pub const fn resync_key(mut self, key: &'static str) -> ParserOptions {
+        self.resync_key = key;
+        self
+    }
+
+    /// Aliases for the keys of the built-in fields, as `(alias, key)` pairs,
+    /// e.g. `("time", "ts")`. Defaults to no aliases.
+    ///
+    /// This allows parsing logfmt records written with different field names,
+    /// e.g. using std-logger's `Config::with_field_name`. Keys with an alias
+    /// are parsed as the built-in field, e.g. `severity="INFO"` as the log
+    /// level with the alias `("severity", "lvl")`. The keys are `ts`, `lvl`,
+    /// `msg`, `target`, `module` and `file`. Note that the [resync key] is not
+    /// affected by the aliases.
+    ///
+    /// [resync key]: ParserOptions::resync_key
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::Level;
+    /// use std_logger_parser::ParserOptions;
+    ///
+    /// let logs = b"severity=\"WARN\" message=\"Hello\" target=\"main\"\n";
+    /// let options = ParserOptions::new().key_aliases(&[("severity", "lvl"), ("message", "msg")]);
+    /// let record = options.parse(&logs[..]).next().unwrap().unwrap();
+    /// assert_eq!(record.level, Level::Warn);
+    /// assert_eq!(record.msg, "Hello");
+    /// assert!(record.key_values.is_empty());
+    /// ```
+    pub const fn key_aliases(
+        mut self,
+        aliases: &'static [(&'static str, &'static str)],
+    ) -> ParserOptions {
+        self.key_aliases = aliases;
+        self
+    }
+
+    /// Returns the key `key` is an alias for, or `key` itself.
+    fn unalias<'k>(&self, key: &'k str) -> &'k str {
+        self.key_aliases
+            .iter()
+            .find(|(alias, _)| *alias == key)
+            .map_or(key, |(_, key)| key)
+    }
+
+    /// Stop parsing after the first error. Defaults to `false`.
+    ///
+    /// By default the parser skips invalid records and continues with the next
+    /// one. In strict mode the parser stops, i.e. [`Parser`] returns `None`,
+    /// after returning the first error. This is useful to validate logs.
+    pub const fn strict(mut self, enable: bool) -> ParserOptions {
+        self.strict = enable;
+        self
+    }
+
+    /// Detect records that are interleaved with other records, e.g. when
+    /// multiple processes write to the same file without coordination.
+    /// Defaults to `false`.
+    ///
+    /// Concurrent writers can cause a record to be split in two, with another
+    /// record written in between. Normally this results in errors for the
+    /// following records as well, e.g. because of unbalanced quotes. In this
+    /// mode the parser returns an [`ParseErrorKind::Interleaved`] error for the
+    /// partial record and continues at the next plausible record start.
+    ///
+    /// A line that doesn't start with the [resync key], or a `{` for JSON
+    /// formatted records, is considered a fragment of an interleaved record.
+    /// The parser continues at the next line that does start with the key. A
+    /// record that contains the resync key followed by a timestamp, e.g.
+    /// `ts="2021-02-23T13:15:48.624447Z"`, after its start is considered
+    /// truncated by another record starting at that point.
+    ///
+    /// [resync key]: ParserOptions::resync_key
+    pub const fn interleaved(mut self, enable: bool) -> ParserOptions {
+        self.interleaved = enable;
+        self
+    }
+
+    /// Maximum size of the line included in a [`ParseError`], in bytes.
+    /// Defaults to 1 KiB.
+    ///
+    /// Lines of invalid records longer than this are truncated, keeping the
+    /// start and end of the line with a marker in between, e.g. `lvl="INFO"
+    /// msg="a very ... [1000 bytes omitted] ... long message"`. This prevents
+    /// a single corrupted record, which can be megabytes, from blowing up the
+    /// memory usage and error output.
+    pub const fn max_error_line(mut self, max: usize) -> ParserOptions {
+        self.max_error_line = max;
+        self
+    }
+
+    /// Create a new [`Parser`] using these options.
+    pub fn parse<R>(self, reader: R) -> Parser<R>
+    where
+        R: Read,
+    {
+        Parser::new(reader, self)
+    }
+
+    /// Create a new [`AsyncParser`] using these options, see [`parse_async`].
+    ///
+    /// Requires the *tokio* feature.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use futures_util::StreamExt;
+    /// use std_logger_parser::ParserOptions;
+    ///
+    /// # #[tokio::main(flavor = "current_thread")]
+    /// # async fn main() -> Result<(), std_logger_parser::ParseError> {
+    /// let logs = /* Anything that implements `tokio::io::AsyncRead`, e.g. a `TcpStream`. */
+    /// #    b"" as &[u8];
+    ///
+    /// let mut records = ParserOptions::new().strict(true).parse_async(logs);
+    /// while let Some(record) = records.next().await {
+    ///     let record = record?;
+    ///
+    ///     println!("parsed a record: {:?}", record);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(feature = "tokio")]
+    pub fn parse_async<R>(self, reader: R) -> AsyncParser<R>
+    where
+        R: AsyncRead + Unpin,
+    {
+        AsyncParser {
+            parser: Parser::new(reader, self),
+            sleep: None,
+        }
     }
 }
 
+impl Default for ParserOptions {
+    fn default() -> ParserOptions {
+        ParserOptions::new()
+    }
+}
+
+/// Create a new [`Parser`] that reads from an asynchronous `reader`.
+///
+/// The returned [`AsyncParser`] implements [`Stream`], yielding the same items
+/// as the [`Iterator`] implementation of [`Parser`] does. Use
+/// [`ParserOptions::parse_async`] to use other options.
+///
+/// Requires the *tokio* feature.
+///
+/// # Examples
+///
+/// ```
+/// use futures_util::StreamExt;
+/// use std_logger_parser::parse_async;
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() -> Result<(), std_logger_parser::ParseError> {
+/// let logs = /* Anything that implements `tokio::io::AsyncRead`, e.g. a `TcpStream`. */
+/// #    b"" as &[u8];
+///
+/// let mut records = parse_async(logs);
+/// while let Some(record) = records.next().await {
+///     let record = record?;
+///
+///     println!("parsed a record: {:?}", record);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(feature = "tokio")]
+pub fn parse_async<R>(reader: R) -> AsyncParser<R>
+where
+    R: AsyncRead + Unpin,
+{
+    ParserOptions::new().parse_async(reader)
+}
+
 /// A struct to parse logfmt formatted logs.
 ///
+/// The parser also supports JSON formatted logs (one object per line), as
+/// created by the JSON and gcloud formats of std-logger. Lines starting with
+/// `{` are parsed as JSON, this means a single input can contain both logfmt
+/// and JSON formatted records.
+///
 /// See the example below for usage.
 ///
 /// # Notes
@@ -33,10 +418,17 @@ where
 /// loosely check values but isn't too strict about it.
 ///
 /// If this parser returns an [error] it will skip the problematic line and
-/// continue with the next one. Note however that if a problem exists in
-/// multi-line log message the records returned after might be invalid.
+/// continue with the next one. If the problematic record spans multiple lines
+/// the parser continues with the next line that starts with the [resync key]
+/// (`ts` by default). Note however that a problem in a multi-line log message
+/// could still cause the records returned after it to be invalid. Use [strict
+/// mode] to stop parsing after the first error. For logs written by multiple
+/// uncoordinated writers see [interleaved mode].
 ///
 /// [error]: ParseError
+/// [resync key]: ParserOptions::resync_key
+/// [strict mode]: ParserOptions::strict
+/// [interleaved mode]: ParserOptions::interleaved
 ///
 /// # Examples
 ///
@@ -57,26 +449,355 @@ where
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug)]
 pub struct Parser<R> {
     reader: R,
     /// Amount of bytes parsed from the start of `buf`.
     parsed: usize,
     buf: Vec<u8>,
-    /// If `true` `next` will read from `R` into `buf`.
-    needs_read: bool,
-    /// If `fale` `parse_line` will not return `None` if it hits the end of the
-    /// item. Once its `false` `next` will return `None` and `parse_line` will
-    /// return the remainder of the record (if any).
+    /// Offset of the start of `buf` in the reader.
+    buf_offset: u64,
+    /// Range in `buf` of the last returned record, see [`Parser::last_raw`].
+    last: Option<Range<usize>>,
+    /// If `true` the reader hit the end of the input, meaning that the
+    /// remainder of `buf` (if any) is the last record.
     hit_eof: bool,
+    /// If `true` an error was returned in [strict] mode, meaning we stop
+    /// parsing.
+    ///
+    /// [strict]: ParserOptions::strict
+    stopped: bool,
+    options: ParserOptions,
+    stats: ParserStats,
+    /// Called after reading from `reader`, see [`Parser::with_progress`].
+    progress: Option<Box<ProgressFn>>,
+}
+
+impl<R> Parser<R> {
+    fn new(reader: R, options: ParserOptions) -> Parser<R> {
+        Parser {
+            parsed: 0,
+            reader,
+            buf: Vec::with_capacity(4096),
+            buf_offset: 0,
+            last: None,
+            hit_eof: false,
+            stopped: false,
+            options,
+            stats: ParserStats::default(),
+            progress: None,
+        }
+    }
+
+    /// Returns the statistics of the parser so far.
+    pub const fn stats(&self) -> ParserStats {
+        self.stats
+    }
+
+    /// Returns the raw bytes of the last record returned by the parser, as
+    /// read from the reader, or `None` if no record was returned (or the last
+    /// call returned an I/O error or `None`).
+    ///
+    /// This is also returned for invalid records. It can be used to pass
+    /// through the original records, e.g. to filter the records without
+    /// reformatting them. The raw bytes are only available until the next
+    /// record is requested.
+    ///
+    /// # Examples
+    ///
+    /// Only keep the error records, keeping them as they are.
+    ///
+    /// ```
+    /// use log::Level;
+    /// use std_logger_parser::parse;
+    ///
+    /// let logs: &[u8] = b"lvl=INFO msg=\"Hello\" target=\"main\"
+    /// lvl=ERROR msg=\"Oops\" target=\"main\" error=\"not found\"
+    /// ";
+    ///
+    /// let mut output = Vec::new();
+    /// let mut parser = parse(logs);
+    /// while let Some(record) = parser.next() {
+    ///     let record = record.expect("failed to parse record");
+    ///     if record.level == Level::Error {
+    ///         let raw = parser.last_raw().unwrap();
+    ///         output.extend_from_slice(raw.bytes);
+    ///         output.push(b'\n');
+    ///         assert_eq!(raw.range, 35..87);
+    ///     }
+    /// }
+    /// assert_eq!(output, b"lvl=ERROR msg=\"Oops\" target=\"main\" error=\"not found\"\n");
+    /// ```
+    pub fn last_raw(&self) -> Option<RawRecord<'_>> {
+        let range = self.last.clone()?;
+        let start = self.buf_offset + range.start as u64;
+        let end = self.buf_offset + range.end as u64;
+        Some(RawRecord {
+            bytes: &self.buf[range],
+            range: start..end,
+        })
+    }
+
+    /// Call `progress` with the current statistics each time after reading
+    /// from the reader, e.g. to report the progress of importing a (large) log
+    /// file.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger_parser::parse;
+    ///
+    /// let logs = /* Open some log file, anything that implements `io::Read`. */
+    /// #    b"lvl=\"INFO\" msg=\"Hello world\"\n" as &[u8];
+    ///
+    /// let parser = parse(logs).with_progress(|stats| {
+    ///     eprintln!("read {} bytes, parsed {} records", stats.bytes_read, stats.records);
+    /// });
+    /// for record in parser {
+    ///     let record = record.expect("failed to parse record");
+    ///
+    ///     println!("parsed a record: {:?}", record);
+    /// }
+    /// ```
+    pub fn with_progress<F>(mut self, progress: F) -> Parser<R>
+    where
+        F: FnMut(&ParserStats) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+}
+
+impl<R: fmt::Debug> fmt::Debug for Parser<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Parser")
+            .field("reader", &self.reader)
+            .field("parsed", &self.parsed)
+            .field("buf", &self.buf)
+            .field("buf_offset", &self.buf_offset)
+            .field("last", &self.last)
+            .field("hit_eof", &self.hit_eof)
+            .field("stopped", &self.stopped)
+            .field("options", &self.options)
+            .field("stats", &self.stats)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// The raw bytes of a record, see [`Parser::last_raw`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct RawRecord<'a> {
+    /// The bytes of the record, without the line ending.
+    pub bytes: &'a [u8],
+    /// Byte offset range of the record in the reader.
+    pub range: Range<u64>,
+}
+
+/// Callback used in [`Parser::with_progress`].
+type ProgressFn = dyn FnMut(&ParserStats) + Send + Sync;
+
+/// Statistics of a [`Parser`], see [`Parser::stats`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct ParserStats {
+    /// Number of bytes read from the reader.
+    pub bytes_read: u64,
+    /// Number of records successfully parsed.
+    pub records: u64,
+    /// Number of lines skipped because they couldn't be parsed. A record
+    /// spanning multiple lines counts all its lines.
+    pub lines_skipped: u64,
+    /// Number of errors returned, including I/O errors.
+    pub errors: u64,
 }
 
 impl<R: Read> Parser<R> {
-    fn fill_buf(&mut self) -> io::Result<()> {
+    /// Returns the next record, borrowing from the parser's buffer.
+    ///
+    /// This is the same as the [`Iterator`] implementation, but doesn't
+    /// allocate for the fields that don't contain escaped characters. Use
+    /// [`RecordRef::into_owned`] to convert it into a [`Record`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger_parser::parse;
+    ///
+    /// # fn main() -> Result<(), std_logger_parser::ParseError> {
+    /// let logs = /* Open some log file, anything that implements `io::Read`. */
+    /// #    b"" as &[u8];
+    ///
+    /// let mut parser = parse(logs);
+    /// while let Some(record) = parser.next_record() {
+    ///     let record = record?;
+    ///
+    ///     println!("parsed a record: {:?}", record.msg);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn next_record(&mut self) -> Option<Result<RecordRef<'_>, ParseError>> {
+        loop {
+            match self.find_record() {
+                Found::Record(len) => return Some(self.parse_record(len)),
+                Found::Eof => return None,
+                Found::NeedsRead => match self.fill_buf() {
+                    Ok(0) if self.options.follow => {
+                        // Wait for more input to be written.
+                        thread::sleep(self.options.poll_interval);
+                    }
+                    Ok(_) => { /* Try again. */ }
+                    Err(err) => {
+                        self.stopped = self.options.strict;
+                        self.stats.errors += 1;
+                        return Some(Err(ParseError {
+                            line: None,
+                            kind: ParseErrorKind::Io(err),
+                            fields: Vec::new(),
+                        }));
+                    }
+                },
+            }
+        }
+    }
+
+    /// Returns the number of bytes read.
+    fn fill_buf(&mut self) -> io::Result<usize> {
+        let original_len = self.prepare_read();
+        let result = self.reader.read(&mut self.buf[original_len..]);
+        self.finish_read(original_len, result)
+    }
+}
+
+impl<R: Read + Seek> Parser<R> {
+    /// Seek to the first record with a timestamp at or after `timestamp`,
+    /// returning the offset of the record in the reader.
+    ///
+    /// This uses a binary search on the timestamps (key `ts`) of the records,
+    /// so it expects the records to be ordered by time and to start with the
+    /// timestamp, as they are when logged by std-logger. If no such record
+    /// exists it seeks to the end of the reader.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::File;
+    /// use std::time::{Duration, SystemTime};
+    ///
+    /// use std_logger_parser::parse;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let logs = File::open("/var/log/my_app.log")?;
+    ///
+    /// // Only parse the records of the last hour.
+    /// let mut parser = parse(logs);
+    /// parser.seek_to_timestamp(SystemTime::now() - Duration::from_secs(60 * 60))?;
+    /// for record in parser {
+    ///     let record = record.expect("failed to parse record");
+    ///
+    ///     println!("parsed a record: {:?}", record);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn seek_to_timestamp(&mut self, timestamp: SystemTime) -> io::Result<u64> {
+        let end = self.reader.seek(SeekFrom::End(0))?;
+        // Find the lowest position for which the first record at or after it
+        // has a timestamp at or after `timestamp`.
+        let mut low = 0;
+        let mut high = end;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            match find_timestamp(&mut self.reader, mid)? {
+                // All positions up to `start` find the same record.
+                Some((start, ts)) if ts < timestamp => low = start + 1,
+                Some(_) | None => high = mid,
+            }
+        }
+        let offset = match find_timestamp(&mut self.reader, low)? {
+            Some((start, _)) => start,
+            None => end,
+        };
+
+        let offset = self.reader.seek(SeekFrom::Start(offset))?;
+        self.buf.clear();
+        self.buf_offset = offset;
+        self.last = None;
+        self.parsed = 0;
+        self.hit_eof = false;
+        Ok(offset)
+    }
+}
+
+/// Find the first record, starting with a timestamp, at or after `pos`,
+/// returning the offset of the record and its timestamp.
+fn find_timestamp<R: Read + Seek>(
+    reader: &mut R,
+    pos: u64,
+) -> io::Result<Option<(u64, SystemTime)>> {
+    /// Maximum length of `ts="..."` we need to parse the timestamp.
+    const MAX_TS_LEN: usize = 48;
+
+    // A record starts at the start of a line, so we start looking one byte
+    // earlier to find the new line (if `pos` is the start of a line).
+    let mut offset = pos.saturating_sub(1);
+    let _ = reader.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    let mut hit_eof = false;
+    // If `true` `buf` starts at the start of a line.
+    let mut line_start = pos == 0;
+    loop {
+        if line_start {
+            if buf.len() >= MAX_TS_LEN || hit_eof {
+                if let Some(timestamp) = line_timestamp(&buf) {
+                    return Ok(Some((offset, timestamp)));
+                }
+                // Not a record, or invalid timestamp, try the next line.
+                line_start = false;
+                continue;
+            }
+        } else if let Some(idx) = buf.iter().position(|b| *b == b'\n') {
+            drop(buf.drain(..=idx));
+            offset += idx as u64 + 1;
+            line_start = true;
+            continue;
+        } else if hit_eof {
+            return Ok(None);
+        } else {
+            offset += buf.len() as u64;
+            buf.clear();
+        }
+
+        let n = reader.by_ref().take(4096).read_to_end(&mut buf)?;
+        hit_eof = n == 0;
+    }
+}
+
+/// Returns the timestamp of the record starting at the start of `line`, if
+/// any.
+fn line_timestamp(line: &[u8]) -> Option<SystemTime> {
+    let value = line.strip_prefix(b"ts=")?;
+    let value = match value.strip_prefix(b"\"") {
+        Some(value) => &value[..value.iter().position(|b| *b == b'"')?],
+        None => {
+            let end = value.iter().position(|b| matches!(b, b' ' | b'\t' | b'\n'));
+            &value[..end.unwrap_or(value.len())]
+        }
+    };
+    parse_timestamp(value).ok()
+}
+
+impl<R> Parser<R> {
+    /// Prepares `buf` for reading, returning the index from which to read
+    /// into `buf`. [`Parser::finish_read`] must be called after the read.
+    fn prepare_read(&mut self) -> usize {
         self.remove_spaces();
         // Remove already processed bytes.
         drop(self.buf.drain(..self.parsed));
+        self.buf_offset += self.parsed as u64;
         self.parsed = 0;
+        self.last = None;
 
         // If a log message is the same size as the buffer's capacity double the
         // capacity to read more bytes.
@@ -87,13 +808,23 @@ impl<R: Read> Parser<R> {
         // Resize the buffer to read into the unused space.
         let original_len = self.buf.len();
         self.buf.resize(self.buf.capacity(), 0);
-        match self.reader.read(&mut self.buf[original_len..]) {
+        original_len
+    }
+
+    /// Processes the `result` of reading into `buf` after calling
+    /// [`Parser::prepare_read`], returning the number of bytes read.
+    fn finish_read(&mut self, original_len: usize, result: io::Result<usize>) -> io::Result<usize> {
+        match result {
             Ok(n) => {
                 self.buf.truncate(original_len + n);
-                if n == 0 {
+                if n == 0 && !self.options.follow {
                     self.hit_eof = true;
                 }
-                Ok(())
+                self.stats.bytes_read += n as u64;
+                if let Some(progress) = self.progress.as_mut() {
+                    progress(&self.stats);
+                }
+                Ok(n)
             }
             Err(err) => {
                 self.buf.truncate(original_len);
@@ -102,135 +833,397 @@ impl<R: Read> Parser<R> {
         }
     }
 
+    /// Find the next complete record in `buf`, skipping empty lines.
+    fn find_record(&mut self) -> Found {
+        self.last = None;
+        if self.stopped {
+            return Found::Eof;
+        }
+        loop {
+            self.remove_spaces();
+            let input = &self.buf[self.parsed..];
+            let line = single_line(input);
+            if line.len() < input.len() {
+                if line.is_empty() {
+                    // Empty line.
+                    self.parsed += 1;
+                    continue;
+                }
+                return Found::Record(line.len());
+            } else if !self.hit_eof {
+                // Record could continue.
+                return Found::NeedsRead;
+            } else if line.is_empty() {
+                return Found::Eof;
+            } else {
+                // Last record without a line ending.
+                return Found::Record(line.len());
+            }
+        }
+    }
+
+    /// Parse the record of `len` bytes found by [`Parser::find_record`].
+    fn parse_record(&mut self, len: usize) -> Result<RecordRef<'_>, ParseError> {
+        let start = self.parsed;
+        // Mark the line (new line included) as parsed, also if the line is
+        // invalid so that we continue with the next one.
+        self.parsed += len;
+        if let Some(b'\n') = self.buf.get(self.parsed) {
+            self.parsed += 1;
+        }
+        self.last = Some(start..start + len);
+        let line = &self.buf[start..start + len];
+        if self.options.interleaved {
+            if let Some(fragment) = interleaved_fragment(line, self.options.resync_key) {
+                self.stopped = self.options.strict;
+                self.stats.errors += 1;
+                let lines = match fragment {
+                    Fragment::Line(end) => {
+                        if end < len {
+                            // Continue with the line after `end`.
+                            self.parsed = start + end + 1;
+                            self.last = Some(start..start + end);
+                        }
+                        line[..end].iter().filter(|b| **b == b'\n').count() + 1
+                    }
+                    Fragment::Truncated(end) => {
+                        // Continue with the record that starts at `end`.
+                        self.parsed = start + end;
+                        self.last = Some(start..start + end);
+                        line[..end].iter().filter(|b| **b == b'\n').count()
+                    }
+                };
+                self.stats.lines_skipped += lines as u64;
+                let line = &self.buf[start..start + fragment.end()];
+                return Err(ParseError {
+                    line: Some(error_line(line, self.options.max_error_line)),
+                    kind: ParseErrorKind::Interleaved,
+                    fields: Vec::new(),
+                });
+            }
+        }
+        match parse_record(line, &self.options) {
+            Ok(record) => {
+                self.stats.records += 1;
+                Ok(record)
+            }
+            Err(RecordError { kind, fields }) => {
+                self.stopped = self.options.strict;
+                self.stats.errors += 1;
+                // If the record spans multiple lines we might have combined
+                // multiple records, e.g. due to an unbalanced quote. Continue
+                // with the next line that looks like the start of a record.
+                let line = match resync_point(line, self.options.resync_key) {
+                    Some(end) => {
+                        self.parsed = start + end + 1;
+                        self.last = Some(start..start + end);
+                        &line[..end]
+                    }
+                    None => line,
+                };
+                let lines = line.iter().filter(|b| **b == b'\n').count() + 1;
+                self.stats.lines_skipped += lines as u64;
+                Err(ParseError {
+                    line: Some(error_line(line, self.options.max_error_line)),
+                    kind,
+                    fields,
+                })
+            }
+        }
+    }
+
     /// Updates `parsed` to remove all spaces from the start of `buf`.
     fn remove_spaces(&mut self) {
         let input = &self.buf[self.parsed..];
         let input_left = eat_space(input);
         self.parsed += input.len() - input_left.len();
     }
+}
 
-    /// Returns `None` the log message is incomplete.
-    fn parse_line(&mut self) -> Result<Option<Record>, ParseError> {
-        let mut record = Record::empty();
-        let mut record_is_empty = true;
-        // Remove spaces from the start to ensure `create_line_error` doesn't
-        // include a bunch of empty spaces.
-        self.remove_spaces();
-        let mut input = &self.buf[self.parsed..];
+/// Returns `line` for use in [`ParseError::line`], truncating it to `max`
+/// bytes (plus the marker) by removing the middle of the line.
+fn error_line(line: &[u8], max: usize) -> Box<[u8]> {
+    if line.len() <= max {
+        return line.into();
+    }
+    let head = &line[..max - max / 2];
+    let tail = &line[line.len() - max / 2..];
+    let marker = format!(" ... [{} bytes omitted] ... ", line.len() - max);
+    let mut truncated = Vec::with_capacity(max + marker.len());
+    truncated.extend_from_slice(head);
+    truncated.extend_from_slice(marker.as_bytes());
+    truncated.extend_from_slice(tail);
+    truncated.into_boxed_slice()
+}
 
-        loop {
-            input = eat_space(input);
-            if input.is_empty() || input[0] == b'\n' {
-                // Mark the line (new line included) as parser.
-                self.parsed = (self.buf.len() - input.len()) + if input.is_empty() { 0 } else { 1 };
+/// Fragment of an interleaved record, see [`interleaved_fragment`].
+#[derive(Copy, Clone)]
+enum Fragment {
+    /// Fragment doesn't start with the resync key, ends at the line ending at
+    /// the index.
+    Line(usize),
+    /// Record is truncated by another record starting at the index.
+    Truncated(usize),
+}
 
-                return Ok((!record_is_empty).then(|| record));
-            }
+impl Fragment {
+    const fn end(self) -> usize {
+        match self {
+            Fragment::Line(end) | Fragment::Truncated(end) => end,
+        }
+    }
+}
 
-            let (i, key) = parse_key(input).map_err(|err| self.create_line_error(err))?;
-            if i.is_empty() {
-                return Ok(None);
-            }
-            input = i;
+/// Returns the fragment at the start of `record` if it's part of a record
+/// interleaved with another record, see [`ParserOptions::interleaved`].
+fn interleaved_fragment(record: &[u8], key: &str) -> Option<Fragment> {
+    let input = eat_space(record);
+    if json::is_json(input) {
+        return None;
+    }
+    if !starts_with_key(input, key) {
+        let end = resync_point(record, key).unwrap_or(record.len());
+        return Some(Fragment::Line(end));
+    }
+    (1..record.len())
+        .find(|i| starts_with_timestamp_key(&record[*i..], key))
+        .map(Fragment::Truncated)
+}
+
+/// Returns `true` if `input` starts with `key=`.
+fn starts_with_key(input: &[u8], key: &str) -> bool {
+    input.starts_with(key.as_bytes()) && input.get(key.len()) == Some(&b'=')
+}
 
-            let (i, value) = parse_value(input);
-            if i.is_empty() && !self.hit_eof {
-                // If this is the end of the input we expect it to be the end of
-                // the value as well and we don't return here.
-                return Ok(None);
+/// Returns `true` if `input` starts with `key=` followed by a (quoted)
+/// timestamp.
+fn starts_with_timestamp_key(input: &[u8], key: &str) -> bool {
+    if !starts_with_key(input, key) {
+        return false;
+    }
+    let value = &input[key.len() + 1..];
+    let value = value.strip_prefix(b"\"").unwrap_or(value);
+    let end = value
+        .iter()
+        .position(|b| matches!(b, b'"' | b' ' | b'\n'))
+        .unwrap_or(value.len());
+    parse_timestamp(&value[..end]).is_ok()
+}
+
+/// Result of [`Parser::find_record`].
+enum Found {
+    /// Found a (possibly invalid) record of the length.
+    Record(usize),
+    /// Need to read more input before we can parse the next record.
+    NeedsRead,
+    /// Hit the end of the input.
+    Eof,
+}
+
+/// Parses a single complete record from `input`, without the line ending.
+fn parse_record<'a>(
+    input: &'a [u8],
+    options: &ParserOptions,
+) -> Result<RecordRef<'a>, RecordError> {
+    if json::is_json(eat_space(input)) {
+        return json::parse_record(input, options);
+    }
+
+    let mut record = RecordRef::empty();
+    let mut errors = FieldErrors::default();
+    // Key and file of a `file` without a line, which can be written as separate
+    // `line` key, e.g. `file="src/main.rs" line=12`.
+    let mut file_without_line: Option<(Cow<str>, &str)> = None;
+    let mut input = input;
+    loop {
+        input = eat_space(input);
+        if input.is_empty() {
+            if let Some((key, _)) = file_without_line {
+                let _ = errors.check::<()>(&key, Err(ParseErrorKind::InvalidFile));
             }
+            return errors.finish(record);
+        }
+
+        let (i, (key, has_value)) = match parse_key(input) {
+            Ok(result) => result,
+            Err(kind) => return Err(errors.stop(kind)),
+        };
+        if i.len() == input.len() {
+            // No progress, e.g. a new line outside of a quoted value, which
+            // can happen when continuing after an error in a record spanning
+            // multiple lines.
+            input = &input[1..];
+            continue;
+        }
+        input = i;
+
+        let (value, quoted) = if has_value {
+            let (i, value, quoted) = parse_value(input);
             input = i;
+            (value, quoted)
+        } else {
+            // Key without a value, e.g. `key` instead of `key=value`.
+            (&[][..], false)
+        };
 
-            match key {
-                "ts" => {
-                    let timestamp =
-                        parse_timestamp(value).map_err(|err| self.create_line_error(err))?;
-                    record.timestamp = Some(timestamp);
-                }
-                "lvl" => {
-                    let level =
-                        parse_log_level(value).map_err(|err| self.create_line_error(err))?;
+        match options.unalias(&key) {
+            "ts" => record.timestamp = errors.check(&key, parse_timestamp(value)),
+            "lvl" => {
+                if let Some(level) = errors.check(&key, parse_log_level(value)) {
                     record.level = level;
                 }
-                "msg" => {
-                    let msg = parse_string(value).map_err(|err| self.create_line_error(err))?;
-                    record.msg = msg.to_owned();
-                }
-                "target" => {
-                    let target = parse_string(value).map_err(|err| self.create_line_error(err))?;
-                    record.target = target.to_owned();
-                }
-                "module" => {
-                    let module = parse_string(value).map_err(|err| self.create_line_error(err))?;
-                    if !module.is_empty() {
-                        record.module = Some(module.to_owned());
-                    }
+            }
+            "msg" => {
+                if let Some(msg) = errors.check(&key, parse_string(value, quoted)) {
+                    record.msg = msg;
                 }
-                "file" => {
-                    let (file, line) =
-                        parse_file(value).map_err(|err| self.create_line_error(err))?;
-                    record.file = Some((file.to_owned(), line));
+            }
+            "target" => {
+                if let Some(target) = errors.check(&key, parse_string(value, quoted)) {
+                    record.target = target;
                 }
-                _ => {
-                    let value = parse_string(value).map_err(|err| self.create_line_error(err))?;
-                    // Safety: `FromStr` for `Value` never fails.
-                    // TODO: what to do when overwriting a key?
-                    let _ = record
-                        .key_values
-                        .insert(key.to_owned(), value.parse().unwrap());
+            }
+            "module" => {
+                let module = errors.check(&key, parse_string(value, quoted));
+                record.module = module.filter(|module| !module.is_empty());
+            }
+            "file" => match errors.check(&key, parse_file(value)) {
+                Some((file, Some(line))) => record.file = Some((Cow::Borrowed(file), line)),
+                Some((file, None)) => file_without_line = Some((key, file)),
+                None => {}
+            },
+            "line" if file_without_line.is_some() => {
+                let line = str::from_utf8(value)
+                    .ok()
+                    .and_then(|line| line.parse().ok());
+                if let Some(line) = errors.check(&key, line.ok_or(ParseErrorKind::InvalidFile)) {
+                    let (_, file) = file_without_line.take().unwrap();
+                    record.file = Some((Cow::Borrowed(file), line));
                 }
             }
-            // If we get to here we've assigned at least a single field so we
-            // want to keep the record.
-            record_is_empty = false;
+            // Bare key, e.g. `canary`, is used as a flag.
+            _ if !has_value => record.key_values.push((key, ValueRef::Bool(true))),
+            _ if !quoted && value.is_empty() => record.key_values.push((key, ValueRef::Null)),
+            _ => {
+                let value = match errors.check(&key, parse_string(value, quoted)) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let value = match parse_optional_types(&value, options) {
+                    Some(value) => value,
+                    None if quoted && options.quoted_strings => ValueRef::String(value),
+                    None => ValueRef::parse(value),
+                };
+                record.key_values.push((key, value));
+            }
         }
     }
+}
 
-    fn create_line_error(&self, kind: ParseErrorKind) -> ParseError {
-        let line = single_line(&self.buf[self.parsed..])
-            .to_owned()
-            .into_boxed_slice();
-        ParseError {
-            line: Some(line),
-            kind,
+/// Parses `value` as one of the types that have to be enabled in `options`,
+/// e.g. a timestamp using [`ParserOptions::timestamp_values`].
+fn parse_optional_types(value: &str, options: &ParserOptions) -> Option<ValueRef<'static>> {
+    if options.timestamp_values {
+        if let Ok(timestamp) = parse_timestamp(value.as_bytes()) {
+            return Some(ValueRef::Timestamp(timestamp));
         }
     }
+    if options.unit_values {
+        if let Some(duration) = units::parse_duration(value) {
+            return Some(ValueRef::Duration(duration));
+        } else if let Some(bytes) = units::parse_bytes(value) {
+            return Some(ValueRef::Bytes(bytes));
+        }
+    }
+    None
 }
 
 impl<R: Read> Iterator for Parser<R> {
     type Item = Result<Record, ParseError>;
 
     fn next(&mut self) -> Option<Self::Item> {
+        self.next_record()
+            .map(|result| result.map(RecordRef::into_owned))
+    }
+}
+
+/// Asynchronous version of [`Parser`], see [`parse_async`].
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncParser<R> {
+    parser: Parser<R>,
+    /// Waiting before reading again when following the input, see
+    /// [`ParserOptions::follow`].
+    sleep: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+#[cfg(feature = "tokio")]
+impl<R> AsyncParser<R> {
+    /// Returns the statistics of the parser so far, see [`Parser::stats`].
+    pub const fn stats(&self) -> ParserStats {
+        self.parser.stats
+    }
+
+    /// Returns the raw bytes of the last record, see [`Parser::last_raw`].
+    pub fn last_raw(&self) -> Option<RawRecord<'_>> {
+        self.parser.last_raw()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R: AsyncRead + Unpin> Stream for AsyncParser<R> {
+    type Item = Result<Record, ParseError>;
+
+    fn poll_next(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context<'_>,
+    ) -> Poll<Option<Self::Item>> {
+        let AsyncParser {
+            parser: this,
+            sleep,
+        } = &mut *self;
         loop {
-            if self.needs_read {
-                match self.fill_buf() {
-                    Ok(()) => { /* Continue below. */ }
-                    Err(err) => {
-                        return Some(Err(ParseError {
-                            line: None,
-                            kind: ParseErrorKind::Io(err),
-                        }));
-                    }
+            if let Some(s) = sleep {
+                match s.as_mut().poll(ctx) {
+                    Poll::Ready(()) => *sleep = None,
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            match this.find_record() {
+                Found::Record(len) => {
+                    let result = this.parse_record(len).map(RecordRef::into_owned);
+                    return Poll::Ready(Some(result));
                 }
+                Found::Eof => return Poll::Ready(None),
+                Found::NeedsRead => { /* Read below. */ }
             }
 
-            match self.parse_line() {
-                Ok(Some(record)) => return Some(Ok(record)),
-                Ok(None) if self.hit_eof => return None,
-                Ok(None) => {
-                    self.needs_read = true;
-                    continue; // Read again.
+            let original_len = this.prepare_read();
+            let mut buf = ReadBuf::new(&mut this.buf[original_len..]);
+            let result = match Pin::new(&mut this.reader).poll_read(ctx, &mut buf) {
+                Poll::Ready(Ok(())) => Ok(buf.filled().len()),
+                Poll::Ready(Err(err)) => Err(err),
+                Poll::Pending => {
+                    // Nothing was read, undo `prepare_read`.
+                    this.buf.truncate(original_len);
+                    return Poll::Pending;
                 }
+            };
+            match this.finish_read(original_len, result) {
+                Ok(0) if this.options.follow => {
+                    // Wait for more input to be written.
+                    *sleep = Some(Box::pin(tokio::time::sleep(this.options.poll_interval)));
+                }
+                Ok(_) => { /* Try again. */ }
                 Err(err) => {
-                    // Skip the troublesome line.
-                    if let Some(line) = err.line.as_ref() {
-                        self.parsed += line.len();
-                        if let Some(b'\n') = self.buf.get(self.parsed) {
-                            // Also skip the next new line.
-                            self.parsed += 1
-                        }
-                    }
-                    return Some(Err(err));
+                    this.stopped = this.options.strict;
+                    this.stats.errors += 1;
+                    return Poll::Ready(Some(Err(ParseError {
+                        line: None,
+                        kind: ParseErrorKind::Io(err),
+                        fields: Vec::new(),
+                    })));
                 }
             }
         }
@@ -242,23 +1235,54 @@ type ParseResult<'a, T> = Result<(&'a [u8], T), ParseErrorKind>;
 
 /// Error returned by the [`Parser`].
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParseError {
     /// The line in which the error occurred. This will be `None` for [I/O]
     /// errors.
     ///
+    /// Long lines are truncated, see [`ParserOptions::max_error_line`].
+    ///
     /// [I/O]: ParseErrorKind::Io
     pub line: Option<Box<[u8]>>,
     /// Error detail.
+    ///
+    /// If the record contains multiple errors this is the error that stopped
+    /// parsing the record, e.g. [`ParseErrorKind::InvalidJson`], or the first
+    /// error in [`fields`].
+    ///
+    /// [`fields`]: ParseError::fields
     pub kind: ParseErrorKind,
+    /// All errors in the fields of the record, in the order they appear in
+    /// the record, e.g. both an invalid timestamp and level. This is empty for
+    /// errors not related to a single field, such as [I/O] errors.
+    ///
+    /// [I/O]: ParseErrorKind::Io
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub fields: Vec<FieldError>,
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         if let Some(line) = self.line.as_ref() {
+            f.write_str("error parsing log message: ")?;
+            match self.fields.first() {
+                Some(first) if self.fields.len() > 1 || first.kind != self.kind => {
+                    for (i, field) in self.fields.iter().enumerate() {
+                        if i != 0 {
+                            f.write_str(", ")?;
+                        }
+                        write!(f, "{} (key `{}`)", field.kind, field.key)?;
+                    }
+                    // Error that stopped parsing the record.
+                    if first.kind != self.kind {
+                        write!(f, ", {}", self.kind)?;
+                    }
+                }
+                _ => write!(f, "{}", self.kind)?,
+            }
             write!(
                 f,
-                "error parsing log message: {}, in line `{:?}`",
-                self.kind,
+                ", in line `{:?}`",
                 str::from_utf8(line)
                     .as_ref()
                     .map_or_else(|_| line as &dyn fmt::Debug, |line| line as &dyn fmt::Debug)
@@ -275,8 +1299,78 @@ impl fmt::Debug for ParseError {
     }
 }
 
+/// Error in a single field of a record, see [`ParseError::fields`].
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FieldError {
+    /// Key of the field, e.g. `ts`.
+    pub key: String,
+    /// Error detail.
+    pub kind: ParseErrorKind,
+}
+
+/// Errors found while parsing a single record, see [`FieldErrors`].
+struct RecordError {
+    /// See [`ParseError::kind`].
+    kind: ParseErrorKind,
+    /// See [`ParseError::fields`].
+    fields: Vec<FieldError>,
+}
+
+impl From<ParseErrorKind> for RecordError {
+    fn from(kind: ParseErrorKind) -> RecordError {
+        RecordError {
+            kind,
+            fields: Vec::new(),
+        }
+    }
+}
+
+/// Collects the errors in the fields of a record, so that all errors are
+/// reported rather than only the first.
+#[derive(Default)]
+struct FieldErrors(Vec<FieldError>);
+
+impl FieldErrors {
+    /// Returns the value of `result`, or `None` after recording the error
+    /// for `key`.
+    fn check<T>(&mut self, key: &str, result: Result<T, ParseErrorKind>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(kind) => {
+                self.0.push(FieldError {
+                    key: key.to_owned(),
+                    kind,
+                });
+                None
+            }
+        }
+    }
+
+    /// Returns `record` if no errors were recorded.
+    fn finish<T>(self, record: T) -> Result<T, RecordError> {
+        match self.0.first() {
+            None => Ok(record),
+            Some(first) => Err(RecordError {
+                kind: first.kind.copy(),
+                fields: self.0,
+            }),
+        }
+    }
+
+    /// Returns the error `kind` that stopped parsing the record.
+    fn stop(self, kind: ParseErrorKind) -> RecordError {
+        RecordError {
+            kind,
+            fields: self.0,
+        }
+    }
+}
+
 /// Error detail for [`ParseError`].
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParseErrorKind {
     /// Key contains invalid UTF-8.
     KeyInvalidUt8,
@@ -289,8 +1383,38 @@ pub enum ParseErrorKind {
     InvalidFile,
     /// A value contains invalid UTF-8.
     InvalidValue,
+    /// A JSON formatted record is invalid.
+    InvalidJson,
+    /// Record is interleaved with another record, see
+    /// [`ParserOptions::interleaved`].
+    Interleaved,
     /// I/O error.
-    Io(io::Error),
+    ///
+    /// Note that when using serde the I/O error is (de)serialised using its
+    /// description, the error kind and OS error code are lost.
+    Io(#[cfg_attr(feature = "serde", serde(with = "serde_io_error"))] io::Error),
+}
+
+/// (De)serialise an [`io::Error`] using its description.
+#[cfg(feature = "serde")]
+mod serde_io_error {
+    use std::io;
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S>(err: &io::Error, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.collect_str(err)
+    }
+
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<io::Error, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        String::deserialize(deserializer).map(io::Error::other)
+    }
 }
 
 #[doc(hidden)] // This is here for testing purposes.
@@ -302,7 +1426,9 @@ impl PartialEq for ParseErrorKind {
             | (InvalidTimestamp, InvalidTimestamp)
             | (InvalidLevel, InvalidLevel)
             | (InvalidFile, InvalidFile)
-            | (InvalidValue, InvalidValue) => true,
+            | (InvalidValue, InvalidValue)
+            | (InvalidJson, InvalidJson)
+            | (Interleaved, Interleaved) => true,
             (Io(s_err), Io(o_err)) => match (s_err.raw_os_error(), o_err.raw_os_error()) {
                 (Some(s), Some(o)) => s == o,
                 _ => false,
@@ -312,6 +1438,23 @@ impl PartialEq for ParseErrorKind {
     }
 }
 
+impl ParseErrorKind {
+    /// Returns a copy of the error, only I/O errors lose some details.
+    fn copy(&self) -> ParseErrorKind {
+        use ParseErrorKind::*;
+        match self {
+            KeyInvalidUt8 => KeyInvalidUt8,
+            InvalidTimestamp => InvalidTimestamp,
+            InvalidLevel => InvalidLevel,
+            InvalidFile => InvalidFile,
+            InvalidValue => InvalidValue,
+            InvalidJson => InvalidJson,
+            Interleaved => Interleaved,
+            Io(err) => Io(io::Error::new(err.kind(), err.to_string())),
+        }
+    }
+}
+
 impl fmt::Display for ParseErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use ParseErrorKind::*;
@@ -321,6 +1464,8 @@ impl fmt::Display for ParseErrorKind {
             InvalidLevel => "invalid level",
             InvalidFile => "invalid file",
             InvalidValue => "invalid UTF-8 in value",
+            InvalidJson => "invalid JSON",
+            Interleaved => "interleaved record",
             Io(err) => return err.fmt(f),
         };
         f.write_str(msg)
@@ -328,11 +1473,14 @@ impl fmt::Display for ParseErrorKind {
 }
 
 /// Returns a single line.
-fn single_line<'a>(input: &'a [u8]) -> &'a [u8] {
+fn single_line(input: &[u8]) -> &[u8] {
     let mut i = 0;
     let mut quote_count = 0;
+    let mut escaped = false;
     for b in input.iter().copied() {
         match b {
+            _ if escaped => escaped = false,
+            b'\\' if quote_count % 2 == 1 => escaped = true,
             b'"' => quote_count += 1,
             // Ignore new lines inside quotes, e.g. in backtraces.
             b'\n' if quote_count % 2 == 0 => break,
@@ -343,9 +1491,24 @@ fn single_line<'a>(input: &'a [u8]) -> &'a [u8] {
     &input[..i]
 }
 
+/// Returns the index of the first new line in `record` that is followed by a
+/// line starting with `key=`, if any.
+fn resync_point(record: &[u8], key: &str) -> Option<usize> {
+    let mut start = 0;
+    while let Some(i) = record[start..].iter().position(|b| *b == b'\n') {
+        let end = start + i;
+        let line = eat_space(&record[end + 1..]);
+        if starts_with_key(line, key) {
+            return Some(end);
+        }
+        start = end + 1;
+    }
+    None
+}
+
 /// Removes all spaces and tabs at the start of `input`. It does not remove new
 /// lines.
-fn eat_space<'a>(input: &'a [u8]) -> &'a [u8] {
+fn eat_space(input: &[u8]) -> &[u8] {
     let mut i = 0;
     for b in input.iter().copied() {
         if b != b' ' && b != b'\t' {
@@ -357,7 +1520,7 @@ fn eat_space<'a>(input: &'a [u8]) -> &'a [u8] {
 }
 
 /// Same as [`eat_space`], but removes from the start of the input.
-fn eat_space_end<'a>(input: &'a [u8]) -> &'a [u8] {
+fn eat_space_end(input: &[u8]) -> &[u8] {
     let mut i = 0;
     for b in input.iter().rev().copied() {
         if b != b' ' && b != b'\t' {
@@ -369,40 +1532,78 @@ fn eat_space_end<'a>(input: &'a [u8]) -> &'a [u8] {
 }
 
 /// Calls both [`eat_space`] and [`eat_space_end`].
-fn eat_space_both<'a>(input: &'a [u8]) -> &'a [u8] {
+fn eat_space_both(input: &[u8]) -> &[u8] {
     eat_space(eat_space_end(input))
 }
 
-/// Parses a key, i.e. `key=`.
-fn parse_key<'a>(input: &'a [u8]) -> ParseResult<'a, &'a str> {
+/// Parses a key, i.e. `key=` or `"key"=`.
+///
+/// Also supports keys without a value, e.g. `key` (followed by a space or new
+/// line). Returns `true` if the key is followed by a value. If the input ends
+/// before the key does it returns an empty input and `false`.
+fn parse_key<'a>(input: &'a [u8]) -> ParseResult<'a, (Cow<'a, str>, bool)> {
     let mut i = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut has_value = false;
     for b in input.iter().copied() {
-        if b == b'=' {
-            break;
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if quoted => escaped = true,
+            b'"' => quoted = !quoted,
+            b'=' if !quoted => {
+                has_value = true;
+                break;
+            }
+            b' ' | b'\t' | b'\n' if !quoted => {
+                // Either white space between the key and `=`, or the end of a
+                // key without a value.
+                let rest = eat_space(&input[i..]);
+                match rest.first() {
+                    Some(b'=') => {
+                        has_value = true;
+                        i = input.len() - rest.len();
+                    }
+                    Some(_) => {}
+                    // Can't determine it yet.
+                    None => i = input.len(),
+                }
+                break;
+            }
+            _ => {}
         }
         i += 1;
     }
     let (mut key_bytes, mut input) = input.split_at(i);
-    if !input.is_empty() {
+    if has_value {
         input = &input[1..]; // Remove the `=`.
     }
     key_bytes = eat_space_both(key_bytes);
     // Remove starting and ending quote, if any.
-    if let (Some(b'"'), Some(b'"')) = (key_bytes.first(), key_bytes.last()) {
-        key_bytes = eat_space_both(&key_bytes[1..key_bytes.len() - 1]);
+    let quoted = key_bytes.len() >= 2
+        && matches!(
+            (key_bytes.first(), key_bytes.last()),
+            (Some(b'"'), Some(b'"'))
+        );
+    if quoted {
+        key_bytes = &key_bytes[1..key_bytes.len() - 1];
     }
 
     match str::from_utf8(key_bytes) {
-        Ok(key) => Ok((input, key)),
+        Ok(key) if quoted => Ok((input, (unescape(key), has_value))),
+        Ok(key) => Ok((input, (Cow::Borrowed(key), has_value))),
         Err(_) => Err(ParseErrorKind::KeyInvalidUt8),
     }
 }
 
 /// Parse a timestamp with the format: `yyyy-mm-ddThh:mm:ss.nnnnnnZhh:mm`, e.g.
-/// `2021-02-23T13:15:48.624447Z`.
-fn parse_timestamp<'a>(value: &'a [u8]) -> Result<SystemTime, ParseErrorKind> {
-    if value.len() < 20 {
-        // Shorted valid timestamp is 20: `yyyy-mm-ddThh:mm:ssZ`.
+/// `2021-02-23T13:15:48.624447Z`. The fractional seconds can have a precision
+/// of 1 to 9 digits and are optional.
+fn parse_timestamp(value: &[u8]) -> Result<SystemTime, ParseErrorKind> {
+    if value.len() < 20 || !value.is_ascii() {
+        // Shorted valid timestamp is 20: `yyyy-mm-ddThh:mm:ssZ`. Non-ASCII
+        // input is never valid and could lead to slicing in the middle of a
+        // character below.
         return Err(ParseErrorKind::InvalidTimestamp);
     }
 
@@ -437,38 +1638,40 @@ fn parse_timestamp<'a>(value: &'a [u8]) -> Result<SystemTime, ParseErrorKind> {
     #[rustfmt::skip]
     let sec: i32 = value[17..19].parse().map_err(|_| ParseErrorKind::InvalidTimestamp)?;
     let (nanos, idx) = if value.as_bytes()[19] == b'.' {
-        let nanos = match value.get(20..26) {
-            Some(nanos) => nanos
-                .parse()
-                .map_err(|_| ParseErrorKind::InvalidTimestamp)?,
-            None => return Err(ParseErrorKind::InvalidTimestamp),
-        };
-        (nanos, 26)
+        // Support any precision from seconds up to nanoseconds.
+        let digits = value.as_bytes()[20..]
+            .iter()
+            .take_while(|b| b.is_ascii_digit())
+            .count();
+        check_value!(digits == 0 || digits > 9);
+        #[rustfmt::skip]
+        let fraction: u32 = value[20..20 + digits].parse().map_err(|_| ParseErrorKind::InvalidTimestamp)?;
+        (fraction * 10_u32.pow(9 - digits as u32), 20 + digits)
     } else {
         (0, 19)
     };
-    let gmtoff = match value.as_bytes()[idx] {
+    let gmtoff = match value.as_bytes().get(idx).copied().unwrap_or(0) {
         // UTC timezone.
         b'Z' => 0,
         // Timezone offset `+HH:MM` or `-HH:MM`.
         b @ b'+' | b @ b'-' if value.len() == idx + 6 => {
-            let mut offset: libc::c_long = 0;
+            let mut offset: i64 = 0;
             // DRY this.
             match value.as_bytes()[idx + 1] {
-                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as libc::c_long,
+                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as i64,
                 _ => return Err(ParseErrorKind::InvalidTimestamp),
             }
             match value.as_bytes()[idx + 2] {
-                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as libc::c_long,
+                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as i64,
                 _ => return Err(ParseErrorKind::InvalidTimestamp),
             }
             check_value!(value.as_bytes()[idx + 3] != b':');
             match value.as_bytes()[idx + 4] {
-                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as libc::c_long,
+                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as i64,
                 _ => return Err(ParseErrorKind::InvalidTimestamp),
             }
             match value.as_bytes()[idx + 5] {
-                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as libc::c_long,
+                b @ b'0'..=b'9' => offset = offset * 10 + (b - b'0') as i64,
                 _ => return Err(ParseErrorKind::InvalidTimestamp),
             }
 
@@ -487,27 +1690,68 @@ fn parse_timestamp<'a>(value: &'a [u8]) -> Result<SystemTime, ParseErrorKind> {
         _ => return Err(ParseErrorKind::InvalidTimestamp),
     };
 
+    check_value!(!(1..=12).contains(&month));
+    check_value!(day < 1 || day > days_in_month(year, month));
+
     // Convert the timestamp into the number of seconds sinch Unix Epoch.
-    let mut tm = libc::tm {
-        tm_sec: sec,
-        tm_min: min,
-        tm_hour: hour,
-        tm_mday: day,
-        tm_mon: month - 1,
-        tm_year: year - 1900,
-        tm_wday: 0,
-        tm_yday: 0,
-        tm_isdst: 0,
-        tm_gmtoff: gmtoff,
-        tm_zone: std::ptr::null_mut(),
+    let days = days_from_civil(i64::from(year), i64::from(month), i64::from(day));
+    let secs = days * 86400 + i64::from(hour * 3600 + min * 60 + sec) - gmtoff;
+    // Create the timestamp from the seconds and the nanosecond precision.
+    let timestamp = if secs >= 0 {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
     };
-    let time_offset = unsafe { libc::timegm(&mut tm) };
-    // Create the timestamp from the time offset and the nanosecond precision.
-    Ok(SystemTime::UNIX_EPOCH + Duration::new(time_offset as u64, nanos))
+    Ok(timestamp + Duration::from_nanos(u64::from(nanos)))
+}
+
+/// Returns the number of days since 1970-01-01 of the date in the (proleptic)
+/// Gregorian calendar.
+///
+/// This is the `days_from_civil` algorithm from
+/// <http://howardhinnant.github.io/date_algorithms.html>.
+const fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400; // [0, 399].
+    let month = (month + 9) % 12; // Starting at March, [0, 11].
+    let day_of_year = (153 * month + 2) / 5 + day - 1; // [0, 365].
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the number of days in `month` (1-12) of `year` in the (proleptic)
+/// Gregorian calendar.
+const fn days_in_month(year: i32, month: i32) -> i32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the year, month and day of the number of days since 1970-01-01, the
+/// inverse of [`days_from_civil`].
+///
+/// This is the `civil_from_days` algorithm from
+/// <http://howardhinnant.github.io/date_algorithms.html>.
+const fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097; // [0, 146096].
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153; // Starting at March, [0, 11].
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
 }
 
 /// Parse a log level, using [`Level::from_str`].
-fn parse_log_level<'a>(value: &'a [u8]) -> Result<Level, ParseErrorKind> {
+fn parse_log_level(value: &[u8]) -> Result<Level, ParseErrorKind> {
     match str::from_utf8(value) {
         Ok(value) => match value.parse() {
             Ok(level) => Ok(level),
@@ -517,80 +1761,178 @@ fn parse_log_level<'a>(value: &'a [u8]) -> Result<Level, ParseErrorKind> {
     }
 }
 
-fn parse_string<'a>(value: &'a [u8]) -> Result<&'a str, ParseErrorKind> {
+/// Parse a string value, unescaping it if it was `quoted`, see [`unescape`].
+fn parse_string(value: &[u8], quoted: bool) -> Result<Cow<'_, str>, ParseErrorKind> {
     match str::from_utf8(value) {
-        Ok(value) => Ok(value),
+        Ok(value) if quoted => Ok(unescape(value)),
+        // Unquoted values are taken as is, e.g. `path=C:\new`.
+        Ok(value) => Ok(Cow::Borrowed(value)),
         Err(_) => Err(ParseErrorKind::InvalidValue),
     }
 }
 
+/// Unescapes `\"`, `\\`, `\n`, `\r`, `\t` and `\uXXXX` in `value`. Any other
+/// escape sequences are left as is.
+fn unescape(value: &str) -> Cow<'_, str> {
+    if !value.contains('\\') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('"') => unescaped.push('"'),
+            Some('\\') => unescaped.push('\\'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('u') => {
+                let hex = chars.as_str().get(..4).unwrap_or("");
+                match u32::from_str_radix(hex, 16).ok().and_then(char::from_u32) {
+                    Some(c) if hex.len() == 4 => {
+                        unescaped.push(c);
+                        chars = chars.as_str()[4..].chars();
+                    }
+                    _ => unescaped.push_str("\\u"),
+                }
+            }
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    Cow::Owned(unescaped)
+}
+
 /// Parse file value, format: `path/to/file:column`, e.g.
-/// `examples/simple.rs:51`.
-fn parse_file<'a>(value: &'a [u8]) -> Result<(&'a str, u32), ParseErrorKind> {
+/// `examples/simple.rs:51`, or `path/to/file` if the line is written as
+/// separate key.
+fn parse_file(value: &[u8]) -> Result<(&str, Option<u32>), ParseErrorKind> {
     match str::from_utf8(value) {
         Ok(value) => {
             if let Some((file, column)) = value.rsplit_once(':') {
                 match column.parse() {
-                    Ok(column) => Ok((file, column)),
+                    Ok(column) => Ok((file, Some(column))),
                     Err(_) => Err(ParseErrorKind::InvalidFile),
                 }
             } else {
-                Err(ParseErrorKind::InvalidFile)
+                // Line is written as separate key.
+                Ok((value, None))
             }
         }
         Err(_) => Err(ParseErrorKind::InvalidFile),
     }
 }
 
-/// Returns `(remaining_input, value)`.
-fn parse_value<'a>(input: &'a [u8]) -> (&'a [u8], &'a [u8]) {
-    let input = eat_space(input);
-    if input.first().copied() == Some(b'"') {
-        parse_quoted_value(input)
+/// Returns `(remaining_input, value, quoted)`.
+fn parse_value(input: &[u8]) -> (&[u8], &[u8], bool) {
+    let trimmed = eat_space(input);
+    if trimmed.len() != input.len() && is_quoted_key(trimmed) {
+        // Empty value followed by a quoted key, e.g. `key= "next key"=value`.
+        (trimmed, &[], false)
+    } else if trimmed.first().copied() == Some(b'"') {
+        let (input, value) = parse_quoted_value(trimmed);
+        (input, value, true)
     } else {
-        parse_naked_value(input)
+        let (rest, value) = parse_naked_value(trimmed);
+        if trimmed.len() != input.len() && value.contains(&b'=') {
+            // Empty value followed by the next key, e.g. `key= next=value`.
+            return (trimmed, &[], false);
+        }
+        (rest, value, false)
     }
 }
 
 /// See [`parse_value`], expects `input` to contain a quoted value, i.e. it
 /// starts and ends with `"`.
-fn parse_quoted_value<'a>(input: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+///
+/// The value ends at the first unescaped quote (`"`) that is followed by the
+/// end of the input or the next key. This means that quoted values can contain
+/// (unescaped) new lines, e.g. in backtraces, escaped quotes (`\"`) and, for
+/// logs not created by std-logger, unescaped quotes. If the input contains no
+/// such quote the last quote is used as the end of the value, or if there is
+/// no end quote at all the remainder of the input.
+fn parse_quoted_value(input: &[u8]) -> (&[u8], &[u8]) {
     debug_assert!(input[0] == b'"');
-    let mut i = 1;
-    let mut quote_count = 1; // Support quotes inside quotes.
-    let bytes = input.iter().skip(1).copied();
-    // Set `i` to the index of the `=` of the next key-value pair.
-    for b in bytes {
+    // Index of the last unescaped quote (`"`).
+    let mut last_quote = 0;
+    let mut escaped = false;
+    for (i, b) in input.iter().copied().enumerate().skip(1) {
         match b {
-            b'"' => quote_count += 1,
-            b'=' if quote_count % 2 == 0 => break,
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => {
+                last_quote = i;
+                if is_value_end(&input[i + 1..]) {
+                    return (&input[i + 1..], &input[1..i]);
+                }
+            }
             _ => {}
         }
-        i += 1;
     }
 
-    // This is include the key of the next key-value pair.
-    // Skip start quote.
-    let input_value = &input[1..i];
-    // Reduce `i` to index of the last quote (`"`) from the value.
-    for b in input_value.iter().rev().copied() {
-        i -= 1;
-        if b == b'"' {
-            break;
+    if last_quote == 0 {
+        // No end quote, the remainder of the input is the value.
+        (&[], &input[1..])
+    } else {
+        (&input[last_quote + 1..], &input[1..last_quote])
+    }
+}
+
+/// Returns `true` if `input`, the input following a quote, marks the end of a
+/// quoted value, i.e. it is followed by the end of the input or the next key.
+fn is_value_end(input: &[u8]) -> bool {
+    match input.first() {
+        None => return true,
+        Some(b' ' | b'\t') => {}
+        // Unescaped quote inside a value, e.g. `"Some "great" message"`.
+        Some(_) => return false,
+    }
+
+    let input = eat_space(input);
+    match input.first() {
+        None => true,
+        Some(b'"') => is_quoted_key(input),
+        // Naked key, e.g. `key=` or a bare key.
+        Some(_) => {
+            for b in input.iter().copied() {
+                match b {
+                    b'"' => return false,
+                    b'=' | b' ' | b'\t' | b'\n' => return true,
+                    _ => {}
+                }
+            }
+            true
         }
     }
+}
 
-    let value = &input[1..i]; // Skip start quote.
-    let input = if i == input.len() {
-        &[]
-    } else {
-        &input[i + 1..] // Skip end quote.
-    };
-    (input, value)
+/// Returns `true` if `input` starts with a quoted key, e.g. `"key"=`.
+fn is_quoted_key(input: &[u8]) -> bool {
+    if input.first() != Some(&b'"') {
+        return false;
+    }
+    let mut escaped = false;
+    for (i, b) in input.iter().copied().enumerate().skip(1) {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return eat_space(&input[i + 1..]).first() == Some(&b'='),
+            _ => {}
+        }
+    }
+    false
 }
 
 /// Parses a single value, expecting a space (` `) as value end.
-fn parse_naked_value<'a>(input: &'a [u8]) -> (&'a [u8], &'a [u8]) {
+fn parse_naked_value(input: &[u8]) -> (&[u8], &[u8]) {
     let mut i = 0;
     for b in input.iter().copied() {
         if b == b' ' || b == b'\n' {
@@ -606,29 +1948,63 @@ fn parse_naked_value<'a>(input: &'a [u8]) -> (&'a [u8], &'a [u8]) {
 /// A parser log record.
 #[derive(Debug, PartialEq)]
 #[non_exhaustive]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Record {
     /// Timestamp *in UTC* (key `ts`).
     pub timestamp: Option<SystemTime>,
     /// Log level (key `lvl`).
     pub level: Level,
     /// Log message (key `msg`).
-    pub msg: String,
+    pub msg: RecordString,
     /// Log message (key `target`).
-    pub target: String,
+    pub target: RecordString,
     /// Module that logged the message (key `module`).
-    pub module: Option<String>,
-    /// File and line number from where the message oriented (key `file`).
+    pub module: Option<RecordString>,
+    /// File and line number from where the message oriented (key `file`, e.g.
+    /// `file="src/main.rs:12"`, or keys `file` and `line`, e.g.
+    /// `file="src/main.rs" line=12`).
     pub file: Option<(String, u32)>,
     /// Additional key value pairs.
-    pub key_values: HashMap<String, Value>,
+    pub key_values: HashMap<RecordString, Value>,
 }
 
+/// String type used in [`Record`] for the message, target, module and keys.
+///
+/// This is a [`String`] by default. With the *compact_str* feature enabled
+/// it's a `CompactString` from the [compact_str] crate instead, which stores
+/// strings up to 24 bytes inline. Most targets, modules and keys (and short
+/// messages) fit in that, which reduces the number of allocations and memory
+/// usage when keeping a lot of records in memory.
+///
+/// Both types dereference to `str` and can be created using `From<&str>`,
+/// e.g. using `"key".into()`.
+///
+/// [compact_str]: https://docs.rs/compact_str
+#[cfg(not(feature = "compact_str"))]
+pub type RecordString = String;
+
+/// String type used in [`Record`] for the message, target, module and keys.
+///
+/// This is a `CompactString` from the [compact_str] crate, which stores strings
+/// up to 24 bytes inline. Without the *compact_str* feature it's a [`String`].
+///
+/// [compact_str]: https://docs.rs/compact_str
+#[cfg(feature = "compact_str")]
+pub type RecordString = compact_str::CompactString;
+
 /// A parsed value from a key-value pair.
 ///
 /// Note that parsing is done based on a best-effort basis, which means
 /// integers, floats etc. might actual be represented as a [`Value::String`].
+///
+/// When using serde the value is (de)serialised without the variant name, e.g.
+/// `Value::Int(123)` is serialised as `123` in JSON.
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
 pub enum Value {
+    /// Key without a value, e.g. `key=`, as opposed to an empty string
+    /// `key=""`.
+    Null,
     /// Parsed boolean.
     Bool(bool),
     /// Parsed integer.
@@ -637,6 +2013,17 @@ pub enum Value {
     Float(f64),
     /// Unparsed string.
     String(String),
+    /// Parsed timestamp, only if enabled using
+    /// [`ParserOptions::timestamp_values`].
+    Timestamp(SystemTime),
+    /// Parsed duration, e.g. `15ms`, only if enabled using
+    /// [`ParserOptions::unit_values`].
+    Duration(Duration),
+    /// Parsed byte size in bytes, e.g. `1.5MB`, only if enabled using
+    /// [`ParserOptions::unit_values`].
+    ///
+    /// Note that when using serde this is deserialised as [`Value::Int`].
+    Bytes(u64),
 }
 
 impl FromStr for Value {
@@ -644,15 +2031,7 @@ impl FromStr for Value {
     type Err = Infallible;
 
     fn from_str(value: &str) -> Result<Self, Self::Err> {
-        if let Ok(b) = value.parse() {
-            Ok(Value::Bool(b))
-        } else if let Ok(i) = value.parse() {
-            Ok(Value::Int(i))
-        } else if let Ok(f) = value.parse() {
-            Ok(Value::Float(f))
-        } else {
-            Ok(Value::String(value.to_owned()))
-        }
+        Ok(ValueRef::parse(Cow::Borrowed(value)).into_owned())
     }
 }
 
@@ -663,11 +2042,281 @@ impl Record {
         Record {
             timestamp: None,
             level: Level::Info,
-            msg: String::new(),
-            target: String::new(),
+            msg: RecordString::default(),
+            target: RecordString::default(),
             module: None,
             file: None,
             key_values: HashMap::new(),
         }
     }
+
+    /// Write the record as a logfmt line, including the line ending, in the
+    /// same format as std-logger.
+    ///
+    /// The key-value pairs are sorted by key. Parsing the written line results
+    /// in the same record, except that a [`Value::String`] that looks like
+    /// another type, e.g. `"123"`, is parsed as that type (unless
+    /// [`ParserOptions::quoted_strings`] is used).
+    ///
+    /// The line is written using a single call to [`Write::write_all`].
+    ///
+    /// # Examples
+    ///
+    /// Only keep the error records.
+    ///
+    /// ```
+    /// use log::Level;
+    /// use std_logger_parser::parse;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let logs: &[u8] = b"lvl=INFO msg=\"Hello\" target=\"main\"
+    /// lvl=ERROR msg=\"Oops\" target=\"main\" error=\"not found\"
+    /// ";
+    ///
+    /// let mut output = Vec::new();
+    /// for record in parse(logs) {
+    ///     let record = record.expect("failed to parse record");
+    ///     if record.level == Level::Error {
+    ///         record.write_logfmt(&mut output)?;
+    ///     }
+    /// }
+    /// assert_eq!(
+    ///     output,
+    ///     b"lvl=\"ERROR\" msg=\"Oops\" target=\"main\" module=\"\" error=\"not found\"\n"
+    /// );
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_logfmt<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut buf = Vec::with_capacity(256);
+        if let Some(timestamp) = self.timestamp {
+            buf.extend_from_slice(b"ts=\"");
+            write_timestamp(&mut buf, timestamp);
+            buf.extend_from_slice(b"\" ");
+        }
+        buf.extend_from_slice(b"lvl=\"");
+        buf.extend_from_slice(self.level.as_str().as_bytes());
+        buf.extend_from_slice(b"\" msg=");
+        write_quoted(&mut buf, &self.msg);
+        buf.extend_from_slice(b" target=");
+        write_quoted(&mut buf, &self.target);
+        buf.extend_from_slice(b" module=");
+        write_quoted(&mut buf, self.module.as_deref().unwrap_or(""));
+
+        let mut key_values = self.key_values.iter().collect::<Vec<_>>();
+        key_values.sort_unstable_by_key(|(key, _)| *key);
+        for (key, value) in key_values {
+            buf.push(b' ');
+            if key_needs_quotes(key) {
+                write_quoted(&mut buf, key);
+            } else {
+                buf.extend_from_slice(key.as_bytes());
+            }
+            buf.push(b'=');
+            match value {
+                Value::Null => {}
+                Value::Bool(b) => buf.extend_from_slice(if *b { b"true" } else { b"false" }),
+                Value::Int(i) => buf.extend_from_slice(i.to_string().as_bytes()),
+                // NOTE: using `Debug` as it always includes a decimal point,
+                // e.g. `1.0` rather than `1`, which would be parsed as integer.
+                Value::Float(f) => buf.extend_from_slice(format!("{f:?}").as_bytes()),
+                Value::String(value) => write_quoted(&mut buf, value),
+                Value::Timestamp(timestamp) => {
+                    buf.push(b'"');
+                    write_timestamp(&mut buf, *timestamp);
+                    buf.push(b'"');
+                }
+                // NOTE: `Debug` formats durations as e.g. `1.5ms`.
+                Value::Duration(duration) => {
+                    buf.extend_from_slice(format!("{duration:?}").as_bytes());
+                }
+                Value::Bytes(bytes) => buf.extend_from_slice(format!("{bytes}B").as_bytes()),
+            }
+        }
+
+        if let Some((file, line)) = &self.file {
+            // NOTE: the file is not escaped as the parser doesn't unescape it,
+            // e.g. for Windows paths such as `C:\new\file.rs`.
+            buf.extend_from_slice(b" file=\"");
+            buf.extend_from_slice(file.as_bytes());
+            buf.push(b':');
+            buf.extend_from_slice(line.to_string().as_bytes());
+            buf.push(b'"');
+        }
+        buf.push(b'\n');
+        writer.write_all(&buf)
+    }
+}
+
+/// Writes `value` as quoted string, escaping it so that [`unescape`] returns
+/// the original `value`.
+fn write_quoted(buf: &mut Vec<u8>, value: &str) {
+    buf.push(b'"');
+    for c in value.chars() {
+        match c {
+            '"' => buf.extend_from_slice(b"\\\""),
+            '\\' => buf.extend_from_slice(b"\\\\"),
+            '\n' => buf.extend_from_slice(b"\\n"),
+            '\r' => buf.extend_from_slice(b"\\r"),
+            '\t' => buf.extend_from_slice(b"\\t"),
+            c if c.is_ascii_control() => {
+                buf.extend_from_slice(format!("\\u{:04x}", c as u32).as_bytes());
+            }
+            c => buf.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+        }
+    }
+    buf.push(b'"');
+}
+
+/// Writes `timestamp` to `buf`, see [`format_timestamp`].
+fn write_timestamp(buf: &mut Vec<u8>, timestamp: SystemTime) {
+    buf.extend_from_slice(format_timestamp(timestamp).as_bytes());
+}
+
+/// Format `timestamp` in the same format as std-logger, RFC 3339 in UTC, e.g.
+/// `2021-02-23T13:15:48.624447Z`. Uses nine fractional digits if the
+/// timestamp has nanosecond precision.
+///
+/// This is the inverse of parsing the `ts` key, i.e. [`Record::timestamp`].
+pub fn format_timestamp(timestamp: SystemTime) -> String {
+    let (secs, nanos) = match timestamp.duration_since(SystemTime::UNIX_EPOCH) {
+        Ok(elapsed) => (elapsed.as_secs() as i64, elapsed.subsec_nanos()),
+        Err(err) => {
+            let before = err.duration();
+            match before.subsec_nanos() {
+                0 => (-(before.as_secs() as i64), 0),
+                nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+            }
+        }
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86400));
+    let secs = secs.rem_euclid(86400);
+    let fraction = if nanos % 1000 == 0 {
+        format!("{:06}", nanos / 1000)
+    } else {
+        format!("{nanos:09}")
+    };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        fraction,
+    )
+}
+
+/// A parsed log record that borrows from the [`Parser`]'s buffer, see
+/// [`Parser::next_record`].
+///
+/// Fields only allocate if the value contains escaped characters, e.g. `\n`.
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub struct RecordRef<'a> {
+    /// Timestamp *in UTC* (key `ts`).
+    pub timestamp: Option<SystemTime>,
+    /// Log level (key `lvl`).
+    pub level: Level,
+    /// Log message (key `msg`).
+    pub msg: Cow<'a, str>,
+    /// Log message (key `target`).
+    pub target: Cow<'a, str>,
+    /// Module that logged the message (key `module`).
+    pub module: Option<Cow<'a, str>>,
+    /// File and line number from where the message oriented, see
+    /// [`Record::file`].
+    pub file: Option<(Cow<'a, str>, u32)>,
+    /// Additional key value pairs, in the order they were logged. Unlike
+    /// [`Record::key_values`] this can contain duplicate keys.
+    pub key_values: Vec<(Cow<'a, str>, ValueRef<'a>)>,
+}
+
+impl<'a> RecordRef<'a> {
+    fn empty() -> RecordRef<'a> {
+        RecordRef {
+            timestamp: None,
+            level: Level::Info,
+            msg: Cow::Borrowed(""),
+            target: Cow::Borrowed(""),
+            module: None,
+            file: None,
+            key_values: Vec::new(),
+        }
+    }
+
+    /// Convert the record into an owned [`Record`].
+    ///
+    /// If [`RecordRef::key_values`] contains duplicate keys the last value is
+    /// used.
+    pub fn into_owned(self) -> Record {
+        Record {
+            timestamp: self.timestamp,
+            level: self.level,
+            msg: self.msg.into(),
+            target: self.target.into(),
+            module: self.module.map(RecordString::from),
+            file: self.file.map(|(file, line)| (file.into_owned(), line)),
+            key_values: self
+                .key_values
+                .into_iter()
+                .map(|(key, value)| (key.into(), value.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+/// A parsed value from a key-value pair that borrows from the [`Parser`]'s
+/// buffer, see [`RecordRef`] and [`Value`].
+#[derive(Debug, PartialEq)]
+pub enum ValueRef<'a> {
+    /// Key without a value, e.g. `key=`, as opposed to an empty string
+    /// `key=""`.
+    Null,
+    /// Parsed boolean.
+    Bool(bool),
+    /// Parsed integer.
+    Int(i64),
+    /// Parsed floating pointer number.
+    Float(f64),
+    /// Unparsed string.
+    String(Cow<'a, str>),
+    /// Parsed timestamp, only if enabled using
+    /// [`ParserOptions::timestamp_values`].
+    Timestamp(SystemTime),
+    /// Parsed duration, only if enabled using [`ParserOptions::unit_values`].
+    Duration(Duration),
+    /// Parsed byte size in bytes, only if enabled using
+    /// [`ParserOptions::unit_values`].
+    Bytes(u64),
+}
+
+impl<'a> ValueRef<'a> {
+    /// Parse `value` on a best-effort basis, see [`Value`].
+    fn parse(value: Cow<'a, str>) -> ValueRef<'a> {
+        if let Ok(b) = value.parse() {
+            ValueRef::Bool(b)
+        } else if let Ok(i) = value.parse() {
+            ValueRef::Int(i)
+        } else if let Ok(f) = value.parse() {
+            ValueRef::Float(f)
+        } else {
+            ValueRef::String(value)
+        }
+    }
+
+    /// Convert the value into an owned [`Value`].
+    pub fn into_owned(self) -> Value {
+        match self {
+            ValueRef::Null => Value::Null,
+            ValueRef::Bool(b) => Value::Bool(b),
+            ValueRef::Int(i) => Value::Int(i),
+            ValueRef::Float(f) => Value::Float(f),
+            ValueRef::String(s) => Value::String(s.into_owned()),
+            ValueRef::Timestamp(timestamp) => Value::Timestamp(timestamp),
+            ValueRef::Duration(duration) => Value::Duration(duration),
+            ValueRef::Bytes(bytes) => Value::Bytes(bytes),
+        }
+    }
 }
@@ -0,0 +1,191 @@
+//! Merging the records of multiple streams in timestamp order, see
+//! [`parse_merged`].
+//!
+//! [`parse_merged`]: crate::parse_merged
+
+use std::io::Read;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::{ParseError, Parser, ParserStats, Record};
+
+/// Number of records read ahead by each reader thread.
+const READ_AHEAD: usize = 16;
+
+/// Default maximum time to wait for the next record of a reader, see
+/// [`Merged::max_wait`].
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// Record (or error) read by a reader thread, with the statistics of its
+/// parser after reading it.
+type Next = (Result<Record, ParseError>, ParserStats);
+
+/// Merges the records of multiple parsers in timestamp order, see
+/// [`parse_merged`].
+///
+/// [`parse_merged`]: crate::parse_merged
+#[derive(Debug)]
+pub struct Merged {
+    /// Receiving side of the channel of each reader thread, `None` once the
+    /// reader is done.
+    readers: Vec<Option<Receiver<Next>>>,
+    /// Notified by the reader threads after reading a record and when done.
+    wake: Receiver<()>,
+    /// Next record of each reader, if already received.
+    next: Vec<Option<Result<Record, ParseError>>>,
+    /// Since when we're waiting for the next record of each reader.
+    waiting: Vec<Option<Instant>>,
+    /// Statistics of the parser of each reader.
+    stats: Vec<ParserStats>,
+    max_wait: Duration,
+    /// Index of the parser of the last returned record or error.
+    last: Option<usize>,
+}
+
+impl Merged {
+    pub(crate) fn new<R>(parsers: Vec<Parser<R>>) -> Merged
+    where
+        R: Read + Send + 'static,
+    {
+        let (waker, wake) = mpsc::channel();
+        let readers = parsers
+            .into_iter()
+            .map(|mut parser| {
+                let (sender, receiver) = mpsc::sync_channel(READ_AHEAD);
+                let waker = waker.clone();
+                let _ = thread::spawn(move || {
+                    while let Some(result) = parser.next() {
+                        if sender.send((result, parser.stats())).is_err() {
+                            // `Merged` is dropped.
+                            return;
+                        }
+                        let _ = waker.send(());
+                    }
+                    drop(sender);
+                    let _ = waker.send(());
+                });
+                Some(receiver)
+            })
+            .collect::<Vec<_>>();
+        Merged {
+            next: readers.iter().map(|_| None).collect(),
+            waiting: readers.iter().map(|_| None).collect(),
+            stats: readers.iter().map(|_| ParserStats::default()).collect(),
+            readers,
+            wake,
+            max_wait: MAX_WAIT,
+            last: None,
+        }
+    }
+
+    /// Set the maximum time to wait for the next record of a reader, defaults
+    /// to one second.
+    ///
+    /// To return the records in timestamp order the next record of all
+    /// readers is needed. If a reader blocks, e.g. when following a log file
+    /// that isn't written to (see [`ParserOptions::follow`]), the records of
+    /// the other readers are returned once we waited `max_wait` for the
+    /// blocked reader. Its records are returned once read, which means they
+    /// can be older than records already returned.
+    ///
+    /// [`ParserOptions::follow`]: crate::ParserOptions::follow
+    pub const fn max_wait(mut self, max_wait: Duration) -> Merged {
+        self.max_wait = max_wait;
+        self
+    }
+
+    /// Returns the index of the reader, as passed to [`parse_merged`], of the
+    /// last returned record or error.
+    ///
+    /// [`parse_merged`]: crate::parse_merged
+    pub const fn last_source(&self) -> Option<usize> {
+        self.last
+    }
+
+    /// Returns the [statistics] of the parser of each reader, as of the last
+    /// record received from it.
+    ///
+    /// [statistics]: Parser::stats
+    pub fn stats(&self) -> &[ParserStats] {
+        &self.stats
+    }
+
+    /// Receive the next record of the readers we don't have the next record
+    /// of, without blocking.
+    fn receive(&mut self) {
+        for index in 0..self.readers.len() {
+            if self.next[index].is_some() {
+                continue;
+            }
+            let Some(reader) = &self.readers[index] else {
+                continue;
+            };
+            match reader.try_recv() {
+                Ok((result, stats)) => {
+                    self.next[index] = Some(result);
+                    self.stats[index] = stats;
+                    self.waiting[index] = None;
+                }
+                Err(TryRecvError::Empty) => {}
+                Err(TryRecvError::Disconnected) => self.readers[index] = None,
+            }
+        }
+    }
+
+    /// Return the next record of the reader at `index`.
+    fn take(&mut self, index: usize) -> Option<Result<Record, ParseError>> {
+        self.last = Some(index);
+        self.next[index].take()
+    }
+}
+
+impl Iterator for Merged {
+    type Item = Result<Record, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.receive();
+
+            let now = Instant::now();
+            // Timestamp and index of the oldest record.
+            let mut oldest: Option<(Option<SystemTime>, usize)> = None;
+            // Until when to wait for the readers we don't have a record of.
+            let mut wait_until: Option<Instant> = None;
+            for index in 0..self.next.len() {
+                match &self.next[index] {
+                    // Errors are returned as is.
+                    Some(Err(_)) => return self.take(index),
+                    // Records without a timestamp are returned first, for
+                    // equal timestamps the first reader wins.
+                    Some(Ok(record)) => {
+                        let key = (record.timestamp, index);
+                        if oldest.is_none_or(|oldest| key < oldest) {
+                            oldest = Some(key);
+                        }
+                    }
+                    None if self.readers[index].is_some() => {
+                        let deadline = *self.waiting[index].get_or_insert(now) + self.max_wait;
+                        if deadline > now {
+                            wait_until = Some(wait_until.map_or(deadline, |w| w.min(deadline)));
+                        }
+                    }
+                    // Reader is done.
+                    None => {}
+                }
+            }
+
+            match (oldest, wait_until) {
+                (Some((_, index)), None) => return self.take(index),
+                (Some(_), Some(wait_until)) => {
+                    let _ = self.wake.recv_timeout(wait_until - now);
+                }
+                (None, _) if self.readers.iter().all(Option::is_none) => return None,
+                (None, _) => {
+                    // Wait for any record.
+                    let _ = self.wake.recv();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Extracting numeric time series from records, see [`TimeSeries`].
+
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime};
+
+use crate::{Aggregator, Record, Value};
+
+/// Extracts a numeric time series of the values of a key from records.
+///
+/// The records are grouped into buckets of a fixed size based on their
+/// timestamp and the values in each bucket are combined using an [`Aggregate`]
+/// function. Records without a timestamp or without a numeric value (an integer
+/// or float) for the key are ignored.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use std_logger_parser::{parse, Aggregate, TimeSeries};
+///
+/// let logs = /* Open some log file, anything that implements `io::Read`. */
+/// #    b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"request\" response_time=12\n" as &[u8];
+///
+/// // The maximum response time per minute.
+/// let mut series = TimeSeries::new("response_time", Duration::from_secs(60))
+///     .aggregate(Aggregate::Max);
+/// for record in parse(logs) {
+///     let record = record.expect("failed to parse record");
+///     series.add(&record);
+/// }
+/// for (time, max) in series.points() {
+///     println!("{:?}: {}", time, max);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct TimeSeries {
+    key: String,
+    bucket: Duration,
+    aggregate: Aggregate,
+    /// Bucket index -> values.
+    buckets: BTreeMap<u128, Bucket>,
+}
+
+/// How the values in a bucket of a [`TimeSeries`] are combined.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Aggregate {
+    /// Sum of the values.
+    Sum,
+    /// Average (mean) of the values.
+    Avg,
+    /// Minimum value.
+    Min,
+    /// Maximum value.
+    Max,
+    /// Number of values.
+    Count,
+}
+
+/// Values of a single bucket.
+#[derive(Debug)]
+struct Bucket {
+    sum: f64,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl TimeSeries {
+    /// Create a new time series for the values of `key`, using buckets of
+    /// `bucket` size. Defaults to [`Aggregate::Sum`].
+    ///
+    /// # Panics
+    ///
+    /// This panics if `bucket` is zero.
+    pub fn new(key: &str, bucket: Duration) -> TimeSeries {
+        assert!(
+            !bucket.is_zero(),
+            "bucket size of time series can't be zero"
+        );
+        TimeSeries {
+            key: key.to_owned(),
+            bucket,
+            aggregate: Aggregate::Sum,
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    /// Set the function used to combine the values in a bucket.
+    pub fn aggregate(mut self, aggregate: Aggregate) -> TimeSeries {
+        self.aggregate = aggregate;
+        self
+    }
+
+    /// Add the value of `record`, if any.
+    pub fn add(&mut self, record: &Record) {
+        let value = match record.key_values.get(self.key.as_str()) {
+            Some(Value::Int(value)) => *value as f64,
+            Some(Value::Float(value)) => *value,
+            _ => return,
+        };
+        let index = match bucket_index(record, self.bucket) {
+            Some(index) => index,
+            None => return,
+        };
+        let bucket = self.buckets.entry(index).or_insert(Bucket {
+            sum: 0.0,
+            count: 0,
+            min: value,
+            max: value,
+        });
+        bucket.sum += value;
+        bucket.count += 1;
+        bucket.min = bucket.min.min(value);
+        bucket.max = bucket.max.max(value);
+    }
+
+    /// Returns the points of the series, ordered by time.
+    ///
+    /// Each point is the start time of the bucket and the aggregated value of
+    /// the values in it. Buckets without values are not included.
+    pub fn points(&self) -> Vec<(SystemTime, f64)> {
+        self.buckets
+            .iter()
+            .map(|(index, bucket)| {
+                let value = match self.aggregate {
+                    Aggregate::Sum => bucket.sum,
+                    Aggregate::Avg => bucket.sum / bucket.count as f64,
+                    Aggregate::Min => bucket.min,
+                    Aggregate::Max => bucket.max,
+                    Aggregate::Count => bucket.count as f64,
+                };
+                (bucket_start(*index, self.bucket), value)
+            })
+            .collect()
+    }
+}
+
+impl Aggregator for TimeSeries {
+    fn add(&mut self, record: &Record) {
+        TimeSeries::add(self, record);
+    }
+}
+
+/// Returns the index of the bucket of `bucket` size for the timestamp of
+/// `record`, if any.
+pub(crate) fn bucket_index(record: &Record, bucket: Duration) -> Option<u128> {
+    let elapsed = record
+        .timestamp
+        .and_then(|ts| ts.duration_since(SystemTime::UNIX_EPOCH).ok())?;
+    Some(elapsed.as_nanos() / bucket.as_nanos())
+}
+
+/// Returns the start time of the bucket with `index` of `bucket` size.
+pub(crate) fn bucket_start(index: u128, bucket: Duration) -> SystemTime {
+    let nanos = index * bucket.as_nanos();
+    let secs = (nanos / 1_000_000_000) as u64;
+    let nanos = (nanos % 1_000_000_000) as u32;
+    SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
+}
@@ -0,0 +1,95 @@
+//! Parsing of values with a unit, e.g. durations (`15ms`) and byte sizes
+//! (`1.5MB`).
+
+use std::convert::TryFrom;
+use std::time::Duration;
+
+/// Parses a duration, e.g. `15ms` or `2.5s` as created by the `Debug`
+/// implementation of [`Duration`], or `1h30m` (as used by Go).
+pub(crate) fn parse_duration(value: &str) -> Option<Duration> {
+    const UNITS: [(&str, u128); 8] = [
+        ("ns", 1),
+        ("us", 1_000),
+        ("µs", 1_000),
+        ("μs", 1_000), // Greek small letter mu, rather than the micro sign.
+        ("ms", 1_000_000),
+        ("s", 1_000_000_000),
+        ("m", 60 * 1_000_000_000),
+        ("h", 60 * 60 * 1_000_000_000),
+    ];
+
+    let mut value = value;
+    let mut nanos: u128 = 0;
+    while !value.is_empty() {
+        let (rest, int, fraction) = parse_number(value)?;
+        let unit_end = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (unit, rest) = rest.split_at(unit_end);
+        let (_, scale) = UNITS.iter().find(|(u, _)| *u == unit)?;
+        nanos = nanos.checked_add(scaled(int, fraction, *scale)?)?;
+        value = rest;
+    }
+    let secs = u64::try_from(nanos / 1_000_000_000).ok()?;
+    Some(Duration::new(secs, (nanos % 1_000_000_000) as u32))
+}
+
+/// Parses a byte size, e.g. `512B`, `1.5MB` (decimal units) or `4KiB` (binary
+/// units), returning the number of bytes.
+pub(crate) fn parse_bytes(value: &str) -> Option<u64> {
+    const UNITS: [(&str, u128); 10] = [
+        ("B", 1),
+        ("kB", 1_000),
+        ("KB", 1_000),
+        ("MB", 1_000_000),
+        ("GB", 1_000_000_000),
+        ("TB", 1_000_000_000_000),
+        ("KiB", 1 << 10),
+        ("MiB", 1 << 20),
+        ("GiB", 1 << 30),
+        ("TiB", 1 << 40),
+    ];
+
+    let (unit, int, fraction) = parse_number(value)?;
+    let (_, scale) = UNITS.iter().find(|(u, _)| *u == unit)?;
+    u64::try_from(scaled(int, fraction, *scale)?).ok()
+}
+
+/// Parses a non-negative decimal number, e.g. `1` or `1.5`, returning the
+/// remaining input, the integer part and the fractional digits.
+fn parse_number(value: &str) -> Option<(&str, u128, &str)> {
+    let int_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    if int_end == 0 {
+        return None;
+    }
+    let int = value[..int_end].parse().ok()?;
+    match value[int_end..].strip_prefix('.') {
+        Some(rest) => {
+            let fraction_end = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            if fraction_end == 0 {
+                return None;
+            }
+            Some((&rest[fraction_end..], int, &rest[..fraction_end]))
+        }
+        None => Some((&value[int_end..], int, "")),
+    }
+}
+
+/// Returns `(int + 0.fraction) * scale`, rounded down.
+fn scaled(int: u128, fraction: &str, scale: u128) -> Option<u128> {
+    let mut value = int.checked_mul(scale)?;
+    let mut divisor = 1;
+    for digit in fraction.bytes() {
+        divisor *= 10;
+        if divisor > scale {
+            // Precision is lower than the unit.
+            break;
+        }
+        value += u128::from(digit - b'0') * scale / divisor;
+    }
+    Some(value)
+}
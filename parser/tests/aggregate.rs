@@ -0,0 +1,131 @@
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+use std_logger_parser::{
+    Aggregate, Aggregator, Column, CountBy, ErrorRate, ParserOptions, Percentiles, Record,
+    TimeSeries,
+};
+
+const LOGS: &[u8] =
+    b"ts=\"2021-02-23T13:15:00.000000Z\" lvl=\"INFO\" msg=\"GET /\" target=\"request\" status=200 took=10ms
+ts=\"2021-02-23T13:15:10.000000Z\" lvl=\"ERROR\" msg=\"failed\" target=\"app\"
+ts=\"2021-02-23T13:15:20.000000Z\" lvl=\"INFO\" msg=\"GET /a\" target=\"request\" status=404 took=20ms
+ts=\"2021-02-23T13:15:30.000000Z\" lvl=\"WARN\" msg=\"slow\" target=\"app\"
+ts=\"2021-02-23T13:16:00.000000Z\" lvl=\"INFO\" msg=\"GET /b\" target=\"request\" status=200 took=30ms
+lvl=\"ERROR\" msg=\"no timestamp\" target=\"app\" took=40ms
+ts=\"2021-02-23T13:16:20.000000Z\" lvl=\"INFO\" msg=\"GET /c\" target=\"request\" status=200 took=\"fast\"
+";
+
+/// 2021-02-23T13:15:00Z.
+const START: u64 = 1614086100;
+
+fn records() -> Vec<Record> {
+    ParserOptions::new()
+        .unit_values(true)
+        .parse(LOGS)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+fn time(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(START + secs)
+}
+
+#[test]
+fn count_by() {
+    let mut status = CountBy::new(Column::Key("status".into()));
+    let mut target = CountBy::new(Column::Target);
+    let mut msg = CountBy::new(Column::Message);
+    for record in records() {
+        status.add(&record);
+        target.add(&record);
+        msg.add(&record);
+    }
+    assert_eq!(status.counts(), [("200", 3), ("404", 1)]);
+    assert_eq!(status.missing(), 3);
+    assert_eq!(target.counts(), [("request", 4), ("app", 3)]);
+    assert_eq!(target.missing(), 0);
+    // Equal counts are ordered by value.
+    let msgs = msg
+        .counts()
+        .into_iter()
+        .map(|(msg, _)| msg)
+        .collect::<Vec<_>>();
+    let want = [
+        "GET /",
+        "GET /a",
+        "GET /b",
+        "GET /c",
+        "failed",
+        "no timestamp",
+        "slow",
+    ];
+    assert_eq!(msgs, want);
+}
+
+#[test]
+fn percentiles() {
+    let mut took = Percentiles::new("took");
+    assert_eq!(took.percentile(50.0), None);
+    for record in records() {
+        took.add(&record);
+    }
+    assert_eq!(took.count(), 4);
+    let tests = [
+        (0.0, 0.010),
+        (25.0, 0.010),
+        (50.0, 0.020),
+        (75.0, 0.030),
+        (90.0, 0.040),
+        (100.0, 0.040),
+    ];
+    for (percentile, want) in tests {
+        assert_eq!(took.percentile(percentile), Some(want), "p{percentile}");
+    }
+
+    // Integers and floats.
+    let mut values = Percentiles::new("value");
+    for value in ["3", "1.5", "2", "\"two\""] {
+        let logs = format!("lvl=\"INFO\" msg=\"\" value={value}\n");
+        let record = ParserOptions::new().parse(logs.as_bytes()).next().unwrap();
+        values.add(&record.unwrap());
+    }
+    assert_eq!(values.percentile(50.0), Some(2.0));
+}
+
+#[test]
+#[should_panic = "percentile must be between 0 and 100"]
+fn percentiles_invalid() {
+    let _ = Percentiles::new("took").percentile(100.1);
+}
+
+#[test]
+fn error_rate() {
+    let mut errors = ErrorRate::new(Duration::from_secs(60));
+    let mut warnings = ErrorRate::new(Duration::from_secs(60)).level(Level::Warn);
+    for record in records() {
+        errors.add(&record);
+        warnings.add(&record);
+    }
+    assert_eq!(errors.points(), [(time(0), 0.25), (time(60), 0.0)]);
+    assert_eq!(warnings.points(), [(time(0), 0.5), (time(60), 0.0)]);
+}
+
+#[test]
+fn aggregators() {
+    let mut status = CountBy::new(Column::Key("status".into()));
+    let mut took = Percentiles::new("took");
+    let mut errors = ErrorRate::new(Duration::from_secs(60));
+    let mut series = TimeSeries::new("status", Duration::from_secs(60)).aggregate(Aggregate::Count);
+    let mut aggregators: [&mut dyn Aggregator; 4] =
+        [&mut status, &mut took, &mut errors, &mut series];
+    for record in records() {
+        for aggregator in aggregators.iter_mut() {
+            aggregator.add(&record);
+        }
+    }
+    assert_eq!(status.counts().len(), 2);
+    assert_eq!(took.count(), 4);
+    assert_eq!(errors.points().len(), 2);
+    assert_eq!(series.points(), [(time(0), 2.0), (time(60), 2.0)]);
+}
@@ -0,0 +1,134 @@
+//! Tests for `parse_async` and `ParserOptions::parse_async`.
+
+#![cfg(feature = "tokio")]
+
+use std::io;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{self, Poll};
+use std::time::{Duration, SystemTime};
+
+use futures_util::StreamExt;
+use log::Level;
+use std_logger_parser::{parse_async, ParseErrorKind, ParserOptions, Record};
+use tokio::io::{AsyncRead, ReadBuf};
+
+/// `AsyncRead` implementation that returns `Poll::Pending` before returning
+/// each slice in `slices`.
+struct PendingSlices<'a> {
+    slices: &'a [&'a [u8]],
+    pending: bool,
+}
+
+impl<'a> AsyncRead for PendingSlices<'a> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        ctx: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.pending {
+            self.pending = false;
+            ctx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.pending = true;
+        if let Some((slice, rest)) = self.slices.split_first() {
+            buf.put_slice(slice);
+            self.slices = rest;
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn parse_async_pending_reads() {
+    let reader = PendingSlices {
+        slices: &[
+            b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"Hello",
+            b" world\" target=\"target\" module=\"module\"\nlvl=\"WARN\" msg=\"second\" \n",
+        ],
+        pending: true,
+    };
+
+    let mut expected1 = Record::empty();
+    expected1.timestamp = Some(SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447000));
+    expected1.level = Level::Info;
+    expected1.msg = "Hello world".into();
+    expected1.target = "target".into();
+    expected1.module = Some("module".into());
+    let mut expected2 = Record::empty();
+    expected2.level = Level::Warn;
+    expected2.msg = "second".into();
+
+    let got: Vec<Record> = parse_async(reader)
+        .map(|record| record.expect("unexpected parsing error"))
+        .collect()
+        .await;
+    assert_eq!(got, vec![expected1, expected2]);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn parse_async_with_options() {
+    let reader = PendingSlices {
+        slices: &[
+            b"severity=\"WARN\" message=\"Hello\"\n",
+            b"lvl=\"NOT_INFO\" msg=\"a very long message\"\nlvl=\"INFO\" msg=\"not returned\"\n",
+        ],
+        pending: true,
+    };
+    let mut records = ParserOptions::new()
+        .key_aliases(&[("severity", "lvl"), ("message", "msg")])
+        .strict(true)
+        .max_error_line(16)
+        .parse_async(reader);
+
+    let record = records.next().await.unwrap().unwrap();
+    assert_eq!(record.level, Level::Warn);
+    assert_eq!(record.msg, "Hello");
+    let err = records.next().await.unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    assert_eq!(
+        &*err.line.unwrap(),
+        b"lvl=\"NOT ... [24 bytes omitted] ... message\""
+    );
+    // Stopped after the first error.
+    assert!(records.next().await.is_none());
+    assert_eq!(records.stats().errors, 1);
+}
+
+/// `AsyncRead` implementation that returns the bytes written to it so far,
+/// like a log file that is being written to.
+struct Growing(Arc<Mutex<Vec<u8>>>);
+
+impl AsyncRead for Growing {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _: &mut task::Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut data = self.0.lock().unwrap();
+        let n = data.len().min(buf.remaining());
+        buf.put_slice(&data[..n]);
+        drop(data.drain(..n));
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn parse_async_follow() {
+    let data = Arc::new(Mutex::new(b"lvl=\"INFO\" msg=\"first\"\n".to_vec()));
+    let mut records = ParserOptions::new()
+        .follow(true)
+        .poll_interval(Duration::from_millis(5))
+        .parse_async(Growing(data.clone()));
+
+    assert_eq!(records.next().await.unwrap().unwrap().msg, "first");
+    // Waits for more input, rather than returning `None`.
+    let next = tokio::time::timeout(Duration::from_millis(50), records.next()).await;
+    assert!(next.is_err());
+
+    data.lock()
+        .unwrap()
+        .extend_from_slice(b"lvl=\"INFO\" msg=\"second\"\n");
+    assert_eq!(records.next().await.unwrap().unwrap().msg, "second");
+}
@@ -0,0 +1,157 @@
+//! Exporting records as CSV, Arrow and Parquet.
+
+use std_logger_parser::{write_csv, Column, ParserOptions, Record};
+
+const LOGS: &[u8] = b"ts=\"2021-02-23T13:15:01.000000Z\" lvl=\"INFO\" msg=\"GET /\" target=\"request\" module=\"app\" status=200 took=1.5ms size=1kB ok=true file=\"src/main.rs:12\"
+ts=\"2021-02-23T13:15:02.000000Z\" lvl=\"WARN\" msg=\"quote \\\"a\\\", b\" target=\"request\" status=404.5 took=2ms ok=false user=\"Bob\"
+lvl=\"ERROR\" msg=\"multi\\nline\" target=\"db\" status= user=42
+";
+
+fn records() -> Vec<Record> {
+    ParserOptions::new()
+        .unit_values(true)
+        .parse(LOGS)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap()
+}
+
+fn columns() -> Vec<Column> {
+    let columns = [
+        "ts", "lvl", "msg", "target", "module", "file", "line", "status", "took", "size", "ok",
+        "user", "missing",
+    ];
+    columns.iter().map(|c| c.parse().unwrap()).collect()
+}
+
+#[test]
+fn column_from_str() {
+    assert_eq!("ts".parse::<Column>().unwrap(), Column::Timestamp);
+    assert_eq!("line".parse::<Column>().unwrap(), Column::Line);
+    let column = "request_id".parse::<Column>().unwrap();
+    assert_eq!(column, Column::Key("request_id".to_owned()));
+    assert_eq!(column.name(), "request_id");
+}
+
+#[test]
+fn csv() {
+    let mut output = Vec::new();
+    write_csv(&mut output, &columns(), records().iter()).unwrap();
+    let want = "ts,lvl,msg,target,module,file,line,status,took,size,ok,user,missing
+2021-02-23T13:15:01.000000Z,INFO,GET /,request,app,src/main.rs,12,200,1.5ms,1000B,true,,
+2021-02-23T13:15:02.000000Z,WARN,\"quote \"\"a\"\", b\",request,,,,404.5,2ms,,false,Bob,
+,ERROR,\"multi
+line\",db,,,,,,,,42,
+";
+    assert_eq!(String::from_utf8(output).unwrap(), want);
+}
+
+#[test]
+fn csv_no_records() {
+    let mut output = Vec::new();
+    write_csv(&mut output, &columns()[..3], Vec::<Record>::new()).unwrap();
+    assert_eq!(output, b"ts,lvl,msg\n");
+}
+
+#[test]
+#[cfg(feature = "arrow")]
+fn arrow() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::{
+        DurationNanosecondType, Float64Type, TimestampNanosecondType, UInt32Type, UInt64Type,
+    };
+    use arrow_array::Array;
+    use arrow_schema::{DataType, TimeUnit};
+    use std_logger_parser::to_arrow;
+
+    let batch = to_arrow(&columns(), records()).unwrap();
+    assert_eq!(batch.num_rows(), 3);
+    let schema = batch.schema();
+    let types = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            (
+                field.name().as_str(),
+                field.data_type().clone(),
+                field.is_nullable(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let timestamp = DataType::Timestamp(TimeUnit::Nanosecond, Some("UTC".into()));
+    let want = vec![
+        ("ts", timestamp, true),
+        ("lvl", DataType::Utf8, false),
+        ("msg", DataType::Utf8, false),
+        ("target", DataType::Utf8, false),
+        ("module", DataType::Utf8, true),
+        ("file", DataType::Utf8, true),
+        ("line", DataType::UInt32, true),
+        // Mix of integers and floats.
+        ("status", DataType::Float64, true),
+        ("took", DataType::Duration(TimeUnit::Nanosecond), true),
+        ("size", DataType::UInt64, true),
+        ("ok", DataType::Boolean, true),
+        // Mix of strings and integers.
+        ("user", DataType::Utf8, true),
+        ("missing", DataType::Utf8, true),
+    ];
+    assert_eq!(types, want);
+
+    let ts = batch.column(0).as_primitive::<TimestampNanosecondType>();
+    assert_eq!(ts.value(0), 1_614_086_101_000_000_000);
+    assert!(ts.is_null(2));
+    assert_eq!(batch.column(2).as_string::<i32>().value(2), "multi\nline");
+    assert!(batch.column(4).is_null(1));
+    assert_eq!(batch.column(6).as_primitive::<UInt32Type>().value(0), 12);
+    let status = batch.column(7).as_primitive::<Float64Type>();
+    assert_eq!((status.value(0), status.value(1)), (200.0, 404.5));
+    assert!(status.is_null(2));
+    let took = batch.column(8).as_primitive::<DurationNanosecondType>();
+    assert_eq!(took.value(0), 1_500_000);
+    assert_eq!(batch.column(9).as_primitive::<UInt64Type>().value(0), 1000);
+    assert!(!batch.column(10).as_boolean().value(1));
+    let user = batch.column(11).as_string::<i32>();
+    assert_eq!((user.value(1), user.value(2)), ("Bob", "42"));
+    assert_eq!(batch.column(12).null_count(), 3);
+
+    assert!(to_arrow(&[], records()).is_err());
+}
+
+#[test]
+#[cfg(feature = "parquet")]
+fn parquet() {
+    use std::fs::File;
+
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Int64Type;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use std_logger_parser::{to_arrow, write_parquet};
+
+    let path = std::env::temp_dir().join("std-logger-parser-export.parquet");
+    write_parquet(File::create(&path).unwrap(), &columns(), records()).unwrap();
+
+    let file = File::open(&path).unwrap();
+    let batches = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(batches.len(), 1);
+    let got = &batches[0];
+    let want = to_arrow(&columns(), records()).unwrap();
+    assert_eq!(got.num_rows(), want.num_rows());
+    for (i, field) in want.schema().fields().iter().enumerate() {
+        if field.name() == "took" {
+            // Durations are written as nanoseconds.
+            let took = got.column(i).as_primitive::<Int64Type>();
+            assert_eq!(took.values(), &[1_500_000, 2_000_000, 0]);
+            assert!(took.is_null(2));
+        } else {
+            assert_eq!(got.schema().field(i), &**field);
+            assert_eq!(got.column(i), want.column(i), "column {}", field.name());
+        }
+    }
+}
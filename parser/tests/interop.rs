@@ -0,0 +1,156 @@
+//! Logs produced by other logfmt implementations.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+use std_logger_parser::{parse, Record, Value};
+
+#[track_caller]
+fn test_parser(logs: &[u8], expected: Vec<Record>) {
+    let got = parse(logs)
+        .collect::<Result<Vec<Record>, _>>()
+        .expect("unexpected parsing error");
+    assert_eq!(got, expected);
+}
+
+fn new_record(timestamp: Option<SystemTime>, msg: &str, key_values: Vec<(&str, Value)>) -> Record {
+    let mut record = Record::empty();
+    record.timestamp = timestamp;
+    record.level = Level::Info;
+    record.msg = msg.into();
+    record.key_values = key_values
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect::<HashMap<_, _>>();
+    record
+}
+
+fn timestamp(secs: u64, nanos: u32) -> Option<SystemTime> {
+    Some(SystemTime::UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_owned())
+}
+
+/// Logs produced by <https://github.com/go-logfmt/logfmt> (used by go-kit).
+#[test]
+fn go_logfmt() {
+    let logs: &[u8] = b"level=info ts=2021-02-23T13:15:48.624447123Z caller=main.go:42 msg=\"starting server\" addr=:8080
+level=warn ts=2021-02-23T13:15:48.6Z msg=\"quote \\\"inside\\\" message\" err=\"line one\\nline two\"
+level=debug ts=2021-02-23T14:15:48+01:00 msg=\"\\u001b[1mbold\\u001b[0m\" canary count=3 ratio=0.5 ok=true
+level= path=C:\\new\\table msg=\"tab\\tseparated\"
+";
+    let expected = vec![
+        new_record(
+            timestamp(1614086148, 624447123),
+            "starting server",
+            vec![
+                ("level", string("info")),
+                ("caller", string("main.go:42")),
+                ("addr", string(":8080")),
+            ],
+        ),
+        new_record(
+            timestamp(1614086148, 600000000),
+            "quote \"inside\" message",
+            vec![
+                ("level", string("warn")),
+                ("err", string("line one\nline two")),
+            ],
+        ),
+        new_record(
+            timestamp(1614086148, 0),
+            "\u{1b}[1mbold\u{1b}[0m",
+            vec![
+                ("level", string("debug")),
+                ("canary", Value::Bool(true)),
+                ("count", Value::Int(3)),
+                ("ratio", Value::Float(0.5)),
+                ("ok", Value::Bool(true)),
+            ],
+        ),
+        new_record(
+            None,
+            "tab\tseparated",
+            vec![("level", Value::Null), ("path", string("C:\\new\\table"))],
+        ),
+    ];
+    test_parser(logs, expected);
+}
+
+/// Logs produced by the Heroku router, see
+/// <https://devcenter.heroku.com/articles/http-routing#heroku-router-log-format>.
+#[test]
+fn heroku_router() {
+    let logs: &[u8] = b"at=info method=GET path=\"/\" host=myapp.herokuapp.com request_id=8601b555-6a83-4c12-8269-97c8e32cdb22 fwd=\"204.204.204.204\" dyno=web.1 connect=1ms service=18ms status=200 bytes=13 protocol=https
+at=error code=H12 desc=\"Request timeout\" method=GET path=\"/\" host=myapp.herokuapp.com request_id=8601b555-6a83-4c12-8269-97c8e32cdb22 fwd=\"204.204.204.204\" dyno=web.1 connect=0ms service=30000ms status=503 bytes=0 protocol=https
+";
+    let common = || {
+        vec![
+            ("method", string("GET")),
+            ("path", string("/")),
+            ("host", string("myapp.herokuapp.com")),
+            ("request_id", string("8601b555-6a83-4c12-8269-97c8e32cdb22")),
+            ("fwd", string("204.204.204.204")),
+            ("dyno", string("web.1")),
+            ("protocol", string("https")),
+        ]
+    };
+    let mut first = common();
+    first.extend([
+        ("at", string("info")),
+        ("connect", string("1ms")),
+        ("service", string("18ms")),
+        ("status", Value::Int(200)),
+        ("bytes", Value::Int(13)),
+    ]);
+    let mut second = common();
+    second.extend([
+        ("at", string("error")),
+        ("code", string("H12")),
+        ("desc", string("Request timeout")),
+        ("connect", string("0ms")),
+        ("service", string("30000ms")),
+        ("status", Value::Int(503)),
+        ("bytes", Value::Int(0)),
+    ]);
+    let expected = vec![new_record(None, "", first), new_record(None, "", second)];
+    test_parser(logs, expected);
+}
+
+/// Logs produced by Grafana Loki, see <https://github.com/grafana/loki>.
+#[test]
+fn loki() {
+    let logs: &[u8] = b"level=info ts=2019-12-12T15:00:00.000000001Z caller=table_manager.go:476 msg=\"creating table\" table=index_2600
+level=error ts=2019-12-12T15:00:00.1Z caller=client.go:349 component=client host=loki:3100 msg=\"final error sending batch\" status=-1 error=\"Post \\\"http://loki:3100/loki/api/v1/push\\\": dial tcp: lookup loki on 127.0.0.11:53: no such host\"
+";
+    let expected = vec![
+        new_record(
+            timestamp(1576162800, 1),
+            "creating table",
+            vec![
+                ("level", string("info")),
+                ("caller", string("table_manager.go:476")),
+                ("table", string("index_2600")),
+            ],
+        ),
+        new_record(
+            timestamp(1576162800, 100000000),
+            "final error sending batch",
+            vec![
+                ("level", string("error")),
+                ("caller", string("client.go:349")),
+                ("component", string("client")),
+                ("host", string("loki:3100")),
+                ("status", Value::Int(-1)),
+                (
+                    "error",
+                    string("Post \"http://loki:3100/loki/api/v1/push\": dial tcp: lookup loki on 127.0.0.11:53: no such host"),
+                ),
+            ],
+        ),
+    ];
+    test_parser(logs, expected);
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use std_logger_parser::{parse, Join, JoinGroup, ParseErrorKind};
+
+const REQUESTS: &[u8] = b"ts=\"2021-02-23T13:15:01.000000Z\" lvl=\"INFO\" msg=\"GET /a\" target=\"request\" request_id=\"1\"
+ts=\"2021-02-23T13:15:02.000000Z\" lvl=\"INFO\" msg=\"GET /b\" target=\"request\" request_id=\"2\"
+ts=\"2021-02-23T13:15:30.000000Z\" lvl=\"INFO\" msg=\"GET /c\" target=\"request\" request_id=3
+ts=\"2021-02-23T13:15:31.000000Z\" lvl=\"INFO\" msg=\"GET /d\" target=\"request\"
+";
+
+const ERRORS: &[u8] =
+    b"ts=\"2021-02-23T13:15:00.500000Z\" lvl=\"ERROR\" msg=\"error a1\" request_id=\"1\"
+ts=\"2021-02-23T13:15:00.600000Z\" lvl=\"WARN\" msg=\"error a2\" request_id=\"1\"
+ts=\"2021-02-23T13:15:29.000000Z\" lvl=\"ERROR\" msg=\"error c\" request_id=\"3\"
+ts=\"2021-02-23T13:15:40.000000Z\" lvl=\"ERROR\" msg=\"error e\" request_id=\"4\"
+";
+
+fn msgs(group: &JoinGroup) -> (&str, Vec<&str>, Vec<&str>) {
+    let left = group.left.iter().map(|r| &*r.msg).collect();
+    let right = group.right.iter().map(|r| &*r.msg).collect();
+    (&group.key, left, right)
+}
+
+#[test]
+fn join() {
+    let groups = Join::new("request_id", parse(REQUESTS), parse(ERRORS))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let got = groups.iter().map(msgs).collect::<Vec<_>>();
+    let want = vec![
+        ("1", vec!["GET /a"], vec!["error a1", "error a2"]),
+        ("2", vec!["GET /b"], vec![]),
+        ("3", vec!["GET /c"], vec!["error c"]),
+        ("4", vec![], vec!["error e"]),
+    ];
+    assert_eq!(got, want);
+}
+
+#[test]
+fn join_window() {
+    let mut join =
+        Join::new("request_id", parse(REQUESTS), parse(ERRORS)).window(Duration::from_secs(10));
+    // Groups 1 and 2 are complete once we see the records of request 3.
+    let group = join.next().unwrap().unwrap();
+    assert_eq!(
+        msgs(&group),
+        ("1", vec!["GET /a"], vec!["error a1", "error a2"])
+    );
+    let group = join.next().unwrap().unwrap();
+    assert_eq!(msgs(&group), ("2", vec!["GET /b"], vec![]));
+    let got = join.map(|group| group.unwrap().key).collect::<Vec<_>>();
+    assert_eq!(got, ["3", "4"]);
+}
+
+#[test]
+fn join_errors() {
+    let errors: &[u8] = b"lvl=\"NOT_INFO\" msg=\"invalid\"\n";
+    let mut join = Join::new("request_id", parse(REQUESTS), parse(errors));
+    let err = join.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    assert_eq!(join.count(), 3);
+}
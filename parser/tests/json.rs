@@ -0,0 +1,248 @@
+//! Logs in JSON format (NDJSON).
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+use std_logger_parser::{parse, JsonTimestamp, ParseErrorKind, ParserOptions, Record, Value};
+
+#[track_caller]
+fn test_parser(logs: &[u8], expected: Vec<Record>) {
+    let got = parse(logs)
+        .collect::<Result<Vec<Record>, _>>()
+        .expect("unexpected parsing error");
+    assert_eq!(got, expected);
+}
+
+fn new_record(
+    timestamp: Option<SystemTime>,
+    level: Level,
+    msg: &str,
+    target: &str,
+    file: Option<(&str, u32)>,
+    key_values: Vec<(&str, Value)>,
+) -> Record {
+    let mut record = Record::empty();
+    record.timestamp = timestamp;
+    record.level = level;
+    record.msg = msg.into();
+    record.target = target.into();
+    record.module = Some(target.into());
+    record.file = file.map(|(f, l)| (f.to_owned(), l));
+    record.key_values = key_values
+        .into_iter()
+        .map(|(key, value)| (key.into(), value))
+        .collect::<HashMap<_, _>>();
+    record
+}
+
+fn timestamp(secs: u64, nanos: u32) -> Option<SystemTime> {
+    Some(SystemTime::UNIX_EPOCH + Duration::new(secs, nanos))
+}
+
+fn string(value: &str) -> Value {
+    Value::String(value.to_owned())
+}
+
+/// Logs produced by the JSON format of std-logger.
+#[test]
+fn json_format() {
+    let logs: &[u8] = br#"{"timestamp":"2021-02-23T13:15:48.624447Z","level":"INFO","message":"Hello world","target":"my_app","module":"my_app","user":"Thomas","id":123,"ratio":0.5,"ok":true,"none":null}
+{"level":"ERROR","message":"oops","target":"my_app","module":"my_app","error":"line one\nline two","file":"src/main.rs","line":"12"}
+"#;
+    let expected = vec![
+        new_record(
+            timestamp(1614086148, 624447000),
+            Level::Info,
+            "Hello world",
+            "my_app",
+            None,
+            vec![
+                ("user", string("Thomas")),
+                ("id", Value::Int(123)),
+                ("ratio", Value::Float(0.5)),
+                ("ok", Value::Bool(true)),
+                ("none", Value::Null),
+            ],
+        ),
+        new_record(
+            None,
+            Level::Error,
+            "oops",
+            "my_app",
+            Some(("src/main.rs", 12)),
+            vec![("error", string("line one\nline two"))],
+        ),
+    ];
+    test_parser(logs, expected);
+}
+
+/// Logs produced by the Google Cloud Platform format of std-logger.
+#[test]
+fn gcloud_format() {
+    let logs: &[u8] = br#"{"timestamp":"2021-02-23T13:15:48.624447Z","severity":"WARNING","message":"Hello world","target":"my_app","module":"my_app","id":"123"}
+{"severity":"CRITICAL","message":"panicked","target":"panic","module":"my_app","sourceLocation":{"file":"src/main.rs","line":"12"}}
+"#;
+    let expected = vec![
+        new_record(
+            timestamp(1614086148, 624447000),
+            Level::Warn,
+            "Hello world",
+            "my_app",
+            None,
+            vec![("id", string("123"))],
+        ),
+        {
+            let mut record = new_record(
+                None,
+                Level::Error,
+                "panicked",
+                "panic",
+                Some(("src/main.rs", 12)),
+                Vec::new(),
+            );
+            record.module = Some("my_app".into());
+            record
+        },
+    ];
+    test_parser(logs, expected);
+}
+
+#[test]
+fn mixed_logfmt_and_json() {
+    let logs: &[u8] = br#"lvl="INFO" msg="logfmt" target="my_app" module="my_app"
+  {"level":"INFO","message":"json","target":"my_app","module":"my_app"}
+"#;
+    let expected = vec![
+        new_record(None, Level::Info, "logfmt", "my_app", None, Vec::new()),
+        new_record(None, Level::Info, "json", "my_app", None, Vec::new()),
+    ];
+    test_parser(logs, expected);
+}
+
+#[test]
+fn escapes() {
+    let logs = r#"{ "level" : "INFO" , "message" : "quote \" slash \/ \\ \té\u00e9😀\ud83d\ude00", "target":"my_app","module":"my_app","key \"quoted\"":"\b\f\r"}
+"#;
+    let expected = vec![new_record(
+        None,
+        Level::Info,
+        "quote \" slash / \\ \téé😀😀",
+        "my_app",
+        None,
+        vec![("key \"quoted\"", string("\u{0008}\u{000C}\r"))],
+    )];
+    test_parser(logs.as_bytes(), expected);
+}
+
+#[test]
+fn nested_values() {
+    let logs: &[u8] = br#"{"level":"INFO","message":"nested","target":"my_app","module":"my_app","object":{"a":[1,"}"]},"array":[{},[]]}
+"#;
+    let expected = vec![new_record(
+        None,
+        Level::Info,
+        "nested",
+        "my_app",
+        None,
+        vec![
+            ("object", string(r#"{"a":[1,"}"]}"#)),
+            ("array", string("[{},[]]")),
+        ],
+    )];
+    test_parser(logs, expected);
+}
+
+#[test]
+fn invalid_json() {
+    let tests: &[&[u8]] = &[
+        br#"{"level":"INFO","message":"missing end""#,
+        br#"{"level":"INFO","message":"trailing"} garbage"#,
+        br#"{"level":"INFO","message":"invalid escape \x"}"#,
+        br#"{"level":"INFO","message":"invalid surrogate \ud83d"}"#,
+        br#"{"level":"INFO",message:"unquoted key"}"#,
+        br#"{"level":"INFO","message":invalid}"#,
+    ];
+    for test in tests {
+        let mut input = test.to_vec();
+        input.push(b'\n');
+        let mut got = parse(&*input);
+        let err = got.next().unwrap().unwrap_err();
+        assert_eq!(err.line.as_deref(), Some(*test));
+        assert_eq!(err.kind, ParseErrorKind::InvalidJson);
+        assert!(got.next().is_none());
+    }
+
+    let mut got = parse(&br#"{"level":"LOUD","message":"invalid level"}"#[..]);
+    let err = got.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+}
+
+#[test]
+fn write_json() {
+    let mut record = new_record(
+        timestamp(1614086148, 624447000),
+        Level::Warn,
+        "quote \" newline \n",
+        "my_app",
+        Some(("src/main.rs", 12)),
+        vec![
+            ("user", string("Thomas")),
+            ("id", Value::Int(-123)),
+            ("ratio", Value::Float(1.0)),
+            ("nan", Value::Float(f64::NAN)),
+            ("ok", Value::Bool(false)),
+            ("none", Value::Null),
+            ("at", Value::Timestamp(timestamp(1614086100, 1).unwrap())),
+            ("took", Value::Duration(Duration::from_micros(1500))),
+            ("size", Value::Bytes(1024)),
+            ("key \"quoted\"", string("\u{0001}")),
+        ],
+    );
+    let mut got = Vec::new();
+    record.write_json(&mut got, JsonTimestamp::Rfc3339).unwrap();
+    let want = r#"{"timestamp":"2021-02-23T13:15:48.624447Z","level":"WARN","message":"quote \" newline \n","target":"my_app","module":"my_app","at":"2021-02-23T13:15:00.000000001Z","id":-123,"key \"quoted\"":"\u0001","nan":null,"none":null,"ok":false,"ratio":1.0,"size":"1024B","took":"1.5ms","user":"Thomas","file":"src/main.rs","line":"12"}
+"#;
+    assert_eq!(String::from_utf8(got).unwrap(), want);
+
+    // Parsing the written record should return the same record.
+    let _ = record.key_values.remove("nan");
+    let mut output = Vec::new();
+    record
+        .write_json(&mut output, JsonTimestamp::Rfc3339)
+        .unwrap();
+    let got = ParserOptions::new()
+        .timestamp_values(true)
+        .unit_values(true)
+        .parse(&*output)
+        .collect::<Result<Vec<Record>, _>>()
+        .expect("unexpected parsing error");
+    assert_eq!(got, vec![record]);
+}
+
+#[test]
+fn write_json_timestamps() {
+    let tests = &[
+        (JsonTimestamp::Rfc3339, r#""2021-02-23T13:15:48.624447Z""#),
+        (JsonTimestamp::UnixSecs, "1614086148.624447"),
+        (JsonTimestamp::UnixMillis, "1614086148624"),
+        (JsonTimestamp::UnixNanos, "1614086148624447000"),
+    ];
+    let mut record = Record::empty();
+    record.timestamp = timestamp(1614086148, 624447000);
+    for (representation, want) in tests {
+        let mut got = Vec::new();
+        record.write_json(&mut got, *representation).unwrap();
+        let want = format!(
+            r#"{{"timestamp":{},"level":"INFO","message":"","target":"","module":""}}
+"#,
+            want
+        );
+        assert_eq!(
+            String::from_utf8(got).unwrap(),
+            want,
+            "{:?}",
+            representation
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use std::io::{self, Read};
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
+
+use std_logger_parser::{parse_merged, ParseErrorKind};
+
+const REPLICA1: &[u8] = b"ts=\"2021-02-23T13:15:01.000000Z\" lvl=\"INFO\" msg=\"1a\"
+ts=\"2021-02-23T13:15:03.000000Z\" lvl=\"INFO\" msg=\"1b\"
+ts=\"2021-02-23T13:15:05.000000Z\" lvl=\"INFO\" msg=\"1c\"
+";
+
+const REPLICA2: &[u8] = b"ts=\"2021-02-23T13:15:00.000000Z\" lvl=\"INFO\" msg=\"2a\"
+ts=\"2021-02-23T13:15:03.000000Z\" lvl=\"INFO\" msg=\"2b\"
+ts=\"2021-02-23T13:15:04.000000Z\" lvl=\"INFO\" msg=\"2c\"
+ts=\"2021-02-23T13:15:06.000000Z\" lvl=\"INFO\" msg=\"2d\"
+";
+
+const REPLICA3: &[u8] = b"ts=\"2021-02-23T13:15:02.000000Z\" lvl=\"INFO\" msg=\"3a\"
+";
+
+#[test]
+fn merge() {
+    let mut merged = parse_merged(vec![REPLICA1, REPLICA2, REPLICA3]);
+    let mut got = Vec::new();
+    while let Some(record) = merged.next() {
+        let record = record.unwrap();
+        got.push((record.msg, merged.last_source().unwrap()));
+    }
+    let want = [
+        ("2a", 1),
+        ("1a", 0),
+        ("3a", 2),
+        // Equal timestamps are ordered by reader.
+        ("1b", 0),
+        ("2b", 1),
+        ("2c", 1),
+        ("1c", 0),
+        ("2d", 1),
+    ];
+    let got = got.iter().map(|(m, i)| (&**m, *i)).collect::<Vec<_>>();
+    assert_eq!(got, want);
+    assert_eq!(merged.stats().len(), 3);
+    assert_eq!(merged.stats()[1].records, 4);
+}
+
+#[test]
+fn merge_empty() {
+    let mut merged = parse_merged(Vec::<&[u8]>::new());
+    assert!(merged.next().is_none());
+    assert!(merged.last_source().is_none());
+
+    let mut merged = parse_merged(vec![&b""[..], REPLICA3]);
+    assert_eq!(merged.next().unwrap().unwrap().msg, "3a");
+    assert!(merged.next().is_none());
+}
+
+#[test]
+fn merge_errors() {
+    let invalid: &[u8] = b"lvl=\"NOT_INFO\" msg=\"invalid\"
+ts=\"2021-02-23T13:15:02.500000Z\" lvl=\"INFO\" msg=\"valid\"
+";
+    let mut merged = parse_merged(vec![REPLICA3, invalid]);
+    let err = merged.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    assert_eq!(merged.last_source(), Some(1));
+    let got = merged.map(|record| record.unwrap().msg).collect::<Vec<_>>();
+    assert_eq!(got, ["3a", "valid"]);
+}
+
+/// Reader that blocks until it receives the bytes to return.
+struct Blocking(Receiver<&'static [u8]>);
+
+impl Read for Blocking {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.recv() {
+            Ok(bytes) => {
+                buf[..bytes.len()].copy_from_slice(bytes);
+                Ok(bytes.len())
+            }
+            // End of the input.
+            Err(_) => Ok(0),
+        }
+    }
+}
+
+#[test]
+fn merge_blocking_reader() {
+    let (sender, receiver) = mpsc::channel();
+    let blocking: Box<dyn Read + Send> = Box::new(Blocking(receiver));
+    let readers = vec![blocking, Box::new(REPLICA1)];
+    let mut merged = parse_merged(readers).max_wait(Duration::from_millis(50));
+
+    // The records of the other reader are returned after waiting for the
+    // blocked reader once.
+    let start = Instant::now();
+    let got = (&mut merged)
+        .take(3)
+        .map(|record| record.unwrap().msg)
+        .collect::<Vec<_>>();
+    assert_eq!(got, ["1a", "1b", "1c"]);
+    assert!(start.elapsed() < Duration::from_secs(1));
+
+    sender
+        .send(b"ts=\"2021-02-23T13:15:00.000000Z\" lvl=\"INFO\" msg=\"late\"\n")
+        .unwrap();
+    let record = merged.next().unwrap().unwrap();
+    assert_eq!(record.msg, "late");
+    assert_eq!(merged.last_source(), Some(0));
+
+    drop(sender);
+    assert!(merged.next().is_none());
+}
@@ -1,9 +1,13 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::io::{self, Read};
+use std::io::{self, Cursor, Read};
 use std::time::{Duration, SystemTime};
 
 use log::Level;
-use std_logger_parser::{parse, ParseErrorKind, Record, Value};
+use std_logger_parser::{
+    format_timestamp, parse, ParseErrorKind, ParserOptions, ParserStats, Record, RecordString,
+    Value, ValueRef,
+};
 
 const BUF_SIZE: usize = 4096;
 
@@ -32,14 +36,14 @@ fn new_record(
     target: &str,
     module: Option<&str>,
     file: Option<(&str, u32)>,
-    key_values: HashMap<String, Value>,
+    key_values: HashMap<RecordString, Value>,
 ) -> Record {
     let mut record = Record::empty();
     record.timestamp = timestamp;
     record.level = level;
-    record.msg = msg.to_owned();
-    record.target = target.to_owned();
-    record.module = module.map(|m| m.to_owned());
+    record.msg = msg.into();
+    record.target = target.into();
+    record.module = module.map(|m| m.into());
     record.file = file.map(|(f, l)| (f.to_owned(), l));
     record.key_values = key_values;
     record
@@ -51,24 +55,31 @@ fn new_timestamp(ts: &str) -> SystemTime {
 }
 
 #[track_caller]
-fn new_timestamptz(ts: &str, gmtoff: libc::c_long) -> SystemTime {
-    let mut tm = libc::tm {
-        tm_sec: ts[17..19].parse().unwrap(),
-        tm_min: ts[14..16].parse().unwrap(),
-        tm_hour: ts[11..13].parse().unwrap(),
-        tm_mday: ts[8..10].parse().unwrap(),
-        tm_mon: (ts[5..7].parse::<i32>().unwrap()) - 1,
-        tm_year: (ts[0..4].parse::<i32>().unwrap()) - 1900,
-        tm_wday: 0,
-        tm_yday: 0,
-        tm_isdst: 0,
-        tm_gmtoff: gmtoff,
-        tm_zone: std::ptr::null_mut(),
-    };
-    let time_offset = unsafe { libc::timegm(&mut tm) };
+fn new_timestamptz(ts: &str, gmtoff: i64) -> SystemTime {
+    let year: u64 = ts[0..4].parse().unwrap();
+    let month: u64 = ts[5..7].parse().unwrap();
+    let day: u64 = ts[8..10].parse().unwrap();
+    let hour: u64 = ts[11..13].parse().unwrap();
+    let min: u64 = ts[14..16].parse().unwrap();
+    let sec: u64 = ts[17..19].parse().unwrap();
+    let is_leap = |year| (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let mut days = (1970..year)
+        .map(|year| if is_leap(year) { 366 } else { 365 })
+        .sum::<u64>();
+    for month in 1..month {
+        days += match month {
+            2 if is_leap(year) => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+    }
+    days += day - 1;
+    let secs = days * 86400 + hour * 3600 + min * 60 + sec;
     // Create the timestamp from the time offset and the nanosecond precision.
-    let nanos: u32 = ts[20..26].parse().unwrap();
-    SystemTime::UNIX_EPOCH + Duration::new(time_offset as u64, nanos)
+    let nanos = ts[20..26].parse::<u32>().unwrap() * 1000;
+    let secs = (secs as i64 - gmtoff) as u64;
+    SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
 }
 
 struct MultiSlice<'a> {
@@ -83,7 +94,7 @@ impl<'a> Read for MultiSlice<'a> {
             match slice.read(&mut buf[accumulated_len..]) {
                 Ok(n) => {
                     accumulated_len += n;
-                    if n == (&*slice).len() {
+                    if n == slice.len() {
                         remove += 1;
                     } else {
                         break;
@@ -93,9 +104,9 @@ impl<'a> Read for MultiSlice<'a> {
             }
         }
 
-        let slices = std::mem::replace(&mut self.slices, &mut []);
+        let slices = std::mem::take(&mut self.slices);
         self.slices = &mut slices[remove..];
-        return Ok(accumulated_len);
+        Ok(accumulated_len)
     }
 }
 
@@ -176,13 +187,13 @@ fn smoke() {
             None,
             {
                 let mut m = HashMap::new();
-                m.insert("key1".to_owned(), Value::String("value1".to_owned()));
-                m.insert("key2".to_owned(), Value::String("value2".to_owned()));
-                m.insert("key3".to_owned(), Value::Int(3));
-                m.insert("key4".to_owned(), Value::Int(-4));
-                m.insert("key5".to_owned(), Value::Float(5.0));
-                m.insert("key6".to_owned(), Value::Bool(true));
-                m.insert("key7".to_owned(), Value::Bool(false));
+                m.insert("key1".into(), Value::String("value1".to_owned()));
+                m.insert("key2".into(), Value::String("value2".to_owned()));
+                m.insert("key3".into(), Value::Int(3));
+                m.insert("key4".into(), Value::Int(-4));
+                m.insert("key5".into(), Value::Float(5.0));
+                m.insert("key6".into(), Value::Bool(true));
+                m.insert("key7".into(), Value::Bool(false));
                 m
             },
         ),
@@ -195,7 +206,7 @@ fn smoke() {
             None,
             {
                 let mut m = HashMap::new();
-                m.insert("backtrace".to_owned(), Value::String("   0: std::backtrace_rs::backtrace::libunwind::trace\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/../../backtrace/src/backtrace/libunwind.rs:90:5\n      std::backtrace_rs::backtrace::trace_unsynchronized\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/../../backtrace/src/backtrace/mod.rs:66:5\n      std::backtrace::Backtrace::create\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/backtrace.rs:327:13\n   1: std::backtrace::Backtrace::force_capture\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/backtrace.rs:310:9\n   2: std_logger::log_panic\n             at ./src/lib.rs:346:21\n   3: core::ops::function::Fn::call\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/core/src/ops/function.rs:70:5\n   4: std::panicking::rust_panic_with_hook\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:595:17\n   5: std::panicking::begin_panic::{{closure}}\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/panicking.rs:520:9\n   6: std::sys_common::backtrace::__rust_end_short_backtrace\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/sys_common/backtrace.rs:141:18\n   7: std::panicking::begin_panic\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/panicking.rs:519:12\n   8: panic::main\n             at ./examples/panic.rs:15:5\n   9: core::ops::function::FnOnce::call_once\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/core/src/ops/function.rs:227:5\n  10: std::sys_common::backtrace::__rust_begin_short_backtrace\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/sys_common/backtrace.rs:125:18\n  11: std::rt::lang_start::{{closure}}\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/rt.rs:66:18\n  12: core::ops::function::impls::<impl core::ops::function::FnOnce<A> for &F>::call_once\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/core/src/ops/function.rs:259:13\n      std::panicking::try::do_call\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:379:40\n      std::panicking::try\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:343:19\n      std::panic::catch_unwind\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panic.rs:431:14\n      std::rt::lang_start_internal\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/rt.rs:51:25\n  13: std::rt::lang_start\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/rt.rs:65:5\n  14: _main\n".to_owned()));
+                m.insert("backtrace".into(), Value::String("   0: std::backtrace_rs::backtrace::libunwind::trace\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/../../backtrace/src/backtrace/libunwind.rs:90:5\n      std::backtrace_rs::backtrace::trace_unsynchronized\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/../../backtrace/src/backtrace/mod.rs:66:5\n      std::backtrace::Backtrace::create\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/backtrace.rs:327:13\n   1: std::backtrace::Backtrace::force_capture\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/backtrace.rs:310:9\n   2: std_logger::log_panic\n             at ./src/lib.rs:346:21\n   3: core::ops::function::Fn::call\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/core/src/ops/function.rs:70:5\n   4: std::panicking::rust_panic_with_hook\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:595:17\n   5: std::panicking::begin_panic::{{closure}}\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/panicking.rs:520:9\n   6: std::sys_common::backtrace::__rust_end_short_backtrace\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/sys_common/backtrace.rs:141:18\n   7: std::panicking::begin_panic\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/panicking.rs:519:12\n   8: panic::main\n             at ./examples/panic.rs:15:5\n   9: core::ops::function::FnOnce::call_once\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/core/src/ops/function.rs:227:5\n  10: std::sys_common::backtrace::__rust_begin_short_backtrace\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/sys_common/backtrace.rs:125:18\n  11: std::rt::lang_start::{{closure}}\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/rt.rs:66:18\n  12: core::ops::function::impls::<impl core::ops::function::FnOnce<A> for &F>::call_once\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/core/src/ops/function.rs:259:13\n      std::panicking::try::do_call\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:379:40\n      std::panicking::try\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panicking.rs:343:19\n      std::panic::catch_unwind\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/panic.rs:431:14\n      std::rt::lang_start_internal\n             at /rustc/a143517d44cac50b20cbd3a0b579addab40dd399/library/std/src/rt.rs:51:25\n  13: std::rt::lang_start\n             at /Users/thomas/.rustup/toolchains/nightly-x86_64-apple-darwin/lib/rustlib/src/rust/library/std/src/rt.rs:65:5\n  14: _main\n".to_owned()));
                 m
             },
         ),
@@ -212,6 +223,73 @@ fn smoke() {
     test_parser(MultiSlice { slices: lines }, expected);
 }
 
+#[test]
+fn timestamp_dates() {
+    let tests: &[(&str, i64, u32)] = &[
+        ("1970-01-01T00:00:00Z", 0, 0),
+        ("1969-12-31T23:59:59.5Z", -1, 500_000_000),
+        ("1900-01-01T00:00:00Z", -2208988800, 0),
+        ("2000-02-29T12:00:00Z", 951825600, 0),
+        ("2024-02-29T00:00:00Z", 1709164800, 0),
+        ("2021-04-30T00:00:00Z", 1619740800, 0),
+        ("2021-12-31T00:00:00Z", 1640908800, 0),
+        ("2000-03-01T00:00:00+01:00", 951865200, 0),
+        ("2100-03-01T00:00:00Z", 4107542400, 0),
+        ("2262-04-11T23:47:16.854775807Z", 9223372036, 854_775_807),
+    ];
+    for (input, secs, nanos) in tests {
+        let logs = format!("ts=\"{}\" lvl=INFO\n", input);
+        let record = parse(logs.as_bytes()).next().unwrap().unwrap();
+        let want = if *secs >= 0 {
+            SystemTime::UNIX_EPOCH + Duration::from_secs(*secs as u64)
+        } else {
+            SystemTime::UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+        } + Duration::from_nanos(u64::from(*nanos));
+        assert_eq!(record.timestamp, Some(want), "input: {}", input);
+
+        // Writing and parsing again should result in the same timestamp.
+        let mut buf = Vec::new();
+        record.write_logfmt(&mut buf).unwrap();
+        let got = parse(&*buf).next().unwrap().unwrap();
+        assert_eq!(got.timestamp, Some(want), "input: {}", input);
+
+        let logs = format!("ts=\"{}\"\n", format_timestamp(want));
+        let got = parse(logs.as_bytes()).next().unwrap().unwrap();
+        assert_eq!(got.timestamp, Some(want), "input: {}", input);
+    }
+
+    let timestamp = SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447000);
+    assert_eq!(format_timestamp(timestamp), "2021-02-23T13:15:48.624447Z");
+    let timestamp = SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447123);
+    assert_eq!(
+        format_timestamp(timestamp),
+        "2021-02-23T13:15:48.624447123Z"
+    );
+
+    for input in [
+        "2021-13-01T00:00:00Z",
+        "2021-00-01T00:00:00Z",
+        "2021-01-00T00:00:00Z",
+        "2021-01-32T00:00:00Z",
+        "2021-02-30T00:00:00Z",
+        "2021-04-31T00:00:00Z",
+        "2021-11-31T00:00:00Z",
+        // Not leap years.
+        "2021-02-29T00:00:00Z",
+        "1900-02-29T00:00:00Z",
+        "2100-02-29T00:00:00Z",
+    ] {
+        let logs = format!("ts=\"{}\"\n", input);
+        let err = parse(logs.as_bytes()).next().unwrap().unwrap_err();
+        assert_eq!(
+            err.kind,
+            ParseErrorKind::InvalidTimestamp,
+            "input: {}",
+            input
+        );
+    }
+}
+
 #[test]
 fn timestamps() {
     let lines: &mut [&[u8]] = &mut [
@@ -417,7 +495,8 @@ fn invalid_lines() {
         &[115, 111, 109, 101, 0x80, 107, 101, 121, 61, 49, 50, 51, 10], // Invalid UTF-8.
 
         // Invalid timestamp.
-        b"ts=2021-02-23T13:15:48.62444Z\n", // Invalid length (too short).
+        b"ts=2021-02-23T13:15:48.Z\n", // Missing fractional seconds.
+        b"ts=2021-02-23T13:15:48.6244470001Z\n", // Too precise (more than nanoseconds).
         b"ts=2021-02-23T13:15:48.624447+02:00A\n", // Invalid length (too long).
         // Incorrect formatting of delimiters.
         b"ts=2021A02-23T13:15:48.624447Z\n", // Year-month.
@@ -486,6 +565,7 @@ fn invalid_lines() {
         ParseErrorKind::InvalidTimestamp,
         ParseErrorKind::InvalidTimestamp,
         ParseErrorKind::InvalidTimestamp,
+        ParseErrorKind::InvalidTimestamp,
         ParseErrorKind::InvalidLevel,
         ParseErrorKind::InvalidLevel,
         ParseErrorKind::InvalidValue,
@@ -522,14 +602,266 @@ fn invalid_lines() {
             err.line,
             expected.0,
             "got: {}, expected: {}",
-            String::from_utf8_lossy(&*err.line.as_ref().unwrap()),
-            String::from_utf8_lossy(&*expected.0.as_ref().unwrap()),
+            String::from_utf8_lossy(err.line.as_ref().unwrap()),
+            String::from_utf8_lossy(expected.0.as_ref().unwrap()),
         );
         assert_eq!(err.kind, *expected.1);
     }
     assert!(expected.len() == 0, "left: {:?}", expected.as_slice());
 }
 
+#[test]
+fn multiple_errors() {
+    let logs: &[u8] =
+        b"ts=\"2021-02-23T13:15:48.Z\" lvl=\"LOUD\" msg=\"multiple errors\" file=\"main.rs\"
+{\"timestamp\":\"yesterday\",\"level\":\"LOUD\",\"message\":\"json\"}
+{\"timestamp\":\"yesterday\",\"message\":invalid}
+lvl=\"LOUD\" msg=\"single error\"
+";
+    let mut parser = parse(logs);
+
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidTimestamp);
+    let fields = err
+        .fields
+        .iter()
+        .map(|field| (&*field.key, &field.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("ts", &ParseErrorKind::InvalidTimestamp),
+            ("lvl", &ParseErrorKind::InvalidLevel),
+            ("file", &ParseErrorKind::InvalidFile),
+        ]
+    );
+    assert!(err.to_string().starts_with("error parsing log message: invalid timestamp (key `ts`), invalid level (key `lvl`), invalid file (key `file`), in line "));
+
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidTimestamp);
+    let fields = err
+        .fields
+        .iter()
+        .map(|field| (&*field.key, &field.kind))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        fields,
+        [
+            ("timestamp", &ParseErrorKind::InvalidTimestamp),
+            ("level", &ParseErrorKind::InvalidLevel),
+        ]
+    );
+
+    // Error that stops parsing the record.
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidJson);
+    assert_eq!(err.fields.len(), 1);
+    assert_eq!(err.fields[0].key, "timestamp");
+    assert_eq!(err.fields[0].kind, ParseErrorKind::InvalidTimestamp);
+    assert!(err.to_string().starts_with(
+        "error parsing log message: invalid timestamp (key `timestamp`), invalid JSON, in line "
+    ));
+
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    assert_eq!(err.fields.len(), 1);
+    assert!(err
+        .to_string()
+        .starts_with("error parsing log message: invalid level, in line "));
+
+    assert!(parser.next().is_none());
+}
+
+#[test]
+fn max_error_line() {
+    let mut logs = b"lvl=\"LOUD\" msg=\"".to_vec();
+    logs.extend(std::iter::repeat_n(b'a', 5000));
+    logs.extend_from_slice(b"\"\nlvl=\"LOUD\" msg=\"short\"\n");
+
+    let mut parser = parse(&*logs);
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    let mut want = b"lvl=\"LOUD\" msg=\"".to_vec();
+    want.extend(std::iter::repeat_n(b'a', 512 - want.len()));
+    want.extend_from_slice(b" ... [3993 bytes omitted] ... ");
+    want.extend(std::iter::repeat_n(b'a', 511));
+    want.push(b'"');
+    assert_eq!(err.line.as_deref(), Some(&*want));
+    // Short lines are never truncated.
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(
+        err.line.as_deref(),
+        Some(&b"lvl=\"LOUD\" msg=\"short\""[..])
+    );
+
+    let mut parser = ParserOptions::new().max_error_line(10).parse(&*logs);
+    let err = parser.next().unwrap().unwrap_err();
+    let want = b"lvl=\" ... [5007 bytes omitted] ... aaaa\"";
+    assert_eq!(err.line.as_deref(), Some(&want[..]));
+}
+
+#[test]
+fn resync_after_multi_line_error() {
+    let logs: &[u8] =
+        b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"ERROR\" msg=\"unbalanced \x80 quote
+ts=\"2021-02-23T13:15:49.624447Z\" lvl=\"INFO\" msg=\"next\"
+  ts=\"2021-02-23T13:15:50.624447Z\" lvl=\"INFO\" msg=\"multi\nline\"
+";
+    let mut parser = parse(logs);
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidValue);
+    assert_eq!(
+        err.line.as_deref(),
+        Some(&b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"ERROR\" msg=\"unbalanced \x80 quote"[..])
+    );
+    let expected = vec![
+        new_record(
+            Some(new_timestamp("2021-02-23T13:15:49.624447Z")),
+            Level::Info,
+            "next",
+            "",
+            None,
+            None,
+            HashMap::new(),
+        ),
+        new_record(
+            Some(new_timestamp("2021-02-23T13:15:50.624447Z")),
+            Level::Info,
+            "multi\nline",
+            "",
+            None,
+            None,
+            HashMap::new(),
+        ),
+    ];
+    let got = parser.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(got, expected);
+
+    // Using a different key.
+    let logs: &[u8] = b"lvl=\"ERROR\" msg=\"unbalanced \x80 quote
+lvl=\"INFO\" msg=\"next\"
+";
+    let mut parser = ParserOptions::new().resync_key("lvl").parse(logs);
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidValue);
+    let expected = vec![new_record(
+        None,
+        Level::Info,
+        "next",
+        "",
+        None,
+        None,
+        HashMap::new(),
+    )];
+    let got = parser.collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn strict() {
+    let logs: &[u8] = b"lvl=\"INFO\" msg=\"first\"
+lvl=\"NOT_INFO\" msg=\"invalid\"
+lvl=\"INFO\" msg=\"not parsed\"
+";
+    let mut parser = ParserOptions::new().strict(true).parse(logs);
+    let record = parser.next().unwrap().unwrap();
+    assert_eq!(record.msg, "first");
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidLevel);
+    assert!(parser.next().is_none());
+    assert!(parser.next().is_none());
+}
+
+#[test]
+fn stats() {
+    let logs: &[u8] = b"lvl=\"INFO\" msg=\"first\"
+
+lvl=\"NOT_INFO\" msg=\"invalid\"
+lvl=\"ERROR\" msg=\"invalid \x80
+multi-line\"
+lvl=\"INFO\" msg=\"last\"
+";
+    let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let p = progress.clone();
+    let mut parser = parse(logs).with_progress(move |stats| p.lock().unwrap().push(*stats));
+    assert_eq!(parser.stats(), ParserStats::default());
+    assert_eq!(parser.by_ref().count(), 4);
+
+    let stats = parser.stats();
+    assert_eq!(stats.bytes_read, logs.len() as u64);
+    assert_eq!(stats.records, 2);
+    assert_eq!(stats.lines_skipped, 3);
+    assert_eq!(stats.errors, 2);
+
+    // Called after the first read and the read hitting the end of the input.
+    let progress = progress.lock().unwrap();
+    assert_eq!(progress.len(), 2);
+    assert_eq!(progress[0].bytes_read, logs.len() as u64);
+    assert_eq!(progress[0].records, 0);
+    assert_eq!(progress[1], stats);
+}
+
+#[test]
+fn interleaved() {
+    let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.000000Z\" lvl=\"INFO\" msg=\"first\"
+ts=\"2021-02-23T13:15:49.000000Z\" lvl=\"INFO\" msg=\"hel ts=\"2021-02-23T13:15:50.000000Z\" lvl=\"INFO\" msg=\"other\"
+lo\" target=\"a\"
+ts=\"2021-02-23T13:15:51.000000Z\" lvl=\"INFO\" msg=\"last\"
+";
+    let mut parser = ParserOptions::new().interleaved(true).parse(logs);
+    assert_eq!(parser.next().unwrap().unwrap().msg, "first");
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::Interleaved);
+    assert_eq!(
+        err.line.as_deref(),
+        Some(&b"ts=\"2021-02-23T13:15:49.000000Z\" lvl=\"INFO\" msg=\"hel "[..])
+    );
+    assert_eq!(parser.next().unwrap().unwrap().msg, "other");
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::Interleaved);
+    assert_eq!(err.line.as_deref(), Some(&b"lo\" target=\"a\""[..]));
+    assert_eq!(parser.next().unwrap().unwrap().msg, "last");
+    assert!(parser.next().is_none());
+    assert_eq!(parser.stats().errors, 2);
+
+    // Disabled by default.
+    let got = parse(logs).filter(Result::is_ok).count();
+    assert!(got < 4, "parsed {} records", got);
+}
+
+#[test]
+fn last_raw() {
+    let mut logs = Vec::new();
+    let mut want = Vec::new();
+    for i in 0..200 {
+        if i % 10 == 0 {
+            logs.extend_from_slice(b"\n  ");
+        }
+        let start = logs.len();
+        let line = if i % 7 == 0 {
+            format!("lvl=\"NOT_INFO\" msg=\"Invalid {i}\"")
+        } else {
+            format!("lvl=\"INFO\" msg=\"Record {i}\" target=\"target\"")
+        };
+        logs.extend_from_slice(line.as_bytes());
+        want.push(start as u64..logs.len() as u64);
+        logs.push(b'\n');
+    }
+
+    let mut parser = parse(&*logs);
+    assert!(parser.last_raw().is_none());
+    let mut want = want.into_iter();
+    while let Some(result) = parser.next() {
+        let want = want.next().unwrap();
+        let raw = parser.last_raw().unwrap();
+        assert_eq!(raw.bytes, &logs[want.start as usize..want.end as usize]);
+        assert_eq!(raw.range, want);
+        assert_eq!(result.is_err(), raw.bytes.starts_with(b"lvl=\"NOT_INFO\""));
+    }
+    assert!(want.next().is_none());
+    assert!(parser.last_raw().is_none());
+}
+
 #[test]
 fn io_error_and_continue() {
     struct ErrReading<'a> {
@@ -579,3 +911,430 @@ fn io_error_and_continue() {
     assert_eq!(got, expected);
     assert!(parser.next().is_none());
 }
+
+#[test]
+fn follow() {
+    /// Reader that hits the end of the input after each chunk.
+    struct Chunks<'a> {
+        chunks: &'a [&'a [u8]],
+        hit_end: bool,
+    }
+
+    impl<'a> Read for Chunks<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if std::mem::replace(&mut self.hit_end, true) {
+                self.hit_end = false;
+                return Ok(0);
+            }
+            match self.chunks.split_first() {
+                Some((chunk, chunks)) => {
+                    self.chunks = chunks;
+                    (&mut &**chunk).read(buf)
+                }
+                None => Ok(0),
+            }
+        }
+    }
+
+    let logs = Chunks {
+        chunks: &[
+            b"lvl=INFO msg=Hello target=target1\n",
+            b"lvl=WARN msg=\"Hello ",
+            b"world\" target=target2",
+            b"\n",
+        ],
+        hit_end: false,
+    };
+
+    let options = ParserOptions::new()
+        .follow(true)
+        .poll_interval(Duration::from_millis(1));
+    let got = options
+        .parse(logs)
+        .take(2)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let expected = vec![
+        new_record(
+            None,
+            Level::Info,
+            "Hello",
+            "target1",
+            None,
+            None,
+            HashMap::new(),
+        ),
+        new_record(
+            None,
+            Level::Warn,
+            "Hello world",
+            "target2",
+            None,
+            None,
+            HashMap::new(),
+        ),
+    ];
+    assert_eq!(got, expected);
+}
+
+#[test]
+fn null_values() {
+    let logs: &[u8] = b"lvl=INFO msg=Hello target=target key1= key2=\"\" key3=  key4=4 key5=\n";
+    let mut key_values = HashMap::new();
+    let _ = key_values.insert("key1".into(), Value::Null);
+    let _ = key_values.insert("key2".into(), Value::String(String::new()));
+    let _ = key_values.insert("key3".into(), Value::Null);
+    let _ = key_values.insert("key4".into(), Value::Int(4));
+    let _ = key_values.insert("key5".into(), Value::Null);
+    let expected = vec![new_record(
+        None,
+        Level::Info,
+        "Hello",
+        "target",
+        None,
+        None,
+        key_values,
+    )];
+    test_parser(logs, expected);
+}
+
+#[test]
+fn next_record_borrowed() {
+    let logs: &[u8] = b"lvl=WARN msg=\"Hello\\nworld\" target=target module=module key1=value1 key2=\"value\\\"2\" key1=3\nlvl=INFO msg=Hello target=target\n";
+    let mut parser = parse(logs);
+
+    let record = parser.next_record().unwrap().unwrap();
+    assert_eq!(record.level, Level::Warn);
+    // Only escaped values allocate.
+    assert!(matches!(record.msg, Cow::Owned(ref msg) if msg == "Hello\nworld"));
+    assert!(matches!(record.target, Cow::Borrowed("target")));
+    assert!(matches!(record.module, Some(Cow::Borrowed("module"))));
+    let want = vec![
+        (
+            Cow::Borrowed("key1"),
+            ValueRef::String(Cow::Borrowed("value1")),
+        ),
+        (
+            Cow::Borrowed("key2"),
+            ValueRef::String(Cow::Owned("value\"2".to_owned())),
+        ),
+        (Cow::Borrowed("key1"), ValueRef::Int(3)),
+    ];
+    assert_eq!(record.key_values, want);
+    // Last value wins.
+    let record = record.into_owned();
+    assert_eq!(record.key_values.len(), 2);
+    assert_eq!(record.key_values["key1"], Value::Int(3));
+
+    let record = parser.next_record().unwrap().unwrap();
+    assert_eq!(record.level, Level::Info);
+    assert_eq!(record.msg, "Hello");
+    assert!(record.key_values.is_empty());
+
+    assert!(parser.next_record().is_none());
+}
+
+#[test]
+fn timestamp_values() {
+    let logs: &[u8] = b"lvl=INFO msg=Hello target=target expires_at=\"2021-02-23T13:15:48.624447Z\" created_at=2021-02-23T13:15:48+02:00 id=2021 name=\"2021-02-23 is not a timestamp\"\n";
+
+    let mut key_values = HashMap::new();
+    let _ = key_values.insert(
+        "expires_at".into(),
+        Value::Timestamp(new_timestamp("2021-02-23T13:15:48.624447Z")),
+    );
+    let _ = key_values.insert(
+        "created_at".into(),
+        Value::Timestamp(new_timestamptz("2021-02-23T13:15:48.000000Z", 7200)),
+    );
+    let _ = key_values.insert("id".into(), Value::Int(2021));
+    let _ = key_values.insert(
+        "name".into(),
+        Value::String("2021-02-23 is not a timestamp".to_owned()),
+    );
+    let expected = new_record(None, Level::Info, "Hello", "target", None, None, key_values);
+    let got = ParserOptions::new()
+        .timestamp_values(true)
+        .parse(logs)
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(got, expected);
+
+    // Disabled by default.
+    let got = parse(logs).next().unwrap().unwrap();
+    assert_eq!(
+        got.key_values["expires_at"],
+        Value::String("2021-02-23T13:15:48.624447Z".to_owned())
+    );
+}
+
+#[test]
+fn unit_values() {
+    let tests: &[(&str, Value)] = &[
+        ("15ms", Value::Duration(Duration::from_millis(15))),
+        ("\"2.5s\"", Value::Duration(Duration::from_millis(2500))),
+        ("1.000000001s", Value::Duration(Duration::new(1, 1))),
+        ("100µs", Value::Duration(Duration::from_micros(100))),
+        ("100us", Value::Duration(Duration::from_micros(100))),
+        ("10ns", Value::Duration(Duration::from_nanos(10))),
+        ("1h30m", Value::Duration(Duration::from_secs(90 * 60))),
+        ("1m0.5s", Value::Duration(Duration::from_millis(60_500))),
+        ("512B", Value::Bytes(512)),
+        ("1.5MB", Value::Bytes(1_500_000)),
+        ("4KiB", Value::Bytes(4096)),
+        ("1.5GiB", Value::Bytes(3 << 29)),
+        ("2kB", Value::Bytes(2000)),
+        // Not a duration or byte size.
+        ("15", Value::Int(15)),
+        ("1.5", Value::Float(1.5)),
+        ("ms", Value::String("ms".to_owned())),
+        ("\"15 ms\"", Value::String("15 ms".to_owned())),
+        ("15.ms", Value::String("15.ms".to_owned())),
+        ("15years", Value::String("15years".to_owned())),
+        ("1.5mb", Value::String("1.5mb".to_owned())),
+        ("-1s", Value::String("-1s".to_owned())),
+        ("1s1", Value::String("1s1".to_owned())),
+    ];
+    for (value, want) in tests {
+        let logs = format!("lvl=INFO msg=Hello value={value}\n");
+        let got = ParserOptions::new()
+            .unit_values(true)
+            .parse(logs.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(got.key_values["value"], *want, "value: {value}");
+    }
+
+    // Disabled by default.
+    let got = parse(&b"lvl=INFO msg=Hello took=15ms\n"[..])
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(got.key_values["took"], Value::String("15ms".to_owned()));
+
+    // Written in a format that is parsed as the same value.
+    let mut record = Record::empty();
+    let _ = record
+        .key_values
+        .insert("took".into(), Value::Duration(Duration::from_micros(1500)));
+    let _ = record.key_values.insert("size".into(), Value::Bytes(1024));
+    let mut buf = Vec::new();
+    record.write_logfmt(&mut buf).unwrap();
+    let got = ParserOptions::new()
+        .unit_values(true)
+        .parse(&*buf)
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(got, record);
+}
+
+#[test]
+fn seek_to_timestamp() {
+    let mut logs = Vec::new();
+    for i in 0..100 {
+        let line = format!(
+            "ts=\"2021-02-23T13:{:02}:{:02}.000000Z\" lvl=INFO msg=\"Record {i}\" target=target backtrace=\"line one\nline two\"\n",
+            i / 60,
+            i % 60,
+        );
+        logs.extend_from_slice(line.as_bytes());
+    }
+    let ts = |i: u64| new_timestamp("2021-02-23T13:00:00.000000Z") + Duration::from_secs(i);
+
+    for i in [0, 1, 42, 59, 60, 99] {
+        let mut parser = parse(Cursor::new(&logs));
+        let offset = parser.seek_to_timestamp(ts(i)).unwrap();
+        let got = parser.next().unwrap().unwrap();
+        assert_eq!(got.msg, format!("Record {i}"));
+        assert_eq!(got.timestamp, Some(ts(i)));
+        assert!(logs[offset as usize..].starts_with(b"ts="));
+        assert_eq!(parser.last_raw().unwrap().range.start, offset);
+    }
+
+    // Before the first record.
+    let mut parser = parse(Cursor::new(&logs));
+    assert_eq!(parser.seek_to_timestamp(SystemTime::UNIX_EPOCH).unwrap(), 0);
+    assert_eq!(parser.next().unwrap().unwrap().msg, "Record 0");
+
+    // After the last record.
+    let mut parser = parse(Cursor::new(&logs));
+    let offset = parser.seek_to_timestamp(ts(100)).unwrap();
+    assert_eq!(offset, logs.len() as u64);
+    assert!(parser.next().is_none());
+}
+
+#[test]
+fn quoted_strings() {
+    let logs: &[u8] = b"lvl=INFO msg=Hello target=target user_id=\"00123\" count=\"3\" took_ms=12 ok=true cached=\"false\"\n";
+
+    let mut key_values = HashMap::new();
+    let _ = key_values.insert("user_id".into(), Value::String("00123".to_owned()));
+    let _ = key_values.insert("count".into(), Value::String("3".to_owned()));
+    let _ = key_values.insert("took_ms".into(), Value::Int(12));
+    let _ = key_values.insert("ok".into(), Value::Bool(true));
+    let _ = key_values.insert("cached".into(), Value::String("false".to_owned()));
+    let expected = new_record(None, Level::Info, "Hello", "target", None, None, key_values);
+    let got = ParserOptions::new()
+        .quoted_strings(true)
+        .parse(logs)
+        .next()
+        .unwrap()
+        .unwrap();
+    assert_eq!(got, expected);
+
+    // Disabled by default.
+    let got = parse(logs).next().unwrap().unwrap();
+    assert_eq!(got.key_values["user_id"], Value::Int(123));
+}
+
+#[test]
+fn msg_last() {
+    let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" target=\"target\" module=\"module\" key1=\"value1\" file=\"file.rs:123\" msg=\"msg \\\"last\\\" key2=value2\"
+ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"ERROR\" target=\"panic\" module=\"\" msg=\"thread 'main' panicked at src/main.rs:1:1:
+oops\"
+";
+
+    let mut key_values = HashMap::new();
+    let _ = key_values.insert("key1".into(), Value::String("value1".to_owned()));
+    let expected = vec![
+        new_record(
+            Some(new_timestamp("2021-02-23T13:15:48.624447Z")),
+            Level::Info,
+            "msg \"last\" key2=value2",
+            "target",
+            Some("module"),
+            Some(("file.rs", 123)),
+            key_values,
+        ),
+        new_record(
+            Some(new_timestamp("2021-02-23T13:15:48.624447Z")),
+            Level::Error,
+            "thread 'main' panicked at src/main.rs:1:1:\noops",
+            "panic",
+            None,
+            None,
+            HashMap::new(),
+        ),
+    ];
+    test_parser(logs, expected);
+}
+
+#[test]
+fn write_logfmt() {
+    let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"WARN\" msg=\"line one\\nline \\\"two\\\"\\u001b\" target=\"target\" module=\"module\" user=\"Thomas\" id=123 ratio=1.0 ok=false empty= \"key with space\"=\"value\" ts2=\"2021-02-23T13:15:48.624447123Z\" file=\"C:\\src\\main.rs:42\"
+lvl=\"INFO\" msg=\"\" target=\"\" module=\"\"
+";
+    let want: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"WARN\" msg=\"line one\\nline \\\"two\\\"\\u001b\" target=\"target\" module=\"module\" empty= id=123 \"key with space\"=\"value\" ok=false ratio=1.0 ts2=\"2021-02-23T13:15:48.624447123Z\" user=\"Thomas\" file=\"C:\\src\\main.rs:42\"
+lvl=\"INFO\" msg=\"\" target=\"\" module=\"\"
+";
+
+    let records = ParserOptions::new()
+        .timestamp_values(true)
+        .parse(logs)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    let mut output = Vec::new();
+    for record in &records {
+        record.write_logfmt(&mut output).unwrap();
+    }
+    assert_eq!(
+        output,
+        want,
+        "got: {}, want: {}",
+        String::from_utf8_lossy(&output),
+        String::from_utf8_lossy(want)
+    );
+
+    let got = ParserOptions::new()
+        .timestamp_values(true)
+        .parse(&*output)
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(got, records);
+}
+
+#[test]
+fn separate_line() {
+    let logs: &[u8] = b"lvl=\"INFO\" msg=\"a\" target=\"t\" file=\"src/main.rs\" line=12
+lvl=\"INFO\" msg=\"b\" target=\"t\" line=3 file=\"src/main.rs:12\"
+lvl=\"INFO\" msg=\"c\" target=\"t\" file=\"src/main.rs\"
+";
+    let mut parser = parse(logs);
+
+    let record = parser.next().unwrap().unwrap();
+    assert_eq!(record.file, Some(("src/main.rs".to_owned(), 12)));
+    assert!(record.key_values.is_empty());
+
+    // `line` without a file without line is a normal key.
+    let record = parser.next().unwrap().unwrap();
+    assert_eq!(record.file, Some(("src/main.rs".to_owned(), 12)));
+    assert_eq!(record.key_values["line"], Value::Int(3));
+
+    let err = parser.next().unwrap().unwrap_err();
+    assert_eq!(err.kind, ParseErrorKind::InvalidFile);
+    assert!(parser.next().is_none());
+}
+
+/// Parses random (but logfmt-like) input read in chunks of random sizes,
+/// checking the parser doesn't panic and that parsed records can be written
+/// and parsed again. See the fuzz targets in `fuzz` for more thorough testing.
+#[test]
+fn random_input() {
+    const ALPHABET: &[u8] = b"ab0=\" \"=\\\n{}:.-Zts=lvl=\"INFO\" msg=file=\"a.rs:1\"\r\t\x80\xff";
+
+    /// Reader that returns the input in chunks of random sizes.
+    struct Chunked<'a> {
+        input: &'a [u8],
+        rng: u64,
+    }
+
+    impl<'a> Read for Chunked<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = (next(&mut self.rng) as usize % 16 + 1)
+                .min(buf.len())
+                .min(self.input.len());
+            buf[..n].copy_from_slice(&self.input[..n]);
+            self.input = &self.input[n..];
+            Ok(n)
+        }
+    }
+
+    /// Xorshift random number generator, to keep the test deterministic.
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    let mut rng = 0x2545_F491_4F6C_DD1D;
+    let mut input = Vec::new();
+    for n in 0..2_000 {
+        input.clear();
+        let len = next(&mut rng) as usize % 256;
+        for _ in 0..len {
+            input.push(ALPHABET[next(&mut rng) as usize % ALPHABET.len()]);
+        }
+
+        let options = ParserOptions::new()
+            .interleaved(n % 2 == 0)
+            .timestamp_values(n % 3 == 0)
+            .unit_values(n % 3 == 0)
+            .max_error_line(n % 64);
+        let reader = Chunked {
+            input: &input,
+            rng: next(&mut rng),
+        };
+        for record in options.parse(reader).flatten() {
+            let mut output = Vec::new();
+            record.write_logfmt(&mut output).unwrap();
+            let reparsed = parse(&*output).next().unwrap();
+            // Writing a parsed record and parsing it again must succeed.
+            assert!(reparsed.is_ok(), "{:?}: {:?}", record, reparsed);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Tests for the serde implementations.
+
+#![cfg(feature = "serde")]
+
+use std::io;
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+use std_logger_parser::{parse, ParseError, ParseErrorKind, ParserOptions, Record, Value};
+
+#[test]
+fn record_round_trip() {
+    let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"WARN\" msg=\"Hello world\" target=\"target\" module=\"module\" file=\"src/lib.rs:12\" key1=value1 key2=2 key3=3.5 key4=true\n";
+    let record = parse(logs).next().unwrap().unwrap();
+
+    let json = serde_json::to_string(&record).unwrap();
+    let got: Record = serde_json::from_str(&json).unwrap();
+    assert_eq!(got, record);
+
+    assert_eq!(
+        got.timestamp,
+        Some(SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447000))
+    );
+    assert_eq!(got.level, Level::Warn);
+    assert_eq!(got.key_values["key1"], Value::String("value1".to_owned()));
+    assert_eq!(got.key_values["key2"], Value::Int(2));
+    assert_eq!(got.key_values["key3"], Value::Float(3.5));
+    assert_eq!(got.key_values["key4"], Value::Bool(true));
+}
+
+#[test]
+fn value_untagged() {
+    let tests = [
+        (Value::Null, "null"),
+        (Value::Bool(true), "true"),
+        (Value::Int(-123), "-123"),
+        (Value::Float(1.5), "1.5"),
+        (Value::String("abc".to_owned()), "\"abc\""),
+        (
+            Value::Timestamp(SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447000)),
+            "{\"secs_since_epoch\":1614086148,\"nanos_since_epoch\":624447000}",
+        ),
+        (
+            Value::Duration(Duration::from_millis(1500)),
+            "{\"secs\":1,\"nanos\":500000000}",
+        ),
+    ];
+    for (value, want) in tests {
+        assert_eq!(serde_json::to_string(&value).unwrap(), want);
+        assert_eq!(serde_json::from_str::<Value>(want).unwrap(), value);
+    }
+}
+
+#[test]
+fn error_round_trip() {
+    let logs: &[u8] = b"lvl=\"NOT_A_LEVEL\" msg=\"Hello world\"\n";
+    let err = parse(logs).next().unwrap().unwrap_err();
+
+    let json = serde_json::to_string(&err).unwrap();
+    let got: ParseError = serde_json::from_str(&json).unwrap();
+    assert_eq!(got.line, err.line);
+    assert_eq!(got.kind, ParseErrorKind::InvalidLevel);
+
+    let kind = ParseErrorKind::Io(io::Error::other("some I/O error"));
+    let json = serde_json::to_string(&kind).unwrap();
+    match serde_json::from_str(&json).unwrap() {
+        ParseErrorKind::Io(err) => assert_eq!(err.to_string(), "some I/O error"),
+        kind => panic!("unexpected error kind: {:?}", kind),
+    }
+}
+
+#[test]
+fn deserialize_into() {
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Request {
+        ts: SystemTime,
+        lvl: Level,
+        msg: String,
+        target: String,
+        module: Option<String>,
+        file: String,
+        method: String,
+        status: u16,
+        ratio: f64,
+        admin: bool,
+        user: Option<String>,
+        missing: Option<String>,
+        took: Duration,
+    }
+
+    let logs: &[u8] = b"ts=\"2021-02-23T13:15:48.624447Z\" lvl=\"INFO\" msg=\"request\" target=\"request\" module=\"my_app\" file=\"src/main.rs:12\" method=\"GET\" status=200 ratio=0.5 admin=false user= took=15ms\n";
+    let record = ParserOptions::new()
+        .unit_values(true)
+        .parse(logs)
+        .next()
+        .unwrap()
+        .unwrap();
+    let got: Request = record.deserialize_into().unwrap();
+    let want = Request {
+        ts: SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447000),
+        lvl: Level::Info,
+        msg: "request".to_owned(),
+        target: "request".to_owned(),
+        module: Some("my_app".to_owned()),
+        file: "src/main.rs:12".to_owned(),
+        method: "GET".to_owned(),
+        status: 200,
+        ratio: 0.5,
+        admin: false,
+        user: None,
+        missing: None,
+        took: Duration::from_millis(15),
+    };
+    assert_eq!(got, want);
+
+    // Borrowing from the record.
+    #[derive(serde::Deserialize)]
+    struct Borrowed<'a> {
+        msg: &'a str,
+        method: &'a str,
+    }
+    let got: Borrowed<'_> = record.deserialize_into().unwrap();
+    assert_eq!(got.msg, "request");
+    assert_eq!(got.method, "GET");
+
+    // Invalid types.
+    #[derive(Debug, serde::Deserialize)]
+    #[allow(dead_code)]
+    struct Invalid {
+        method: u16,
+    }
+    let err = record.deserialize_into::<Invalid>().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "invalid type: string \"GET\", expected u16"
+    );
+}
@@ -0,0 +1,65 @@
+use std::time::{Duration, SystemTime};
+
+use std_logger_parser::{parse, Aggregate, TimeSeries};
+
+const LOGS: &[u8] =
+    b"ts=\"2021-02-23T13:15:00.000000Z\" lvl=\"INFO\" msg=\"request\" response_time=10
+ts=\"2021-02-23T13:15:30.000000Z\" lvl=\"INFO\" msg=\"request\" response_time=20.5
+ts=\"2021-02-23T13:15:59.999999Z\" lvl=\"INFO\" msg=\"request\" response_time=30
+ts=\"2021-02-23T13:16:00.000000Z\" lvl=\"INFO\" msg=\"no value\"
+ts=\"2021-02-23T13:16:10.000000Z\" lvl=\"INFO\" msg=\"not a number\" response_time=\"fast\"
+lvl=\"INFO\" msg=\"no timestamp\" response_time=100
+ts=\"2021-02-23T13:17:01.000000Z\" lvl=\"INFO\" msg=\"request\" response_time=5
+";
+
+/// 2021-02-23T13:15:00Z.
+const START: u64 = 1614086100;
+
+fn series(aggregate: Aggregate) -> Vec<(SystemTime, f64)> {
+    let mut series = TimeSeries::new("response_time", Duration::from_secs(60)).aggregate(aggregate);
+    for record in parse(LOGS) {
+        series.add(&record.expect("unexpected parsing error"));
+    }
+    series.points()
+}
+
+fn time(secs: u64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_secs(START + secs)
+}
+
+#[test]
+fn aggregates() {
+    let tests = &[
+        (Aggregate::Sum, [60.5, 5.0]),
+        (Aggregate::Avg, [60.5 / 3.0, 5.0]),
+        (Aggregate::Min, [10.0, 5.0]),
+        (Aggregate::Max, [30.0, 5.0]),
+        (Aggregate::Count, [3.0, 1.0]),
+    ];
+    for (aggregate, want) in tests {
+        let got = series(*aggregate);
+        assert_eq!(
+            got,
+            vec![(time(0), want[0]), (time(120), want[1])],
+            "aggregate: {:?}",
+            aggregate
+        );
+    }
+}
+
+#[test]
+fn sub_second_buckets() {
+    let mut series = TimeSeries::new("response_time", Duration::from_millis(250));
+    for record in parse(LOGS) {
+        series.add(&record.expect("unexpected parsing error"));
+    }
+    let got = series.points();
+    assert_eq!(got.len(), 4);
+    assert_eq!(got[2], (time(59) + Duration::from_millis(750), 30.0));
+}
+
+#[test]
+#[should_panic = "bucket size of time series can't be zero"]
+fn zero_bucket() {
+    let _ = TimeSeries::new("response_time", Duration::ZERO);
+}
@@ -0,0 +1,60 @@
+//! Tests shared between std-logger and std-logger-parser.
+//!
+//! The tests, found in the `tests` directory, format records using std-logger
+//! and parse them again using std-logger-parser, asserting that the output is
+//! equal to the input. This crate provides the proptest strategies used to
+//! generate the records.
+
+use log::Level;
+use proptest::prelude::*;
+use std_logger_parser::Value;
+
+/// Keys used by the logfmt format itself.
+pub const RESERVED_KEYS: [&str; 6] = ["ts", "lvl", "msg", "target", "module", "file"];
+
+/// Any message, including quotes, `=`, new lines, unicode and control
+/// characters.
+pub fn message() -> impl Strategy<Value = String> {
+    prop_oneof![
+        any::<String>(),
+        // Strings build from the characters that need escaping, which are
+        // unlikely to be generated in large numbers by `any::<String>`.
+        "[ =\"\\\\\n\r\t\u{0}\u{7F}a-z]{0,20}",
+    ]
+}
+
+/// Any key, except for the keys in [`RESERVED_KEYS`].
+pub fn key() -> impl Strategy<Value = String> {
+    message().prop_filter("reserved key", |key| !RESERVED_KEYS.contains(&&**key))
+}
+
+/// Any log level.
+pub fn level() -> impl Strategy<Value = Level> {
+    prop_oneof![
+        Just(Level::Error),
+        Just(Level::Warn),
+        Just(Level::Info),
+        Just(Level::Debug),
+        Just(Level::Trace),
+    ]
+}
+
+/// Key-value pairs with unique keys.
+pub fn key_values() -> impl Strategy<Value = Vec<(String, String)>> {
+    prop::collection::btree_map(key(), message(), 0..8)
+        .prop_map(|key_values| key_values.into_iter().collect())
+}
+
+/// Returns the [`Value`] the parser should return for `value`.
+pub fn expected_value(value: &str) -> Value {
+    // Safety: `FromStr` for `Value` never fails.
+    value.parse().unwrap()
+}
+
+/// Compare two `Value`s, considering NaN equal to NaN.
+pub fn value_eq(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Float(l), Value::Float(r)) if l.is_nan() && r.is_nan() => true,
+        (left, right) => left == right,
+    }
+}
@@ -0,0 +1,40 @@
+//! Tests that std-logger-core formats records the same as std-logger.
+
+use log::Record;
+use proptest::prelude::*;
+use std_logger_core::{logfmt, Options};
+use std_logger_round_trip::{key_values, level, message};
+
+proptest! {
+    #[test]
+    fn same_format(msg in message(), level in level(), key_values in key_values(), add_loc: bool) {
+        let kvs: &[(String, String)] = &key_values;
+        let (got, want) = format(
+            &Record::builder()
+                .args(format_args!("{msg}"))
+                .level(level)
+                .target("round_trip")
+                .module_path_static(Some("round_trip::core"))
+                .file_static(Some("tests/core.rs"))
+                .line(Some(42))
+                .key_values(&kvs)
+                .build(),
+            add_loc,
+        );
+        prop_assert_eq!(String::from_utf8_lossy(&got), String::from_utf8_lossy(&want));
+    }
+}
+
+/// Returns `record` formatted by std-logger-core and std-logger (without the
+/// timestamp).
+fn format(record: &Record, add_loc: bool) -> (Vec<u8>, Vec<u8>) {
+    let mut got = Vec::new();
+    logfmt::format(&mut got, record, &Options::new(add_loc));
+    let mut want = std_logger::_format_logfmt(record, add_loc);
+    // std-logger-core doesn't write the timestamp.
+    if want.starts_with(b"ts=") {
+        let n = want.iter().position(|b| *b == b' ').unwrap();
+        let _ = want.drain(..=n);
+    }
+    (got, want)
+}
@@ -0,0 +1,49 @@
+//! Format-then-parse tests for escaping in logfmt.
+
+use log::Record;
+use proptest::prelude::*;
+use std_logger_parser::parse;
+use std_logger_round_trip::{expected_value, key_values, level, message, value_eq};
+
+proptest! {
+    #[test]
+    fn logfmt_round_trip(msg in message(), level in level(), key_values in key_values(), add_loc: bool) {
+        let kvs: &[(String, String)] = &key_values;
+        let output = std_logger::_format_logfmt(
+            &Record::builder()
+                .args(format_args!("{msg}"))
+                .level(level)
+                .target("round_trip")
+                .module_path_static(Some("round_trip::escaping"))
+                .file_static(Some("tests/escaping.rs"))
+                .line(Some(42))
+                .key_values(&kvs)
+                .build(),
+            add_loc,
+        );
+
+        let mut records = parse(&*output);
+        let got = match records.next() {
+            Some(Ok(got)) => got,
+            Some(Err(err)) => panic!("unexpected error: {err}"),
+            None => panic!("missing record, output: {:?}", String::from_utf8_lossy(&output)),
+        };
+        prop_assert!(records.next().is_none(), "unexpected second record, output: {:?}", String::from_utf8_lossy(&output));
+
+        prop_assert_eq!(got.level, level);
+        prop_assert_eq!(&got.msg, &msg);
+        prop_assert_eq!(&got.target, "round_trip");
+        prop_assert_eq!(got.module.as_deref(), Some("round_trip::escaping"));
+        if add_loc {
+            prop_assert_eq!(got.file, Some(("tests/escaping.rs".to_owned(), 42)));
+        } else {
+            prop_assert_eq!(got.file, None);
+        }
+        prop_assert_eq!(got.key_values.len(), key_values.len());
+        for (key, value) in &key_values {
+            let got = got.key_values.get(key.as_str());
+            let want = expected_value(value);
+            prop_assert!(got.is_some_and(|got| value_eq(got, &want)), "key: {key:?}, got: {got:?}, want: {want:?}");
+        }
+    }
+}
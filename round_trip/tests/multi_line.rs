@@ -0,0 +1,145 @@
+//! Format-then-parse tests for multiple records containing multi-line values,
+//! e.g. panic backtraces, read in small chunks.
+
+use std::cmp::min;
+use std::io::{self, Read};
+
+use log::{kv, Level, Record};
+use proptest::prelude::*;
+use std_logger_parser::{parse, Value};
+use std_logger_round_trip::{expected_value, key_values, level, message, value_eq};
+
+/// Reader that returns at most `chunk_size` bytes per read.
+struct Chunked<'a> {
+    input: &'a [u8],
+    chunk_size: usize,
+}
+
+impl<'a> Read for Chunked<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = min(min(self.chunk_size, buf.len()), self.input.len());
+        buf[..n].copy_from_slice(&self.input[..n]);
+        self.input = &self.input[n..];
+        Ok(n)
+    }
+}
+
+fn format(msg: &str, level: Level, kvs: &dyn kv::Source, add_loc: bool) -> Vec<u8> {
+    std_logger::_format_logfmt(
+        &Record::builder()
+            .args(format_args!("{msg}"))
+            .level(level)
+            .target("round_trip")
+            .module_path_static(Some("round_trip::multi_line"))
+            .file_static(Some("tests/multi_line.rs"))
+            .line(Some(42))
+            .key_values(kvs)
+            .build(),
+        add_loc,
+    )
+}
+
+/// A backtrace as formatted by the standard library, containing new lines,
+/// quotes, `=` and backslashes.
+const BACKTRACE: &str = r#"
+stack backtrace:
+   0: std::backtrace::Backtrace::create
+             at /rustc/library/std/src/backtrace.rs:331:13
+   1: std_logger::log_panic
+             at ./src/config.rs:186:21
+   2: core::ops::function::Fn::call
+             at /rustc/library/core/src/ops/function.rs:79:5
+   3: <alloc::boxed::Box<F,A> as core::ops::function::Fn<Args>>::call
+   4: "quoted" path=C:\new\table
+"#;
+
+#[test]
+fn panic_backtraces() {
+    let kvs_display: &[(&str, kv::Value)] = &[
+        ("backtrace", kv::Value::from_display(&BACKTRACE)),
+        ("thread_name", kv::Value::from("main")),
+    ];
+    let kvs_str: &[(&str, &str)] = &[("backtrace", BACKTRACE), ("thread_name", "main")];
+    let mut output = Vec::new();
+    for _ in 0..3 {
+        output.extend(format(
+            "thread 'main' panicked at src/main.rs:1:1:\noops",
+            Level::Error,
+            &kvs_display,
+            true,
+        ));
+        output.extend(format("after = \"panic\"", Level::Info, &kvs_str, false));
+    }
+
+    for chunk_size in 1..=output.len() {
+        let logs = Chunked {
+            input: &output,
+            chunk_size,
+        };
+        let records = parse(logs)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap_or_else(|err| panic!("unexpected error (chunk_size: {chunk_size}): {err}"));
+        assert_eq!(records.len(), 6, "chunk_size: {chunk_size}");
+        for (i, record) in records.into_iter().enumerate() {
+            if i % 2 == 0 {
+                assert_eq!(
+                    record.msg,
+                    "thread 'main' panicked at src/main.rs:1:1:\noops"
+                );
+                assert_eq!(record.level, Level::Error);
+                assert_eq!(record.file, Some(("tests/multi_line.rs".to_owned(), 42)));
+            } else {
+                assert_eq!(record.msg, "after = \"panic\"");
+                assert_eq!(record.level, Level::Info);
+                assert_eq!(record.file, None);
+            }
+            assert_eq!(
+                record.key_values.get("backtrace"),
+                Some(&Value::String(BACKTRACE.to_owned())),
+                "chunk_size: {chunk_size}, record: {i}",
+            );
+            assert_eq!(
+                record.key_values.get("thread_name"),
+                Some(&Value::String("main".to_owned())),
+            );
+        }
+    }
+}
+
+proptest! {
+    #[test]
+    fn logfmt_round_trip_multiple_records(
+        records in prop::collection::vec((message(), level(), key_values(), any::<bool>()), 1..4),
+        chunk_size in 1..64_usize,
+    ) {
+        let mut output = Vec::new();
+        for (msg, level, key_values, add_loc) in &records {
+            let kvs: &[(String, String)] = key_values;
+            output.extend(format(msg, *level, &kvs, *add_loc));
+        }
+
+        let logs = Chunked { input: &output, chunk_size };
+        let mut got = parse(logs);
+        for (msg, level, key_values, add_loc) in &records {
+            let got = match got.next() {
+                Some(Ok(got)) => got,
+                Some(Err(err)) => panic!("unexpected error: {err}"),
+                None => panic!("missing record, output: {:?}", String::from_utf8_lossy(&output)),
+            };
+            prop_assert_eq!(got.level, *level);
+            prop_assert_eq!(&got.msg, msg);
+            if *add_loc {
+                prop_assert_eq!(got.file, Some(("tests/multi_line.rs".to_owned(), 42)));
+            } else {
+                prop_assert_eq!(got.file, None);
+            }
+            prop_assert_eq!(got.key_values.len(), key_values.len());
+            for (key, value) in key_values {
+                let got = got.key_values.get(key.as_str());
+                let want = expected_value(value);
+                prop_assert!(got.is_some_and(|got| value_eq(got, &want)), "key: {key:?}, got: {got:?}, want: {want:?}");
+            }
+        }
+        prop_assert!(got.next().is_none(), "unexpected additional record, output: {:?}", String::from_utf8_lossy(&output));
+    }
+}
@@ -0,0 +1,205 @@
+//! Admin socket, see [`Config::with_admin_socket`].
+//!
+//! The protocol is line based: the client sends a command per line and the
+//! server responds with a single line, see the [crate level documentation].
+//!
+//! [`Config::with_admin_socket`]: crate::Config::with_admin_socket
+//! [crate level documentation]: index.html#admin-feature
+
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use log::LevelFilter;
+
+use crate::config::parse_directive;
+use crate::reload::Reload;
+
+/// Maximum length of a single command, including the new line.
+#[cfg(unix)]
+const MAX_LINE: u64 = 4096;
+
+/// Parse `directives`, e.g. `info,my_crate::db=debug`, into the target
+/// patterns (`None` for all targets) and their level, see
+/// [`EnvMeaning::Directives`].
+///
+/// [`EnvMeaning::Directives`]: crate::EnvMeaning::Directives
+fn parse_directives(directives: &str) -> Result<Vec<(Option<&str>, LevelFilter)>, &'static str> {
+    directives
+        .split(',')
+        .filter_map(|directive| parse_directive(directive).transpose())
+        .collect()
+}
+
+/// Handle the command in `line`, returning the response.
+pub(crate) fn handle(reload: &Reload, line: &str) -> String {
+    match line.trim() {
+        "" => "error: missing command".into(),
+        "show" => reload.directives(),
+        "reset" => match reload.reset() {
+            Ok(()) => {
+                log::info!("reset logging levels");
+                "ok".into()
+            }
+            Err(err) => format!("error: {err}"),
+        },
+        directives => match parse_directives(directives) {
+            Ok(parsed) => {
+                for (target, level) in parsed {
+                    reload.set_level(target, level);
+                }
+                log::info!(directives = directives; "changed logging levels");
+                "ok".into()
+            }
+            Err(err) => format!("error: {err}"),
+        },
+    }
+}
+
+#[cfg(unix)]
+mod socket {
+    use std::fs;
+    use std::io::{self, BufRead, BufReader, Read, Write};
+    use std::net::Shutdown;
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex, PoisonError};
+    use std::thread::{self, JoinHandle};
+
+    use super::{handle, MAX_LINE};
+    use crate::reload::Reload;
+
+    /// State shared with the thread accepting connections.
+    struct Admin {
+        path: PathBuf,
+        /// Set when the logger is shut down, to stop the thread.
+        stop: AtomicBool,
+        /// Connection currently being handled, shut down to stop the thread.
+        conn: Mutex<Option<UnixStream>>,
+    }
+
+    /// Thread accepting connections on the admin socket, see [`listen`].
+    static ADMIN: Mutex<Option<(Arc<Admin>, JoinHandle<()>)>> = Mutex::new(None);
+
+    pub(crate) fn listen(path: PathBuf, reload: Arc<Reload>) -> io::Result<()> {
+        // Remove the socket left by a previous process.
+        if let Ok(metadata) = fs::symlink_metadata(&path) {
+            if metadata.file_type().is_socket() {
+                fs::remove_file(&path)?;
+            }
+        }
+        let listener = UnixListener::bind(&path)?;
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        let admin = Arc::new(Admin {
+            path,
+            stop: AtomicBool::new(false),
+            conn: Mutex::new(None),
+        });
+        let handle = thread::Builder::new()
+            .name("std-logger-admin".into())
+            .spawn({
+                let admin = admin.clone();
+                move || accept(&listener, &admin, &reload)
+            })?;
+        *ADMIN.lock().unwrap_or_else(PoisonError::into_inner) = Some((admin, handle));
+        Ok(())
+    }
+
+    /// Accept connections on `listener`, handling one at a time.
+    fn accept(listener: &UnixListener, admin: &Admin, reload: &Reload) {
+        for stream in listener.incoming() {
+            if admin.stop.load(Ordering::SeqCst) {
+                break;
+            }
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(err) => {
+                    log::warn!("failed to accept logging admin connection: {err}");
+                    continue;
+                }
+            };
+            *admin.conn.lock().unwrap_or_else(PoisonError::into_inner) = stream.try_clone().ok();
+            // Check again as `stop` could have missed the connection.
+            if admin.stop.load(Ordering::SeqCst) {
+                break;
+            }
+            if let Err(err) = serve(&stream, reload) {
+                log::warn!("failed to handle logging admin connection: {err}");
+            }
+            *admin.conn.lock().unwrap_or_else(PoisonError::into_inner) = None;
+        }
+    }
+
+    /// Handle the commands sent over `stream`.
+    fn serve(mut stream: &UnixStream, reload: &Reload) -> io::Result<()> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.by_ref().take(MAX_LINE).read_line(&mut line)? {
+                0 => return Ok(()),
+                n if n as u64 == MAX_LINE && !line.ends_with('\n') => {
+                    return stream.write_all(b"error: command too long\n");
+                }
+                _ => {}
+            }
+            let mut response = handle(reload, &line);
+            response.push('\n');
+            stream.write_all(response.as_bytes())?;
+        }
+    }
+
+    pub(crate) fn stop() {
+        let admin = ADMIN.lock().unwrap_or_else(PoisonError::into_inner).take();
+        let Some((admin, handle)) = admin else {
+            return;
+        };
+        admin.stop.store(true, Ordering::SeqCst);
+        if let Some(conn) = &*admin.conn.lock().unwrap_or_else(PoisonError::into_inner) {
+            let _ = conn.shutdown(Shutdown::Both);
+        }
+        // Wake up the thread blocked on accepting a connection.
+        let _ = UnixStream::connect(&admin.path);
+        let _ = handle.join();
+        let _ = fs::remove_file(&admin.path);
+    }
+
+    pub(crate) fn forget() {
+        if let Ok(mut admin) = ADMIN.try_lock() {
+            if let Some(admin) = admin.take() {
+                std::mem::forget(admin);
+            }
+        }
+    }
+}
+
+/// Start a thread accepting connections on the admin socket at `path`.
+#[cfg(unix)]
+pub(crate) fn listen(path: PathBuf, reload: Arc<Reload>) -> io::Result<()> {
+    socket::listen(path, reload)
+}
+
+/// Unix sockets are only supported on Unix.
+#[cfg(not(unix))]
+pub(crate) fn listen(_path: PathBuf, _reload: Arc<Reload>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "admin socket only supported on Unix",
+    ))
+}
+
+/// Stop the thread accepting connections on the admin socket, if any, and
+/// remove the socket.
+pub(crate) fn stop() {
+    #[cfg(unix)]
+    socket::stop();
+}
+
+/// Forget the thread accepting connections on the admin socket, without
+/// stopping it, as it doesn't exist in the child process after `fork`.
+#[cfg(unix)]
+pub(crate) fn forget() {
+    socket::forget();
+}
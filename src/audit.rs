@@ -0,0 +1,114 @@
+//! Audit logging, see [`AUDIT_TARGET`] and [`audit!`].
+//!
+//! [`AUDIT_TARGET`]: crate::AUDIT_TARGET
+//! [`audit!`]: crate::audit
+
+use std::fs::File;
+use std::io::{self, IoSlice};
+use std::sync::{Mutex, PoisonError};
+
+use crate::{stderr, stdout, write_all};
+
+/// Output for audit records, see [`Config::with_audit_output`].
+///
+/// [`Config::with_audit_output`]: crate::Config::with_audit_output
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum AuditOutput {
+    /// Standard out.
+    Stdout,
+    /// Standard error, the default.
+    Stderr,
+    /// A file, e.g. opened in append mode.
+    File(File),
+}
+
+/// Writer of audit records.
+#[derive(Debug)]
+pub(crate) struct Audit {
+    output: Output,
+    /// Sync the output after each record.
+    sync: bool,
+}
+
+#[derive(Debug)]
+enum Output {
+    Stdout,
+    Stderr,
+    File(Mutex<File>),
+}
+
+impl Audit {
+    pub(crate) const fn new() -> Audit {
+        Audit {
+            output: Output::Stderr,
+            sync: false,
+        }
+    }
+
+    pub(crate) fn set_output(&mut self, output: AuditOutput) {
+        self.output = match output {
+            AuditOutput::Stdout => Output::Stdout,
+            AuditOutput::Stderr => Output::Stderr,
+            AuditOutput::File(file) => Output::File(Mutex::new(file)),
+        };
+    }
+
+    pub(crate) fn set_sync(&mut self, sync: bool) {
+        self.sync = sync;
+    }
+
+    /// Write the formatted audit record in `bufs`, syncing the output if
+    /// enabled. Audit records are never batched or dropped.
+    pub(crate) fn write(&self, bufs: &[IoSlice]) -> io::Result<()> {
+        match &self.output {
+            Output::Stdout => {
+                write_all(&mut stdout(), bufs)?;
+                if self.sync {
+                    sync_fd(1)?;
+                }
+            }
+            Output::Stderr => {
+                write_all(&mut stderr(), bufs)?;
+                if self.sync {
+                    sync_fd(2)?;
+                }
+            }
+            Output::File(file) => {
+                // A panic while holding the lock doesn't leave the file in an
+                // invalid state, so we can ignore the poisoning.
+                let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+                write_all(&mut *file, bufs)?;
+                if self.sync {
+                    file.sync_all()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sync file descriptor `fd` to disk.
+///
+/// Standard out and error are often a pipe or terminal, which can't be synced,
+/// those errors are ignored.
+#[cfg(all(unix, not(test)))]
+fn sync_fd(fd: i32) -> io::Result<()> {
+    // SAFETY: `fsync` doesn't access any memory.
+    if unsafe { libc::fsync(fd) } == 0 {
+        return Ok(());
+    }
+    let err = io::Error::last_os_error();
+    match err.raw_os_error() {
+        Some(libc::EINVAL | libc::EROFS) => Ok(()),
+        _ => Err(err),
+    }
+}
+
+/// Sync file descriptor `fd` to disk, not supported on this platform (or in
+/// tests, where standard out and error are captured).
+#[cfg(any(not(unix), test))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_fd(_fd: i32) -> io::Result<()> {
+    Ok(())
+}
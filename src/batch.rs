@@ -0,0 +1,321 @@
+//! Batching of records, see [`Config::with_batch`].
+//!
+//! [`Config::with_batch`]: crate::Config::with_batch
+
+use std::cell::RefCell;
+use std::io::{self, IoSlice};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use log::{Level, LevelFilter};
+
+use crate::{full, log_failure, FullPolicy, PANIC_TARGET};
+
+/// When to write a batch of records, see [`Config::with_batch`].
+///
+/// A batch is written once it contains `max_records` records or once the first
+/// record in the batch is `max_delay` old, whichever comes first. Records
+/// logged at or above the flush level (defaults to
+/// [`Level::Error`]) are never held back: the batches, including the record,
+/// are written immediately, see [`BatchPolicy::with_flush_level`].
+///
+/// [`Config::with_batch`]: crate::Config::with_batch
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct BatchPolicy {
+    max_records: usize,
+    max_delay: Duration,
+    flush_level: LevelFilter,
+}
+
+impl BatchPolicy {
+    /// Create a new batch policy.
+    pub const fn new(max_records: usize, max_delay: Duration) -> BatchPolicy {
+        BatchPolicy {
+            max_records,
+            max_delay,
+            flush_level: LevelFilter::Error,
+        }
+    }
+
+    /// Write the batches immediately when a record is logged at `level` or a
+    /// more severe level. Defaults to [`LevelFilter::Error`], so that an error
+    /// logged right before the process crashes isn't lost. Use
+    /// [`LevelFilter::Off`] to batch all records, or [`LevelFilter::Trace`] to
+    /// effectively disable batching for the standard error and out.
+    ///
+    /// Both the batches for standard error and standard out (requests) are
+    /// written, to keep the records logged before the error.
+    pub const fn with_flush_level(mut self, level: LevelFilter) -> BatchPolicy {
+        self.flush_level = level;
+        self
+    }
+}
+
+thread_local! {
+    /// Batches of the current thread, created when the first record is added.
+    /// The `RefCell` is borrowed while adding a record, to detect a record
+    /// logged while writing a batch.
+    static BATCHES: RefCell<Option<Arc<Mutex<Batches>>>> = const { RefCell::new(None) };
+}
+
+/// Batches of all threads, so they can be written by the flusher thread (see
+/// [`start`]) and by [`flush_all`].
+static ALL_BATCHES: Mutex<Vec<Weak<Mutex<Batches>>>> = Mutex::new(Vec::new());
+
+/// Set in the child process after `fork`, see [`discard`].
+static FORKED: AtomicBool = AtomicBool::new(false);
+
+/// Batches for standard out (requests) and standard error (everything else).
+struct Batches {
+    stdout: Batch,
+    stderr: Batch,
+    /// Policy to follow when writing the batches, see [`Config::with_full_policy`].
+    ///
+    /// [`Config::with_full_policy`]: crate::Config::with_full_policy
+    full: FullPolicy,
+}
+
+impl Batches {
+    fn flush(&mut self) {
+        self.stdout.flush(self.full);
+        self.stderr.flush(self.full);
+    }
+
+    /// Write the batches with a first record at least `max_delay` old.
+    /// Returns the time the next batch should be written, if any.
+    fn flush_expired(&mut self, max_delay: Duration) -> Option<Instant> {
+        let mut next = None;
+        for batch in [&mut self.stdout, &mut self.stderr] {
+            if let Some(start) = batch.start {
+                let deadline = start + max_delay;
+                if deadline <= Instant::now() {
+                    batch.flush(self.full);
+                } else if next.map_or(true, |next| deadline < next) {
+                    next = Some(deadline);
+                }
+            }
+        }
+        next
+    }
+}
+
+impl Drop for Batches {
+    fn drop(&mut self) {
+        // Don't lose the records when the thread stops.
+        self.flush();
+    }
+}
+
+/// Batch of records for a single output.
+struct Batch {
+    buf: Vec<u8>,
+    /// Number of records in `buf`.
+    records: usize,
+    /// Time the first record in `buf` was added.
+    start: Option<Instant>,
+    /// Write to standard out, otherwise to standard error.
+    to_stdout: bool,
+}
+
+impl Batch {
+    const fn new(to_stdout: bool) -> Batch {
+        Batch {
+            buf: Vec::new(),
+            records: 0,
+            start: None,
+            to_stdout,
+        }
+    }
+
+    fn add(&mut self, bufs: &[IoSlice], policy: BatchPolicy, full: FullPolicy) {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        for buf in bufs {
+            self.buf.extend_from_slice(buf);
+        }
+        self.records += 1;
+        if self.records >= policy.max_records || start.elapsed() >= policy.max_delay {
+            self.flush(full);
+        }
+    }
+
+    /// Remove all records from the batch, without writing them.
+    fn clear(&mut self) {
+        self.buf.clear();
+        self.records = 0;
+        self.start = None;
+    }
+
+    /// Write the batch the same way as a single record, following the `full`
+    /// policy.
+    fn flush(&mut self, full: FullPolicy) {
+        if self.records == 0 {
+            return;
+        }
+        let result = full::write(self.to_stdout, &[IoSlice::new(&self.buf)], full);
+        self.clear();
+        result.unwrap_or_else(log_failure);
+    }
+}
+
+/// Add the formatted record in `bufs`, logged at `level`, to the batch for
+/// standard out (if `to_stdout` is true) or error of the current thread,
+/// writing the batch(es) if required by the `policy`, following the `full`
+/// policy.
+///
+/// Returns `false` if the record wasn't added and must be written directly.
+/// This is the case for panics, which are never batched (but the batches are
+/// written first to keep the records in order), or when called while the
+/// thread is stopping.
+pub(crate) fn add(
+    target: &str,
+    to_stdout: bool,
+    level: Level,
+    bufs: &[IoSlice],
+    policy: BatchPolicy,
+    full: FullPolicy,
+) -> bool {
+    BATCHES
+        .try_with(|batches| {
+            let mut batches = match batches.try_borrow_mut() {
+                Ok(batches) => batches,
+                // Logging while writing a batch, e.g. when panicking.
+                Err(_) => return false,
+            };
+            let batches = batches.get_or_insert_with(register);
+            let mut batches = batches.lock().unwrap_or_else(PoisonError::into_inner);
+            batches.full = full;
+            if target == PANIC_TARGET {
+                batches.flush();
+                return false;
+            } else if to_stdout {
+                batches.stdout.add(bufs, policy, full);
+            } else {
+                batches.stderr.add(bufs, policy, full);
+            }
+            if level <= policy.flush_level {
+                batches.flush();
+            }
+            true
+        })
+        .unwrap_or(false)
+}
+
+/// Create the batches for the current thread.
+fn register() -> Arc<Mutex<Batches>> {
+    let batches = Arc::new(Mutex::new(Batches {
+        stdout: Batch::new(true),
+        stderr: Batch::new(false),
+        full: FullPolicy::Block,
+    }));
+    if let Some(mut all) = lock(&ALL_BATCHES) {
+        // Remove the batches of stopped threads.
+        all.retain(|batches| batches.strong_count() != 0);
+        all.push(Arc::downgrade(&batches));
+    }
+    batches
+}
+
+/// Returns the batches of all running threads.
+fn all_batches() -> Vec<Arc<Mutex<Batches>>> {
+    match lock(&ALL_BATCHES) {
+        Some(all) => all.iter().filter_map(Weak::upgrade).collect(),
+        None => Vec::new(),
+    }
+}
+
+/// Lock `mutex`, ignoring poisoning as a panic while holding the lock doesn't
+/// leave the batches in an invalid state.
+///
+/// In the child process after `fork` this doesn't block, returning `None`
+/// instead, as locks held by other threads in the parent process are never
+/// released in the child.
+fn lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    if !FORKED.load(Ordering::Relaxed) {
+        return Some(mutex.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+        Err(TryLockError::WouldBlock) => None,
+    }
+}
+
+/// Write the batches of the current thread.
+pub(crate) fn flush() {
+    let _ = BATCHES.try_with(|batches| {
+        if let Ok(batches) = batches.try_borrow_mut() {
+            if let Some(mut batches) = batches.as_deref().and_then(lock) {
+                batches.flush();
+            }
+        }
+    });
+}
+
+/// Write the batches of all threads.
+pub(crate) fn flush_all() {
+    for batches in all_batches() {
+        if let Some(mut batches) = lock(&batches) {
+            batches.flush();
+        }
+    }
+}
+
+/// Start writing the batches once they're `policy.max_delay` old from a
+/// background thread, and write all batches when the process exits (Unix
+/// only).
+pub(crate) fn start(policy: BatchPolicy) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        extern "C" fn flush_at_exit() {
+            // Panicking (e.g. following `ErrorPolicy::Panic`) across the FFI
+            // boundary aborts the process.
+            let _ = std::panic::catch_unwind(flush_all);
+        }
+        // SAFETY: `flush_at_exit` doesn't unwind.
+        let _ = unsafe { libc::atexit(flush_at_exit) };
+    }
+
+    // Don't spin if the delay is zero, every record is written directly then.
+    let max_delay = policy.max_delay.max(Duration::from_millis(1));
+    let _ = thread::Builder::new()
+        .name("std-logger-batch".into())
+        .spawn(move || loop {
+            let mut next = Instant::now() + max_delay;
+            for batches in all_batches() {
+                let Some(mut batches) = lock(&batches) else {
+                    continue;
+                };
+                if let Some(deadline) = batches.flush_expired(max_delay) {
+                    next = next.min(deadline);
+                }
+            }
+            thread::sleep(next.saturating_duration_since(Instant::now()));
+        })?;
+    Ok(())
+}
+
+/// Discard the batches of all threads, without writing them.
+///
+/// Only the thread calling `fork` exists in the child process, the batches of
+/// the other threads are left as is if they're locked. This doesn't allocate
+/// or block.
+#[cfg(unix)]
+pub(crate) fn discard() {
+    FORKED.store(true, Ordering::Relaxed);
+    if let Ok(all) = ALL_BATCHES.try_lock() {
+        // Only this thread exists, so the batches can't be dropped while
+        // they're cleared.
+        for batches in all.iter().filter(|batches| batches.strong_count() != 0) {
+            // SAFETY: the batches are alive, see above. Not upgrading the
+            // `Weak` avoids dropping the last `Arc`, which would deallocate.
+            if let Some(batches) = unsafe { batches.as_ptr().as_ref() } {
+                if let Ok(mut batches) = batches.try_lock() {
+                    batches.stdout.clear();
+                    batches.stderr.clear();
+                }
+            }
+        }
+    }
+}
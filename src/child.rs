@@ -0,0 +1,109 @@
+//! Logging the output of child processes, see [`spawn`].
+
+use std::io::{self, BufRead, BufReader, Read};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::{self, JoinHandle};
+
+use log::Level;
+
+use crate::CHILD_TARGET;
+
+/// Spawn `command`, logging each line it writes to standard out and error.
+///
+/// The lines are logged using the [`CHILD_TARGET`] target with the `pid` of
+/// the child process and the `stream` (`stdout` or `stderr`) as key-values.
+/// Lines written to standard out are logged using the info level, lines
+/// written to standard error using the warn level. Lines are read on two
+/// background threads, which stop once the child closes its output.
+///
+/// This overwrites the standard out and error configuration of `command`.
+///
+/// # Examples
+///
+/// ```
+/// use std::process::Command;
+///
+/// use std_logger::child;
+///
+/// # fn main() -> std::io::Result<()> {
+/// // Logged as:
+/// // `lvl="INFO" msg="hello" target="child" module="std_logger::child" pid=123 stream="stdout"`.
+/// let child = child::spawn(Command::new("echo").arg("hello"))?;
+/// let status = child.wait()?;
+/// assert!(status.success());
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn(command: &mut Command) -> io::Result<LoggedChild> {
+    let mut child = command
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let pid = child.id();
+    let mut threads = Vec::with_capacity(2);
+    if let Some(stdout) = child.stdout.take() {
+        threads.push(log_lines(stdout, pid, "stdout", Level::Info)?);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        threads.push(log_lines(stderr, pid, "stderr", Level::Warn)?);
+    }
+    Ok(LoggedChild { child, threads })
+}
+
+/// Child process of which the output is logged, see [`spawn`].
+#[derive(Debug)]
+pub struct LoggedChild {
+    child: Child,
+    threads: Vec<JoinHandle<()>>,
+}
+
+impl LoggedChild {
+    /// Returns the OS-assigned process identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Kill the child process, see [`Child::kill`].
+    pub fn kill(&mut self) -> io::Result<()> {
+        self.child.kill()
+    }
+
+    /// Wait for the child process to exit and all its output to be logged.
+    pub fn wait(mut self) -> io::Result<ExitStatus> {
+        let status = self.child.wait()?;
+        for thread in self.threads.drain(..) {
+            // The thread only panics if logging panics, which is already
+            // logged.
+            let _ = thread.join();
+        }
+        Ok(status)
+    }
+}
+
+/// Start a thread that logs each line read from `output`.
+fn log_lines<R>(
+    output: R,
+    pid: u32,
+    stream: &'static str,
+    level: Level,
+) -> io::Result<JoinHandle<()>>
+where
+    R: Read + Send + 'static,
+{
+    thread::Builder::new()
+        .name(format!("child-{pid}-{stream}"))
+        .spawn(move || {
+            let mut output = BufReader::new(output);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match output.read_until(b'\n', &mut line) {
+                    Ok(0) | Err(_) => return,
+                    Ok(_) => {}
+                }
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim_end_matches(['\n', '\r']);
+                log::log!(target: CHILD_TARGET, level, pid = pid, stream = stream; "{line}");
+            }
+        })
+}
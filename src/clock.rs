@@ -0,0 +1,154 @@
+//! Time source of the timestamps, see [`Config::with_clock`].
+//!
+//! [`Config::with_clock`]: crate::Config::with_clock
+
+#[cfg(feature = "timestamp")]
+use std::cell::Cell;
+#[cfg(feature = "timestamp")]
+use std::sync::atomic::AtomicU64;
+use std::sync::atomic::{AtomicPtr, AtomicU8, Ordering};
+#[cfg(feature = "timestamp")]
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Time source used for the timestamp of records, see [`Config::with_clock`].
+///
+/// [`Config::with_clock`]: crate::Config::with_clock
+#[derive(Copy, Clone, Debug)]
+#[non_exhaustive]
+pub enum Clock {
+    /// [`SystemTime::now`], the default.
+    System,
+    /// `CLOCK_REALTIME_COARSE` on Linux, which is cheaper to read than
+    /// [`SystemTime::now`] but only has a resolution of a few milliseconds
+    /// (depending on the kernel configuration). On other platforms this is the
+    /// same as [`Clock::System`].
+    Coarse,
+    /// Fake clock that starts at 2001-01-01T00:00:00Z and advances one
+    /// millisecond every time it's read, used to get deterministic output, see
+    /// [`Config::with_deterministic_output`].
+    ///
+    /// [`Config::with_deterministic_output`]: crate::Config::with_deterministic_output
+    Fake,
+    /// User provided function, e.g. returning a fixed time to get
+    /// deterministic output in tests.
+    Custom(fn() -> SystemTime),
+}
+
+/// Current clock, see [`Clock::set`].
+static CLOCK: AtomicU8 = AtomicU8::new(SYSTEM);
+/// Function of [`Clock::Custom`], only valid if `CLOCK` is `CUSTOM`.
+static CUSTOM_CLOCK: AtomicPtr<()> = AtomicPtr::new(std::ptr::null_mut());
+
+const SYSTEM: u8 = 0;
+const COARSE: u8 = 1;
+const CUSTOM: u8 = 2;
+const FAKE: u8 = 3;
+
+impl Clock {
+    /// Set the clock used by [`now`].
+    pub(crate) fn set(self) {
+        match self {
+            Clock::System => CLOCK.store(SYSTEM, Ordering::Relaxed),
+            Clock::Coarse => CLOCK.store(COARSE, Ordering::Relaxed),
+            Clock::Fake => CLOCK.store(FAKE, Ordering::Relaxed),
+            Clock::Custom(f) => {
+                CUSTOM_CLOCK.store(f as *mut (), Ordering::Relaxed);
+                CLOCK.store(CUSTOM, Ordering::Release);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "timestamp")]
+thread_local! {
+    /// Time returned by [`now`] instead of reading the clock, see
+    /// [`with_time`].
+    static TIME: Cell<Option<SystemTime>> = const { Cell::new(None) };
+}
+
+/// Call `f` with [`now`] returning `time` on this thread, e.g. to log an
+/// [`OwnedRecord`] using the time it was originally logged.
+///
+/// [`OwnedRecord`]: crate::OwnedRecord
+#[cfg(feature = "timestamp")]
+pub(crate) fn with_time<R, F: FnOnce() -> R>(time: SystemTime, f: F) -> R {
+    /// Restores the previous time, also when `f` panics.
+    struct Restore(Option<SystemTime>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            TIME.set(self.0);
+        }
+    }
+
+    let _restore = Restore(TIME.replace(Some(time)));
+    f()
+}
+
+/// Returns the current time according to the [`Clock`] set.
+#[cfg(feature = "timestamp")]
+pub(crate) fn now() -> SystemTime {
+    if let Some(time) = TIME.get() {
+        return time;
+    }
+    match CLOCK.load(Ordering::Acquire) {
+        COARSE => coarse_now(),
+        FAKE => fake_now(),
+        CUSTOM => {
+            let f = CUSTOM_CLOCK.load(Ordering::Relaxed);
+            // SAFETY: `CUSTOM_CLOCK` is set to a `fn() -> SystemTime` before
+            // `CLOCK` is set to `CUSTOM`.
+            let f: fn() -> SystemTime = unsafe { std::mem::transmute(f) };
+            f()
+        }
+        _ => system_now(),
+    }
+}
+
+/// Returns the current time using [`SystemTime::now`], or `Date.now()` on
+/// `wasm32-unknown-unknown` as `SystemTime::now` panics on that target.
+#[cfg(feature = "timestamp")]
+fn system_now() -> SystemTime {
+    #[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+    let now = crate::wasm::now();
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown")))]
+    let now = SystemTime::now();
+    now
+}
+
+/// Returns the time of [`Clock::Fake`].
+#[cfg(feature = "timestamp")]
+fn fake_now() -> SystemTime {
+    /// 2001-01-01T00:00:00Z.
+    const START: Duration = Duration::from_secs(978_307_200);
+    /// Number of times the clock was read.
+    static TICKS: AtomicU64 = AtomicU64::new(0);
+
+    let ticks = TICKS.fetch_add(1, Ordering::Relaxed);
+    SystemTime::UNIX_EPOCH + START + Duration::from_millis(ticks)
+}
+
+/// Returns the current time using `CLOCK_REALTIME_COARSE`.
+#[cfg(all(feature = "timestamp", any(target_os = "linux", target_os = "android")))]
+#[allow(clippy::cast_sign_loss)]
+pub(crate) fn coarse_now() -> SystemTime {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `ts` is valid for the duration of the call.
+    if unsafe { libc::clock_gettime(libc::CLOCK_REALTIME_COARSE, &mut ts) } != 0 {
+        return system_now();
+    }
+    SystemTime::UNIX_EPOCH + Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Returns the current time, the coarse clock is only supported on Linux.
+#[cfg(all(
+    feature = "timestamp",
+    not(any(target_os = "linux", target_os = "android"))
+))]
+pub(crate) fn coarse_now() -> SystemTime {
+    system_now()
+}
@@ -0,0 +1,179 @@
+//! Compressed sinks, see [`CompressedSink`].
+
+use std::io::{self, Write};
+#[cfg(feature = "reload")]
+use std::path::Path;
+
+/// Compression algorithm used by [`CompressedSink`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// gzip, requires the *gzip* feature.
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard, requires the *zstd* feature.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// Returns the compression based on the extension of `path`: `.gz` for
+    /// gzip and `.zst` for Zstandard. Used for the sinks of the configuration
+    /// file (only with the *reload* feature).
+    #[cfg(feature = "reload")]
+    pub(crate) fn from_path(path: &Path) -> Option<Compression> {
+        match path.extension()?.to_str()? {
+            #[cfg(feature = "gzip")]
+            "gz" => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Compress `input`, writing a single gzip member or zstd frame to
+    /// `output`.
+    fn compress(self, input: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        match self {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use flate2::write::GzEncoder;
+
+                let mut encoder = GzEncoder::new(output, flate2::Compression::default());
+                encoder.write_all(input)?;
+                encoder.finish().map(|_| ())
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::copy_encode(input, output, 0),
+        }
+    }
+}
+
+/// Default value for [`CompressedSink::with_block_size`].
+const DEFAULT_BLOCK_SIZE: usize = 64 * 1024;
+
+/// Sink that compresses the records before writing them to a writer, e.g. a
+/// file, see [`Config::add_sink`].
+///
+/// Records are compressed in blocks: the records are buffered until the block
+/// is full or the logger is flushed (e.g. by [`shutdown`]), at which point the
+/// block is written as a single gzip member or zstd frame. Both formats
+/// support concatenated members/frames, so the file can be read using `zcat`
+/// or `zstdcat` at any time, and at most a single block is lost if the process
+/// crashes. The block is also written when the sink is dropped.
+///
+/// Sinks listed in the configuration file (see the [reload feature]) are
+/// compressed based on their extension: `.gz` for gzip and `.zst` for
+/// Zstandard.
+///
+/// [`Config::add_sink`]: crate::Config::add_sink
+/// [`shutdown`]: crate::shutdown
+/// [reload feature]: index.html#reload-feature
+///
+/// # Examples
+///
+/// ```
+/// # #[cfg(feature = "gzip")] {
+/// use std::fs::OpenOptions;
+///
+/// use log::LevelFilter;
+/// use std_logger::{CompressedSink, Compression, Config};
+///
+/// # fn main() -> std::io::Result<()> {
+/// # let dir = std::env::temp_dir();
+/// let file = OpenOptions::new()
+///     .create(true)
+///     .append(true)
+///     .open(dir.join("requests.log.gz"))?;
+/// Config::logfmt()
+///     .add_sink(CompressedSink::new(file, Compression::Gzip), LevelFilter::Info)
+///     .init();
+/// # Ok(())
+/// # }
+/// # main().unwrap();
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct CompressedSink<W: Write> {
+    output: W,
+    compression: Compression,
+    /// Records not yet compressed.
+    block: Vec<u8>,
+    block_size: usize,
+    /// Compressed block.
+    compressed: Vec<u8>,
+}
+
+impl<W: Write> CompressedSink<W> {
+    /// Create a new compressed sink writing to `output`.
+    pub fn new(output: W, compression: Compression) -> CompressedSink<W> {
+        CompressedSink {
+            output,
+            compression,
+            block: Vec::new(),
+            block_size: DEFAULT_BLOCK_SIZE,
+            compressed: Vec::new(),
+        }
+    }
+
+    /// Set the size (in bytes, before compressing) of the blocks. Defaults to
+    /// 64 KiB.
+    ///
+    /// Larger blocks compress better, smaller blocks lose less records if the
+    /// process crashes.
+    pub fn with_block_size(mut self, size: usize) -> CompressedSink<W> {
+        self.block_size = size;
+        self
+    }
+
+    /// Returns the underlying writer, without writing the current block.
+    pub fn get_ref(&self) -> &W {
+        &self.output
+    }
+
+    /// Compress and write the current block, if not empty.
+    fn write_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        self.compressed.clear();
+        self.compression
+            .compress(&self.block, &mut self.compressed)?;
+        self.block.clear();
+        self.output.write_all(&self.compressed)
+    }
+}
+
+impl<W: Write> Write for CompressedSink<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.block.extend_from_slice(buf);
+        if self.block.len() >= self.block_size {
+            self.write_block()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        // Keep the parts of a record in the same block.
+        let mut len = 0;
+        for buf in bufs {
+            self.block.extend_from_slice(buf);
+            len += buf.len();
+        }
+        if self.block.len() >= self.block_size {
+            self.write_block()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_block()?;
+        self.output.flush()
+    }
+}
+
+impl<W: Write> Drop for CompressedSink<W> {
+    fn drop(&mut self) {
+        let _ = self.write_block();
+    }
+}
@@ -1,14 +1,28 @@
 //! Configuration of the logger.
 
 use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IoSlice, Write};
 use std::marker::PhantomData;
+#[cfg(feature = "log-panic")]
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
 
 use log::{kv, LevelFilter, SetLoggerError};
 
-use crate::format::{Format, Gcloud, Json, LogFmt};
-#[cfg(feature = "log-panic")]
-use crate::PANIC_TARGET;
-use crate::{Logger, Targets};
+use crate::audit::Audit;
+use crate::format::{
+    CustomFormat, Field, Format, Gcloud, Json, LogFmt, Options, StructuredValues, TypeHint,
+};
+use crate::fsync::SyncedFile;
+use crate::redact::Redact;
+use crate::route::{Route, Routes};
+use crate::transform::Transforms;
+use crate::{
+    AuditOutput, BatchPolicy, Clock, ErrorPolicy, FullPolicy, Logger, RecordTransform, Sink,
+    SyncPolicy, Targets, PANIC_TARGET, REQUEST_TARGET,
+};
 
 /// Configuration of the logger.
 ///
@@ -20,7 +34,42 @@ use crate::{Logger, Targets};
 #[must_use = "the logger must be initialised using `init` or `try_init`"]
 pub struct Config<F, Kvs> {
     filter: LevelFilter,
+    levels: Vec<(Box<str>, LevelFilter)>,
     add_loc: Option<bool>,
+    bare_keys: bool,
+    msg_last: bool,
+    raw: bool,
+    multi_line: bool,
+    max_size: usize,
+    type_hints: Vec<(Box<str>, TypeHint)>,
+    structured: StructuredValues,
+    fields: Vec<(Field, Box<str>, LevelFilter)>,
+    separate_line: bool,
+    batch: Option<BatchPolicy>,
+    panic: PanicOptions,
+    error_policy: ErrorPolicy,
+    clock: Clock,
+    deterministic: bool,
+    always_log: Vec<Box<str>>,
+    audit: Audit,
+    routes: Routes,
+    request_fd: Option<i32>,
+    unlocked_stderr: bool,
+    writer_queue: Option<usize>,
+    env_kvs: Vec<(String, String)>,
+    #[cfg(feature = "reload")]
+    config_file: Option<std::path::PathBuf>,
+    #[cfg(feature = "reload")]
+    config_reload: Option<Duration>,
+    #[cfg(feature = "admin")]
+    admin_socket: Option<std::path::PathBuf>,
+    redact: Redact,
+    transforms: Transforms,
+    full: FullPolicy,
+    metrics_interval: Option<Duration>,
+    sinks: Vec<Sink>,
+    custom_format: Option<CustomFormat>,
+    systemd_prefix: bool,
     targets: Targets,
     kvs: Kvs,
     format: PhantomData<F>,
@@ -44,6 +93,45 @@ impl Config<(), NoKvs> {
     }
 }
 
+impl Config<LogFmt, NoKvs> {
+    /// Configuration used by [`init_for_tests`].
+    ///
+    /// [`init_for_tests`]: crate::init_for_tests
+    pub(crate) fn for_tests() -> Config<LogFmt, NoKvs> {
+        let filter = max_level_from(DEFAULT_ENV_VARS, LevelFilter::Trace);
+        let mut config = Config::logfmt();
+        // Write all records using `eprint!` rather than standard error
+        // directly.
+        config.filter = LevelFilter::Off;
+        config.levels.clear();
+        config.add_loc = Some(filter >= LevelFilter::Debug);
+        config.add_sink(TestOutput, filter)
+    }
+}
+
+/// Writes records using [`eprint!`], which is captured by the test harness.
+struct TestOutput;
+
+impl Write for TestOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        eprint!("{}", String::from_utf8_lossy(buf));
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        // Keep the parts of a record together.
+        let mut record = Vec::with_capacity(bufs.iter().map(|buf| buf.len()).sum());
+        for buf in bufs {
+            record.extend_from_slice(buf);
+        }
+        self.write(&record)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
 impl<F, Kvs> Config<F, Kvs>
 where
     F: Format + Send + Sync + 'static,
@@ -52,7 +140,42 @@ where
     fn new(kvs: Kvs) -> Config<F, Kvs> {
         Config {
             filter: get_max_level(),
+            levels: get_log_levels(),
             add_loc: None,
+            bare_keys: false,
+            msg_last: false,
+            raw: false,
+            multi_line: false,
+            max_size: usize::MAX,
+            type_hints: Vec::new(),
+            structured: StructuredValues::Display,
+            fields: Vec::new(),
+            separate_line: false,
+            batch: None,
+            panic: PanicOptions::DEFAULT,
+            error_policy: ErrorPolicy::Panic,
+            clock: Clock::System,
+            deterministic: false,
+            always_log: vec![REQUEST_TARGET.into(), PANIC_TARGET.into()],
+            audit: Audit::new(),
+            routes: Routes::new(),
+            request_fd: get_request_fd(),
+            unlocked_stderr: false,
+            writer_queue: None,
+            env_kvs: Vec::new(),
+            #[cfg(feature = "reload")]
+            config_file: env::var_os("LOG_CONFIG").map(Into::into),
+            #[cfg(feature = "reload")]
+            config_reload: None,
+            #[cfg(feature = "admin")]
+            admin_socket: None,
+            redact: Redact::new(),
+            transforms: Transforms::new(),
+            full: FullPolicy::Block,
+            metrics_interval: None,
+            sinks: Vec::new(),
+            custom_format: None,
+            systemd_prefix: false,
             targets: get_log_targets(),
             kvs,
             format: PhantomData,
@@ -60,32 +183,1218 @@ where
     }
 
     /// Add the key-values `kvs` to all logged messages.
+    ///
+    /// Also see [`Config::with_kvs_from_env`].
     pub fn with_kvs<K>(self, kvs: K) -> Config<F, K>
     where
         K: kv::Source + Send + Sync + 'static,
     {
         Config {
             filter: self.filter,
+            levels: self.levels,
             add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
             targets: self.targets,
             kvs,
             format: self.format,
         }
     }
 
+    /// Add the values of the environment variables `vars` as key-values to all
+    /// logged messages, e.g. the pod metadata set by Kubernetes. The key is
+    /// the name of the variable in lowercase, e.g. `POD_NAME` is logged as
+    /// `pod_name`. Variables that are not set are ignored.
+    ///
+    /// The variables are read once, when this is called. The key-values are
+    /// logged after the key-values set using [`Config::with_kvs`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::Config;
+    ///
+    /// # fn main() {
+    /// Config::logfmt()
+    ///     .with_kvs_from_env(&["HOSTNAME", "POD_NAME", "REGION"])
+    ///     .init();
+    /// # }
+    /// ```
+    pub fn with_kvs_from_env(mut self, vars: &[&str]) -> Config<F, Kvs> {
+        for var in vars {
+            if let Some(value) = env::var_os(var) {
+                let key = var.to_ascii_lowercase();
+                let value = value.to_string_lossy().into_owned();
+                self.env_kvs.push((key, value));
+            }
+        }
+        self
+    }
+
     /// Enable or disable logging of the call location.
     ///
     /// Default to enable if the debug (or lower) messages are enabled.
     pub fn with_call_location(self, enable: bool) -> Config<F, Kvs> {
         Config {
             filter: self.filter,
+            levels: self.levels,
             add_loc: Some(enable),
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
             targets: self.targets,
             kvs: self.kvs,
             format: self.format,
         }
     }
 
+    /// Enable or disable logging key-value pairs with the value `true` as a
+    /// bare key, e.g. `canary` instead of `canary=true`.
+    ///
+    /// Only supported by the logfmt format, defaults to disabled.
+    pub fn with_bare_keys(self, enable: bool) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: enable,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Enable or disable logging the message as the last field, after the
+    /// key-value pairs and call location, rather than after the log level.
+    ///
+    /// Only supported by the logfmt format, defaults to disabled.
+    pub fn with_msg_last(self, enable: bool) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: enable,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Enable or disable raw mode, in which the message and string values are
+    /// written as is, without escaping them, for maximum throughput.
+    ///
+    /// This should only be enabled if it's guaranteed that messages and string
+    /// values never contain quotes, backslashes or control characters such as
+    /// new lines, otherwise the output will be invalid. If debug assertions are
+    /// enabled the logger panics if this guarantee is violated. Panics are
+    /// never logged in raw mode.
+    ///
+    /// Defaults to disabled.
+    pub fn with_raw_mode(self, enable: bool) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: enable,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Enable or disable writing new lines in the message and values as is,
+    /// rather than escaping them as `\n`, spreading a single log message over
+    /// multiple lines.
+    ///
+    /// This was the behaviour of older versions for string values, which is
+    /// useful for reading backtraces in a terminal, but isn't supported by most
+    /// logfmt consumers (std-logger-parser does support it).
+    ///
+    /// Only supported by the logfmt format, defaults to disabled.
+    pub fn with_multi_line_values(self, enable: bool) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: enable,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Set the maximum size of a record, in bytes. Defaults to unlimited.
+    ///
+    /// The limit applies to the message and string values combined, which
+    /// are the parts of a record that can grow unbounded, e.g. when
+    /// accidentally logging a large blob. Once the limit is reached the
+    /// message and values are truncated and the key-value pair
+    /// `truncated=true` is added to the record. The size of the formatted
+    /// record can exceed the limit by the size of the other fields, e.g. the
+    /// timestamp and keys, and escaping.
+    pub fn with_max_record_size(self, max_size: usize) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Batch records logged on the same thread, writing them using a single
+    /// system call. Defaults to writing each record directly.
+    ///
+    /// This reduces the number of system calls when logging a lot of records
+    /// in bursts. The batches are per thread, so there is no contention
+    /// between threads. Records are written once the batch is full or once the
+    /// first record in the batch reaches the maximum delay, see
+    /// [`BatchPolicy`]. Panics are never batched and errors write the batches
+    /// immediately by default, see [`BatchPolicy::with_flush_level`]. A batch
+    /// is written the same way as a single record, following the
+    /// [`FullPolicy`] (see [`Config::with_full_policy`]).
+    ///
+    /// # Notes
+    ///
+    /// The batches that reach the maximum delay are written by a background
+    /// thread, so the records of a thread that stops logging are not held back
+    /// longer than the delay. The batches of a thread are also written when
+    /// the thread stops or when [`log::logger().flush()`] is called on the
+    /// thread. The batches of all threads are written by [`shutdown`] and, on
+    /// Unix, when the process exits (using `exit(3)`, e.g. when returning from
+    /// `main`).
+    ///
+    /// [`log::logger().flush()`]: log::Log::flush
+    /// [`shutdown`]: crate::shutdown
+    pub fn with_batch(self, policy: BatchPolicy) -> Config<F, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: Some(policy),
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Add a type hint for the values of `key`, see [`TypeHint`].
+    ///
+    /// This ensures the values are formatted as the same type, e.g. an ID that
+    /// should remain a string even if it's numeric. Only applies to the
+    /// user supplied key-values.
+    pub fn with_type_hint(mut self, key: &str, hint: TypeHint) -> Config<F, Kvs> {
+        match self.type_hints.iter_mut().find(|(k, _)| &**k == key) {
+            Some((_, h)) => *h = hint,
+            None => self.type_hints.push((key.into(), hint)),
+        }
+        self
+    }
+
+    /// Set how to format structured values, e.g. maps and nested structs, see
+    /// [`StructuredValues`].
+    ///
+    /// Only has an effect with the *kv-serde* feature, which supports logging
+    /// values implementing `serde::Serialize`. Defaults to
+    /// [`StructuredValues::Display`].
+    pub fn with_structured_values(mut self, structured: StructuredValues) -> Config<F, Kvs> {
+        self.structured = structured;
+        self
+    }
+
+    /// Set the `name` of the built-in `field`, e.g. `time` rather than `ts`
+    /// for the timestamp.
+    ///
+    /// The name must be a valid logfmt key, it's not escaped. Only supported
+    /// by the logfmt format, the other formats ignore it. Use
+    /// `ParserOptions::key_aliases` to parse the records.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::{Config, Field};
+    ///
+    /// // Logged as: `time="..." severity="INFO" message="..." target="..." ...`.
+    /// Config::logfmt()
+    ///     .with_field_name(Field::Timestamp, "time")
+    ///     .with_field_name(Field::Level, "severity")
+    ///     .with_field_name(Field::Message, "message")
+    ///     .init();
+    /// ```
+    pub fn with_field_name(mut self, field: Field, name: &str) -> Config<F, Kvs> {
+        if self.fields.is_empty() {
+            self.fields = default_fields();
+        }
+        if let Some((_, n, _)) = self.fields.iter_mut().find(|(f, _, _)| *f == field) {
+            *n = name.into();
+        }
+        self
+    }
+
+    /// Set the order of the built-in fields.
+    ///
+    /// The fields in `order` are written first, in that order, followed by
+    /// the fields not in `order` in the default order. For example to write
+    /// the message last, after the key-values and file, use
+    /// `[Field::Timestamp, Field::Level, Field::Target, Field::Module,
+    /// Field::KeyValues, Field::File, Field::Message]` or
+    /// [`Config::with_msg_last`]. Only supported by the logfmt format,
+    /// the other formats ignore it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::{Config, Field};
+    ///
+    /// // Logged as: `lvl="INFO" ts="..." target="..." msg="..." module="..." ...`.
+    /// Config::logfmt()
+    ///     .with_field_order([Field::Level, Field::Timestamp, Field::Target])
+    ///     .init();
+    /// ```
+    pub fn with_field_order<I>(mut self, order: I) -> Config<F, Kvs>
+    where
+        I: IntoIterator<Item = Field>,
+    {
+        if self.fields.is_empty() {
+            self.fields = default_fields();
+        }
+        let order: Vec<Field> = order.into_iter().collect();
+        // NOTE: stable sort to keep the fields not in `order` in their
+        // current order.
+        self.fields.sort_by_key(|(field, _, _)| {
+            order.iter().position(|f| f == field).unwrap_or(order.len())
+        });
+        self
+    }
+
+    /// Write the line of the call location as separate key, e.g.
+    /// `file="src/main.rs" line=12` rather than `file="src/main.rs:12"`.
+    ///
+    /// This makes it easier to process the records with tools that don't
+    /// split the file and line. Defaults to `false` for compatibility with
+    /// existing tools. Only applies to the logfmt format, the other formats
+    /// already write the line separately.
+    pub fn with_separate_line(mut self, enable: bool) -> Config<F, Kvs> {
+        self.separate_line = enable;
+        self
+    }
+
+    /// Only write the built-in `field` for records with a level enabled by
+    /// `level`, e.g. [`LevelFilter::Warn`] to write it only for warning and
+    /// error records. Use [`LevelFilter::Off`] to never write it and
+    /// [`LevelFilter::Trace`] to always write it (the default).
+    ///
+    /// For [`Field::File`] this also enables logging of the call location,
+    /// which by default is only enabled if debug messages are enabled, see
+    /// [`Config::with_call_location`]. Only supported by the logfmt format,
+    /// the other formats ignore it.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::LevelFilter;
+    /// use std_logger::{Config, Field};
+    ///
+    /// Config::logfmt()
+    ///     // Never write the module.
+    ///     .with_field_level(Field::Module, LevelFilter::Off)
+    ///     // Add the file and line only to error records.
+    ///     .with_field_level(Field::File, LevelFilter::Error)
+    ///     .init();
+    /// ```
+    pub fn with_field_level(mut self, field: Field, level: LevelFilter) -> Config<F, Kvs> {
+        if self.fields.is_empty() {
+            self.fields = default_fields();
+        }
+        if let Some((_, _, l)) = self.fields.iter_mut().find(|(f, _, _)| *f == field) {
+            *l = level;
+        }
+        if field == Field::File {
+            self.add_loc = Some(true);
+        }
+        self
+    }
+
+    /// Read the log level and targets from the environment variables `vars`,
+    /// rather than from the default variables (`LOG`, `LOG_LEVEL`, `TRACE`,
+    /// `DEBUG` and `LOG_TARGET`, see the [crate level documentation]).
+    ///
+    /// This allows frameworks to use their own variables, e.g.
+    /// `APP_VERBOSITY=3`. The variables are checked in order, the first
+    /// variable that is set (to a valid value) determines the log level or
+    /// targets. If none are set all targets are logged using the info level,
+    /// same as with the default variables.
+    ///
+    /// [crate level documentation]: index.html#setting-severity
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::LevelFilter;
+    /// use std_logger::{Config, EnvMeaning};
+    ///
+    /// # fn main() {
+    /// Config::logfmt()
+    ///     .with_env_vars(&[
+    ///         ("APP_LOG_LEVEL", EnvMeaning::Level),
+    ///         ("APP_VERBOSITY", EnvMeaning::Verbosity),
+    ///         ("APP_QUIET", EnvMeaning::LevelIfSet(LevelFilter::Error)),
+    ///         ("APP_LOG_TARGET", EnvMeaning::Targets),
+    ///     ])
+    ///     .init();
+    /// # }
+    /// ```
+    pub fn with_env_vars(self, vars: &[(&str, EnvMeaning)]) -> Config<F, Kvs> {
+        Config {
+            filter: max_level_from(vars, LevelFilter::Info),
+            levels: log_levels_from(vars),
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            #[cfg(feature = "reload")]
+            config_file: self.config_file,
+            #[cfg(feature = "reload")]
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: log_targets_from(vars),
+            kvs: self.kvs,
+            format: self.format,
+        }
+    }
+
+    /// Set the log level based on the command line flags for verbosity, e.g.
+    /// the number of times `-q`/`--quiet` and `-v`/`--verbose` are passed.
+    ///
+    /// Each `verbose` increases the log level by one step, starting at info,
+    /// and each `quiet` decreases it: `-q` logs warnings, `-qq` only errors,
+    /// `-v` debug and `-vv` trace messages. The same mapping is used as for
+    /// [`EnvMeaning::Verbosity`]. Errors are always logged.
+    ///
+    /// If neither flag is passed (both are zero) the log level from the
+    /// environment is kept, see the [crate level documentation].
+    ///
+    /// [crate level documentation]: index.html#setting-severity
+    ///
+    /// # Examples
+    ///
+    /// Using [clap]'s `ArgAction::Count`:
+    ///
+    /// [clap]: https://docs.rs/clap
+    ///
+    /// ```
+    /// # struct Args { quiet: u8, verbose: u8 }
+    /// # let args = Args { quiet: 0, verbose: 1 };
+    /// std_logger::Config::logfmt()
+    ///     .with_verbosity(args.quiet, args.verbose)
+    ///     .init();
+    /// ```
+    pub fn with_verbosity(mut self, quiet: u8, verbose: u8) -> Config<F, Kvs> {
+        if quiet != 0 || verbose != 0 {
+            let verbosity = (2 + u16::from(verbose)).saturating_sub(u16::from(quiet));
+            self.filter = level_from_verbosity(verbosity);
+        }
+        self
+    }
+
+    /// Set the style of the backtrace logged with panics, see the [log-panic
+    /// feature]. Defaults to [`BacktraceStyle::Short`].
+    ///
+    /// [log-panic feature]: index.html#log-panic-feature
+    pub fn with_panic_backtraces(mut self, style: BacktraceStyle) -> Config<F, Kvs> {
+        self.panic.backtrace_style = style;
+        self
+    }
+
+    /// Set the maximum number of frames of the backtrace logged with panics,
+    /// see the [log-panic feature]. Defaults to all frames.
+    ///
+    /// [log-panic feature]: index.html#log-panic-feature
+    pub fn with_panic_backtrace_depth(mut self, depth: usize) -> Config<F, Kvs> {
+        self.panic.backtrace_depth = depth;
+        self
+    }
+
+    /// Set what to do when standard out or error is full, see [`FullPolicy`].
+    /// Defaults to [`FullPolicy::Block`].
+    pub fn with_full_policy(mut self, policy: FullPolicy) -> Config<F, Kvs> {
+        self.full = policy;
+        self
+    }
+
+    /// Log the metrics of the logger, see [`metrics`], every `interval`.
+    ///
+    /// The metrics are logged using the [`METRICS_TARGET`] target and info
+    /// severity, with the number of records logged per level
+    /// (`records_error`, `records_warn`, etc.), `bytes_written`,
+    /// `records_dropped` and `write_errors` as key-values. As the logger
+    /// doesn't run any background threads the metrics are logged with the first
+    /// record logged after the interval has passed.
+    ///
+    /// [`metrics`]: crate::metrics
+    /// [`METRICS_TARGET`]: crate::METRICS_TARGET
+    pub fn with_metrics_interval(mut self, interval: Duration) -> Config<F, Kvs> {
+        self.metrics_interval = Some(interval);
+        self
+    }
+
+    /// Set what to do when writing a record fails, see [`ErrorPolicy`].
+    /// Defaults to [`ErrorPolicy::Panic`].
+    pub fn with_error_policy(mut self, policy: ErrorPolicy) -> Config<F, Kvs> {
+        self.error_policy = policy;
+        self
+    }
+
+    /// Set the time source used for the timestamp of records, see [`Clock`].
+    /// Defaults to [`Clock::System`].
+    ///
+    /// Only has an effect with the *timestamp* feature.
+    pub fn with_clock(mut self, clock: Clock) -> Config<F, Kvs> {
+        self.clock = clock;
+        self
+    }
+
+    /// Set the targets that are always logged, regardless of the targets set
+    /// using `LOG_TARGET` (see the [crate level documentation]). The log
+    /// level still applies to these targets.
+    ///
+    /// This replaces the default targets: [`REQUEST_TARGET`] and
+    /// [`PANIC_TARGET`], unlike `LOG_TARGET` these are matched exactly.
+    ///
+    /// [crate level documentation]: index.html#limiting-logging-targets
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::{Config, PANIC_TARGET, REQUEST_TARGET};
+    ///
+    /// Config::logfmt()
+    ///     .with_always_logged_targets([REQUEST_TARGET, PANIC_TARGET, "audit", "security"])
+    ///     .init();
+    /// ```
+    pub fn with_always_logged_targets<I, T>(mut self, targets: I) -> Config<F, Kvs>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Box<str>>,
+    {
+        self.always_log = targets.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the output for audit records, see [`audit!`]. Defaults to
+    /// [`AuditOutput::Stderr`].
+    ///
+    /// [`audit!`]: crate::audit
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// use std_logger::{AuditOutput, Config};
+    ///
+    /// let file = OpenOptions::new()
+    ///     .create(true)
+    ///     .append(true)
+    ///     .open("audit.log")
+    ///     .expect("failed to open audit log");
+    /// Config::logfmt()
+    ///     .with_audit_output(AuditOutput::File(file))
+    ///     .with_audit_sync(true)
+    ///     .init();
+    /// ```
+    pub fn with_audit_output(mut self, output: AuditOutput) -> Config<F, Kvs> {
+        self.audit.set_output(output);
+        self
+    }
+
+    /// Sync (`fsync`) the audit output after each audit record, making sure
+    /// it's written to disk before the [`audit!`] call returns. Disabled by
+    /// default.
+    ///
+    /// Only supported on Unix for standard out and error, if these are a pipe
+    /// or terminal (which can't be synced) this has no effect.
+    ///
+    /// [`audit!`]: crate::audit
+    pub fn with_audit_sync(mut self, enable: bool) -> Config<F, Kvs> {
+        self.audit.set_sync(enable);
+        self
+    }
+
+    /// Write the records with `target` to `route`, rather than standard error.
+    /// This generalises how requests (see [`REQUEST_TARGET`]) are written to
+    /// standard out, e.g. to write HTTP and gRPC access logs to separate
+    /// outputs. Requests can be routed as well.
+    ///
+    /// Routed targets are always logged, regardless of the targets set using
+    /// `LOG_TARGET`, but the log level still applies. The target must match
+    /// exactly. Records routed to standard out or error are batched (see
+    /// [`Config::with_batch`]) and follow the [`FullPolicy`], records routed
+    /// to a file are written directly, like sinks (see [`Config::add_sink`]).
+    ///
+    /// [`REQUEST_TARGET`]: crate::REQUEST_TARGET
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use log::info;
+    /// use std_logger::{Config, Route};
+    ///
+    /// # fn main() {
+    /// Config::logfmt()
+    ///     .route_target("grpc-access", Route::Stdout)
+    ///     .init();
+    ///
+    /// info!(target: "grpc-access", method = "Get"; "handled call");
+    /// # }
+    /// ```
+    pub fn route_target<T>(mut self, target: T, route: Route) -> Config<F, Kvs>
+    where
+        T: Into<Box<str>>,
+    {
+        self.routes.add(target.into(), route);
+        self
+    }
+
+    /// Write requests (see [`REQUEST_TARGET`]) to the inherited file descriptor
+    /// `fd`, e.g. a pipe passed by a supervisor as file descriptor 3, instead of
+    /// standard out.
+    ///
+    /// Defaults to the file descriptor in the `REQUEST_LOG_FD` environment
+    /// variable, if set, or standard out otherwise.
+    ///
+    /// The file descriptor must stay open as long as the logger is used, it's
+    /// never closed by the logger.
+    ///
+    /// [`REQUEST_TARGET`]: crate::REQUEST_TARGET
+    #[cfg(unix)]
+    pub fn with_request_fd(mut self, fd: std::os::unix::io::RawFd) -> Config<F, Kvs> {
+        assert!(fd >= 0, "invalid file descriptor: {fd}");
+        self.request_fd = Some(fd);
+        self
+    }
+
+    /// Write records to standard error without taking the lock on it. Disabled
+    /// by default.
+    ///
+    /// By default each record is written while holding the [`Stderr`] lock,
+    /// which under many threads logging at the same time, e.g. during a storm
+    /// of requests, can become a point of contention. With this enabled
+    /// records are written directly to the file descriptor using a single
+    /// system call, relying on the OS to not interleave the records, which it
+    /// guarantees for records up to `PIPE_BUF` bytes (at least 512 bytes, 4096
+    /// on Linux) when writing to a pipe. Larger records, and the remainder of
+    /// partial writes, are still written while holding the lock.
+    ///
+    /// Anything else writing to standard error while not holding the lock,
+    /// e.g. another process, may still interleave with the records.
+    ///
+    /// Whether this is faster depends on the output, it helps when writing to
+    /// a pipe (e.g. a log collector) but not when writing to a file, see the
+    /// `concurrent` benchmark. Batching (see [`Config::with_batch`]) reduces
+    /// the contention further, as batches are written using a single system
+    /// call.
+    ///
+    /// Only supported on Unix, on other platforms this has no effect.
+    ///
+    /// [`Stderr`]: std::io::Stderr
+    pub fn with_unlocked_stderr(mut self, enable: bool) -> Config<F, Kvs> {
+        self.unlocked_stderr = enable;
+        self
+    }
+
+    /// Write the records on a dedicated thread, rather than on the thread
+    /// logging them. The records are still formatted on the logging thread
+    /// and then queued for the writer thread, up to `capacity` records.
+    ///
+    /// This means logging never blocks on a slow output, e.g. standard error
+    /// connected to a throttled log collector or a sink on a slow disk, which
+    /// is important for async applications where blocking a task blocks all
+    /// tasks on the thread, see the *tokio* feature.
+    ///
+    /// If the queue is full the record is dropped, see [`dropped_records`].
+    /// Audit (see [`AUDIT_TARGET`]) and panic (see [`PANIC_TARGET`]) records
+    /// are never queued, they're written directly on the logging thread, as
+    /// they may not be dropped and the process may exit after a panic.
+    ///
+    /// Flushing the logger (see [`log::Log::flush`]) blocks until all queued
+    /// records are written. [`shutdown`] also stops the writer thread, after
+    /// which records are written directly.
+    ///
+    /// [`dropped_records`]: crate::dropped_records
+    /// [`AUDIT_TARGET`]: crate::AUDIT_TARGET
+    /// [`shutdown`]: crate::shutdown
+    ///
+    /// # Panics
+    ///
+    /// This panics if `capacity` is zero.
+    pub fn with_writer_thread(mut self, capacity: usize) -> Config<F, Kvs> {
+        assert!(
+            capacity != 0,
+            "capacity of the writer thread queue can't be zero"
+        );
+        self.writer_queue = Some(capacity);
+        self
+    }
+
+    /// Read the log level, targets, format, levels per target and additional
+    /// file outputs from the configuration file at `path`, see the [crate level
+    /// documentation] for the format. Defaults to the file in the `LOG_CONFIG`
+    /// environment variable, if set.
+    ///
+    /// The settings in the file overwrite the settings of the configuration,
+    /// e.g. the `level` in the file overwrites the level set by the `LOG_LEVEL`
+    /// environment variable. Settings not in the file are unchanged.
+    ///
+    /// The levels set in the file only apply to standard out and error, the
+    /// sinks (both the sinks added using [`Config::add_sink`] and those in the
+    /// file) use their own level.
+    ///
+    /// If the file can't be read or is invalid an error is logged once the
+    /// logger is initialised and the configuration is used as if no file was
+    /// set.
+    ///
+    /// [crate level documentation]: index.html#reload-feature
+    #[cfg(feature = "reload")]
+    pub fn with_config_file<P>(mut self, path: P) -> Config<F, Kvs>
+    where
+        P: Into<std::path::PathBuf>,
+    {
+        self.config_file = Some(path.into());
+        self
+    }
+
+    /// Check the configuration file (see [`Config::with_config_file`]) for
+    /// changes every `interval`, reloading the levels and targets if it
+    /// changed. Defaults to not watching the file.
+    ///
+    /// This starts a thread that checks the modification time of the file. It
+    /// can be stopped using [`shutdown`]. If the changed file is invalid an
+    /// error is logged and the current configuration is kept. Also see
+    /// [`reload_config`].
+    ///
+    /// [`shutdown`]: crate::shutdown
+    /// [`reload_config`]: crate::reload_config
+    #[cfg(feature = "reload")]
+    pub fn with_config_reload(mut self, interval: Duration) -> Config<F, Kvs> {
+        self.config_reload = Some(interval);
+        self
+    }
+
+    /// Listen on a Unix socket at `path` for commands to change the levels
+    /// while the application is running, see the [crate level
+    /// documentation] for the protocol. Defaults to no socket.
+    ///
+    /// This starts a thread accepting connections on the socket, which can be
+    /// stopped using [`shutdown`]. An existing socket at `path` is replaced.
+    /// The socket is only accessible by the owner of the process.
+    ///
+    /// The changes are lost when the configuration file (see
+    /// [`Config::with_config_file`]) is reloaded.
+    ///
+    /// [crate level documentation]: index.html#admin-feature
+    /// [`shutdown`]: crate::shutdown
+    #[cfg(feature = "admin")]
+    pub fn with_admin_socket<P>(mut self, path: P) -> Config<F, Kvs>
+    where
+        P: Into<std::path::PathBuf>,
+    {
+        self.admin_socket = Some(path.into());
+        self
+    }
+
+    /// Redact the values of `keys`, replacing them with `[REDACTED]`, e.g. to
+    /// never log passwords or tokens. The keys are compared ignoring ASCII
+    /// case.
+    ///
+    /// The redaction is applied to the records before they're written to any
+    /// output. Only the key-values of records are redacted, not the key-values
+    /// added using [`Config::with_kvs`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::Config;
+    ///
+    /// Config::logfmt()
+    ///     .with_redacted_keys(["password", "token"])
+    ///     .init();
+    ///
+    /// // Logged as `... user="bob" password="[REDACTED]"`.
+    /// log::info!(user = "bob", password = "hunter2"; "logged in");
+    /// ```
+    pub fn with_redacted_keys<I, T>(mut self, keys: I) -> Config<F, Kvs>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Box<str>>,
+    {
+        self.redact.keys.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Redact all matches of `pattern` in the message and values of records,
+    /// replacing them with `[REDACTED]`. Can be called multiple times to add
+    /// multiple patterns.
+    ///
+    /// Values matching the pattern are formatted as string, e.g. the number
+    /// `1234` with the pattern `\d{2}` is logged as `"[REDACTED][REDACTED]"`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use regex::Regex;
+    /// use std_logger::Config;
+    ///
+    /// Config::logfmt()
+    ///     // Credit card numbers.
+    ///     .with_redacted_pattern(Regex::new(r"\d{16}").unwrap())
+    ///     .init();
+    /// ```
+    #[cfg(feature = "redact")]
+    pub fn with_redacted_pattern(mut self, pattern: regex::Regex) -> Config<F, Kvs> {
+        self.redact.patterns.push(pattern);
+        self
+    }
+
+    /// Replace the nondeterministic parts of the output with fake values, so
+    /// that the output can be compared byte-for-byte, e.g. in snapshot tests.
+    ///
+    /// This uses [`Clock::Fake`] for the timestamps and disables the
+    /// backtraces of panics (see [`Config::with_panic_backtraces`]). With the
+    /// *service* feature the request ids start at zero and increase by one for
+    /// each request and the time requests took is logged as zero.
+    ///
+    /// This is meant for tests, together with the *test* feature to capture
+    /// the records, and should not be used in production.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std_logger::Config;
+    ///
+    /// // Timestamps start at `2001-01-01T00:00:00.000000Z` and increase by one
+    /// // millisecond for each record.
+    /// Config::logfmt().with_deterministic_output().init();
+    /// ```
+    pub fn with_deterministic_output(mut self) -> Config<F, Kvs> {
+        self.clock = Clock::Fake;
+        self.panic.backtrace_style = BacktraceStyle::Off;
+        self.deterministic = true;
+        self
+    }
+
+    /// Also write the records with a level of at least `filter` to `sink`, e.g.
+    /// a file or a TCP connection.
+    ///
+    /// The records are still written to standard out or error (based on the
+    /// target) using the log level set by the environment, see the [crate
+    /// level documentation]. Each record is formatted once and written to all
+    /// outputs that accept its level, for example this can be used to write
+    /// debug records to a file while only writing errors to standard error.
+    /// Records are written to the sinks directly, batching (see
+    /// [`Config::with_batch`]) doesn't apply to sinks. The target filter
+    /// (`LOG_TARGET`) applies to all outputs.
+    ///
+    /// Like for standard out and error, failing to write to a sink panics. To
+    /// sync a file to disk after writing see [`Config::add_file_sink`].
+    ///
+    /// [crate level documentation]: index.html#setting-severity
+    pub fn add_sink<W>(mut self, sink: W, filter: LevelFilter) -> Config<F, Kvs>
+    where
+        W: Write + Send + 'static,
+    {
+        self.sinks.push(Sink::new(Box::new(sink), filter));
+        self
+    }
+
+    /// Same as [`Config::add_sink`], but for a file that is synced (`fsync`)
+    /// to disk following the `sync` policy.
+    ///
+    /// This allows trading throughput for durability, e.g. to make sure audit
+    /// records (see [`audit!`]) are on disk before the call returns, while
+    /// other records are only synced once in a while.
+    ///
+    /// Like failing to write, failing to sync the file is a write failure, see
+    /// [`Config::with_error_policy`].
+    ///
+    /// [`audit!`]: crate::audit
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::fs::OpenOptions;
+    ///
+    /// use log::LevelFilter;
+    /// use std_logger::{Config, SyncPolicy};
+    ///
+    /// let file = OpenOptions::new()
+    ///     .create(true)
+    ///     .append(true)
+    ///     .open("app.log")
+    ///     .expect("failed to open log file");
+    /// Config::logfmt()
+    ///     .add_file_sink(file, LevelFilter::Info, SyncPolicy::Audit)
+    ///     .init();
+    /// ```
+    pub fn add_file_sink(
+        mut self,
+        file: File,
+        filter: LevelFilter,
+        sync: SyncPolicy,
+    ) -> Config<F, Kvs> {
+        self.sinks
+            .push(Sink::file(SyncedFile::new(file, sync), filter));
+        self
+    }
+
+    /// Prefix each record written to standard error with its syslog priority,
+    /// e.g. `<3>` for errors, following `sd-daemon(3)`.
+    ///
+    /// This allows systemd to assign the correct priority to the records when
+    /// reading the output of a service, e.g. to show errors in red using
+    /// `journalctl`. The levels map to the following priorities: error to
+    /// `<3>` (or `<2>`, critical, for panics), warn to `<4>`, info to `<6>`
+    /// and debug and trace to `<7>`. Requests written to standard out and
+    /// records written to sinks are not prefixed. Defaults to `false`.
+    pub fn with_systemd_priority_prefix(mut self, enable: bool) -> Config<F, Kvs> {
+        self.systemd_prefix = enable;
+        self
+    }
+
+    /// Format records using `format` instead of the format of the
+    /// configuration (e.g. logfmt).
+    ///
+    /// `format` must write the complete record, including the timestamp (if
+    /// wanted) and line end, into the buffer, which is empty when `format` is
+    /// called. The formatted record is written like any other record, i.e. to
+    /// standard out (for requests) or error and all sinks, following the batch
+    /// and full policies. Only the formatting options, e.g.
+    /// [`Config::with_call_location`], and the key-values set using
+    /// [`Config::with_kvs`] don't apply, the transforms and redaction do.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// use std_logger::Config;
+    ///
+    /// Config::logfmt()
+    ///     .format_with(|buf, record| {
+    ///         let _ = writeln!(buf, "[{}] {}: {}", record.level(), record.target(), record.args());
+    ///     })
+    ///     .init();
+    /// ```
+    pub fn format_with<C>(mut self, format: C) -> Config<F, Kvs>
+    where
+        C: Fn(&mut Vec<u8>, &log::Record) + Send + Sync + 'static,
+    {
+        self.custom_format = Some(CustomFormat::new(format));
+        self
+    }
+
+    /// Add `transform` to transform all records before they're written, see
+    /// [`RecordTransform`].
+    ///
+    /// Transforms are applied in the order they're added, before the redaction
+    /// (see [`Config::with_redacted_keys`]).
+    pub fn add_transform<T>(mut self, transform: T) -> Config<F, Kvs>
+    where
+        T: RecordTransform + 'static,
+    {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
     /// Initialise the logger.
     ///
     /// See the [crate level documentation] for more.
@@ -94,8 +1403,9 @@ where
     ///
     /// # Panics
     ///
-    /// This will panic if the logger fails to initialise. Use [`Config::try_init`] if
-    /// you want to handle the error yourself.
+    /// This will panic if the logger fails to initialise, i.e. if another
+    /// logger (not from this crate) is already set. Use [`Config::try_init`]
+    /// if you want to handle the error yourself.
     pub fn init(self) {
         self.try_init()
             .unwrap_or_else(|err| panic!("failed to initialise the logger: {err}"));
@@ -106,81 +1416,737 @@ where
     /// Unlike [`Config::init`] this doesn't panic when the logger fails to initialise.
     /// See the [crate level documentation] for more.
     ///
+    /// If the logger of this crate is already initialised, e.g. by a library
+    /// initialising it defensively, this does nothing and returns `Ok`. The
+    /// configuration of the first initialisation is kept, this configuration
+    /// is ignored. Use [`is_initialised`] to check if the logger is already
+    /// initialised. If another logger is set this returns an error.
+    ///
     /// [`init`]: fn.init.html
     /// [crate level documentation]: index.html
+    /// [`is_initialised`]: crate::is_initialised
     pub fn try_init(self) -> Result<(), SetLoggerError> {
-        let logger = Box::new(Logger {
+        #[cfg(feature = "log-panic")]
+        let panic = self.panic;
+        #[cfg_attr(not(feature = "log-panic"), allow(unused_variables))]
+        let set = self.set_logger()?;
+        #[cfg(feature = "log-panic")]
+        if set {
+            panic.set_hook();
+        }
+        Ok(())
+    }
+
+    /// Initialise the logger, returning a guard that shuts the logger down
+    /// when dropped, see [`LoggerGuard`].
+    ///
+    /// # Panics
+    ///
+    /// This will panic if the logger fails to initialise. Use
+    /// [`Config::try_init_with_guard`] if you want to handle the error
+    /// yourself.
+    pub fn init_with_guard(self) -> LoggerGuard {
+        self.try_init_with_guard()
+            .unwrap_or_else(|err| panic!("failed to initialise the logger: {err}"))
+    }
+
+    /// Try to initialise the logger, returning a guard that shuts the logger
+    /// down when dropped, see [`LoggerGuard`].
+    ///
+    /// Unlike [`Config::init_with_guard`] this doesn't panic when the logger
+    /// fails to initialise.
+    ///
+    /// If the logger of this crate is already initialised this does nothing,
+    /// like [`Config::try_init`], and the returned guard doesn't shut the
+    /// logger down when dropped.
+    pub fn try_init_with_guard(self) -> Result<LoggerGuard, SetLoggerError> {
+        let panic = self.panic;
+        if self.set_logger()? {
+            Ok(LoggerGuard::new(panic))
+        } else {
+            Ok(LoggerGuard { active: false })
+        }
+    }
+
+    /// Set the logger and maximum log level. Returns `false` if the logger of
+    /// this crate is already set, in which case nothing is changed.
+    #[cfg_attr(not(feature = "reload"), allow(unused_mut))]
+    fn set_logger(mut self) -> Result<bool, SetLoggerError> {
+        if !crate::start_init() {
+            return Ok(false);
+        }
+        #[cfg(feature = "reload")]
+        {
+            use crate::reload::Format;
+
+            let (file, format) = self.read_config_file();
+            match format {
+                Some(Format::Logfmt) => self.into_format::<LogFmt>().install(file),
+                Some(Format::Json) => self.into_format::<Json>().install(file),
+                Some(Format::Gcloud) => self.into_format::<Gcloud>().install(file),
+                None => self.install(file),
+            }
+        }
+        #[cfg(not(feature = "reload"))]
+        self.install(ConfigFile {})
+    }
+
+    /// Set the logger, after reading the configuration `file`, see
+    /// [`Config::set_logger`].
+    #[cfg_attr(not(feature = "admin"), allow(unused_mut))]
+    #[cfg_attr(not(feature = "reload"), allow(unused_variables))]
+    fn install(mut self, file: ConfigFile) -> Result<bool, SetLoggerError> {
+        #[cfg(feature = "reload")]
+        let config_reload = self.config_reload;
+        #[cfg(feature = "admin")]
+        let admin_socket = self.admin_socket.take();
+        let error_policy = self.error_policy;
+        let clock = self.clock;
+        let deterministic = self.deterministic;
+        let request_fd = self.request_fd;
+        let unlocked_stderr = self.unlocked_stderr;
+        let writer_queue = self.writer_queue;
+        let batch = self.batch;
+        let mut logger = self.into_logger();
+        #[cfg(feature = "reload")]
+        {
+            logger.reload = file.reload.clone();
+        }
+        let receiver = writer_queue.map(|capacity| {
+            let (writer, receiver) = crate::writer::new(capacity);
+            logger.writer = Some(writer);
+            receiver
+        });
+        // Audit records (logged at the info level) are always logged, the
+        // other records are filtered in `Logger::enabled`.
+        let max_level = logger.max_level().max(LevelFilter::Info);
+        // Set the global state used by the logger before setting it, so that
+        // records logged by other threads as soon as it's set use it. If setting
+        // the logger fails it isn't used by anything else.
+        error_policy.set();
+        clock.set();
+        crate::set_deterministic(deterministic);
+        crate::set_request_fd(request_fd);
+        crate::full::set_unlocked_stderr(unlocked_stderr);
+        // NOTE: like `log::set_boxed_logger` this leaks the logger once set, but
+        // we need the reference for the writer thread.
+        let logger = Box::into_raw(Box::new(logger));
+        // SAFETY: `logger` is valid as it's created from a `Box` above, it's only
+        // deallocated below if setting it fails.
+        let logger: &'static Logger<F, Kvs> = unsafe { &*logger };
+        if let Err(err) = log::set_logger(logger) {
+            // SAFETY: the logger wasn't set, so there are no other references
+            // to it.
+            drop(unsafe { Box::from_raw(std::ptr::from_ref(logger).cast_mut()) });
+            crate::finish_init(false);
+            return Err(err);
+        }
+        log::set_max_level(max_level);
+        #[cfg(unix)]
+        crate::fork::register();
+        crate::finish_init(true);
+        if let Some(policy) = batch {
+            if let Err(err) = crate::batch::start(policy) {
+                log::error!("failed to start logging batch thread: {err}");
+            }
+        }
+        if let (Some(writer), Some(receiver)) = (&logger.writer, receiver) {
+            let write = |record: &log::Record, bufs: &[IoSlice]| logger.write_now(record, bufs);
+            if let Err(err) = crate::writer::spawn(writer, receiver, write, || logger.flush_now()) {
+                log::error!("failed to start logging writer thread: {err}");
+            }
+        }
+        #[cfg(feature = "reload")]
+        {
+            for err in file.errors {
+                log::error!("failed to read logging configuration: {err}");
+            }
+            if let Some(reload) = file.reload {
+                crate::reload::set(reload.clone());
+                #[cfg(feature = "admin")]
+                if let Some(path) = admin_socket {
+                    if let Err(err) = crate::admin::listen(path, reload.clone()) {
+                        log::error!("failed to start logging admin socket: {err}");
+                    }
+                }
+                if let Some(interval) = config_reload {
+                    if let Err(err) = crate::reload::watch(reload, interval) {
+                        log::error!("failed to start watching logging configuration: {err}");
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Read the configuration file, if any, see [`Config::with_config_file`].
+    ///
+    /// This applies the settings of the file to the configuration and adds
+    /// the sinks in the file. Returns any errors to be logged once the logger
+    /// is initialised and the format set in the file, if any. The levels are
+    /// also reloadable if only the admin socket is set.
+    #[cfg(feature = "reload")]
+    fn read_config_file(&mut self) -> (ConfigFile, Option<crate::reload::Format>) {
+        use std::fs::OpenOptions;
+        use std::sync::Arc;
+
+        use crate::reload::{self, Reload};
+
+        let path = self.config_file.take();
+        #[cfg(feature = "admin")]
+        let reloadable = path.is_some() || self.admin_socket.is_some();
+        #[cfg(not(feature = "admin"))]
+        let reloadable = path.is_some();
+        if !reloadable {
+            return (ConfigFile::default(), None);
+        }
+        let mut errors = Vec::new();
+        let file = match path.as_deref().map(reload::read) {
+            Some(Ok(file)) => Some(file),
+            Some(Err(err)) => {
+                errors.push(err);
+                None
+            }
+            None => None,
+        };
+        for (sink_path, filter) in file.iter().flat_map(|file| &file.sinks) {
+            match OpenOptions::new().create(true).append(true).open(sink_path) {
+                Ok(sink) => {
+                    #[cfg(any(feature = "gzip", feature = "zstd"))]
+                    let sink: Box<dyn Write + Send> = match crate::Compression::from_path(sink_path)
+                    {
+                        Some(compression) => {
+                            Box::new(crate::CompressedSink::new(sink, compression))
+                        }
+                        None => Box::new(sink),
+                    };
+                    #[cfg(not(any(feature = "gzip", feature = "zstd")))]
+                    let sink = Box::new(sink);
+                    self.sinks.push(Sink::new(sink, *filter));
+                }
+                Err(err) => errors.push(io::Error::new(
+                    err.kind(),
+                    format!("failed to open '{}': {err}", sink_path.display()),
+                )),
+            }
+        }
+        let sinks_level = self
+            .sinks
+            .iter()
+            .map(|sink| sink.filter)
+            .fold(LevelFilter::Off, Ord::max);
+        let reload = Reload::new(
+            path,
+            self.filter,
+            self.targets.clone(),
+            self.levels.clone().into_boxed_slice(),
+            sinks_level,
+            file.as_ref(),
+        );
+        let format = file.as_ref().and_then(|file| file.format);
+        if let Some(level) = file.and_then(|file| file.level) {
+            self.filter = level;
+        }
+        let file = ConfigFile {
+            reload: Some(Arc::new(reload)),
+            errors,
+        };
+        (file, format)
+    }
+
+    /// Change the format to `G`, keeping all other settings.
+    #[cfg(feature = "reload")]
+    fn into_format<G>(self) -> Config<G, Kvs> {
+        Config {
+            filter: self.filter,
+            levels: self.levels,
+            add_loc: self.add_loc,
+            bare_keys: self.bare_keys,
+            msg_last: self.msg_last,
+            raw: self.raw,
+            multi_line: self.multi_line,
+            max_size: self.max_size,
+            type_hints: self.type_hints,
+            structured: self.structured,
+            fields: self.fields,
+            separate_line: self.separate_line,
+            batch: self.batch,
+            panic: self.panic,
+            error_policy: self.error_policy,
+            clock: self.clock,
+            deterministic: self.deterministic,
+            always_log: self.always_log,
+            audit: self.audit,
+            routes: self.routes,
+            request_fd: self.request_fd,
+            unlocked_stderr: self.unlocked_stderr,
+            writer_queue: self.writer_queue,
+            env_kvs: self.env_kvs,
+            config_file: self.config_file,
+            config_reload: self.config_reload,
+            #[cfg(feature = "admin")]
+            admin_socket: self.admin_socket,
+            redact: self.redact,
+            transforms: self.transforms,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks,
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            targets: self.targets,
+            kvs: self.kvs,
+            format: PhantomData,
+        }
+    }
+
+    /// Create the logger from the configuration.
+    pub(crate) fn into_logger(self) -> Logger<F, Kvs> {
+        let max_level = self
+            .levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.filter, Ord::max);
+        // Routed targets are always logged, like requests.
+        let mut always_log = self.always_log;
+        for target in self.routes.targets() {
+            if !always_log.iter().any(|t| &**t == target) {
+                always_log.push(target.into());
+            }
+        }
+        Logger {
             filter: self.filter,
-            add_loc: self.add_loc.unwrap_or(self.filter >= LevelFilter::Debug),
+            levels: self.levels.into_boxed_slice(),
+            options: Options {
+                add_loc: self.add_loc.unwrap_or(max_level >= LevelFilter::Debug),
+                separate_line: self.separate_line,
+                bare_keys: self.bare_keys,
+                msg_last: self.msg_last,
+                raw: self.raw,
+                multi_line: self.multi_line,
+                max_size: self.max_size,
+                type_hints: self.type_hints.into_boxed_slice(),
+                structured: self.structured,
+                fields: fields(self.fields, self.msg_last),
+            },
             targets: self.targets,
+            always_log: always_log.into_boxed_slice(),
+            audit: self.audit,
+            routes: self.routes,
+            transforms: self.transforms,
+            redact: self.redact,
             kvs: self.kvs,
+            env_kvs: self.env_kvs.into_boxed_slice(),
+            batch: self.batch,
+            full: self.full,
+            metrics_interval: self.metrics_interval,
+            sinks: self.sinks.into_boxed_slice(),
+            custom_format: self.custom_format,
+            systemd_prefix: self.systemd_prefix,
+            #[cfg(feature = "reload")]
+            reload: None,
+            writer: None,
             format: self.format,
-        });
-        log::set_boxed_logger(logger)?;
-        log::set_max_level(self.filter);
+        }
+    }
+}
 
+/// Returns all built-in fields using their default names and order, written
+/// for all levels.
+fn default_fields() -> Vec<(Field, Box<str>, LevelFilter)> {
+    Field::ALL
+        .into_iter()
+        .map(|field| (field, field.default_name().into(), LevelFilter::Trace))
+        .collect()
+}
+
+/// Returns the fields for [`Options::fields`], moving the message to the end
+/// if `msg_last` is `true`.
+fn fields(
+    mut fields: Vec<(Field, Box<str>, LevelFilter)>,
+    msg_last: bool,
+) -> Box<[(Field, Box<str>, LevelFilter)]> {
+    if msg_last {
+        if let Some(index) = fields.iter().position(|(f, _, _)| *f == Field::Message) {
+            let msg = fields.remove(index);
+            fields.push(msg);
+        }
+    }
+    fields.into_boxed_slice()
+}
+
+/// Meaning of an environment variable, see [`Config::with_env_vars`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum EnvMeaning {
+    /// Name of the log level, e.g. `info` or `WARN`, like `LOG` and
+    /// `LOG_LEVEL`.
+    Level,
+    /// Set the log level to the level if the variable is set, to any value,
+    /// like `TRACE` and `DEBUG`.
+    LevelIfSet(LevelFilter),
+    /// Verbosity as number: `0` for errors, `1` for warnings, `2` for info,
+    /// `3` for debug and `4` (or higher) for trace messages.
+    Verbosity,
+    /// Comma separated list of targets to log, like `LOG_TARGET`.
+    Targets,
+    /// Directives in the format of the [env_logger] crate, like `RUST_LOG`,
+    /// e.g. `warn,my_crate=info,my_crate::db=debug`. Each directive is a
+    /// level for all targets (`warn`), a level for a target pattern
+    /// (`my_crate::db=debug`) or a target pattern only (`my_crate`), which
+    /// logs all records for the target. If no level for all targets is set
+    /// only the targets in the directives are logged.
+    ///
+    /// The target patterns are the same as for `LOG_TARGET`, which is stricter
+    /// than [env_logger]: `my_crate` matches `my_crate::db`, but not
+    /// `my_crate_other`. Invalid directives and the regex filter (`/regex`)
+    /// are ignored.
+    ///
+    /// This variable is only used if none of the variables before it are set,
+    /// e.g. `RUST_LOG` is only used if `LOG`, `LOG_LEVEL` and `LOG_TARGET`
+    /// etc. are not set.
+    ///
+    /// [env_logger]: https://docs.rs/env_logger
+    Directives,
+}
+
+/// The default environment variables.
+const DEFAULT_ENV_VARS: &[(&str, EnvMeaning)] = &[
+    ("LOG", EnvMeaning::Level),
+    ("LOG_LEVEL", EnvMeaning::Level),
+    ("TRACE", EnvMeaning::LevelIfSet(LevelFilter::Trace)),
+    ("DEBUG", EnvMeaning::LevelIfSet(LevelFilter::Debug)),
+    ("LOG_TARGET", EnvMeaning::Targets),
+    ("RUST_LOG", EnvMeaning::Directives),
+];
+
+/// Result of reading the configuration file, see [`Config::with_config_file`].
+#[derive(Debug, Default)]
+struct ConfigFile {
+    /// Reloadable configuration, if a file or admin socket is set.
+    #[cfg(feature = "reload")]
+    reload: Option<std::sync::Arc<crate::reload::Reload>>,
+    /// Errors reading the file, logged once the logger is initialised.
+    #[cfg(feature = "reload")]
+    errors: Vec<io::Error>,
+}
+
+/// Guard that shuts the logger down when dropped, see
+/// [`Config::init_with_guard`].
+///
+/// Dropping the guard shuts the logger down, see [`shutdown`].
+///
+/// [`shutdown`]: crate::shutdown
+#[must_use = "dropping the guard shuts down the logger"]
+pub struct LoggerGuard {
+    /// Shut down the logger when dropped, `false` if the logger was already
+    /// initialised.
+    active: bool,
+}
+
+impl LoggerGuard {
+    /// Create a new guard, installing the panic hook.
+    #[cfg_attr(not(feature = "log-panic"), allow(unused_variables))]
+    pub(crate) fn new(panic: PanicOptions) -> LoggerGuard {
         #[cfg(feature = "log-panic")]
-        std::panic::set_hook(Box::new(log_panic));
-        Ok(())
+        panic.set_hook();
+        LoggerGuard { active: true }
+    }
+}
+
+impl Drop for LoggerGuard {
+    fn drop(&mut self) {
+        if self.active {
+            crate::shutdown();
+        }
+    }
+}
+
+impl fmt::Debug for LoggerGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoggerGuard").finish_non_exhaustive()
+    }
+}
+
+/// Get the file descriptor to write requests to from the `REQUEST_LOG_FD`
+/// environment variable, if set (and valid).
+fn get_request_fd() -> Option<i32> {
+    if !cfg!(unix) {
+        return None;
+    }
+    match env::var("REQUEST_LOG_FD") {
+        Ok(fd) => fd.trim().parse().ok().filter(|fd| *fd >= 0),
+        Err(_) => None,
     }
 }
 
 /// Get the maximum log level based on the environment.
 pub(crate) fn get_max_level() -> LevelFilter {
-    for var in &["LOG", "LOG_LEVEL"] {
-        if let Ok(level) = env::var(var) {
-            if let Ok(level) = level.parse() {
-                return level;
-            }
+    max_level_from(DEFAULT_ENV_VARS, LevelFilter::Info)
+}
+
+/// Get the maximum log level based on the environment variables `vars`, or
+/// `default` if none are set.
+fn max_level_from(vars: &[(&str, EnvMeaning)], default: LevelFilter) -> LevelFilter {
+    if let Some(directives) = directives_from(vars) {
+        return directives.level.unwrap_or(default);
+    }
+    for (var, meaning) in vars {
+        let Ok(value) = env::var(var) else {
+            continue;
+        };
+        let level = match meaning {
+            EnvMeaning::Level => value.parse().ok(),
+            EnvMeaning::LevelIfSet(level) => Some(*level),
+            EnvMeaning::Verbosity => value.trim().parse().ok().map(level_from_verbosity),
+            EnvMeaning::Targets | EnvMeaning::Directives => None,
+        };
+        if let Some(level) = level {
+            return level;
         }
     }
+    default
+}
 
-    if env::var("TRACE").is_ok() {
-        LevelFilter::Trace
-    } else if env::var("DEBUG").is_ok() {
-        LevelFilter::Debug
-    } else {
-        LevelFilter::Info
+/// Returns the log level for `verbosity`, see [`EnvMeaning::Verbosity`].
+fn level_from_verbosity(verbosity: u16) -> LevelFilter {
+    match verbosity {
+        0 => LevelFilter::Error,
+        1 => LevelFilter::Warn,
+        2 => LevelFilter::Info,
+        3 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
     }
 }
 
 /// Get the targets to log, if any.
 pub(crate) fn get_log_targets() -> Targets {
-    match env::var("LOG_TARGET") {
-        Ok(ref targets) if !targets.is_empty() => {
-            Targets::Only(targets.split(',').map(Into::into).collect())
+    log_targets_from(DEFAULT_ENV_VARS)
+}
+
+/// Get the targets to log based on the environment variables `vars`, if any.
+fn log_targets_from(vars: &[(&str, EnvMeaning)]) -> Targets {
+    if let Some(directives) = directives_from(vars) {
+        return match directives.level {
+            Some(_) => Targets::All,
+            None => Targets::Only(
+                directives
+                    .levels
+                    .into_iter()
+                    .map(|(pattern, _)| pattern)
+                    .collect(),
+            ),
+        };
+    }
+    for (var, meaning) in vars {
+        if *meaning != EnvMeaning::Targets {
+            continue;
+        }
+        match env::var(var) {
+            Ok(ref targets) if !targets.is_empty() => {
+                return Targets::Only(targets.split(',').map(Into::into).collect())
+            }
+            _ => {}
         }
-        _ => Targets::All,
+    }
+    Targets::All
+}
+
+/// Get the levels for specific targets, if any.
+pub(crate) fn get_log_levels() -> Vec<(Box<str>, LevelFilter)> {
+    log_levels_from(DEFAULT_ENV_VARS)
+}
+
+/// Get the levels for specific targets based on the environment variables
+/// `vars`, if any.
+fn log_levels_from(vars: &[(&str, EnvMeaning)]) -> Vec<(Box<str>, LevelFilter)> {
+    directives_from(vars).map_or_else(Vec::new, |directives| directives.levels)
+}
+
+/// Level and levels per target parsed from directives, see
+/// [`EnvMeaning::Directives`].
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct Directives {
+    /// Level for all targets, if set.
+    pub(crate) level: Option<LevelFilter>,
+    pub(crate) levels: Vec<(Box<str>, LevelFilter)>,
+}
+
+/// Returns the parsed directives of the first variable with the
+/// [`EnvMeaning::Directives`] meaning in `vars`, if none of the variables
+/// before it are set.
+fn directives_from(vars: &[(&str, EnvMeaning)]) -> Option<Directives> {
+    for (var, meaning) in vars {
+        let Some(value) = env::var_os(var) else {
+            continue;
+        };
+        if *meaning != EnvMeaning::Directives {
+            return None;
+        }
+        return Some(parse_env_directives(&value.to_string_lossy()));
+    }
+    None
+}
+
+/// Parse `directives`, ignoring invalid directives and the regex filter.
+pub(crate) fn parse_env_directives(directives: &str) -> Directives {
+    let directives = directives.split_once('/').map_or(directives, |(d, _)| d);
+    let mut parsed = Directives {
+        level: None,
+        levels: Vec::new(),
+    };
+    for directive in directives.split(',') {
+        match parse_directive(directive) {
+            Ok(Some((None, level))) => parsed.level = Some(level),
+            Ok(Some((Some(target), level))) => parsed.levels.push((target.into(), level)),
+            Ok(None) | Err(_) => {}
+        }
+    }
+    parsed
+}
+
+/// Parse a single `directive`, see [`EnvMeaning::Directives`]. Returns the
+/// target pattern, `None` for all targets, and the level, or `None` if the
+/// directive is empty.
+pub(crate) fn parse_directive(
+    directive: &str,
+) -> Result<Option<(Option<&str>, LevelFilter)>, &'static str> {
+    let directive = directive.trim();
+    if directive.is_empty() {
+        return Ok(None);
+    }
+    match directive.split_once('=') {
+        Some((target, level)) => {
+            let target = target.trim();
+            if target.is_empty() {
+                return Err("missing target");
+            }
+            match level.trim().parse() {
+                Ok(level) => Ok(Some((Some(target), level))),
+                Err(_) => Err("invalid level"),
+            }
+        }
+        None => match directive.parse() {
+            Ok(level) => Ok(Some((None, level))),
+            Err(_) => Ok(Some((Some(directive), LevelFilter::Trace))),
+        },
+    }
+}
+
+/// Style of the backtrace logged with panics, see
+/// [`Config::with_panic_backtraces`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum BacktraceStyle {
+    /// All frames, including the ones of the standard library's panic
+    /// machinery.
+    Full,
+    /// Only the frames relevant to the panic, like `RUST_BACKTRACE=1`.
+    Short,
+    /// Don't capture a backtrace, the `backtrace` key-value is not logged.
+    Off,
+}
+
+/// Options for logging panics, see the *log-panic* feature.
+#[derive(Copy, Clone, Debug)]
+#[cfg_attr(not(feature = "log-panic"), allow(dead_code))]
+pub(crate) struct PanicOptions {
+    pub(crate) backtrace_style: BacktraceStyle,
+    /// Maximum number of backtrace frames to log.
+    pub(crate) backtrace_depth: usize,
+}
+
+impl PanicOptions {
+    pub(crate) const DEFAULT: PanicOptions = PanicOptions {
+        backtrace_style: BacktraceStyle::Short,
+        backtrace_depth: usize::MAX,
+    };
+
+    /// Set the panic hook to log panics using these options, keeping the
+    /// previous hook to restore it in [`restore_panic_hook`].
+    #[cfg(feature = "log-panic")]
+    fn set_hook(self) {
+        let previous = std::panic::take_hook();
+        *PREVIOUS_PANIC_HOOK
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(previous);
+        std::panic::set_hook(Box::new(move |info| log_panic(info, self)));
+    }
+}
+
+/// Panic hook, as returned by [`std::panic::take_hook`].
+#[cfg(feature = "log-panic")]
+type PanicHook = Box<dyn Fn(&std::panic::PanicHookInfo<'_>) + Sync + Send + 'static>;
+
+/// Panic hook set before the logger was initialised, see
+/// [`PanicOptions::set_hook`].
+#[cfg(feature = "log-panic")]
+static PREVIOUS_PANIC_HOOK: Mutex<Option<PanicHook>> = Mutex::new(None);
+
+/// Restore the panic hook set before the logger was initialised, if any.
+#[cfg(feature = "log-panic")]
+pub(crate) fn restore_panic_hook() {
+    // Can't change the panic hook while panicking.
+    if std::thread::panicking() {
+        return;
+    }
+    let previous = PREVIOUS_PANIC_HOOK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(previous) = previous {
+        std::panic::set_hook(previous);
     }
 }
 
 /// Panic hook that logs the panic using [`log::error!`].
+///
+/// Next to the message the panic is logged with the following key-values:
+/// `panic_message`, `panic_file`, `panic_line`, `thread` and `backtrace`.
 #[cfg(feature = "log-panic")]
-fn log_panic(info: &std::panic::PanicInfo<'_>) {
-    use std::backtrace::Backtrace;
+fn log_panic(info: &std::panic::PanicHookInfo<'_>, options: PanicOptions) {
+    use std::panic::Location;
     use std::thread;
 
+    use log::kv::ToValue;
+
     let mut record = log::Record::builder();
     let thread = thread::current();
     let thread_name = thread.name().unwrap_or("unnamed");
-    let backtrace = Backtrace::force_capture();
+    let payload = info.payload();
+    let message = match payload.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => match payload.downcast_ref::<String>() {
+            Some(message) => &**message,
+            None => "Box<dyn Any>",
+        },
+    };
+    let location = info.location();
+    let file = location.map(Location::file);
+    let line = location.map(Location::line);
+    let backtrace = format_backtrace(options);
 
     let key_values = [
-        ("backtrace", kv::Value::from_display(&backtrace)),
-        ("thread_name", kv::Value::from(thread_name)),
+        ("panic_message", kv::Value::from(message)),
+        ("panic_file", file.to_value()),
+        ("panic_line", line.to_value()),
+        ("thread", kv::Value::from(thread_name)),
+        (
+            "backtrace",
+            kv::Value::from(backtrace.as_deref().unwrap_or("")),
+        ),
     ];
-    let key_values = key_values.as_slice();
+    let key_values = if backtrace.is_some() {
+        key_values.as_slice()
+    } else {
+        &key_values[..key_values.len() - 1]
+    };
 
     let _ = record
         .level(log::Level::Error)
         .target(PANIC_TARGET)
-        .key_values(&key_values);
-
-    if let Some(location) = info.location() {
-        let _ = record
-            .file(Some(location.file()))
-            .line(Some(location.line()));
-    };
+        .key_values(&key_values)
+        .file(file)
+        .line(line);
 
     // Format for {info}: "panicked at '$message', $file:$line:$col".
     log::logger().log(
@@ -190,6 +2156,45 @@ fn log_panic(info: &std::panic::PanicInfo<'_>) {
     );
 }
 
+/// Capture and format a backtrace using `options`, returns `None` if
+/// backtraces are disabled.
+#[cfg(feature = "log-panic")]
+pub(crate) fn format_backtrace(options: PanicOptions) -> Option<String> {
+    use std::backtrace::Backtrace;
+
+    let mut backtrace = match options.backtrace_style {
+        BacktraceStyle::Full => format!("{:#}", Backtrace::force_capture()),
+        BacktraceStyle::Short => Backtrace::force_capture().to_string(),
+        BacktraceStyle::Off => return None,
+    };
+    let len = truncate_backtrace(&backtrace, options.backtrace_depth).len();
+    backtrace.truncate(len);
+    Some(backtrace)
+}
+
+/// Returns the first `depth` frames of the formatted `backtrace`.
+#[cfg(feature = "log-panic")]
+pub(crate) fn truncate_backtrace(backtrace: &str, depth: usize) -> &str {
+    let mut frames = 0;
+    let mut offset = 0;
+    for line in backtrace.split_inclusive('\n') {
+        // Frames start with their number, e.g. `   3: std::panicking::...`,
+        // followed by the optional location on the next line.
+        let is_frame = line
+            .trim_start()
+            .split_once(':')
+            .is_some_and(|(n, _)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+        if is_frame {
+            if frames == depth {
+                break;
+            }
+            frames += 1;
+        }
+        offset += line.len();
+    }
+    &backtrace[..offset]
+}
+
 /// No initial key-values.
 #[derive(Debug)]
 pub struct NoKvs;
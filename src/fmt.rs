@@ -0,0 +1,127 @@
+//! Formatting records the same way as the logger does, see [`write_record`].
+//!
+//! This can be used by other [`Log`] implementations or test harnesses to
+//! create records that are identical to the records written by the logger.
+//!
+//! [`Log`]: log::Log
+
+use std::io::{self, IoSlice, Write};
+use std::thread::LocalKey;
+
+use log::Record;
+
+use crate::config::NoKvs;
+pub use crate::format::Options;
+use crate::format::{BufferPool, Format, Gcloud, Json, LogFmt, BUFS_SIZE};
+use crate::write_all;
+
+// NOTE: the formats expect the buffer to only be used by a single format, e.g.
+// for the position of the timestamp, so each uses its own buffer.
+thread_local! {
+    static LOGFMT_BUFS: BufferPool = const { BufferPool::new() };
+    static JSON_BUFS: BufferPool = const { BufferPool::new() };
+    static GCLOUD_BUFS: BufferPool = const { BufferPool::new() };
+}
+
+/// Write `record` formatted using logfmt to `output`.
+///
+/// This writes the record exactly like the logger does, including the
+/// timestamp (if the *timestamp* feature is enabled), using the formatting
+/// `options`. It doesn't include the key-values added using
+/// [`Config::with_kvs`], as those are not part of the options.
+///
+/// [`Config::with_kvs`]: crate::Config::with_kvs
+///
+/// # Examples
+///
+/// ```
+/// use log::{Level, Record};
+/// use std_logger::fmt::{self, Options};
+///
+/// # fn main() -> std::io::Result<()> {
+/// let kvs = [("user_id", 123)];
+/// let record = Record::builder()
+///     .args(format_args!("Hello world"))
+///     .level(Level::Info)
+///     .target("app")
+///     .module_path_static(Some("app"))
+///     .key_values(&kvs)
+///     .build();
+///
+/// let mut output = Vec::new();
+/// fmt::write_record(&mut output, &record, &Options::new(false))?;
+/// let output = String::from_utf8(output).unwrap();
+/// assert!(output.ends_with("lvl=\"INFO\" msg=\"Hello world\" target=\"app\" module=\"app\" user_id=123\n"));
+/// # Ok(())
+/// # }
+/// ```
+pub fn write_record<W>(output: &mut W, record: &Record, options: &Options) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    logfmt_with(record, options, |bufs| write_all(output, bufs)).unwrap_or_else(nested_error)
+}
+
+/// Same as [`write_record`], but formats `record` using JSON.
+pub fn write_json_record<W>(output: &mut W, record: &Record, options: &Options) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    json_with(record, options, |bufs| write_all(output, bufs)).unwrap_or_else(nested_error)
+}
+
+/// Same as [`write_record`], but formats `record` using JSON for GCP (gcloud).
+pub fn write_gcloud_record<W>(output: &mut W, record: &Record, options: &Options) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    format_with::<Gcloud, _, _>(&GCLOUD_BUFS, record, options, |bufs| {
+        write_all(output, bufs)
+    })
+    .unwrap_or_else(nested_error)
+}
+
+/// Error returned if all buffers are in use, which only happens if formatting
+/// a record (e.g. a `fmt::Display` implementation) formats another record,
+/// which again formats another record.
+fn nested_error() -> io::Result<()> {
+    Err(io::Error::other("formatting records nested too deep"))
+}
+
+/// Formats `record` using logfmt and calls `f` with the formatted buffers.
+/// Returns `None` if all buffers are in use.
+pub(crate) fn logfmt_with<R, F>(record: &Record, options: &Options, f: F) -> Option<R>
+where
+    F: FnOnce(&[IoSlice<'_>]) -> R,
+{
+    format_with::<LogFmt, _, _>(&LOGFMT_BUFS, record, options, f)
+}
+
+/// Formats `record` using JSON and calls `f` with the formatted buffers.
+/// Returns `None` if all buffers are in use.
+pub(crate) fn json_with<R, F>(record: &Record, options: &Options, f: F) -> Option<R>
+where
+    F: FnOnce(&[IoSlice<'_>]) -> R,
+{
+    format_with::<Json, _, _>(&JSON_BUFS, record, options, f)
+}
+
+/// Formats `record` using the format `Fmt` and a buffer from the thread local
+/// `pool` and calls `f` with the formatted buffers.
+fn format_with<Fmt, R, F>(
+    pool: &'static LocalKey<BufferPool>,
+    record: &Record,
+    options: &Options,
+    f: F,
+) -> Option<R>
+where
+    Fmt: Format,
+    F: FnOnce(&[IoSlice<'_>]) -> R,
+{
+    pool.with(|pool| {
+        pool.with(|buf| {
+            let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
+            f(Fmt::format(&mut bufs, buf, record, &NoKvs, options))
+        })
+    })
+}
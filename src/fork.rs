@@ -0,0 +1,34 @@
+//! Handling of `fork(2)`, see the [crate level documentation].
+//!
+//! [crate level documentation]: index.html#forking
+
+use std::sync::Once;
+
+use crate::{batch, full, writer};
+
+/// Register [`after_fork_in_child`] to be called in the child process after
+/// `fork`.
+pub(crate) fn register() {
+    static REGISTER: Once = Once::new();
+    REGISTER.call_once(|| {
+        // SAFETY: `after_fork_in_child` doesn't block or allocate.
+        let _ = unsafe { libc::pthread_atfork(None, None, Some(after_fork_in_child)) };
+    });
+}
+
+/// Called in the child process after `fork`.
+///
+/// The records batched by all threads (only the thread calling `fork` exists
+/// in the child process) and the records buffered because the output was full are
+/// still written by the parent process. To not write them twice they're
+/// discarded in the child process. The writer thread doesn't exist in the
+/// child process, so the child writes its records directly.
+unsafe extern "C" fn after_fork_in_child() {
+    batch::discard();
+    full::discard();
+    writer::forget();
+    #[cfg(feature = "reload")]
+    crate::reload::forget_watcher();
+    #[cfg(feature = "admin")]
+    crate::admin::forget();
+}
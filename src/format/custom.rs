@@ -0,0 +1,51 @@
+//! Custom formatting, see [`Config::format_with`].
+//!
+//! [`Config::format_with`]: crate::Config::format_with
+
+use std::fmt;
+use std::io::IoSlice;
+
+use log::Record;
+
+use crate::format::BufferPool;
+
+/// Function that formats a record, see [`Config::format_with`].
+///
+/// [`Config::format_with`]: crate::Config::format_with
+pub(crate) struct CustomFormat(Box<FormatFn>);
+
+type FormatFn = dyn Fn(&mut Vec<u8>, &Record) + Send + Sync;
+
+impl CustomFormat {
+    pub(crate) fn new<C>(format: C) -> CustomFormat
+    where
+        C: Fn(&mut Vec<u8>, &Record) + Send + Sync + 'static,
+    {
+        CustomFormat(Box::new(format))
+    }
+
+    /// Format `record` and call `write` with the formatted record.
+    pub(crate) fn format<W: FnOnce(&[IoSlice])>(&self, record: &Record, write: W) {
+        // Thread local buffers, like the ones used by the other formats.
+        thread_local! {
+            static BUFS: BufferPool<Vec<u8>> = const { BufferPool::new_custom() };
+        }
+
+        BUFS.with(|pool| {
+            // NOTE: all buffers are only in use if the format function logs a
+            // record, or panics, while formatting, see `log` in the crate
+            // root. The record is dropped to stop the recursion.
+            let _ = pool.with(|buf| {
+                buf.clear();
+                (self.0)(buf, record);
+                write(&[IoSlice::new(buf)]);
+            });
+        });
+    }
+}
+
+impl fmt::Debug for CustomFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CustomFormat").finish_non_exhaustive()
+    }
+}
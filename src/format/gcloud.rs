@@ -9,7 +9,7 @@ use log::{kv, Record};
 #[cfg(feature = "timestamp")]
 use crate::format::format_timestamp;
 use crate::format::json;
-use crate::format::{Buffer, Format, BUFS_SIZE};
+use crate::format::{Buffer, Format, Limit, Options, RawBuf, Truncate, BUFS_SIZE};
 use crate::PANIC_TARGET;
 
 /// Google Cloud Platform structured logging using JSON, following
@@ -23,15 +23,17 @@ impl Format for Gcloud {
         buf: &'b mut Buffer,
         record: &'b Record,
         kvs: &Kvs,
-        add_loc: bool,
+        options: &Options,
     ) -> &'b [IoSlice<'b>] {
         // Write all parts of the buffer that need formatting.
         buf.buf[0] = b'{';
         #[cfg(feature = "timestamp")]
         write_timestamp(buf);
-        write_msg(buf, record.args());
-        write_key_values(buf, record.key_values(), kvs);
-        if add_loc {
+        let mut limit = Limit::new(options);
+        let raw = options.raw(record);
+        write_msg(buf, record.args(), raw, &mut limit);
+        write_key_values(buf, record.key_values(), kvs, options, raw, &mut limit);
+        if options.add_loc {
             write_line(buf, record.line().unwrap_or(0));
         }
 
@@ -61,7 +63,7 @@ impl Format for Gcloud {
         // Optional file, e.g.
         // `","sourceLocation":{"file":"some_file.rs","line":"123"}}`, and a line
         // end.
-        let n = if add_loc {
+        let n = if options.add_loc {
             bufs[10] = IoSlice::new(b",\"sourceLocation\":{\"file\":\"");
             bufs[11] = IoSlice::new(record.file().unwrap_or("??").as_bytes());
             bufs[12] = IoSlice::new(b"\",\"line\":\"");
@@ -85,7 +87,7 @@ const TS_END_INDEX: usize = 1;
 #[inline]
 #[cfg(feature = "timestamp")]
 fn write_timestamp(buf: &mut Buffer) {
-    let _ = buf.buf[TS_END_INDEX];
+    let _ = buf.buf[TS_END_INDEX - 1];
     buf.buf[1] = b'"';
     buf.buf[2] = b't';
     buf.buf[3] = b'i';
@@ -117,17 +119,19 @@ const fn severity(level: log::Level) -> &'static [u8] {
 }
 
 #[inline]
-fn write_msg(buf: &mut Buffer, args: &fmt::Arguments) {
+fn write_msg(buf: &mut Buffer, args: &fmt::Arguments, raw: bool, limit: &mut Limit) {
     buf.buf.truncate(TS_END_INDEX);
-    if let Some(msg) = args.as_str() {
-        json::Buf(&mut buf.buf)
-            .write_str(msg)
-            .unwrap_or_else(|_| unreachable!());
+    if raw {
+        let inner = RawBuf(&mut buf.buf);
+        Truncate { inner, limit }.write_fmt(*args)
+    } else if let Some(msg) = args.as_str() {
+        let inner = json::Buf(&mut buf.buf);
+        Truncate { inner, limit }.write_str(msg)
     } else {
-        json::Buf(&mut buf.buf)
-            .write_fmt(*args)
-            .unwrap_or_else(|_| unreachable!());
+        let inner = json::Buf(&mut buf.buf);
+        Truncate { inner, limit }.write_fmt(*args)
     }
+    .unwrap_or_else(|_| unreachable!());
     buf.indices[0] = buf.buf.len();
 }
 
@@ -137,13 +141,23 @@ fn msg(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_key_values<Kvs: kv::Source>(buf: &mut Buffer, kvs1: &dyn kv::Source, kvs2: Kvs) {
+fn write_key_values<Kvs: kv::Source>(
+    buf: &mut Buffer,
+    kvs1: &dyn kv::Source,
+    kvs2: Kvs,
+    options: &Options,
+    raw: bool,
+    limit: &mut Limit,
+) {
     buf.buf.extend_from_slice(b"\"");
     // TODO: see if we can add to the slice of `IoSlice` using the keys
     // and string values.
-    let mut visitor = json::KeyValueVisitor(&mut buf.buf);
+    let mut visitor = json::KeyValueVisitor::new(&mut buf.buf, options, raw, limit);
     kvs1.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
     kvs2.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
+    if limit.truncated {
+        buf.buf.extend_from_slice(b",\"truncated\":true");
+    }
     buf.indices[1] = buf.buf.len();
 }
 
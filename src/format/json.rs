@@ -5,10 +5,17 @@ use std::io::IoSlice;
 
 use log::kv::{VisitSource, VisitValue};
 use log::{kv, Record};
+#[cfg(feature = "kv-serde")]
+use std_logger_core::structured;
 
 #[cfg(feature = "timestamp")]
 use crate::format::format_timestamp;
-use crate::format::{Buffer, Format, BUFS_SIZE};
+#[cfg(feature = "kv-serde")]
+use crate::format::StructuredValues;
+use crate::format::{
+    duration_millis, is_number, Buffer, Bytes, Format, Limit, Options, RawBuf, Truncate, TypeHint,
+    BUFS_SIZE, MAX_MILLIS,
+};
 
 /// Structured logging using JSON.
 #[allow(missing_debug_implementations)]
@@ -20,15 +27,17 @@ impl Format for Json {
         buf: &'b mut Buffer,
         record: &'b Record,
         kvs: &Kvs,
-        add_loc: bool,
+        options: &Options,
     ) -> &'b [IoSlice<'b>] {
         // Write all parts of the buffer that need formatting.
         buf.buf[0] = b'{';
         #[cfg(feature = "timestamp")]
         write_timestamp(buf);
-        write_msg(buf, record.args());
-        write_key_values(buf, record.key_values(), kvs);
-        if add_loc {
+        let mut limit = Limit::new(options);
+        let raw = options.raw(record);
+        write_msg(buf, record.args(), raw, &mut limit);
+        write_key_values(buf, record.key_values(), kvs, options, raw, &mut limit);
+        if options.add_loc {
             write_line(buf, record.line().unwrap_or(0));
         }
 
@@ -52,7 +61,7 @@ impl Format for Json {
         bufs[9] = IoSlice::new(key_values(buf));
         // Optional file, e.g. `","file":"some_file.rs","line":"123"}`, and a
         // line end.
-        let n = if add_loc {
+        let n = if options.add_loc {
             bufs[10] = IoSlice::new(b",\"file\":\"");
             bufs[11] = IoSlice::new(record.file().unwrap_or("??").as_bytes());
             bufs[12] = IoSlice::new(b"\",\"line\":\"");
@@ -76,7 +85,7 @@ const TS_END_INDEX: usize = 1;
 #[inline]
 #[cfg(feature = "timestamp")]
 fn write_timestamp(buf: &mut Buffer) {
-    let _ = buf.buf[TS_END_INDEX];
+    let _ = buf.buf[TS_END_INDEX - 1];
     buf.buf[1] = b'"';
     buf.buf[2] = b't';
     buf.buf[3] = b'i';
@@ -101,17 +110,19 @@ fn timestamp(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_msg(buf: &mut Buffer, args: &fmt::Arguments) {
+fn write_msg(buf: &mut Buffer, args: &fmt::Arguments, raw: bool, limit: &mut Limit) {
     buf.buf.truncate(TS_END_INDEX);
-    if let Some(msg) = args.as_str() {
-        Buf(&mut buf.buf)
-            .write_str(msg)
-            .unwrap_or_else(|_| unreachable!());
+    if raw {
+        let inner = RawBuf(&mut buf.buf);
+        Truncate { inner, limit }.write_fmt(*args)
+    } else if let Some(msg) = args.as_str() {
+        let inner = Buf(&mut buf.buf);
+        Truncate { inner, limit }.write_str(msg)
     } else {
-        Buf(&mut buf.buf)
-            .write_fmt(*args)
-            .unwrap_or_else(|_| unreachable!());
+        let inner = Buf(&mut buf.buf);
+        Truncate { inner, limit }.write_fmt(*args)
     }
+    .unwrap_or_else(|_| unreachable!());
     buf.indices[0] = buf.buf.len();
 }
 
@@ -121,13 +132,23 @@ fn msg(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_key_values<Kvs: kv::Source>(buf: &mut Buffer, kvs1: &dyn kv::Source, kvs2: Kvs) {
+fn write_key_values<Kvs: kv::Source>(
+    buf: &mut Buffer,
+    kvs1: &dyn kv::Source,
+    kvs2: Kvs,
+    options: &Options,
+    raw: bool,
+    limit: &mut Limit,
+) {
     buf.buf.extend_from_slice(b"\"");
     // TODO: see if we can add to the slice of `IoSlice` using the keys
     // and string values.
-    let mut visitor = KeyValueVisitor(&mut buf.buf);
+    let mut visitor = KeyValueVisitor::new(&mut buf.buf, options, raw, limit);
     kvs1.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
     kvs2.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
+    if limit.truncated {
+        buf.buf.extend_from_slice(b",\"truncated\":true");
+    }
     buf.indices[1] = buf.buf.len();
 }
 
@@ -151,75 +172,191 @@ fn line(buf: &Buffer) -> &[u8] {
 /// Formats key value pairs as a part of an JSON object, in the following
 /// format: `"key":"value"`. For example:
 /// `"user_name":"Thomas","user_id":123,"is_admin":true`.
-pub(super) struct KeyValueVisitor<'b>(pub(super) &'b mut Vec<u8>);
+///
+/// Values of keys with a [`TypeHint`] are formatted according to the hint.
+pub(super) struct KeyValueVisitor<'b> {
+    buf: &'b mut Bytes,
+    options: &'b Options,
+    /// Write string values without escaping them, see [`RawBuf`].
+    raw: bool,
+    /// Limit for the string values, see [`Truncate`].
+    limit: &'b mut Limit,
+    /// Type hint for the value currently being formatted.
+    hint: Option<TypeHint>,
+}
+
+impl<'b> KeyValueVisitor<'b> {
+    pub(super) fn new(
+        buf: &'b mut Bytes,
+        options: &'b Options,
+        raw: bool,
+        limit: &'b mut Limit,
+    ) -> KeyValueVisitor<'b> {
+        KeyValueVisitor {
+            buf,
+            options,
+            raw,
+            limit,
+            hint: None,
+        }
+    }
+
+    /// Writes a number or boolean `value`, quoting it if the value should be
+    /// formatted as a string.
+    fn write_unquoted(&mut self, value: &[u8]) {
+        if self.hint == Some(TypeHint::String) {
+            self.buf.push(b'"');
+            self.buf.extend_from_slice(value);
+            self.buf.push(b'"');
+        } else {
+            self.buf.extend_from_slice(value);
+        }
+    }
+
+    /// Removes the quotes around the string value starting at `start` if the
+    /// value should be, and can be, formatted as a number. Durations are
+    /// replaced by their milliseconds, see [`TypeHint::Duration`].
+    fn unquote_number(&mut self, start: usize) {
+        let value = &self.buf[start + 1..self.buf.len() - 1];
+        let mut buf = [0; MAX_MILLIS];
+        if self.hint == Some(TypeHint::Duration) {
+            if let Some(millis) = duration_millis(value, &mut buf) {
+                self.buf.truncate(start);
+                self.buf.extend_from_slice(millis);
+                return;
+            }
+        }
+        if matches!(self.hint, Some(TypeHint::Number | TypeHint::Duration)) && is_number(value) {
+            let _ = self.buf.pop();
+            let _ = self.buf.remove(start);
+        }
+    }
+}
+
+#[cfg(feature = "kv-serde")]
+impl<'b> KeyValueVisitor<'b> {
+    /// Writes the structured `value` as flattened key-value pairs or as JSON
+    /// value, see [`StructuredValues`].
+    fn visit_structured(&mut self, key: &str, value: &structured::Node) -> Result<(), kv::Error> {
+        match self.options.structured {
+            StructuredValues::Flatten => {
+                let mut result = Ok(());
+                value.flatten(key, &mut |key, value| {
+                    if result.is_ok() {
+                        result = VisitSource::visit_pair(self, kv::Key::from(key), value);
+                    }
+                });
+                result
+            }
+            _ => {
+                self.buf.push(b',');
+                self.buf.push(b'"');
+                let _ = fmt::Write::write_str(&mut Buf(self.buf), key);
+                self.buf.push(b'"');
+                self.buf.push(b':');
+                self.buf.extend_from_slice(value.to_json().as_bytes());
+                Ok(())
+            }
+        }
+    }
+}
 
 impl<'b, 'kvs> VisitSource<'kvs> for KeyValueVisitor<'b> {
     fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
-        self.0.push(b',');
-        self.0.push(b'"');
-        let _ = fmt::Write::write_str(&mut Buf(self.0), key.as_str());
-        self.0.push(b'"');
-        self.0.push(b':');
+        #[cfg(feature = "kv-serde")]
+        if self.options.structured != StructuredValues::Display {
+            if let Some(node) = structured::to_node(&value) {
+                return self.visit_structured(key.as_str(), &node);
+            }
+        }
+
+        self.buf.push(b',');
+        self.buf.push(b'"');
+        let _ = fmt::Write::write_str(&mut Buf(self.buf), key.as_str());
+        self.buf.push(b'"');
+        self.buf.push(b':');
+        self.hint = self.options.type_hint(key.as_str());
         value.visit(self)
     }
 }
 
 impl<'b, 'v> VisitValue<'v> for KeyValueVisitor<'b> {
     fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
-        self.0.push(b'\"');
-        Buf(self.0)
-            .write_fmt(format_args!("{value}"))
-            .unwrap_or_else(|_| unreachable!());
-        self.0.push(b'\"');
+        let start = self.buf.len();
+        self.buf.push(b'\"');
+        let limit = &mut *self.limit;
+        if self.raw {
+            let inner = RawBuf(self.buf);
+            Truncate { inner, limit }.write_fmt(format_args!("{value}"))
+        } else {
+            let inner = Buf(self.buf);
+            Truncate { inner, limit }.write_fmt(format_args!("{value}"))
+        }
+        .unwrap_or_else(|_| unreachable!());
+        self.buf.push(b'\"');
+        self.unquote_number(start);
+        Ok(())
+    }
+
+    fn visit_null(&mut self) -> Result<(), kv::Error> {
+        self.buf.extend_from_slice(b"null");
         Ok(())
     }
 
     fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
         let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
+        self.write_unquoted(itoa.format(value).as_bytes());
         Ok(())
     }
 
     fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
         let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
+        self.write_unquoted(itoa.format(value).as_bytes());
         Ok(())
     }
 
     fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
         let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
+        self.write_unquoted(itoa.format(value).as_bytes());
         Ok(())
     }
 
     fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
         let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
+        self.write_unquoted(itoa.format(value).as_bytes());
         Ok(())
     }
 
     fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
         let mut ryu = ryu::Buffer::new();
-        self.0.extend_from_slice(ryu.format(value).as_bytes());
+        self.write_unquoted(ryu.format(value).as_bytes());
         Ok(())
     }
 
     fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
-        self.0
-            .extend_from_slice(if value { b"true" } else { b"false" });
+        self.write_unquoted(if value { b"true" } else { b"false" });
         Ok(())
     }
 
     fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
-        self.0.push(b'\"');
-        let _ = fmt::Write::write_str(&mut Buf(self.0), value);
-        self.0.push(b'\"');
+        let start = self.buf.len();
+        self.buf.push(b'\"');
+        let limit = &mut *self.limit;
+        let _ = if self.raw {
+            let inner = RawBuf(self.buf);
+            Truncate { inner, limit }.write_str(value)
+        } else {
+            let inner = Buf(self.buf);
+            Truncate { inner, limit }.write_str(value)
+        };
+        self.buf.push(b'\"');
+        self.unquote_number(start);
         Ok(())
     }
 }
 
 /// [`fmt::Write`] implementation that writes escaped JSON strings.
-pub(super) struct Buf<'b>(pub(super) &'b mut Vec<u8>);
+pub(super) struct Buf<'b>(pub(super) &'b mut Bytes);
 
 impl<'b> fmt::Write for Buf<'b> {
     #[inline]
@@ -1,14 +1,18 @@
 //! Logfmt following <https://www.brandur.org/logfmt>.
 
-use std::fmt::{self, Write};
+//!
+//! The formatting itself is done by [`std_logger_core::logfmt`], this only
+//! writes the timestamp and orders the buffers.
+
+use std::fmt;
 use std::io::IoSlice;
 
-use log::kv::{VisitSource, VisitValue};
 use log::{kv, Record};
+use std_logger_core::logfmt;
 
 #[cfg(feature = "timestamp")]
 use crate::format::format_timestamp;
-use crate::format::{Buffer, Format, BUFS_SIZE};
+use crate::format::{Buffer, Format, Limit, Options, BUFS_SIZE};
 
 /// Logfmt following <https://www.brandur.org/logfmt>.
 #[allow(missing_debug_implementations)]
@@ -20,15 +24,25 @@ impl Format for LogFmt {
         buf: &'b mut Buffer,
         record: &'b Record,
         kvs: &Kvs,
-        add_loc: bool,
+        options: &Options,
     ) -> &'b [IoSlice<'b>] {
+        if !options.fields.is_empty() {
+            return format_fields(bufs, buf, record, kvs, options);
+        }
+
         // Write all parts of the buffer that need formatting.
         #[cfg(feature = "timestamp")]
         write_timestamp(buf);
-        write_msg(buf, record.args());
-        write_key_values(buf, record.key_values(), kvs);
-        if add_loc {
-            write_line(buf, record.line().unwrap_or(0));
+        let mut limit = Limit::new(options);
+        let raw = options.raw(record);
+        write_msg(buf, record.args(), raw, options.multi_line, &mut limit);
+        write_key_values(buf, record.key_values(), kvs, options, raw, &mut limit);
+        if options.add_loc {
+            write_line(buf, record.line().unwrap_or(0), options.separate_line);
+        }
+
+        if options.msg_last {
+            return msg_last(bufs, buf, record, options.add_loc);
         }
 
         // Now that we've written the message to our buffer we have to construct it.
@@ -50,7 +64,7 @@ impl Format for LogFmt {
         // Any key value pairs supplied by the user.
         bufs[9] = IoSlice::new(key_values(buf));
         // Optional file, e.g. ` file="some_file:123"`, and a line end.
-        let n = if add_loc {
+        let n = if options.add_loc {
             bufs[10] = IoSlice::new(b" file=\"");
             bufs[11] = IoSlice::new(record.file().unwrap_or("??").as_bytes());
             bufs[12] = IoSlice::new(line(buf));
@@ -64,6 +78,63 @@ impl Format for LogFmt {
     }
 }
 
+/// Same as [`LogFmt::format`], but with the message as the last field, e.g.
+/// `lvl="INFO" target="request" module="stored::http" msg="some message"`.
+fn msg_last<'b>(
+    bufs: &'b mut [IoSlice<'b>; BUFS_SIZE],
+    buf: &'b Buffer,
+    record: &'b Record,
+    add_loc: bool,
+) -> &'b [IoSlice<'b>] {
+    bufs[0] = IoSlice::new(timestamp(buf));
+    bufs[1] = IoSlice::new(b"lvl=\"");
+    bufs[2] = IoSlice::new(record.level().as_str().as_bytes());
+    bufs[3] = IoSlice::new(b"\" target=\"");
+    bufs[4] = IoSlice::new(record.target().as_bytes());
+    bufs[5] = IoSlice::new(b"\" module=\"");
+    bufs[6] = IoSlice::new(record.module_path().unwrap_or("").as_bytes());
+    bufs[7] = IoSlice::new(key_values(buf));
+    let n = if add_loc {
+        bufs[8] = IoSlice::new(b" file=\"");
+        bufs[9] = IoSlice::new(record.file().unwrap_or("??").as_bytes());
+        // Without the line end.
+        let line = line(buf);
+        bufs[10] = IoSlice::new(&line[..line.len() - 1]);
+        11
+    } else {
+        8
+    };
+    bufs[n] = IoSlice::new(b" msg=\"");
+    bufs[n + 1] = IoSlice::new(msg(buf));
+    bufs[n + 2] = IoSlice::new(b"\"\n");
+    &bufs[..n + 3]
+}
+
+/// Same as [`LogFmt::format`], but using the fields (names, order and levels)
+/// set in [`Options::fields`].
+fn format_fields<'b, Kvs: kv::Source>(
+    bufs: &'b mut [IoSlice<'b>; BUFS_SIZE],
+    buf: &'b mut Buffer,
+    record: &'b Record,
+    kvs: &Kvs,
+    options: &Options,
+) -> &'b [IoSlice<'b>] {
+    #[cfg(feature = "timestamp")]
+    let mut ts = [0; 27];
+    #[cfg(feature = "timestamp")]
+    format_timestamp(&mut ts);
+    #[cfg(feature = "timestamp")]
+    let timestamp = Some(&ts[..]);
+    #[cfg(not(feature = "timestamp"))]
+    let timestamp = None;
+
+    // NOTE: not truncating the timestamp as `write_timestamp` expects it.
+    buf.buf.truncate(TS_END_INDEX);
+    logfmt::format_fields(&mut buf.buf, record, kvs, options, timestamp);
+    bufs[0] = IoSlice::new(&buf.buf[TS_END_INDEX..]);
+    &bufs[..1]
+}
+
 /// Index of the end of `ts="..."`.
 #[cfg(feature = "timestamp")]
 const TS_END_INDEX: usize = 33;
@@ -73,7 +144,7 @@ const TS_END_INDEX: usize = 0;
 #[inline]
 #[cfg(feature = "timestamp")]
 fn write_timestamp(buf: &mut Buffer) {
-    let _ = buf.buf[TS_END_INDEX];
+    let _ = buf.buf[TS_END_INDEX - 1];
     buf.buf[0] = b't';
     buf.buf[1] = b's';
     buf.buf[2] = b'=';
@@ -89,17 +160,15 @@ fn timestamp(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_msg(buf: &mut Buffer, args: &fmt::Arguments) {
+fn write_msg(
+    buf: &mut Buffer,
+    args: &fmt::Arguments,
+    raw: bool,
+    multi_line: bool,
+    limit: &mut Limit,
+) {
     buf.buf.truncate(TS_END_INDEX);
-    if let Some(msg) = args.as_str() {
-        Buf(&mut buf.buf)
-            .write_str(msg)
-            .unwrap_or_else(|_| unreachable!());
-    } else {
-        Buf(&mut buf.buf)
-            .write_fmt(*args)
-            .unwrap_or_else(|_| unreachable!());
-    }
+    logfmt::write_msg(&mut buf.buf, args, raw, multi_line, limit);
     buf.indices[0] = buf.buf.len();
 }
 
@@ -109,13 +178,18 @@ fn msg(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_key_values<Kvs: kv::Source>(buf: &mut Buffer, kvs1: &dyn kv::Source, kvs2: Kvs) {
+fn write_key_values<Kvs: kv::Source>(
+    buf: &mut Buffer,
+    kvs1: &dyn kv::Source,
+    kvs2: Kvs,
+    options: &Options,
+    raw: bool,
+    limit: &mut Limit,
+) {
     buf.buf.extend_from_slice(b"\"");
     // TODO: see if we can add to the slice of `IoSlice` using the keys
     // and string values.
-    let mut visitor = KeyValueVisitor(&mut buf.buf);
-    kvs1.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
-    kvs2.visit(&mut visitor).unwrap_or_else(|_| unreachable!());
+    logfmt::write_key_values(&mut buf.buf, kvs1, kvs2, options, raw, limit);
     buf.indices[1] = buf.buf.len();
 }
 
@@ -125,11 +199,12 @@ fn key_values(buf: &Buffer) -> &[u8] {
 }
 
 #[inline]
-fn write_line(buf: &mut Buffer, line: u32) {
-    buf.buf.push(b':');
-    let mut itoa = itoa::Buffer::new();
-    buf.buf.extend_from_slice(itoa.format(line).as_bytes());
-    buf.buf.extend_from_slice(b"\"\n");
+fn write_line(buf: &mut Buffer, line: u32, separate_line: bool) {
+    if separate_line {
+        logfmt::write_line_key(&mut buf.buf, line);
+    } else {
+        logfmt::write_line(&mut buf.buf, line);
+    }
     buf.indices[2] = buf.buf.len();
 }
 
@@ -137,116 +212,3 @@ fn write_line(buf: &mut Buffer, line: u32) {
 fn line(buf: &Buffer) -> &[u8] {
     &buf.buf[buf.indices[1]..buf.indices[2]]
 }
-
-/// Formats key value pairs in the following format: `key="value"`. For example:
-/// `user_name="Thomas" user_id=123 is_admin=true`
-struct KeyValueVisitor<'b>(&'b mut Vec<u8>);
-
-impl<'b, 'kvs> VisitSource<'kvs> for KeyValueVisitor<'b> {
-    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
-        self.0.push(b' ');
-        Buf(self.0).extend_from_slice(key.as_str().as_bytes());
-        self.0.push(b'=');
-        value.visit(self)
-    }
-}
-
-impl<'b, 'v> VisitValue<'v> for KeyValueVisitor<'b> {
-    fn visit_any(&mut self, value: kv::Value) -> Result<(), kv::Error> {
-        self.0.push(b'\"');
-        Buf(self.0)
-            .write_fmt(format_args!("{value}"))
-            .unwrap_or_else(|_| unreachable!());
-        self.0.push(b'\"');
-        Ok(())
-    }
-
-    fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
-        let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
-        Ok(())
-    }
-
-    fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
-        let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
-        Ok(())
-    }
-
-    fn visit_u128(&mut self, value: u128) -> Result<(), kv::Error> {
-        let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
-        Ok(())
-    }
-
-    fn visit_i128(&mut self, value: i128) -> Result<(), kv::Error> {
-        let mut itoa = itoa::Buffer::new();
-        self.0.extend_from_slice(itoa.format(value).as_bytes());
-        Ok(())
-    }
-
-    fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
-        let mut ryu = ryu::Buffer::new();
-        self.0.extend_from_slice(ryu.format(value).as_bytes());
-        Ok(())
-    }
-
-    fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
-        self.0
-            .extend_from_slice(if value { b"true" } else { b"false" });
-        Ok(())
-    }
-
-    fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
-        self.0.push(b'\"');
-        Buf(self.0).extend_from_slice(value.as_bytes());
-        self.0.push(b'\"');
-        Ok(())
-    }
-}
-
-/// [`fmt::Write`] implementation that writes escaped quotes.
-struct Buf<'b>(&'b mut Vec<u8>);
-
-impl<'b> Buf<'b> {
-    fn extend_from_slice(&mut self, bytes: &[u8]) {
-        for &b in bytes {
-            if b == b'"' {
-                self.0.push(b'\\');
-            }
-            self.0.push(b);
-        }
-    }
-}
-
-impl<'b> fmt::Write for Buf<'b> {
-    #[inline]
-    fn write_str(&mut self, string: &str) -> fmt::Result {
-        for c in string.chars() {
-            let _ = self.write_char(c);
-        }
-        Ok(())
-    }
-
-    #[inline]
-    fn write_char(&mut self, c: char) -> fmt::Result {
-        // See RFC 8259, section 7
-        // <https://datatracker.ietf.org/doc/html/rfc8259#section-7>.
-        let mut bytes = [0; 8];
-        let bytes: &[u8] = match c {
-            // Quotation mark.
-            '"' => b"\\\"",
-            // Reverse solidus.
-            '\\' => b"\\\\",
-            // Line feed.
-            '\u{000A}' => b"\\n",
-            // Carriage return.
-            '\u{000D}' => b"\\r",
-            // Tab.
-            '\u{0009}' => b"\\t",
-            _ => c.encode_utf8(&mut bytes).as_bytes(),
-        };
-        self.0.extend_from_slice(bytes);
-        Ok(())
-    }
-}
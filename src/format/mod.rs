@@ -1,7 +1,19 @@
+#[cfg(feature = "timestamp")]
+use std::cell::Cell;
+use std::cell::RefCell;
+use std::fmt;
 use std::io::IoSlice;
+use std::ops::{Deref, DerefMut};
+#[cfg(feature = "timestamp")]
+use std::time::{Duration, SystemTime};
 
 use log::{kv, Record};
 
+pub(crate) use std_logger_core::{
+    duration_millis, is_number, Limit, Output, RawBuf, Truncate, MAX_MILLIS,
+};
+pub use std_logger_core::{Field, Options, StructuredValues, TypeHint};
+
 pub(crate) mod logfmt;
 pub(crate) use logfmt::LogFmt;
 
@@ -11,6 +23,9 @@ pub(crate) use json::Json;
 pub(crate) mod gcloud;
 pub(crate) use gcloud::Gcloud;
 
+pub(crate) mod custom;
+pub(crate) use custom::CustomFormat;
+
 /// Trait that defines how to format a [`log::Record`].
 pub trait Format {
     /// Formats a log `record`.
@@ -19,13 +34,13 @@ pub trait Format {
     /// it resets itself. The returned slices is based on `bufs`, which is used
     /// to order the writable buffers.
     ///
-    /// If `add_loc` is `true` the file and line are added.
+    /// See [`Options`] for the formatting options.
     fn format<'b, Kvs: kv::Source>(
         bufs: &'b mut [IoSlice<'b>; BUFS_SIZE],
         buf: &'b mut Buffer,
         record: &'b Record,
         kvs: &Kvs,
-        add_loc: bool,
+        options: &Options,
     ) -> &'b [IoSlice<'b>];
 }
 
@@ -41,24 +56,242 @@ const N_INDICES: usize = 3;
 /// Formatting buffer.
 #[derive(Debug)]
 pub struct Buffer {
-    buf: Vec<u8>,
+    buf: Bytes,
     indices: [usize; N_INDICES],
 }
 
 impl Buffer {
     /// Create a new format `Buffer`.
-    pub(crate) fn new() -> Buffer {
+    pub(crate) const fn new() -> Buffer {
         Buffer {
-            buf: vec![0; 2048],
+            buf: Bytes::new(),
             indices: [0; N_INDICES],
         }
     }
 }
 
+/// Number of buffers in a [`BufferPool`].
+const POOL_SIZE: usize = 2;
+
+/// Pool of format buffers, used as thread local to format records.
+///
+/// Formatting a record can log another record, e.g. when a `fmt::Display`
+/// implementation panics and the panic is logged (with the *log-panic*
+/// feature), which needs a second buffer while the first one is still in use.
+/// Using a pool, instead of creating a new buffer, means we don't use another
+/// 2 KB of stack (or more) in the panic path and bounds the recursion if the
+/// record being formatted logs itself.
+///
+/// Custom formats (see [`Config::format_with`]) use a pool of `Vec<u8>`
+/// instead.
+///
+/// [`Config::format_with`]: crate::Config::format_with
+pub(crate) struct BufferPool<B = Buffer>([RefCell<B>; POOL_SIZE]);
+
+impl BufferPool {
+    /// Create a new pool.
+    pub(crate) const fn new() -> BufferPool {
+        BufferPool([const { RefCell::new(Buffer::new()) }; POOL_SIZE])
+    }
+}
+
+impl BufferPool<Vec<u8>> {
+    /// Create a new pool for custom formats.
+    pub(crate) const fn new_custom() -> BufferPool<Vec<u8>> {
+        BufferPool([const { RefCell::new(Vec::new()) }; POOL_SIZE])
+    }
+}
+
+impl<B> BufferPool<B> {
+    /// Call `f` with the first buffer not in use. Returns `None` if all
+    /// buffers are in use, i.e. if we're formatting records nested more than
+    /// [`POOL_SIZE`] deep.
+    pub(crate) fn with<R, F>(&self, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut B) -> R,
+    {
+        let mut buf = self.0.iter().find_map(|buf| buf.try_borrow_mut().ok())?;
+        Some(f(&mut buf))
+    }
+}
+
+/// Size of the inline storage of [`Bytes`].
+const INLINE_SIZE: usize = 2048;
+
+/// Byte buffer used in [`Buffer`].
+///
+/// Typical records are formatted into a fixed-size array, which avoids heap
+/// (re)allocations. Only records that don't fit into it spill over into a heap
+/// allocated `Vec`, which is kept around to be reused for the next large
+/// record. Truncating the buffer (which happens at the start of formatting a
+/// record) moves it back to the inline storage.
+pub(crate) struct Bytes {
+    inline: [u8; INLINE_SIZE],
+    /// Length of `inline`, only used if `spilled` is false.
+    len: usize,
+    /// Used instead of `inline` if `spilled` is true.
+    heap: Vec<u8>,
+    spilled: bool,
+}
+
+impl Bytes {
+    /// Create a new buffer, filled with zeros so the timestamp can be written
+    /// by index.
+    const fn new() -> Bytes {
+        Bytes {
+            inline: [0; INLINE_SIZE],
+            len: INLINE_SIZE,
+            heap: Vec::new(),
+            spilled: false,
+        }
+    }
+
+    #[inline]
+    pub(crate) fn push(&mut self, b: u8) {
+        if !self.spilled && self.len < INLINE_SIZE {
+            self.inline[self.len] = b;
+            self.len += 1;
+        } else {
+            self.extend_from_slice(&[b]);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn extend_from_slice(&mut self, bytes: &[u8]) {
+        if self.spilled {
+            self.heap.extend_from_slice(bytes);
+            return;
+        }
+
+        let end = self.len + bytes.len();
+        if end <= INLINE_SIZE {
+            self.inline[self.len..end].copy_from_slice(bytes);
+            self.len = end;
+        } else {
+            self.spill(bytes);
+        }
+    }
+
+    /// Move the inline bytes and `bytes` to the heap.
+    #[cold]
+    fn spill(&mut self, bytes: &[u8]) {
+        self.heap.clear();
+        self.heap.reserve(self.len + bytes.len());
+        self.heap.extend_from_slice(&self.inline[..self.len]);
+        self.heap.extend_from_slice(bytes);
+        self.spilled = true;
+    }
+
+    #[inline]
+    pub(crate) fn truncate(&mut self, len: usize) {
+        if !self.spilled {
+            self.len = self.len.min(len);
+        } else if len <= INLINE_SIZE {
+            let len = self.heap.len().min(len);
+            self.inline[..len].copy_from_slice(&self.heap[..len]);
+            self.len = len;
+            self.heap.clear();
+            self.spilled = false;
+        } else {
+            self.heap.truncate(len);
+        }
+    }
+
+    #[inline]
+    pub(crate) fn pop(&mut self) -> Option<u8> {
+        if self.spilled {
+            self.heap.pop()
+        } else if self.len == 0 {
+            None
+        } else {
+            self.len -= 1;
+            Some(self.inline[self.len])
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `index` is out of bounds.
+    #[inline]
+    pub(crate) fn remove(&mut self, index: usize) -> u8 {
+        if self.spilled {
+            return self.heap.remove(index);
+        }
+
+        let b = self[index];
+        self.inline.copy_within(index + 1..self.len, index);
+        self.len -= 1;
+        b
+    }
+}
+
+impl Deref for Bytes {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        if self.spilled {
+            &self.heap
+        } else {
+            &self.inline[..self.len]
+        }
+    }
+}
+
+impl DerefMut for Bytes {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        if self.spilled {
+            &mut self.heap
+        } else {
+            &mut self.inline[..self.len]
+        }
+    }
+}
+
+impl Output for Bytes {
+    #[inline]
+    fn extend_from_slice(&mut self, bytes: &[u8]) {
+        Bytes::extend_from_slice(self, bytes);
+    }
+
+    #[inline]
+    fn push(&mut self, b: u8) {
+        Bytes::push(self, b);
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Option<u8> {
+        Bytes::pop(self)
+    }
+
+    #[inline]
+    fn remove(&mut self, index: usize) -> u8 {
+        Bytes::remove(self, index)
+    }
+
+    #[inline]
+    fn as_bytes(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Bytes")
+            .field("len", &self.len())
+            .field("spilled", &self.spilled)
+            .finish()
+    }
+}
+
 /// Format the timestamp in the following format:
 /// `YYYY-MM-DDThh:mm:ss.SSSSSSZ`. For example:
 /// `2020-12-31T11:00:01.743357Z`.
 ///
+/// The date and time up to the seconds is cached per thread, so only the
+/// microseconds are formatted for all but the first record logged in a second.
+///
 /// # Notes
 ///
 /// The `buf` must come from [`Buffer::ts`] as it only overwrites the date, not
@@ -66,8 +299,34 @@ impl Buffer {
 #[inline]
 #[cfg(feature = "timestamp")]
 fn format_timestamp(buf: &mut [u8]) {
+    thread_local! {
+        /// Seconds since the Unix epoch and the formatted
+        /// `YYYY-MM-DDThh:mm:ss` of the last timestamp.
+        static CACHE: Cell<(u64, [u8; 19])> = const { Cell::new((u64::MAX, [0; 19])) };
+    }
+
     let _ = buf[26];
-    let timestamp = crate::timestamp::Timestamp::now();
+    let now = crate::clock::now();
+    let diff = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_else(|_| Duration::new(0, 0));
+    let (secs, mut date_time) = CACHE.get();
+    if secs != diff.as_secs() {
+        format_date_time(&mut date_time, now);
+        CACHE.set((diff.as_secs(), date_time));
+    }
+    buf[0..19].copy_from_slice(&date_time);
+    buf[19] = b'.';
+    let mut itoa = itoa::Buffer::new();
+    zero_pad6(&mut buf[20..], itoa.format(diff.subsec_micros()).as_bytes());
+    buf[26] = b'Z';
+}
+
+/// Format the date and time of `time` in the following format:
+/// `YYYY-MM-DDThh:mm:ss`.
+#[cfg(feature = "timestamp")]
+fn format_date_time(buf: &mut [u8; 19], time: SystemTime) {
+    let timestamp = crate::timestamp::Timestamp::from(time);
     let mut itoa = itoa::Buffer::new();
     buf[0..4].copy_from_slice(itoa.format(timestamp.year).as_bytes());
     buf[4] = b'-';
@@ -80,9 +339,6 @@ fn format_timestamp(buf: &mut [u8]) {
     zero_pad2(&mut buf[14..], itoa.format(timestamp.min).as_bytes());
     buf[16] = b':';
     zero_pad2(&mut buf[17..], itoa.format(timestamp.sec).as_bytes());
-    buf[19] = b'.';
-    zero_pad6(&mut buf[20..], itoa.format(timestamp.micro).as_bytes());
-    buf[26] = b'Z';
 }
 
 #[inline]
@@ -0,0 +1,91 @@
+//! Durability of file sinks, see [`SyncPolicy`].
+
+use std::fs::File;
+use std::io::{self, IoSlice};
+use std::num::NonZeroU32;
+
+use crate::{write_all, AUDIT_TARGET};
+
+/// When to sync (`fsync`) a file sink to disk, see [`Config::add_file_sink`].
+///
+/// Syncing makes sure the records are written to disk before the logging call
+/// returns, so they survive a crash of the machine, at the cost of
+/// throughput. Records that are not yet synced are synced when the logger is
+/// flushed, unless the policy is [`SyncPolicy::Never`].
+///
+/// [`Config::add_file_sink`]: crate::Config::add_file_sink
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum SyncPolicy {
+    /// Never sync the file, leaving it to the OS. This is the default.
+    #[default]
+    Never,
+    /// Sync the file after every `n` records.
+    Every(NonZeroU32),
+    /// Sync the file after each audit record (see [`AUDIT_TARGET`]), which
+    /// also syncs the records written before it.
+    Audit,
+}
+
+impl SyncPolicy {
+    /// Returns `true` if the file should be synced after writing a record
+    /// with `target`, with `unsynced` records written (including this one)
+    /// since the last sync.
+    pub(crate) fn should_sync(self, target: &str, unsynced: u32) -> bool {
+        match self {
+            SyncPolicy::Never => false,
+            SyncPolicy::Every(n) => unsynced >= n.get(),
+            SyncPolicy::Audit => target == AUDIT_TARGET,
+        }
+    }
+}
+
+/// File sink synced following a [`SyncPolicy`].
+#[derive(Debug)]
+pub(crate) struct SyncedFile {
+    file: File,
+    policy: SyncPolicy,
+    /// Number of records written since the last sync.
+    unsynced: u32,
+}
+
+impl SyncedFile {
+    pub(crate) const fn new(file: File, policy: SyncPolicy) -> SyncedFile {
+        SyncedFile {
+            file,
+            policy,
+            unsynced: 0,
+        }
+    }
+
+    /// Write the formatted record with `target` in `bufs`, syncing the file if
+    /// the policy requires it.
+    pub(crate) fn write(&mut self, target: &str, bufs: &[IoSlice]) -> io::Result<()> {
+        write_all(&mut self.file, bufs)?;
+        self.unsynced = self.unsynced.saturating_add(1);
+        if self.policy.should_sync(target, self.unsynced) {
+            self.sync()?;
+        }
+        Ok(())
+    }
+
+    /// Sync the records written since the last sync, unless the policy is
+    /// [`SyncPolicy::Never`].
+    pub(crate) fn flush(&mut self) -> io::Result<()> {
+        if self.policy == SyncPolicy::Never {
+            return Ok(());
+        }
+        self.sync()
+    }
+
+    /// Sync the records written since the last sync, if any.
+    fn sync(&mut self) -> io::Result<()> {
+        if self.unsynced != 0 {
+            // The file is only appended to, so syncing the data (and the size)
+            // is enough.
+            self.file.sync_data()?;
+            self.unsynced = 0;
+        }
+        Ok(())
+    }
+}
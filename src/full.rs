@@ -0,0 +1,214 @@
+//! Handling of full outputs, see [`Config::with_full_policy`].
+//!
+//! [`Config::with_full_policy`]: crate::Config::with_full_policy
+
+use std::io::{self, IoSlice};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+#[cfg(all(unix, not(test)))]
+use crate::format::BUFS_SIZE;
+#[cfg(test)]
+use crate::test_instruments::is_full;
+use crate::{request_fd, stderr, write_all, write_requests};
+
+/// What to do when standard out or error is full, i.e. when writing a record
+/// would block, see [`Config::with_full_policy`].
+///
+/// This happens when the process reading the output, e.g. a log collector that
+/// is throttled, can't keep up. The output is considered full if it can't
+/// accept any bytes without blocking, which is only detected on Unix, on other
+/// platforms the writes always block.
+///
+/// Batches (see [`Config::with_batch`]) are written following this policy like
+/// a single record, sinks (see [`Config::add_sink`]) always block.
+///
+/// [`Config::with_full_policy`]: crate::Config::with_full_policy
+/// [`Config::with_batch`]: crate::Config::with_batch
+/// [`Config::add_sink`]: crate::Config::add_sink
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum FullPolicy {
+    /// Block until the record is written, the default.
+    Block,
+    /// Drop the record, see [`dropped_records`].
+    Drop,
+    /// Buffer records in memory, up to the given number of bytes (per output).
+    /// The buffered records are written before the next record once the output
+    /// is no longer full. If the buffer is full the record is dropped, see
+    /// [`dropped_records`].
+    Buffer(usize),
+}
+
+/// Number of records dropped, see [`dropped_records`].
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the number of records dropped because standard out or error was
+/// full, see [`FullPolicy`], because the queue of the writer thread was full,
+/// see [`Config::with_writer_thread`], or because a network sink couldn't send
+/// them (only with the *net* feature).
+///
+/// [`Config::with_writer_thread`]: crate::Config::with_writer_thread
+pub fn dropped_records() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+/// Count a dropped record, see [`dropped_records`].
+pub(crate) fn add_dropped() {
+    let _ = DROPPED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Buffered records for standard out and error, see [`FullPolicy::Buffer`].
+static STDOUT_BUF: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+static STDERR_BUF: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+/// Write the formatted record in `bufs` to standard out (or the file descriptor
+/// for requests) if `to_stdout` is true, or standard error otherwise, following
+/// `policy`.
+pub(crate) fn write(to_stdout: bool, bufs: &[IoSlice], policy: FullPolicy) -> io::Result<()> {
+    let fd = if to_stdout {
+        request_fd().unwrap_or(1)
+    } else {
+        2
+    };
+    match policy {
+        FullPolicy::Block => write_to(to_stdout, bufs),
+        FullPolicy::Drop if is_full(fd) => {
+            add_dropped();
+            Ok(())
+        }
+        FullPolicy::Drop => write_to(to_stdout, bufs),
+        FullPolicy::Buffer(limit) => {
+            let buf = if to_stdout { &STDOUT_BUF } else { &STDERR_BUF };
+            let mut buf = buf.lock().unwrap_or_else(PoisonError::into_inner);
+            if is_full(fd) {
+                let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+                if buf.len() + len <= limit {
+                    for b in bufs {
+                        buf.extend_from_slice(b);
+                    }
+                } else {
+                    add_dropped();
+                }
+                return Ok(());
+            }
+            if !buf.is_empty() {
+                write_to(to_stdout, &[IoSlice::new(&buf)])?;
+                buf.clear();
+            }
+            write_to(to_stdout, bufs)
+        }
+    }
+}
+
+/// Write the records buffered because the output was full, blocking until
+/// they're written, see [`FullPolicy::Buffer`].
+pub(crate) fn flush() -> io::Result<()> {
+    for (to_stdout, buf) in [(true, &STDOUT_BUF), (false, &STDERR_BUF)] {
+        let mut buf = buf.lock().unwrap_or_else(PoisonError::into_inner);
+        if !buf.is_empty() {
+            write_to(to_stdout, &[IoSlice::new(&buf)])?;
+            buf.clear();
+        }
+    }
+    Ok(())
+}
+
+/// Discard the records buffered because the output was full, without writing
+/// them.
+///
+/// If another thread holds the lock on a buffer it's left as is, as the lock
+/// will never be released when called after `fork`.
+#[cfg(unix)]
+pub(crate) fn discard() {
+    for buf in [&STDOUT_BUF, &STDERR_BUF] {
+        if let Ok(mut buf) = buf.try_lock() {
+            buf.clear();
+        }
+    }
+}
+
+fn write_to(to_stdout: bool, bufs: &[IoSlice]) -> io::Result<()> {
+    if to_stdout {
+        write_requests(bufs)
+    } else if UNLOCKED_STDERR.load(Ordering::Relaxed) {
+        write_unlocked_stderr(bufs)
+    } else {
+        write_all(&mut stderr(), bufs)
+    }
+}
+
+/// Whether to write to standard error without holding the lock, see
+/// [`Config::with_unlocked_stderr`].
+///
+/// [`Config::with_unlocked_stderr`]: crate::Config::with_unlocked_stderr
+static UNLOCKED_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Set whether to write to standard error without holding the lock.
+pub(crate) fn set_unlocked_stderr(enable: bool) {
+    UNLOCKED_STDERR.store(enable, Ordering::Relaxed);
+}
+
+/// Write `bufs` to standard error using a single system call, without holding
+/// the lock on it. Falls back to writing (the remainder) while holding the lock
+/// if the record is larger than `PIPE_BUF` or on partial writes.
+#[cfg(all(unix, not(test)))]
+fn write_unlocked_stderr(bufs: &[IoSlice]) -> io::Result<()> {
+    use std::fs::File;
+    use std::io::Write;
+    use std::mem::ManuallyDrop;
+    use std::os::unix::io::FromRawFd;
+
+    let len: usize = bufs.iter().map(|buf| buf.len()).sum();
+    #[allow(clippy::unnecessary_cast)] // Not a `usize` on all platforms.
+    if len > libc::PIPE_BUF as usize {
+        return write_all(&mut stderr().lock(), bufs);
+    }
+
+    // SAFETY: standard error is open for the lifetime of the process, we don't
+    // close it as the `File` isn't dropped.
+    let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(libc::STDERR_FILENO) });
+    match file.write_vectored(bufs) {
+        Ok(n) if n == len => Ok(()),
+        Ok(0) => Err(io::ErrorKind::WriteZero.into()),
+        Ok(n) => {
+            let mut storage = [IoSlice::new(&[]); BUFS_SIZE];
+            storage[..bufs.len()].copy_from_slice(bufs);
+            let mut bufs = &mut storage[..bufs.len()];
+            IoSlice::advance_slices(&mut bufs, n);
+            write_all(&mut stderr().lock(), bufs)
+        }
+        Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {
+            write_all(&mut stderr().lock(), bufs)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// Write `bufs` to standard error, which can't be done without the lock on
+/// this platform.
+#[cfg(any(not(unix), test))]
+fn write_unlocked_stderr(bufs: &[IoSlice]) -> io::Result<()> {
+    write_all(&mut stderr(), bufs)
+}
+
+/// Returns `true` if writing to file descriptor `fd` would block.
+#[cfg(all(unix, not(test)))]
+fn is_full(fd: i32) -> bool {
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    // SAFETY: `pollfd` is valid for the duration of the call.
+    let n = unsafe { libc::poll(&mut pollfd, 1, 0) };
+    // On errors, e.g. if the descriptor is closed, we let the write fail.
+    n == 0
+}
+
+/// Returns `true` if writing to file descriptor `fd` would block, which can't
+/// be detected on this platform.
+#[cfg(all(not(unix), not(test)))]
+fn is_full(_fd: i32) -> bool {
+    false
+}
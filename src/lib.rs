@@ -43,6 +43,11 @@
 //! Note: the timestamp is not printed when the *timestamp* feature is not
 //! enabled, this feature is enabled by default, see [Timestamp feature] below.
 //!
+//! The logfmt formatting is also available without the standard library, e.g.
+//! for embedded targets, in the [std-logger-core] crate.
+//!
+//! [std-logger-core]: https://crates.io/crates/std-logger-core
+//!
 //! ### JSON (NDJSON)
 //!
 //! Formatting using JSON uses one object per limit, which looks like the
@@ -122,9 +127,28 @@
 //! If none of these environment variables are found it will default to an
 //! information severity.
 //!
+//! For compatibility with the [env_logger] crate the `RUST_LOG` variable is
+//! used if none of the variables above (including `LOG_TARGET`) are set. It
+//! supports the directives of env_logger, e.g. `RUST_LOG=warn,my_crate=debug`
+//! logs debug messages for `my_crate` and warnings for all other targets, see
+//! [`EnvMeaning::Directives`].
+//!
+//! ```bash
+//! ## In your shell of your choice:
+//!
+//! ## Enables debug logging for `my_crate`, only logging `my_crate`.
+//! $ RUST_LOG=my_crate=debug ./my_binary
+//! ```
+//!
+//! Frameworks can use their own environment variables, e.g. `APP_VERBOSITY=3`,
+//! using [`Config::with_env_vars`] or [`init_from_env`]. Command line tools
+//! can set the severity using their `--quiet` and `--verbose` flags, using
+//! [`Config::with_verbosity`].
+//!
 //! In addition to these runtime filters the [log] crate provides [compile time
 //! filters] which allows you to filter log messages at compile time.
 //!
+//! [env_logger]: https://docs.rs/env_logger
 //! [log]: mod@log
 //! [compile time filters]: mod@log#compile-time-filters
 //!
@@ -135,6 +159,14 @@
 //! standard out, rather then standard error. This allows for separate
 //! processing of error messages and request logs.
 //!
+//! On Unix the requests can also be written to another (inherited) file
+//! descriptor, e.g. a pipe for access logs passed by a supervisor, by setting
+//! the `REQUEST_LOG_FD` environment variable (e.g. `REQUEST_LOG_FD=3`) or
+//! using [`Config::with_request_fd`].
+//!
+//! Other targets can be written to their own output as well, e.g. a separate
+//! output for gRPC access logs, using [`Config::route_target`].
+//!
 //! ```
 //! use std_logger::request;
 //!
@@ -143,6 +175,23 @@
 //! # }
 //! ```
 //!
+//! # Logging queries
+//!
+//! Similar to requests the [`query`] macro can be used to log (SQL) queries,
+//! using the [`QUERY_TARGET`] target. It can optionally normalize the query,
+//! replacing all values with `?`, and log the duration and number of rows of
+//! the query.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use std_logger::query;
+//!
+//! # fn main() {
+//! query!(normalize: "SELECT * FROM users WHERE id = 123", duration = Duration::from_millis(2), rows = 1);
+//! # }
+//! ```
+//!
 //!
 //! # Limiting logging targets
 //!
@@ -163,22 +212,73 @@
 //! ## Multiple log targets are also supported by separating the values by a comma.
 //! $ LOG_TARGET=my_crate::my_module,my_crate::my_other_module ./my_binary
 //!
+//! ## A `*` matches any single module, e.g. the `db` module in all modules of
+//! ## your crate.
+//! $ LOG_TARGET=my_crate::*::db ./my_binary
+//!
+//! ## Prefix the target with `=` to only log the module itself, not its
+//! ## sub-modules.
+//! $ LOG_TARGET==my_crate::my_module ./my_binary
+//!
 //! ## Very useful in combination with trace severity to get all messages you
 //! ## want, but filter out the messages for crates you're not interested in.
 //! $ LOG_LEVEL=trace LOG_TARGET=my_crate::my_module ./my_binary
 //! ```
 //!
-//! Note that [requests] and panics (with [target="panic"]) are always logged.
+//! Targets are matched per module, `LOG_TARGET=my_crate` matches the target
+//! `my_crate` and `my_crate::my_module`, but not `my_crate_extras`.
+//!
+//! Note that [requests] and panics (with [target="panic"]) are always logged,
+//! this can be changed using [`Config::with_always_logged_targets`].
 //!
 //! [requests]: index.html#logging-requests
 //! [target="panic"]: PANIC_TARGET
 //!
 //!
+//! # Initialising more than once
+//!
+//! Only the first initialisation of the logger, e.g. using [`Config::init`],
+//! sets the logger. Initialising the logger again, e.g. a library doing so
+//! defensively, does nothing, keeping the configuration of the first
+//! initialisation. [`is_initialised`] can be used to check if the logger is
+//! initialised. Initialising the logger when another logger (not from this
+//! crate) is set fails.
+//!
+//!
+//! # Forking
+//!
+//! After `fork(2)` only the thread that called `fork` exists in the child
+//! process. On Unix the records batched by all threads (see
+//! [`Config::with_batch`]) and the records buffered because the output was
+//! full (see [`FullPolicy::Buffer`]) are discarded in the child process, as
+//! they're written by the parent process. The same goes for the records queued
+//! for the writer thread (see [`Config::with_writer_thread`]), which doesn't
+//! exist in the child process, the child writes its records directly.
+//!
+//! If another thread was writing a record to an output at the time of the
+//! `fork`, the lock on that output is never released in the child process,
+//! blocking the child if it logs to the same output. To avoid this fork before
+//! starting other threads, or only call async-signal-safe functions (e.g.
+//! `exec`) in the child process, as required by POSIX.
+//!
+//!
 //! # Crate features
 //!
-//! This crate has three features:
+//! This crate has fifteen features:
 //! * *timestamp*, enabled by default.
 //! * *log-panic*, enabled by default.
+//! * *service*, disabled by default.
+//! * *net*, disabled by default.
+//! * *test*, disabled by default.
+//! * *tracing*, disabled by default.
+//! * *kv-serde*, disabled by default.
+//! * *redact*, disabled by default.
+//! * *wasm*, disabled by default.
+//! * *reload*, disabled by default.
+//! * *admin*, disabled by default.
+//! * *gzip*, disabled by default.
+//! * *zstd*, disabled by default.
+//! * *tokio*, disabled by default.
 //! * *nightly*, disabled by default.
 //!
 //!
@@ -192,7 +292,9 @@
 //!
 //! This feature uses [`SystemTime`] as time source, which **is not monotonic**.
 //! This means that a log message created after an *earlier* log message can
-//! have a timestamp **before** the earlier created log message.
+//! have a timestamp **before** the earlier created log message. A different
+//! time source, e.g. a cheaper coarse clock or a fixed time in tests, can be
+//! set using [`Config::with_clock`].
 //!
 //! [`SystemTime`]: std::time::SystemTime
 //!
@@ -202,10 +304,19 @@
 //! The *log-panic* feature will log all panics using the `error` severity,
 //! rather then using the default panic handler. It will log the panic message
 //! as well as the location and a backtrace, see the log output below for an
-//! example (this example doesn't include a timestamp).
+//! example (this example doesn't include a timestamp and is logged using
+//! [`Config::with_multi_line_values`], by default the new lines in the
+//! backtrace are escaped as `\n`).
+//!
+//! Next to the message the panic message, file, line and thread are logged as
+//! separate key-values (`panic_message`, `panic_file`, `panic_line` and
+//! `thread`), which makes it easy to group panics by location. The number of
+//! frames in the backtrace can be limited using
+//! [`Config::with_panic_backtrace_depth`] and the style (or disabling the
+//! backtrace) set using [`Config::with_panic_backtraces`].
 //!
 //! ```log
-//! lvl="ERROR" msg="thread 'main' panicked at 'oops', examples/panic.rs:24" target="panic" module="" backtrace="
+//! lvl="ERROR" msg="thread 'main' panicked at 'oops', examples/panic.rs:24" target="panic" module="" panic_message="oops" panic_file="examples/panic.rs" panic_line=24 thread="main" backtrace="
 //! stack backtrace:
 //!    0:        0x106ba8f74 - backtrace::backtrace::trace<closure>
 //!                         at backtrace-0.3.2/src/backtrace/mod.rs:42
@@ -231,6 +342,162 @@
 //! prefixed with a timestamp as described in the [Timestamp feature].
 //!
 //!
+//! ## Service feature
+//!
+//! The *service* feature enables the `service` module, which combines request
+//! ids, adding the request id to all records logged while handling a request,
+//! access logging and catching panics for (HTTP) services. It can be used with
+//! a single call to `service::handle` per request.
+//!
+//!
+//! ## Net feature
+//!
+//! The *net* feature enables the `net` module, which contains sinks that send
+//! the records to a remote host over TCP or UDP, e.g. to Logstash, Vector or
+//! Fluent Bit. These can be added to the logger using [`Config::add_sink`].
+//!
+//!
+//! ## Test feature
+//!
+//! The *test* feature enables the `test` module, which captures the records
+//! logged on the current thread so that tests can assert on them, e.g. that a
+//! specific message was logged. The captured records are parsed using the
+//! [std-logger-parser] crate.
+//!
+//! [std-logger-parser]: https://crates.io/crates/std-logger-parser
+//!
+//!
+//! ## Tracing feature
+//!
+//! The *tracing* feature enables the `tracing` module, which contains a layer
+//! for [tracing-subscriber] that logs tracing events (including the fields of
+//! their spans) using this crate's logger. This way crates using [log] and
+//! crates using tracing produce the same output.
+//!
+//! [tracing-subscriber]: https://crates.io/crates/tracing-subscriber
+//!
+//!
+//! ## Kv-serde feature
+//!
+//! The *kv-serde* feature supports key-values implementing `serde::Serialize`,
+//! e.g. maps, sequences and nested structs, logged using `log`'s `:serde`
+//! capture. By default these values are still formatted using their `Display`
+//! implementation, [`Config::with_structured_values`] can be used to flatten
+//! them into dotted keys (`user.id=7 user.name="bob"`) or to format them as
+//! JSON instead.
+//!
+//!
+//! ## Redact feature
+//!
+//! The *redact* feature adds [`Config::with_redacted_pattern`], which replaces
+//! all matches of a regular expression, e.g. credit card numbers, in the
+//! message and values of all records with `[REDACTED]`. It uses the [regex]
+//! crate. Values of specific keys, e.g. `password`, can be redacted without
+//! this feature using [`Config::with_redacted_keys`].
+//!
+//! [regex]: https://crates.io/crates/regex
+//!
+//!
+//! ## WASM feature
+//!
+//! The logger supports WebAssembly targets. On `wasm32-wasi` (and other WASM
+//! targets with WASI support) it works the same as on other platforms, writing
+//! to standard out and error.
+//!
+//! The `wasm32-unknown-unknown` target (e.g. used in browsers) doesn't support
+//! standard out and error or getting the current time. The *wasm* feature
+//! writes the records to the JavaScript console instead, using the console
+//! function matching the log level (e.g. `console.error` for errors) and uses
+//! `Date.now()` for the timestamp. Batching (see [`Config::with_batch`]) is not
+//! supported by the console. The feature has no effect on other targets.
+//!
+//! Without the *wasm* feature the *timestamp* feature must be disabled on
+//! `wasm32-unknown-unknown` as getting the time panics on that target.
+//!
+//!
+//! ## Reload feature
+//!
+//! The *reload* feature reads the log level, targets, format, levels per
+//! target and additional file outputs from a [TOML] configuration file, set
+//! using the `LOG_CONFIG` environment variable or `Config::with_config_file`.
+//! All settings are optional:
+//!
+//! ```toml
+//! # Overwrites `LOG_LEVEL`.
+//! level = "info"
+//! # Overwrites `LOG_TARGET`, an empty array logs all targets.
+//! targets = []
+//! # Overwrites the format of the `Config`: "logfmt", "json" or "gcloud".
+//! format = "json"
+//!
+//! # Levels for specific targets, using the same patterns as `LOG_TARGET`.
+//! [levels]
+//! "my_crate::db" = "debug"
+//! hyper = "warn"
+//!
+//! # Files to write the records to, and their level.
+//! [sinks]
+//! "/var/log/my_app.log" = "debug"
+//! ```
+//!
+//! The levels and targets can be changed while the application is running by
+//! watching the file, using `Config::with_config_reload`, or by calling
+//! `reload_config`, e.g. on `SIGHUP`. The format and sinks are only read when
+//! the logger is initialised.
+//!
+//! [TOML]: https://toml.io
+//!
+//!
+//! ## Admin feature
+//!
+//! The *admin* feature, which enables the *reload* feature, allows the levels
+//! to be changed while the application is running using a Unix socket, set
+//! using `Config::with_admin_socket`. For example to log debug records for a
+//! single module in production.
+//!
+//! The protocol is line based, each line is a command and receives a single
+//! line as response: `ok`, the requested output or `error: <description>`.
+//! The following commands are supported:
+//! * Directives, e.g. `info,my_crate::db=debug`: a comma separated list of
+//!   levels for all targets (`info`) or for a target pattern
+//!   (`my_crate::db=debug`), using the same patterns as `LOG_TARGET`.
+//! * `show`: responds with the current levels as directives.
+//! * `reset`: resets the levels to the configuration file, or the
+//!   configuration of the logger if there is no file.
+//!
+//! For example using `socat`:
+//!
+//! ```text
+//! $ echo 'my_crate::db=debug' | socat - UNIX-CONNECT:/run/my_app/log.sock
+//! ok
+//! ```
+//!
+//!
+//! ## Gzip and zstd features
+//!
+//! The *gzip* and *zstd* features enable [`CompressedSink`], a sink that
+//! compresses the records using gzip or Zstandard respectively before writing
+//! them to, for example, a file. Sinks in the configuration file of the
+//! *reload* feature with a `.gz` or `.zst` extension are compressed
+//! automatically.
+//!
+//! The records are compressed in blocks, see [`CompressedSink`] for the
+//! trade-offs. Call [`shutdown`] before exiting the process to write the last
+//! block.
+//!
+//!
+//! ## Tokio feature
+//!
+//! The *tokio* feature enables the `tokio` module, which initialises the
+//! logger for async applications, e.g. those using the [tokio] runtime. It
+//! writes the records on a dedicated thread (see
+//! [`Config::with_writer_thread`]), so that logging never blocks the runtime
+//! on a slow standard error or sink. It doesn't depend on tokio itself and
+//! works with any runtime.
+//!
+//! [tokio]: https://crates.io/crates/tokio
+//!
+//!
 //! ## Nightly feature
 //!
 //! Enabling this feature enables the crate to use unstable (i.e. nightly-only)
@@ -286,17 +553,111 @@
 
 #![warn(missing_debug_implementations, missing_docs, unused_results)]
 
-use std::cell::RefCell;
 use std::io::{self, IoSlice, Write};
 use std::marker::PhantomData;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU8, Ordering};
+use std::sync::{Mutex, Once, PoisonError};
+use std::time::Duration;
 
-use log::{kv, LevelFilter, Log, Metadata, Record};
+use log::{kv, Level, LevelFilter, Log, Metadata, Record};
 
 mod format;
-use format::{Buffer, Format, BUFS_SIZE};
+use audit::Audit;
+use format::{Buffer, BufferPool, CustomFormat, Format, Options, BUFS_SIZE};
+pub use format::{Field, StructuredValues, TypeHint};
+use fsync::SyncedFile;
+use redact::Redact;
+use route::Routes;
+use transform::Transforms;
 
 mod config;
-pub use config::Config;
+pub use config::{BacktraceStyle, Config, EnvMeaning, LoggerGuard};
+
+mod audit;
+pub use audit::AuditOutput;
+
+mod route;
+pub use route::Route;
+
+mod batch;
+pub use batch::BatchPolicy;
+
+mod fsync;
+pub use fsync::SyncPolicy;
+
+mod writer;
+
+mod clock;
+pub use clock::Clock;
+
+mod full;
+pub use full::{dropped_records, FullPolicy};
+
+#[cfg(unix)]
+mod fork;
+
+mod metrics;
+pub use metrics::{metrics, Metrics};
+
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+mod wasm;
+
+mod query;
+pub use query::{normalize_query, NormalizedQuery};
+
+mod wire;
+pub use wire::{hex_dump, HexDump};
+
+mod owned;
+pub use owned::{OwnedRecord, OwnedValue};
+
+mod redact;
+
+#[cfg(feature = "reload")]
+mod reload;
+#[cfg(feature = "reload")]
+pub use reload::reload_config;
+
+#[cfg(feature = "admin")]
+mod admin;
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+mod compress;
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+pub use compress::{CompressedSink, Compression};
+
+mod ring_buffer;
+pub use ring_buffer::RingBufferSink;
+
+mod scoped;
+pub use scoped::ScopedLogger;
+
+mod timer;
+pub use timer::LogTimer;
+
+mod transform;
+pub use transform::{Action, RecordTransform};
+
+pub mod child;
+
+pub mod fmt;
+
+pub mod value;
+
+#[cfg(feature = "service")]
+pub mod service;
+
+#[cfg(feature = "net")]
+pub mod net;
+
+#[cfg(feature = "test")]
+pub mod test;
+
+#[cfg(feature = "tracing")]
+pub mod tracing;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
 
 #[cfg(feature = "timestamp")]
 mod timestamp;
@@ -313,8 +674,188 @@ mod tests;
 /// [crate level documentation]: index.html#logging-requests
 pub const REQUEST_TARGET: &str = "request";
 
+/// Target for audit records.
+///
+/// The [`audit`] macro provides a convenient way to log audit records. These
+/// records are always logged, regardless of the log level and targets set, and
+/// written to the output set using [`Config::with_audit_output`].
+pub const AUDIT_TARGET: &str = "audit";
+
+/// Target for logging the output of child processes, see [`child::spawn`].
+pub const CHILD_TARGET: &str = "child";
+
 /// Target for logging panics.
-pub const PANIC_TARGET: &str = "panic";
+pub const PANIC_TARGET: &str = std_logger_core::PANIC_TARGET;
+
+/// Default target for logging queries, see the [`query`] macro.
+pub const QUERY_TARGET: &str = "query";
+
+/// Target for logging byte buffers, see the [`hex_dump!`] macro.
+pub const WIRE_TARGET: &str = "wire";
+
+/// Target for logging the metrics of the logger, see
+/// [`Config::with_metrics_interval`].
+pub const METRICS_TARGET: &str = "metrics";
+
+/// Returns the crate features that are enabled, e.g. `["timestamp",
+/// "log-panic"]` for the default features.
+///
+/// This is useful for bug reports, to state exactly which code paths were
+/// compiled in, see the [crate features].
+///
+/// [crate features]: index.html#crate-features
+///
+/// # Examples
+///
+/// ```
+/// use log::info;
+///
+/// # fn main() {
+/// std_logger::Config::logfmt().init();
+/// // Logs: `features="timestamp,log-panic"`.
+/// info!(features = std_logger::features().join(","); "starting");
+/// # }
+/// ```
+pub const fn features() -> &'static [&'static str] {
+    &[
+        #[cfg(feature = "timestamp")]
+        "timestamp",
+        #[cfg(feature = "log-panic")]
+        "log-panic",
+        #[cfg(feature = "service")]
+        "service",
+        #[cfg(feature = "net")]
+        "net",
+        #[cfg(feature = "test")]
+        "test",
+        #[cfg(feature = "tracing")]
+        "tracing",
+        #[cfg(feature = "kv-serde")]
+        "kv-serde",
+        #[cfg(feature = "redact")]
+        "redact",
+        #[cfg(feature = "wasm")]
+        "wasm",
+        #[cfg(feature = "reload")]
+        "reload",
+        #[cfg(feature = "admin")]
+        "admin",
+        #[cfg(feature = "gzip")]
+        "gzip",
+        #[cfg(feature = "zstd")]
+        "zstd",
+        #[cfg(feature = "tokio")]
+        "tokio",
+        #[cfg(feature = "nightly")]
+        "nightly",
+    ]
+}
+
+/// Initialise the logger using logfmt, reading the log level and targets from
+/// the environment variables `vars`, see [`Config::with_env_vars`].
+///
+/// # Panics
+///
+/// This will panic if the logger fails to initialise.
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::EnvMeaning;
+///
+/// # fn main() {
+/// std_logger::init_from_env(&[("APP_VERBOSITY", EnvMeaning::Verbosity)]);
+/// # }
+/// ```
+pub fn init_from_env(vars: &[(&str, EnvMeaning)]) {
+    Config::logfmt().with_env_vars(vars).init();
+}
+
+/// Initialise the logger for tests, writing the records using [`eprint!`].
+///
+/// Unlike [`Config::init`] this can be called multiple times, e.g. at the start
+/// of every test, only the first call initialises the logger. Because the
+/// records are written using `eprint!` the test harness captures them per
+/// test, showing them only if the test fails (or when running with
+/// `--nocapture`).
+///
+/// The log level and targets are read from the environment, see the [crate
+/// level documentation], but the log level defaults to trace instead of info.
+/// All records, including requests, are written to standard error.
+///
+/// [crate level documentation]: index.html#setting-severity
+///
+/// # Examples
+///
+/// ```
+/// use log::debug;
+///
+/// # fn main() {
+/// // At the start of every test.
+/// std_logger::init_for_tests();
+/// debug!("only shown if the test fails");
+/// # }
+/// ```
+pub fn init_for_tests() {
+    static INIT: Once = Once::new();
+    INIT.call_once(|| {
+        // Another logger is already set, e.g. by the test itself.
+        let _ = Config::for_tests().try_init();
+    });
+}
+
+/// Shut the logger down.
+///
+/// This:
+///  * stops the writer thread, blocking until the records queued for it are
+///    written (only with [`Config::with_writer_thread`]), records logged
+///    afterwards are written directly,
+///  * writes the batched records of all threads, including the threads
+///    logging the output of child processes (see [`child::spawn`]), and
+///    flushes the sinks, see [`Log::flush`],
+///  * writes the records buffered because standard out or error was full,
+///    blocking until they're written, see [`FullPolicy::Buffer`], and
+///  * restores the panic hook that was set before the logger was initialised
+///    (only with the *log-panic* feature).
+///
+/// It also stops the thread watching the configuration file (only with the
+/// *reload* feature), see `Config::with_config_reload`, and removes the admin
+/// socket (only with the *admin* feature), see `Config::with_admin_socket`.
+///
+/// The logger itself remains set, the [log] crate doesn't support removing
+/// it, so records logged after shutting down are still logged.
+///
+/// This should be called before the process exits, as batched and buffered
+/// records are otherwise lost. Alternatively use [`Config::init_with_guard`],
+/// which calls this function when the returned guard is dropped.
+///
+/// [log]: mod@log
+///
+/// # Examples
+///
+/// ```
+/// use log::info;
+///
+/// # fn main() {
+/// std_logger::Config::logfmt().init();
+/// info!("starting");
+/// // Run the application...
+/// info!("stopping");
+/// std_logger::shutdown();
+/// # }
+/// ```
+pub fn shutdown() {
+    writer::stop();
+    batch::flush_all();
+    log::logger().flush();
+    full::flush().unwrap_or_else(log_failure);
+    #[cfg(feature = "admin")]
+    admin::stop();
+    #[cfg(feature = "reload")]
+    reload::stop();
+    #[cfg(feature = "log-panic")]
+    config::restore_panic_hook();
+}
 
 /// Logs a request.
 ///
@@ -330,24 +871,609 @@ macro_rules! request {
     )
 }
 
-// Not part of the API. Only here for use in the `request!` macro.
+/// Logs an audit record.
+///
+/// This uses [info] level severity and the [`AUDIT_TARGET`] target to log an
+/// audit record, e.g. a user changing permissions. Audit records are always
+/// logged, regardless of the log level and targets set, and are never batched
+/// or dropped. They're written to standard error by default, see
+/// [`Config::with_audit_output`] to change it and [`Config::with_audit_sync`]
+/// to sync the output after each record. File sinks can sync after each audit
+/// record as well, see [`SyncPolicy::Audit`].
+///
+/// [info]: log::Level::Info
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::audit;
+///
+/// # fn main() {
+/// audit!(user_id = 123, role = "admin"; "granted role");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! audit {
+    ($( $arg: tt )*) => (
+        $crate::_log::log!(target: $crate::AUDIT_TARGET, $crate::_log::Level::Info, $($arg)*);
+    )
+}
+
+/// Logs a (SQL) query.
+///
+/// This uses [info] level severity and the [`QUERY_TARGET`] target, unless
+/// another target is given using `target: "my_target"`, to log the query as
+/// message. Prefixing the query with `normalize:` replaces all literals in the
+/// query with `?`, see [`normalize_query`].
+///
+/// Optionally the duration of the query (a [`Duration`]) and the number of
+/// rows returned or affected can be logged, as the `duration_ms` (as float)
+/// and `rows` key-values respectively. This makes it possible to build a slow
+/// query log from the application logs.
+///
+/// [info]: log::Level::Info
+/// [`Duration`]: std::time::Duration
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Instant;
+///
+/// use std_logger::query;
+///
+/// # fn main() {
+/// let start = Instant::now();
+/// let sql = "SELECT * FROM users WHERE id = 123";
+/// // Execute the query...
+///
+/// // Logs: `msg="SELECT * FROM users WHERE id = 123"`.
+/// query!(sql);
+/// // Logs: `msg="SELECT * FROM users WHERE id = ?" duration_ms=1.23 rows=1`.
+/// query!(normalize: sql, duration = start.elapsed(), rows = 1);
+/// // Logs using the `db` target.
+/// query!(target: "db", sql, rows = 1);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! query {
+    (target: $target: expr, $( $arg: tt )+) => {
+        $crate::query!(@query $target, $($arg)+)
+    };
+    // Not part of the API, used to parse the query.
+    (@query $target: expr, normalize: $query: expr $(, $( $field: tt )+)?) => {
+        $crate::query!(@fields $target, $crate::normalize_query(&$query) $(, $($field)+)?)
+    };
+    (@query $target: expr, $query: expr $(, $( $field: tt )+)?) => {
+        $crate::query!(@fields $target, $query $(, $($field)+)?)
+    };
+    // Not part of the API, used to parse the optional fields.
+    (@fields $target: expr, $query: expr $(, duration = $duration: expr)? $(, rows = $rows: expr)? $(,)?) => {
+        $crate::query!(@log $target, $query,
+            $( (duration_ms, ::std::time::Duration::as_secs_f64(&$duration) * 1000.0) )?
+            $( (rows, $rows) )?
+        )
+    };
+    // Not part of the API, used to log the query.
+    (@log $target: expr, $query: expr, $( ($key: ident, $value: expr) )+) => {
+        $crate::_log::log!(target: $target, $crate::_log::Level::Info, $( $key = $value ),+; "{}", $query)
+    };
+    (@log $target: expr, $query: expr,) => {
+        $crate::_log::log!(target: $target, $crate::_log::Level::Info, "{}", $query)
+    };
+    ($( $arg: tt )+) => {
+        $crate::query!(@query $crate::QUERY_TARGET, $($arg)+)
+    };
+}
+
+/// Logs a byte buffer as hex dump.
+///
+/// This uses [trace] level severity and the [`WIRE_TARGET`] target to log the
+/// message, with the length of the buffer (`len`) and a hex dump of it
+/// (`dump`) as key-values, see [`hex_dump()`]. This is useful when debugging
+/// (custom) network protocols.
+///
+/// Prefixing the arguments with `sample: n` only logs one in every `n` buffers
+/// (per call site), to limit the amount of logs for busy connections. Note
+/// that `n` must not be zero.
+///
+/// [trace]: log::Level::Trace
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::hex_dump;
+///
+/// # fn main() {
+/// let frame = b"\x00\x05Hello";
+/// hex_dump!(frame, "received frame");
+/// // Only log one in every 100 frames.
+/// hex_dump!(sample: 100, frame, "received frame from {}", "127.0.0.1");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! hex_dump {
+    (sample: $n: expr, $bytes: expr, $( $arg: tt )+) => {{
+        if $crate::_log::log_enabled!(target: $crate::WIRE_TARGET, $crate::_log::Level::Trace) {
+            static COUNT: ::std::sync::atomic::AtomicUsize = ::std::sync::atomic::AtomicUsize::new(0);
+            if COUNT.fetch_add(1, ::std::sync::atomic::Ordering::Relaxed) % $n == 0 {
+                $crate::hex_dump!($bytes, $($arg)+);
+            }
+        }
+    }};
+    ($bytes: expr, $( $arg: tt )+) => {{
+        let bytes: &[u8] = ::std::convert::AsRef::as_ref(&$bytes);
+        $crate::_log::log!(
+            target: $crate::WIRE_TARGET,
+            $crate::_log::Level::Trace,
+            len = bytes.len(), dump = $crate::hex_dump(bytes);
+            $($arg)+
+        );
+    }};
+}
+
+/// Logs an error together with its sources.
+///
+/// This uses [error] level severity to log the message, adding the error as
+/// `error` key-value and its sources (what caused the error) as `error.chain`
+/// key-value, see [`value::error_chain`]. The error can be any type that
+/// implements [`std::error::Error`], including `&dyn Error`. Additional
+/// key-values can be passed before the message, as with the [log] macros.
+///
+/// The backtrace of the error is not logged, the standard library doesn't
+/// provide a way to get it from an error on stable Rust.
+///
+/// [error]: log::Level::Error
+/// [log]: mod@log
+///
+/// # Examples
+///
+/// ```
+/// use std::{error, fmt, io};
+///
+/// use std_logger::log_error;
+///
+/// #[derive(Debug)]
+/// struct ConfigError(io::Error);
+///
+/// impl fmt::Display for ConfigError {
+///     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+///         f.write_str("invalid configuration")
+///     }
+/// }
+///
+/// impl error::Error for ConfigError {
+///     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+///         Some(&self.0)
+///     }
+/// }
+///
+/// # fn main() {
+/// let err = ConfigError(io::Error::new(io::ErrorKind::PermissionDenied, "permission denied"));
+/// // Logs: `msg="failed to start" error="invalid configuration" error.chain="permission denied"`.
+/// log_error!(err, "failed to start");
+/// // Logs the same, with an additional `path="app.toml"` key-value.
+/// log_error!(err, path = "app.toml"; "failed to start {}", "server");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! log_error {
+    ($err: expr, $( $key: tt $(= $value: expr)? ),+; $( $arg: tt )+) => {{
+        let err: &dyn ::std::error::Error = &$err;
+        $crate::_log::error!(
+            error = $crate::_log::kv::Value::from_dyn_display(err),
+            "error.chain" = $crate::value::error_chain(err),
+            $( $key $(= $value)? ),+;
+            $($arg)+
+        );
+    }};
+    ($err: expr, $( $arg: tt )+) => {{
+        let err: &dyn ::std::error::Error = &$err;
+        $crate::_log::error!(
+            error = $crate::_log::kv::Value::from_dyn_display(err),
+            "error.chain" = $crate::value::error_chain(err);
+            $($arg)+
+        );
+    }};
+}
+
+/// Logs the time it takes to run a block of code.
+///
+/// This returns a [`LogTimer`] guard that, once dropped, logs a record with
+/// the name of the timer as message and the elapsed time as `elapsed`
+/// key-value (see [`value::duration`]), using the current module as target.
+/// Additional key-values can be passed after the name, these are evaluated
+/// when the timer is started. By default [info] level severity is used,
+/// another level can be set using `level: Level::Debug`.
+///
+/// Timers can be nested: if another timer is running on the same thread when
+/// the timer is started, its name is logged as `parent` key-value. This makes
+/// it possible to see what part of, for example, a request took the most
+/// time.
+///
+/// When deterministic output is enabled (see
+/// [`Config::with_deterministic_output`]) the elapsed time is always zero.
+///
+/// [info]: log::Level::Info
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+/// use std_logger::timer;
+///
+/// # fn main() {
+/// # let id = 123;
+/// {
+///     let _t = timer!("handle_request");
+///     {
+///         // Logs: `msg="db_query" query_id=123 parent="handle_request" elapsed="1.2ms"`.
+///         let _t = timer!("db_query", query_id = id);
+///         // Execute the query...
+///     }
+///     let _t = timer!(level: Level::Debug, "render");
+///     // Render the response...
+/// }
+/// // Logs: `msg="handle_request" elapsed="1.5ms"`, after the `render` timer.
+/// # }
+/// ```
+#[macro_export]
+macro_rules! timer {
+    (level: $level: expr, $name: expr $(, $key: ident = $value: expr)* $(,)?) => {
+        $crate::LogTimer::_new(
+            $name,
+            $level,
+            (::std::module_path!(), ::std::file!(), ::std::line!()),
+            ::std::vec![$(
+                (
+                    ::std::stringify!($key),
+                    $crate::OwnedValue::from($crate::_log::kv::ToValue::to_value(&$value)),
+                )
+            ),*],
+        )
+    };
+    ($name: expr $(, $key: ident = $value: expr)* $(,)?) => {
+        $crate::timer!(level: $crate::_log::Level::Info, $name $(, $key = $value)*)
+    };
+}
+
+/// Lazily evaluates the value of a key-value pair.
+///
+/// The expression is only evaluated if the record passes filtering and is
+/// actually formatted, making it useful for values that are expensive to
+/// compute, e.g. a summary of a large collection. The expression must result
+/// in a type that implements [`std::fmt::Display`], it's logged as a string.
+///
+/// # Examples
+///
+/// ```
+/// use log::debug;
+/// use std_logger::lazy_kv;
+///
+/// # fn main() {
+/// let items = vec![1, 2, 3];
+/// // The sum is only calculated if debug messages are logged.
+/// debug!(sum = lazy_kv!(items.iter().sum::<u64>()); "processed items");
+/// # }
+/// ```
+#[macro_export]
+macro_rules! lazy_kv {
+    ($value: expr) => {
+        $crate::Lazy::new(|| $value)
+    };
+}
+
+/// Lazily evaluated value of a key-value pair, see [`lazy_kv`].
+pub struct Lazy<F>(F);
+
+impl<F, T> Lazy<F>
+where
+    F: Fn() -> T,
+    T: std::fmt::Display,
+{
+    /// Create a new lazily evaluated value, evaluated by calling `f` each time
+    /// the value is formatted.
+    pub const fn new(f: F) -> Lazy<F> {
+        Lazy(f)
+    }
+}
+
+impl<F, T> std::fmt::Display for Lazy<F>
+where
+    F: Fn() -> T,
+    T: std::fmt::Display,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        (self.0)().fmt(f)
+    }
+}
+
+impl<F> std::fmt::Debug for Lazy<F> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lazy").finish_non_exhaustive()
+    }
+}
+
+impl<F, T> kv::ToValue for Lazy<F>
+where
+    F: Fn() -> T,
+    T: std::fmt::Display,
+{
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+// Not part of the API. Only here for use in the `request!`, `query!` and
+// `hex_dump!` macros.
 #[doc(hidden)]
 pub use log as _log;
 
+/// Formats `record` using logfmt.
+// Not part of the API. Only here for the round trip tests in `round_trip`.
+#[doc(hidden)]
+pub fn _format_logfmt(record: &Record, add_loc: bool) -> Vec<u8> {
+    let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
+    let mut buf = Buffer::new();
+    let options = Options::new(add_loc);
+    let bufs = format::LogFmt::format(&mut bufs, &mut buf, record, &config::NoKvs, &options);
+    bufs.iter().flat_map(|buf| buf.iter().copied()).collect()
+}
+
+/// Formats `record` using logfmt, or JSON if `json` is `true`, and calls `f`
+/// with the formatted buffers. Like the logger this reuses a thread local
+/// buffer.
+// Not part of the API. Only here for the benchmarks in `benches`.
+#[doc(hidden)]
+pub fn _format_with<R, F>(record: &Record, json: bool, f: F) -> R
+where
+    F: FnOnce(&[IoSlice<'_>]) -> R,
+{
+    let options = Options::new(false);
+    if json {
+        fmt::json_with(record, &options, f)
+    } else {
+        fmt::logfmt_with(record, &options, f)
+    }
+    .expect("formatting records nested too deep")
+}
+
 /// Our `Log` implementation.
 struct Logger<F, Kvs> {
     /// The filter used to determine what messages to log.
     filter: LevelFilter,
-    /// `add_loc` argument to `Format::format`.
-    add_loc: bool,
+    /// Levels for specific target patterns, overwriting `filter`, see
+    /// [`EnvMeaning::Directives`].
+    levels: Box<[(Box<str>, LevelFilter)]>,
+    /// `options` argument to `Format::format`.
+    options: Options,
     /// What logging targets to log.
     targets: Targets,
+    /// Targets that are always logged, see [`Config::with_always_logged_targets`].
+    always_log: Box<[Box<str>]>,
+    /// Output for audit records, see [`AUDIT_TARGET`].
+    audit: Audit,
+    /// Outputs of routed targets, see [`Config::route_target`].
+    routes: Routes,
+    /// Transforms applied to all records, see [`Config::add_transform`].
+    transforms: Transforms,
+    /// Redaction of sensitive data, see [`Config::with_redacted_keys`].
+    redact: Redact,
     /// Key-values supplied for all logs.
     kvs: Kvs,
+    /// Key-values read from the environment, see
+    /// [`Config::with_kvs_from_env`].
+    env_kvs: Box<[(String, String)]>,
+    /// Batch records before writing them, see [`Config::with_batch`].
+    batch: Option<BatchPolicy>,
+    /// What to do when the output is full, see [`Config::with_full_policy`].
+    full: FullPolicy,
+    /// Log the metrics every interval, see [`Config::with_metrics_interval`].
+    metrics_interval: Option<Duration>,
+    /// Additional outputs, see [`Config::add_sink`].
+    sinks: Box<[Sink]>,
+    /// Formats records instead of `F`, see [`Config::format_with`].
+    custom_format: Option<CustomFormat>,
+    /// Prefix records with their syslog priority, see
+    /// [`Config::with_systemd_priority_prefix`].
+    systemd_prefix: bool,
+    /// Reloadable levels and targets, see [`Config::with_config_file`].
+    #[cfg(feature = "reload")]
+    reload: Option<std::sync::Arc<reload::Reload>>,
+    /// Queue of the writer thread, see [`Config::with_writer_thread`].
+    writer: Option<writer::Writer>,
     format: PhantomData<F>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+impl<F: Format, Kvs: kv::Source> Logger<F, Kvs> {
+    /// Redact (see [`Config::with_redacted_keys`]) and log `record`.
+    fn redact_and_log(&self, record: &Record) {
+        if self.redact.is_empty() {
+            log(self, record);
+        } else {
+            self.redact.apply(record, |record| log(self, record));
+        }
+    }
+}
+
+impl<F, Kvs> Logger<F, Kvs> {
+    /// Returns the most verbose level enabled for any output.
+    fn max_level(&self) -> LevelFilter {
+        #[cfg(feature = "reload")]
+        if let Some(reload) = &self.reload {
+            return reload.max_level();
+        }
+        self.levels
+            .iter()
+            .map(|(_, level)| *level)
+            .chain(self.sinks.iter().map(|sink| sink.filter))
+            .fold(self.filter, Ord::max)
+    }
+
+    /// Returns the level filter for standard out and error for `target`.
+    #[inline]
+    #[cfg_attr(not(feature = "reload"), allow(unused_variables))]
+    fn filter(&self, target: &str) -> LevelFilter {
+        #[cfg(feature = "reload")]
+        if let Some(reload) = &self.reload {
+            return reload.filter(target);
+        }
+        if self.levels.is_empty() {
+            self.filter
+        } else {
+            target_level(&self.levels, target, self.filter)
+        }
+    }
+
+    /// Returns `true` if `target` should be logged.
+    #[inline]
+    fn should_log(&self, target: &str) -> bool {
+        #[cfg(feature = "reload")]
+        if let Some(reload) = &self.reload {
+            return reload.should_log(target, &self.always_log);
+        }
+        self.targets.should_log(target, &self.always_log)
+    }
+
+    /// Write the formatted `record` in `bufs`, sending it to the writer thread
+    /// if it's running (see [`Config::with_writer_thread`]).
+    fn write(&self, record: &Record, bufs: &[IoSlice]) {
+        if let Some(writer) = &self.writer {
+            let target = record.target();
+            if writer.is_active()
+                && target != AUDIT_TARGET
+                && target != PANIC_TARGET
+                && writer.send(record, bufs)
+            {
+                return;
+            }
+        }
+        self.write_now(record, bufs);
+    }
+
+    /// Write the formatted `record` in `bufs` to all outputs that accept its
+    /// level.
+    fn write_now(&self, record: &Record, bufs: &[IoSlice]) {
+        let level = record.level();
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        metrics::add_record(level);
+        // While panicking the sinks are written first, so the records dumped
+        // by a sink (see `RingBufferSink::dump_on_panic`) are written before
+        // the panic.
+        let panicking = std::thread::panicking();
+        if panicking {
+            self.write_sinks(record, bufs, len);
+        }
+        if record.target() == AUDIT_TARGET {
+            self.audit.write(bufs).unwrap_or_else(log_failure);
+            metrics::add_bytes_written(len);
+        } else if self.filter(record.target()) >= level {
+            match self.routes.get(record.target()) {
+                Some(route::Output::File(file)) => {
+                    route::write_file(file, bufs).unwrap_or_else(log_failure);
+                }
+                Some(route::Output::Stdout) => self.write_std(record, true, bufs),
+                Some(route::Output::Stderr) => self.write_std(record, false, bufs),
+                None => self.write_std(record, record.target() == REQUEST_TARGET, bufs),
+            }
+            metrics::add_bytes_written(len);
+        }
+        if !panicking {
+            self.write_sinks(record, bufs, len);
+        }
+    }
+
+    /// Write the formatted `record` in `bufs`, of `len` bytes, to all sinks
+    /// that accept its level.
+    fn write_sinks(&self, record: &Record, bufs: &[IoSlice], len: usize) {
+        for sink in self.sinks.iter() {
+            if sink.filter >= record.level() {
+                sink.write(record, bufs);
+                metrics::add_bytes_written(len);
+            }
+        }
+    }
+}
+
+impl<F, Kvs> Logger<F, Kvs> {
+    /// Write the formatted `record` in `bufs` to standard out if `to_stdout`
+    /// is true, or standard error otherwise.
+    fn write_std(&self, record: &Record, to_stdout: bool, bufs: &[IoSlice]) {
+        if self.systemd_prefix && !to_stdout {
+            let mut prefixed = [IoSlice::new(&[]); BUFS_SIZE];
+            prefixed[0] = IoSlice::new(syslog_priority(record));
+            prefixed[1..=bufs.len()].copy_from_slice(bufs);
+            write_record(
+                record,
+                to_stdout,
+                &prefixed[..=bufs.len()],
+                self.batch,
+                self.full,
+            );
+        } else {
+            write_record(record, to_stdout, bufs, self.batch, self.full);
+        }
+    }
+}
+
+/// Additional output for records, see [`Config::add_sink`] and
+/// [`Config::add_file_sink`].
+struct Sink {
+    /// The filter used to determine what messages to write.
+    filter: LevelFilter,
+    output: Mutex<SinkOutput>,
+}
+
+enum SinkOutput {
+    Writer(Box<dyn Write + Send>),
+    File(SyncedFile),
+}
+
+impl Sink {
+    fn new(output: Box<dyn Write + Send>, filter: LevelFilter) -> Sink {
+        Sink {
+            filter,
+            output: Mutex::new(SinkOutput::Writer(output)),
+        }
+    }
+
+    fn file(file: SyncedFile, filter: LevelFilter) -> Sink {
+        Sink {
+            filter,
+            output: Mutex::new(SinkOutput::File(file)),
+        }
+    }
+
+    /// Write the formatted `record` in `bufs` to the sink.
+    fn write(&self, record: &Record, bufs: &[IoSlice]) {
+        // A panic while holding the lock doesn't leave the output in an invalid
+        // state, so we can ignore the poisoning.
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        match &mut *output {
+            SinkOutput::Writer(writer) => write_all(&mut **writer, bufs),
+            SinkOutput::File(file) => file.write(record.target(), bufs),
+        }
+        .unwrap_or_else(log_failure);
+    }
+
+    /// Flush the sink, ignoring errors.
+    fn flush(&self) {
+        let mut output = self.output.lock().unwrap_or_else(PoisonError::into_inner);
+        let _ = match &mut *output {
+            SinkOutput::Writer(writer) => writer.flush(),
+            SinkOutput::File(file) => file.flush(),
+        };
+    }
+}
+
+impl std::fmt::Debug for Sink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sink")
+            .field("filter", &self.filter)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
 enum Targets {
     /// Log all targets.
     All,
@@ -356,105 +1482,404 @@ enum Targets {
 }
 
 impl Targets {
-    /// Returns `true` if the `target` should be logged.
-    fn should_log(&self, target: &str) -> bool {
-        if target == REQUEST_TARGET || target == PANIC_TARGET {
-            // Always log requests and panics.
+    /// Returns `true` if the `target` should be logged. Targets in
+    /// `always_log` are always logged.
+    fn should_log(&self, target: &str, always_log: &[Box<str>]) -> bool {
+        if always_log.iter().any(|t| &**t == target) {
             return true;
         }
         match self {
             Targets::All => true, // All targets should be logged.
-            Targets::Only(targets) => {
-                // Log all targets that start with an allowed target. This way
-                // we can just use `LOG_TARGET=my_crate`, rather then
-                // `LOG_TARGET=my_crate::module1,my_crate::module2` etc.
-                targets
-                    .iter()
-                    .any(|log_target| target.starts_with(&**log_target))
-            }
+            Targets::Only(targets) => targets
+                .iter()
+                .any(|log_target| target_matches(log_target, target)),
         }
     }
 }
 
+/// Returns the level of the longest pattern in `levels` that matches
+/// `target`, or `default` if none match. If multiple patterns of the same
+/// length match the last one is used.
+fn target_level(
+    levels: &[(Box<str>, LevelFilter)],
+    target: &str,
+    default: LevelFilter,
+) -> LevelFilter {
+    levels
+        .iter()
+        .filter(|(pattern, _)| target_matches(pattern, target))
+        .max_by_key(|(pattern, _)| pattern.len())
+        .map_or(default, |(_, level)| *level)
+}
+
+/// Returns `true` if `target` matches `pattern`, see the [crate level
+/// documentation].
+///
+/// A pattern starting with `=` only matches the exact target. Otherwise the
+/// pattern matches the target and all its sub-modules, i.e. all targets that
+/// start with the pattern followed by `::`. This way we can just use
+/// `LOG_TARGET=my_crate`, rather then
+/// `LOG_TARGET=my_crate::module1,my_crate::module2` etc. A `*` in the pattern
+/// matches a single path segment, e.g. `*::db` matches `crate1::db` but not
+/// `crate1::mod1::db`.
+///
+/// [crate level documentation]: index.html#limiting-logging-targets
+fn target_matches(pattern: &str, target: &str) -> bool {
+    let (pattern, exact) = match pattern.strip_prefix('=') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut segments = target.split("::");
+    for pattern_segment in pattern.split("::") {
+        match segments.next() {
+            Some(segment) if pattern_segment == "*" || pattern_segment == segment => {}
+            _ => return false,
+        }
+    }
+    !exact || segments.next().is_none()
+}
+
 impl<F, Kvs> Log for Logger<F, Kvs>
 where
     F: Format + Sync + Send,
     Kvs: kv::Source + Sync + Send,
 {
     fn enabled(&self, metadata: &Metadata) -> bool {
-        self.filter >= metadata.level() && self.targets.should_log(metadata.target())
+        let level = metadata.level();
+        let target = metadata.target();
+        if target == AUDIT_TARGET {
+            // Always log audit records.
+            return true;
+        }
+        #[cfg(feature = "test")]
+        if test::is_capturing() {
+            // Capture records of all levels, but not of filtered targets.
+            return self.should_log(target);
+        }
+        (self.filter(target) >= level || self.sinks.iter().any(|sink| sink.filter >= level))
+            && self.should_log(target)
     }
 
     fn log(&self, record: &Record) {
+        if let Some(interval) = self.metrics_interval {
+            if metrics::should_log(interval) {
+                log_metrics(self);
+            }
+        }
         if self.enabled(record.metadata()) {
-            log::<F, Kvs>(record, &self.kvs, self.add_loc);
+            if !self.transforms.is_empty() {
+                self.transforms
+                    .apply(record, |record| self.redact_and_log(record));
+            } else {
+                self.redact_and_log(record);
+            }
+            #[cfg(feature = "test")]
+            if test::is_capturing() {
+                test::add(record, &GlobalKvs(&self.kvs, &self.env_kvs), &self.options);
+            }
         }
     }
 
     fn flush(&self) {
-        // Can't flush standard error/out.
+        if let Some(writer) = &self.writer {
+            if writer.is_active() {
+                writer.flush();
+            }
+        }
+        self.flush_now();
+    }
+}
+
+impl<F, Kvs> Logger<F, Kvs> {
+    /// Flush the outputs written to by this thread.
+    fn flush_now(&self) {
+        // Can't flush standard error/out, but we can write the batched records
+        // of this thread.
+        if self.batch.is_some() {
+            batch::flush();
+        }
+        for sink in self.sinks.iter() {
+            sink.flush();
+        }
+    }
+}
+
+/// Log the metrics of the logger, see [`Config::with_metrics_interval`].
+fn log_metrics<L: Log>(logger: &L) {
+    let metrics = metrics();
+    let key_values = [
+        ("records_error", metrics.records(Level::Error)),
+        ("records_warn", metrics.records(Level::Warn)),
+        ("records_info", metrics.records(Level::Info)),
+        ("records_debug", metrics.records(Level::Debug)),
+        ("records_trace", metrics.records(Level::Trace)),
+        ("bytes_written", metrics.bytes_written),
+        ("records_dropped", metrics.records_dropped),
+        ("write_errors", metrics.write_errors),
+    ];
+    let key_values = key_values.as_slice();
+    logger.log(
+        &Record::builder()
+            .args(format_args!("logger metrics"))
+            .level(Level::Info)
+            .target(METRICS_TARGET)
+            .module_path_static(Some(module_path!()))
+            .key_values(&key_values)
+            .build(),
+    );
+}
+
+/// Key-values added to all records: those set using [`Config::with_kvs`]
+/// followed by those read using [`Config::with_kvs_from_env`].
+struct GlobalKvs<'a, Kvs>(&'a Kvs, &'a [(String, String)]);
+
+impl<'a, Kvs: kv::Source> kv::Source for GlobalKvs<'a, Kvs> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        self.0.visit(visitor)?;
+        self.1.visit(visitor)
     }
 }
 
 /// The actual logging of a record.
-#[allow(clippy::single_match_else)]
-fn log<F: Format, Kvs: kv::Source>(record: &Record, kvs: &Kvs, add_loc: bool) {
-    // Thread local buffer for logging. This way we only lock standard out/error
-    // for a single writev call and don't create half written logs.
+fn log<F: Format, Kvs: kv::Source>(logger: &Logger<F, Kvs>, record: &Record) {
+    // Thread local buffers for logging. This way we only lock standard
+    // out/error for a single writev call and don't create half written logs.
     thread_local! {
-        static BUF: RefCell<Buffer> = RefCell::new(Buffer::new());
-    }
-
-    BUF.with(|buf| {
-        let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
-        match buf.try_borrow_mut() {
-            Ok(mut buf) => {
-                // NOTE: keep in sync with the `Err` branch below.
-                let bufs = F::format(&mut bufs, &mut buf, record, kvs, add_loc);
-                match record.target() {
-                    REQUEST_TARGET => write_once(stdout(), bufs),
-                    _ => write_once(stderr(), bufs),
-                }
-                .unwrap_or_else(log_failure);
-            }
-            Err(_) => {
-                // NOTE: We only get to this branch if we're panicking while
-                // calling `F::format`, e.g. when a `fmt::Display` impl in the
-                // `record` panics, and the `log-panic` feature is enabled which
-                // calls `error!` and in turn this function again, while still
-                // borrowing `BUF`.
-                let mut buf = Buffer::new();
-                // NOTE: keep in sync with the `Ok` branch above.
-                let bufs = F::format(&mut bufs, &mut buf, record, kvs, add_loc);
-                match record.target() {
-                    REQUEST_TARGET => write_once(stdout(), bufs),
-                    _ => write_once(stderr(), bufs),
-                }
-                .unwrap_or_else(log_failure);
-            }
-        }
+        static BUFS: BufferPool = const { BufferPool::new() };
+    }
+
+    if let Some(custom_format) = &logger.custom_format {
+        custom_format.format(record, |bufs| logger.write(record, bufs));
+        return;
+    }
+
+    BUFS.with(|pool| {
+        // NOTE: if all buffers are in use we're logging while formatting a
+        // record, e.g. when a `fmt::Display` impl in the `record` panics and
+        // the `log-panic` feature is enabled, which formats the panic record
+        // using the second buffer. Only if that fails again, or if the record
+        // logs itself, are all buffers in use, in which case we drop the record
+        // to stop the recursion.
+        let _ = pool.with(|buf| {
+            let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
+            let kvs = GlobalKvs(&logger.kvs, &logger.env_kvs);
+            let bufs = F::format(&mut bufs, buf, record, &kvs, &logger.options);
+            logger.write(record, bufs);
+        });
     });
 }
 
-/// Write the entire `buf`fer into the `output` or return an error.
+/// Write the formatted `record` in `bufs` to standard out if `to_stdout` is
+/// true, or standard error otherwise. Adds the record to the batch if `batch`
+/// is set, follows the `full` policy otherwise.
 #[inline]
-fn write_once<W>(mut output: W, bufs: &[IoSlice]) -> io::Result<()>
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown")))]
+fn write_record(
+    record: &Record,
+    to_stdout: bool,
+    bufs: &[IoSlice],
+    batch: Option<BatchPolicy>,
+    full: FullPolicy,
+) {
+    if let Some(policy) = batch {
+        if batch::add(
+            record.target(),
+            to_stdout,
+            record.level(),
+            bufs,
+            policy,
+            full,
+        ) {
+            return;
+        }
+    }
+    full::write(to_stdout, bufs, full).unwrap_or_else(log_failure);
+}
+
+/// Write the formatted `record` in `bufs` to the JavaScript console. The
+/// console doesn't support batching or blocking, so `batch` and `full` are
+/// ignored.
+#[inline]
+#[cfg(all(feature = "wasm", target_arch = "wasm32", target_os = "unknown"))]
+fn write_record(
+    record: &Record,
+    _to_stdout: bool,
+    bufs: &[IoSlice],
+    _batch: Option<BatchPolicy>,
+    _full: FullPolicy,
+) {
+    wasm::write(record.level(), bufs);
+}
+
+/// Returns the syslog priority prefix of `record`, see
+/// [`Config::with_systemd_priority_prefix`].
+fn syslog_priority(record: &Record) -> &'static [u8] {
+    match record.level() {
+        Level::Error if record.target() == PANIC_TARGET => b"<2>",
+        Level::Error => b"<3>",
+        Level::Warn => b"<4>",
+        Level::Info => b"<6>",
+        Level::Debug | Level::Trace => b"<7>",
+    }
+}
+
+/// Write all `bufs` into the `output`, retrying on partial writes and
+/// interruptions, or return an error.
+///
+/// Most writers, e.g. a pipe on a heavily loaded system or a `TcpStream`,
+/// don't guarantee to write all bufs in a single call.
+fn write_all<W>(output: &mut W, bufs: &[IoSlice]) -> io::Result<()>
 where
-    W: Write,
+    W: Write + ?Sized,
 {
-    output.write_vectored(bufs).and_then(|written| {
-        let total_len = bufs.iter().map(|b| b.len()).sum();
-        if written == total_len {
-            Ok(())
-        } else {
-            // Not completely correct when going by the name alone, but it's the
-            // closest we can get to a descriptive error.
-            Err(io::Error::new(
-                io::ErrorKind::WriteZero,
-                "failed to write entire log message",
-            ))
+    let mut storage = [IoSlice::new(&[]); BUFS_SIZE];
+    storage[..bufs.len()].copy_from_slice(bufs);
+    let mut bufs = &mut storage[..bufs.len()];
+    // Remove empty buffers at the start.
+    IoSlice::advance_slices(&mut bufs, 0);
+    while !bufs.is_empty() {
+        match output.write_vectored(bufs) {
+            Ok(0) => return Err(io::ErrorKind::WriteZero.into()),
+            Ok(n) => IoSlice::advance_slices(&mut bufs, n),
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
         }
-    })
+    }
+    Ok(())
+}
+
+/// What to do when writing a record fails, see [`Config::with_error_policy`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ErrorPolicy {
+    /// Panic, the default.
+    ///
+    /// If the thread is already panicking this uses [`ErrorPolicy::FallbackRaw`]
+    /// instead, as panicking again would abort the process.
+    Panic,
+    /// Ignore the error, dropping the record.
+    Ignore,
+    /// Write a short message about the error directly to standard error (file
+    /// descriptor 2 on Unix), without any buffering, locking or allocating, and
+    /// drop the record.
+    FallbackRaw,
+}
+
+/// Current error policy, see [`ErrorPolicy::current`].
+static ERROR_POLICY: AtomicU8 = AtomicU8::new(ErrorPolicy::Panic as u8);
+
+impl ErrorPolicy {
+    /// Set the error policy used by [`log_failure`].
+    pub(crate) fn set(self) {
+        ERROR_POLICY.store(self as u8, Ordering::Relaxed);
+    }
+
+    /// Returns the error policy used by [`log_failure`].
+    fn current() -> ErrorPolicy {
+        match ERROR_POLICY.load(Ordering::Relaxed) {
+            n if n == ErrorPolicy::Ignore as u8 => ErrorPolicy::Ignore,
+            n if n == ErrorPolicy::FallbackRaw as u8 => ErrorPolicy::FallbackRaw,
+            _ => ErrorPolicy::Panic,
+        }
+    }
+}
+
+/// The logger isn't initialised, see [`INIT_STATE`].
+const UNINITIALISED: u8 = 0;
+/// The logger is being initialised.
+const INITIALISING: u8 = 1;
+/// The logger is initialised.
+const INITIALISED: u8 = 2;
+
+/// State of the initialisation of the logger of this crate.
+static INIT_STATE: AtomicU8 = AtomicU8::new(UNINITIALISED);
+
+/// Start initialising the logger. Returns `false` if the logger of this crate
+/// is already initialised.
+///
+/// If another thread is initialising the logger this waits until it's done,
+/// as it might fail (if another logger is set).
+pub(crate) fn start_init() -> bool {
+    loop {
+        match INIT_STATE.compare_exchange(
+            UNINITIALISED,
+            INITIALISING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => return true,
+            Err(INITIALISED) => return false,
+            Err(_) => std::thread::yield_now(),
+        }
+    }
+}
+
+/// Finish initialising the logger, started by [`start_init`].
+pub(crate) fn finish_init(initialised: bool) {
+    let state = if initialised {
+        INITIALISED
+    } else {
+        UNINITIALISED
+    };
+    INIT_STATE.store(state, Ordering::Release);
+}
+
+/// Returns `true` if the logger of this crate is initialised, e.g. using
+/// [`Config::init`].
+///
+/// Returns `false` if another logger is set, or if no logger is set at all.
+pub fn is_initialised() -> bool {
+    INIT_STATE.load(Ordering::Acquire) == INITIALISED
+}
+
+/// Whether or not the output should be deterministic, see
+/// [`Config::with_deterministic_output`].
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+/// Set whether or not the output should be deterministic.
+pub(crate) fn set_deterministic(deterministic: bool) {
+    DETERMINISTIC.store(deterministic, Ordering::Relaxed);
+}
+
+/// Returns `true` if the output should be deterministic, see
+/// [`Config::with_deterministic_output`].
+pub(crate) fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/// File descriptor to write requests to, or -1 to write them to standard out,
+/// see [`Config::with_request_fd`].
+static REQUEST_FD: AtomicI32 = AtomicI32::new(-1);
+
+/// Set the file descriptor to write requests to, `None` for standard out.
+pub(crate) fn set_request_fd(fd: Option<i32>) {
+    REQUEST_FD.store(fd.unwrap_or(-1), Ordering::Relaxed);
+}
+
+/// Returns the file descriptor to write requests to, if not standard out.
+fn request_fd() -> Option<i32> {
+    match REQUEST_FD.load(Ordering::Relaxed) {
+        -1 => None,
+        fd => Some(fd),
+    }
+}
+
+/// Write `bufs` to the output for requests, the file descriptor set using
+/// [`Config::with_request_fd`] or standard out.
+fn write_requests(bufs: &[IoSlice]) -> io::Result<()> {
+    #[cfg(unix)]
+    if let Some(fd) = request_fd() {
+        use std::fs::File;
+        use std::mem::ManuallyDrop;
+        use std::os::unix::io::FromRawFd;
+
+        // SAFETY: the file descriptor is inherited by the process, we don't
+        // close it as the `File` isn't dropped. If it's not open the write
+        // fails, which is handled like any other write error.
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd) });
+        return write_all(&mut *file, bufs);
+    }
+    write_all(&mut stdout(), bufs)
 }
 
 /// The function that gets called when we're unable to print a message.
@@ -462,13 +1887,48 @@ where
 #[cold]
 #[allow(clippy::needless_pass_by_value)]
 fn log_failure(err: io::Error) {
-    // We've just failed to log, no point in failing to log the fact that we
-    // have failed to log... So we remove our panic hook and use the default
-    // instead.
-    #[cfg(feature = "log-panic")]
-    drop(std::panic::take_hook());
+    metrics::add_write_error();
+    match ErrorPolicy::current() {
+        // Panicking while panicking aborts the process.
+        ErrorPolicy::Panic if std::thread::panicking() => write_raw_failure(&err),
+        ErrorPolicy::Panic => {
+            // We've just failed to log, no point in failing to log the fact
+            // that we have failed to log... So we remove our panic hook and use
+            // the default instead.
+            #[cfg(feature = "log-panic")]
+            drop(std::panic::take_hook());
 
-    panic!("unexpected error logging message: {err}")
+            panic!("unexpected error logging message: {err}")
+        }
+        ErrorPolicy::Ignore => {}
+        ErrorPolicy::FallbackRaw => write_raw_failure(&err),
+    }
+}
+
+/// Write a message about `err` directly to standard error, see
+/// [`ErrorPolicy::FallbackRaw`].
+fn write_raw_failure(err: &io::Error) {
+    // Format into a fixed size buffer to not allocate, the message is
+    // truncated if it doesn't fit.
+    let mut buf = [0; 256];
+    let mut output = &mut buf[..];
+    let _ = writeln!(
+        output,
+        "std-logger: unexpected error logging message: {err}"
+    );
+    let len = 256 - output.len();
+    let msg = &buf[..len];
+
+    #[cfg(unix)]
+    {
+        // SAFETY: `msg` is valid for `msg.len()` bytes. Errors are ignored,
+        // there is nothing else we can do.
+        let _ = unsafe { libc::write(libc::STDERR_FILENO, msg.as_ptr().cast(), msg.len()) };
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = io::stderr().write_all(msg);
+    }
 }
 
 // Functions to get standard out/error, which are stubbed in testing. Even
@@ -484,6 +1944,7 @@ use std::io::{stderr, stdout};
 
 #[cfg(test)]
 mod test_instruments {
+    use std::cell::Cell;
     use std::io::{self, IoSlice, Write};
     use std::mem::take;
     use std::sync::Mutex;
@@ -525,4 +1986,13 @@ mod test_instruments {
     pub(crate) fn stderr() -> LogOutput {
         LogOutput { inner: Vec::new() }
     }
+
+    thread_local! {
+        /// Whether standard out/error are full, see `is_full`.
+        pub(crate) static OUTPUT_FULL: Cell<bool> = const { Cell::new(false) };
+    }
+
+    pub(crate) fn is_full(_fd: i32) -> bool {
+        OUTPUT_FULL.with(Cell::get)
+    }
 }
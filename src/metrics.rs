@@ -0,0 +1,109 @@
+//! Metrics about the logger itself, see [`metrics`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use log::Level;
+
+use crate::dropped_records;
+
+/// Number of records logged per level, indexed by `level as usize - 1`.
+static RECORDS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+/// Number of bytes written to all outputs.
+static BYTES_WRITTEN: AtomicU64 = AtomicU64::new(0);
+/// Number of failures to write a record.
+static WRITE_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// Returns a snapshot of the metrics about the logger itself.
+///
+/// The metrics can also be logged periodically, see
+/// [`Config::with_metrics_interval`].
+///
+/// [`Config::with_metrics_interval`]: crate::Config::with_metrics_interval
+///
+/// # Examples
+///
+/// ```
+/// use log::Level;
+///
+/// let metrics = std_logger::metrics();
+/// println!("logged {} errors", metrics.records(Level::Error));
+/// ```
+pub fn metrics() -> Metrics {
+    Metrics {
+        records: [
+            RECORDS[0].load(Ordering::Relaxed),
+            RECORDS[1].load(Ordering::Relaxed),
+            RECORDS[2].load(Ordering::Relaxed),
+            RECORDS[3].load(Ordering::Relaxed),
+            RECORDS[4].load(Ordering::Relaxed),
+        ],
+        bytes_written: BYTES_WRITTEN.load(Ordering::Relaxed),
+        records_dropped: dropped_records(),
+        write_errors: WRITE_ERRORS.load(Ordering::Relaxed),
+    }
+}
+
+/// Snapshot of the metrics about the logger, see [`metrics`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct Metrics {
+    records: [u64; 5],
+    /// Number of bytes written to all outputs, records written to multiple
+    /// outputs are counted once for each output.
+    pub bytes_written: u64,
+    /// Number of records dropped because standard out or error was full, see
+    /// [`FullPolicy`].
+    ///
+    /// [`FullPolicy`]: crate::FullPolicy
+    pub records_dropped: u64,
+    /// Number of failures to write a record, see [`ErrorPolicy`].
+    ///
+    /// [`ErrorPolicy`]: crate::ErrorPolicy
+    pub write_errors: u64,
+}
+
+impl Metrics {
+    /// Returns the number of records logged with `level`.
+    pub const fn records(&self, level: Level) -> u64 {
+        self.records[level as usize - 1]
+    }
+
+    /// Returns the total number of records logged.
+    pub fn total_records(&self) -> u64 {
+        self.records.iter().sum()
+    }
+}
+
+/// Count a record logged with `level`.
+pub(crate) fn add_record(level: Level) {
+    let _ = RECORDS[level as usize - 1].fetch_add(1, Ordering::Relaxed);
+}
+
+/// Count `n` bytes written to an output.
+pub(crate) fn add_bytes_written(n: usize) {
+    let _ = BYTES_WRITTEN.fetch_add(n as u64, Ordering::Relaxed);
+}
+
+/// Count a failure to write a record.
+pub(crate) fn add_write_error() {
+    let _ = WRITE_ERRORS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Returns `true` if the metrics should be logged, i.e. if `interval` has
+/// passed since the last time they were logged (or since the first call).
+pub(crate) fn should_log(interval: Duration) -> bool {
+    /// Time of the first call.
+    static START: OnceLock<Instant> = OnceLock::new();
+    /// Time the metrics were last logged, in milliseconds since `START`.
+    static LAST: AtomicU64 = AtomicU64::new(0);
+
+    let now = START.get_or_init(Instant::now).elapsed().as_millis() as u64;
+    let last = LAST.load(Ordering::Relaxed);
+    // Only one thread gets to log the metrics.
+    now.saturating_sub(last) >= interval.as_millis() as u64
+        && LAST
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+}
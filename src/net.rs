@@ -0,0 +1,265 @@
+//! Network sinks, see the *net* feature.
+//!
+//! Sinks that send the records to a remote host, e.g. Logstash, Vector or
+//! Fluent Bit, for deployments without a sidecar collecting the standard out
+//! and error of the application. Use them with [`Config::add_sink`].
+//!
+//! [`Config::add_sink`]: crate::Config::add_sink
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use log::LevelFilter;
+//! use std_logger::net::{TcpSink, UdpSink};
+//!
+//! # fn main() -> std::io::Result<()> {
+//! std_logger::Config::logfmt()
+//!     .add_sink(TcpSink::new("logs.example.com:5170"), LevelFilter::Info)
+//!     .add_sink(UdpSink::new("127.0.0.1:5171")?, LevelFilter::Debug)
+//!     .init();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::{self, IoSlice, Write};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::{full, write_all};
+
+/// Initial time to wait before reconnecting.
+const MIN_BACKOFF: Duration = Duration::from_millis(100);
+/// Maximum time to wait before reconnecting.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+/// Timeout for connecting and writing, to not block the application for too
+/// long if the remote host is unresponsive.
+const TIMEOUT: Duration = Duration::from_secs(1);
+/// Maximum number of bytes buffered while disconnected.
+const MAX_BUFFERED: usize = 1024 * 1024;
+
+/// Sink that sends records over TCP, one record per line.
+///
+/// The connection is made by a background thread, started when the sink is
+/// created, so logging never waits for resolving the address or connecting.
+/// If connecting fails the thread waits before reconnecting, doubling the wait
+/// time after each failure (starting at 100 milliseconds, up to 30 seconds).
+/// If writing fails the connection is dropped and the thread reconnects.
+///
+/// Records written while disconnected are buffered, up to 1 MB, and send once
+/// connected. Records that don't fit in the buffer are dropped (see
+/// [`dropped_records`]), as the application shouldn't fail because the remote
+/// host is unavailable.
+///
+/// The address is resolved on each connect, so DNS changes are picked up.
+/// Writing blocks for at most one second.
+///
+/// [`dropped_records`]: crate::dropped_records
+#[derive(Debug)]
+pub struct TcpSink {
+    stream: Option<TcpStream>,
+    /// Records written while disconnected.
+    buffered: Vec<u8>,
+    /// Request a new connection from the connecting thread.
+    connect: Sender<()>,
+    /// Connections made by the connecting thread.
+    connected: Receiver<TcpStream>,
+}
+
+impl TcpSink {
+    /// Create a new TCP sink for `addr`, e.g. `logs.example.com:5170`.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the connecting thread can't be started.
+    pub fn new(addr: &str) -> TcpSink {
+        let (connect, requests) = mpsc::channel();
+        let (connections, connected) = mpsc::channel();
+        let addr: Box<str> = addr.into();
+        let _ = thread::Builder::new()
+            .name("std-logger-tcp".into())
+            .spawn(move || connect_thread(&addr, &requests, &connections))
+            .expect("failed to start TCP sink thread");
+        // Connect right away, rather than on the first record.
+        let _ = connect.send(());
+        TcpSink {
+            stream: None,
+            buffered: Vec::new(),
+            connect,
+            connected,
+        }
+    }
+
+    /// Returns the connected stream, if any, writing the buffered records
+    /// once connected.
+    fn stream(&mut self) -> Option<&mut TcpStream> {
+        if self.stream.is_none() {
+            let mut stream = match self.connected.try_recv() {
+                Ok(stream) => stream,
+                Err(TryRecvError::Empty | TryRecvError::Disconnected) => return None,
+            };
+            if !self.buffered.is_empty() {
+                let result = write_all(&mut stream, &[IoSlice::new(&self.buffered)]);
+                self.buffered.clear();
+                if result.is_err() {
+                    self.failed();
+                    return None;
+                }
+            }
+            self.stream = Some(stream);
+        }
+        self.stream.as_mut()
+    }
+
+    /// Drop the connection and request a new one.
+    fn failed(&mut self) {
+        self.stream = None;
+        let _ = self.connect.send(());
+    }
+
+    /// Buffer the record in `bufs` while disconnected, or drop it if the
+    /// buffer is full.
+    fn buffer(&mut self, bufs: &[IoSlice<'_>], len: usize) {
+        if self.buffered.len() + len > MAX_BUFFERED {
+            full::add_dropped();
+            return;
+        }
+        for buf in bufs {
+            self.buffered.extend_from_slice(buf);
+        }
+    }
+}
+
+/// Connect to `addr` for each request, waiting between failed attempts. Stops
+/// once the sink is dropped.
+fn connect_thread(addr: &str, requests: &Receiver<()>, connections: &Sender<TcpStream>) {
+    while requests.recv().is_ok() {
+        let mut backoff = MIN_BACKOFF;
+        let stream = loop {
+            match connect(addr) {
+                Ok(stream) => break stream,
+                Err(_) => match requests.recv_timeout(backoff) {
+                    Ok(()) | Err(RecvTimeoutError::Timeout) => {}
+                    Err(RecvTimeoutError::Disconnected) => return,
+                },
+            }
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        };
+        // Ignore requests made while connecting.
+        while requests.try_recv().is_ok() {}
+        if connections.send(stream).is_err() {
+            return;
+        }
+    }
+}
+
+/// Connect to the first address `addr` resolves to that accepts the
+/// connection.
+fn connect(addr: &str) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addr.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, TIMEOUT) {
+            Ok(stream) => {
+                stream.set_write_timeout(Some(TIMEOUT))?;
+                stream.set_nodelay(true)?;
+                return Ok(stream);
+            }
+            Err(err) => last_err = Some(err),
+        }
+    }
+    Err(last_err.unwrap_or_else(|| io::ErrorKind::AddrNotAvailable.into()))
+}
+
+impl Write for TcpSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let result = match self.stream() {
+            Some(stream) => stream.write_vectored(bufs),
+            None => {
+                self.buffer(bufs, len);
+                return Ok(len);
+            }
+        };
+        match result {
+            Ok(0) | Err(_) => {
+                // Drop the (remainder of the) record and reconnect.
+                self.failed();
+                full::add_dropped();
+                Ok(len)
+            }
+            Ok(n) => Ok(n),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Also writes the buffered records if connected in the meantime.
+        match self.stream() {
+            Some(stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Sink that sends records over UDP, one record per datagram.
+///
+/// Records that don't fit in a single datagram, or that can't be send, are
+/// dropped.
+///
+/// The address is resolved once, when the sink is created, sending a record
+/// never resolves the address.
+#[derive(Debug)]
+pub struct UdpSink {
+    socket: UdpSocket,
+    /// Buffer to combine the parts of a record into a single datagram.
+    buf: Vec<u8>,
+}
+
+impl UdpSink {
+    /// Create a new UDP sink for `addr`, e.g. `127.0.0.1:5171`, using the first
+    /// address `addr` resolves to.
+    pub fn new<A: ToSocketAddrs>(addr: A) -> io::Result<UdpSink> {
+        let addr = addr
+            .to_socket_addrs()?
+            .next()
+            .ok_or(io::ErrorKind::AddrNotAvailable)?;
+        let local: SocketAddr = if addr.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        // Connecting to a `SocketAddr` (rather than `A`) doesn't resolve it
+        // again, and sets the destination of `send`.
+        socket.connect(addr)?;
+        Ok(UdpSink {
+            socket,
+            buf: Vec::new(),
+        })
+    }
+}
+
+impl Write for UdpSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Errors, e.g. because the remote isn't listening, are ignored.
+        let _ = self.socket.send(buf);
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.buf.clear();
+        for buf in bufs {
+            self.buf.extend_from_slice(buf);
+        }
+        let _ = self.socket.send(&self.buf);
+        Ok(self.buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
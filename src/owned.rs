@@ -0,0 +1,227 @@
+//! Owned version of a [`log::Record`], see [`OwnedRecord`].
+
+use std::fmt;
+use std::time::SystemTime;
+
+use log::{kv, Level, Record};
+
+/// Owned version of a [`log::Record`], used by [`RecordTransform`].
+///
+/// This can also be used to buffer records or forward them to another
+/// thread, e.g. in a custom [`Log`] implementation, and log them later using
+/// [`OwnedRecord::log`].
+///
+/// [`RecordTransform`]: crate::RecordTransform
+/// [`Log`]: log::Log
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct OwnedRecord {
+    /// Level of the record.
+    pub level: Level,
+    /// Target of the record.
+    pub target: String,
+    /// Module the record was logged in, if known.
+    pub module_path: Option<String>,
+    /// File the record was logged in, if known.
+    pub file: Option<String>,
+    /// Line the record was logged at, if known.
+    pub line: Option<u32>,
+    /// The formatted message.
+    pub msg: String,
+    /// Key-values of the record, in the order they were logged.
+    pub key_values: Vec<(String, OwnedValue)>,
+    /// Time the record was logged, read from the clock set using
+    /// [`Config::with_clock`] when converting the [`log::Record`]. `None` if
+    /// the *timestamp* feature is disabled.
+    ///
+    /// [`Config::with_clock`]: crate::Config::with_clock
+    pub timestamp: Option<SystemTime>,
+}
+
+/// Owned key-value value, see [`OwnedRecord`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub enum OwnedValue {
+    /// Boolean.
+    Bool(bool),
+    /// Signed integer.
+    I64(i64),
+    /// Unsigned integer.
+    U64(u64),
+    /// Floating point number.
+    F64(f64),
+    /// String, also used for all values not listed above (using their
+    /// `Display` implementation).
+    String(String),
+}
+
+impl OwnedRecord {
+    /// Returns the value of `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&OwnedValue> {
+        self.key_values
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Set `key` to `value`, replacing the existing value if any.
+    pub fn insert<V: Into<OwnedValue>>(&mut self, key: &str, value: V) {
+        let value = value.into();
+        match self.key_values.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.key_values.push((key.to_owned(), value)),
+        }
+    }
+
+    /// Remove `key`, returning its value if any.
+    pub fn remove(&mut self, key: &str) -> Option<OwnedValue> {
+        let index = self.key_values.iter().position(|(k, _)| k == key)?;
+        Some(self.key_values.remove(index).1)
+    }
+
+    /// Log the record using the logger (see [`log::logger`]), using the
+    /// timestamp of the record rather than the current time.
+    pub fn log(&self) {
+        self.with_record(|record| log::logger().log(record));
+    }
+
+    /// Call `f` with the record as [`log::Record`], the timestamp of the
+    /// formatted record is the timestamp of this record.
+    pub(crate) fn with_record<F: FnOnce(&Record)>(&self, f: F) {
+        #[cfg(feature = "timestamp")]
+        if let Some(timestamp) = self.timestamp {
+            return crate::clock::with_time(timestamp, || self.with_record_now(f));
+        }
+        self.with_record_now(f);
+    }
+
+    fn with_record_now<F: FnOnce(&Record)>(&self, f: F) {
+        f(&Record::builder()
+            .args(format_args!("{}", self.msg))
+            .level(self.level)
+            .target(&self.target)
+            .module_path(self.module_path.as_deref())
+            .file(self.file.as_deref())
+            .line(self.line)
+            .key_values(self)
+            .build());
+    }
+}
+
+impl<'a> From<&Record<'a>> for OwnedRecord {
+    fn from(record: &Record<'a>) -> OwnedRecord {
+        let mut collect = Collect(Vec::new());
+        let _ = record.key_values().visit(&mut collect);
+        OwnedRecord {
+            level: record.level(),
+            target: record.target().to_owned(),
+            module_path: record.module_path().map(ToOwned::to_owned),
+            file: record.file().map(ToOwned::to_owned),
+            line: record.line(),
+            msg: record.args().to_string(),
+            key_values: collect.0,
+            #[cfg(feature = "timestamp")]
+            timestamp: Some(crate::clock::now()),
+            #[cfg(not(feature = "timestamp"))]
+            timestamp: None,
+        }
+    }
+}
+
+impl kv::Source for OwnedRecord {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in &self.key_values {
+            visitor.visit_pair(kv::Key::from(&**key), value.to_value())?;
+        }
+        Ok(())
+    }
+}
+
+/// Collects the key-values of a record.
+struct Collect(Vec<(String, OwnedValue)>);
+
+impl<'kvs> kv::VisitSource<'kvs> for Collect {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.0
+            .push((key.as_str().to_owned(), OwnedValue::from(value)));
+        Ok(())
+    }
+}
+
+impl OwnedValue {
+    pub(crate) fn to_value(&self) -> kv::Value<'_> {
+        match self {
+            OwnedValue::Bool(value) => kv::Value::from(*value),
+            OwnedValue::I64(value) => kv::Value::from(*value),
+            OwnedValue::U64(value) => kv::Value::from(*value),
+            OwnedValue::F64(value) => kv::Value::from(*value),
+            OwnedValue::String(value) => kv::Value::from(&**value),
+        }
+    }
+}
+
+impl<'v> From<kv::Value<'v>> for OwnedValue {
+    fn from(value: kv::Value<'v>) -> OwnedValue {
+        if let Some(value) = value.to_borrowed_str() {
+            OwnedValue::String(value.to_owned())
+        } else if let Some(value) = value.to_bool() {
+            OwnedValue::Bool(value)
+        } else if let Some(value) = value.to_i64() {
+            OwnedValue::I64(value)
+        } else if let Some(value) = value.to_u64() {
+            OwnedValue::U64(value)
+        } else if let Some(value) = value.to_f64() {
+            OwnedValue::F64(value)
+        } else {
+            OwnedValue::String(value.to_string())
+        }
+    }
+}
+
+impl From<bool> for OwnedValue {
+    fn from(value: bool) -> OwnedValue {
+        OwnedValue::Bool(value)
+    }
+}
+
+impl From<i64> for OwnedValue {
+    fn from(value: i64) -> OwnedValue {
+        OwnedValue::I64(value)
+    }
+}
+
+impl From<u64> for OwnedValue {
+    fn from(value: u64) -> OwnedValue {
+        OwnedValue::U64(value)
+    }
+}
+
+impl From<f64> for OwnedValue {
+    fn from(value: f64) -> OwnedValue {
+        OwnedValue::F64(value)
+    }
+}
+
+impl From<String> for OwnedValue {
+    fn from(value: String) -> OwnedValue {
+        OwnedValue::String(value)
+    }
+}
+
+impl From<&str> for OwnedValue {
+    fn from(value: &str) -> OwnedValue {
+        OwnedValue::String(value.to_owned())
+    }
+}
+
+impl fmt::Display for OwnedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OwnedValue::Bool(value) => value.fmt(f),
+            OwnedValue::I64(value) => value.fmt(f),
+            OwnedValue::U64(value) => value.fmt(f),
+            OwnedValue::F64(value) => value.fmt(f),
+            OwnedValue::String(value) => value.fmt(f),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Support for logging (SQL) queries, see the [`query!`] macro.
+//!
+//! [`query!`]: crate::query
+
+use std::fmt;
+
+use log::kv;
+
+/// Normalize a SQL `query` by replacing all literals with `?`.
+///
+/// String (`'...'`) and numeric literals are replaced, identifiers (including
+/// quoted ones), keywords, placeholders (e.g. `$1`) and comments are left as
+/// is. This makes it possible to group logs of the same query, regardless of
+/// its values, and avoids logging sensitive values.
+///
+/// The normalization is done when the query is formatted, so no work is done
+/// if the log message is filtered.
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::normalize_query;
+///
+/// let query = normalize_query("SELECT * FROM users WHERE name = 'Thomas' AND age > 30");
+/// assert_eq!(query.to_string(), "SELECT * FROM users WHERE name = ? AND age > ?");
+/// ```
+pub const fn normalize_query(query: &str) -> NormalizedQuery<'_> {
+    NormalizedQuery { query }
+}
+
+/// Normalized query, see [`normalize_query`].
+#[derive(Copy, Clone, Debug)]
+pub struct NormalizedQuery<'a> {
+    query: &'a str,
+}
+
+impl<'a> fmt::Display for NormalizedQuery<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let query = self.query;
+        let bytes = query.as_bytes();
+        // Start of the part of `query` we haven't written yet.
+        let mut start = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                // String literal.
+                b'\'' => {
+                    f.write_str(&query[start..i])?;
+                    f.write_str("?")?;
+                    i = string_end(bytes, i + 1);
+                    start = i;
+                }
+                // Quoted identifier.
+                quote @ (b'"' | b'`') => {
+                    i = find(bytes, i + 1, |b| b == quote).map_or(bytes.len(), |i| i + 1);
+                }
+                // Line comment.
+                b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                    i = find(bytes, i + 2, |b| b == b'\n').unwrap_or(bytes.len());
+                }
+                // Block comment.
+                b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                    i = query[i + 2..]
+                        .find("*/")
+                        .map_or(bytes.len(), |end| i + 2 + end + 2);
+                }
+                // Numeric literal, e.g. `123`, `1.5`, `1e-10` or `0xFF`.
+                b'0'..=b'9' => {
+                    f.write_str(&query[start..i])?;
+                    f.write_str("?")?;
+                    i = number_end(bytes, i);
+                    start = i;
+                }
+                b if is_identifier(b) => {
+                    // Skip the entire identifier, to not replace the numbers
+                    // in `table1` or `$1`.
+                    i = find(bytes, i, |b| !is_identifier(b)).unwrap_or(bytes.len());
+                }
+                _ => i += 1,
+            }
+        }
+        f.write_str(&query[start..])
+    }
+}
+
+impl<'a> kv::ToValue for NormalizedQuery<'a> {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+/// Returns the index of the first byte in `bytes`, starting at `start`, for
+/// which `predicate` returns true.
+fn find<P: Fn(u8) -> bool>(bytes: &[u8], start: usize, predicate: P) -> Option<usize> {
+    bytes[start..]
+        .iter()
+        .position(|b| predicate(*b))
+        .map(|i| start + i)
+}
+
+/// Returns the index after the end of the string literal starting at `i`
+/// (after the opening quote).
+fn string_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            // Escaped quote, e.g. `'it''s'`.
+            b'\'' if bytes.get(i + 1) == Some(&b'\'') => i += 2,
+            b'\'' => return i + 1,
+            // Backslash escape, used by e.g. MySQL.
+            b'\\' => i += 2,
+            _ => i += 1,
+        }
+    }
+    bytes.len()
+}
+
+/// Returns the index after the end of the number starting at `i`.
+fn number_end(bytes: &[u8], mut i: usize) -> usize {
+    while i < bytes.len() {
+        match bytes[i] {
+            // Exponent, e.g. `1e-10`.
+            b'e' | b'E' if matches!(bytes.get(i + 1), Some(b'+' | b'-')) => i += 2,
+            b'.' => i += 1,
+            b if b.is_ascii_alphanumeric() => i += 1,
+            _ => break,
+        }
+    }
+    i
+}
+
+/// Returns `true` if `b` is part of an identifier.
+const fn is_identifier(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_' || b == b'$' || !b.is_ascii()
+}
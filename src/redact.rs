@@ -0,0 +1,131 @@
+//! Redaction of sensitive data, see [`Config::with_redacted_keys`].
+//!
+//! [`Config::with_redacted_keys`]: crate::Config::with_redacted_keys
+
+use log::kv::{self, ToValue};
+use log::Record;
+
+/// Value that replaces redacted data.
+const REDACTED: &str = "[REDACTED]";
+
+/// Redaction rules.
+#[derive(Debug)]
+pub(crate) struct Redact {
+    /// Keys of which the values are redacted.
+    pub(crate) keys: Vec<Box<str>>,
+    /// Patterns that are redacted in the message and values.
+    #[cfg(feature = "redact")]
+    pub(crate) patterns: Vec<regex::Regex>,
+}
+
+impl Redact {
+    pub(crate) const fn new() -> Redact {
+        Redact {
+            keys: Vec::new(),
+            #[cfg(feature = "redact")]
+            patterns: Vec::new(),
+        }
+    }
+
+    /// Returns `true` if there is nothing to redact.
+    pub(crate) fn is_empty(&self) -> bool {
+        #[cfg(feature = "redact")]
+        if !self.patterns.is_empty() {
+            return false;
+        }
+        self.keys.is_empty()
+    }
+
+    /// Call `f` with `record` after redacting it.
+    pub(crate) fn apply<F: FnOnce(&Record)>(&self, record: &Record, f: F) {
+        let mut collect = Collect(Vec::new());
+        let _ = record.key_values().visit(&mut collect);
+        let key_values = KeyValues(
+            collect
+                .0
+                .into_iter()
+                .map(|(key, value)| {
+                    let value = self.redact_value(key.as_str(), value);
+                    (key, value)
+                })
+                .collect(),
+        );
+
+        #[cfg(feature = "redact")]
+        if !self.patterns.is_empty() {
+            let msg = self.redact_str(record.args().to_string());
+            f(&record
+                .to_builder()
+                .args(format_args!("{msg}"))
+                .key_values(&key_values)
+                .build());
+            return;
+        }
+        f(&record.to_builder().key_values(&key_values).build());
+    }
+
+    fn redact_value<'v>(&self, key: &str, value: kv::Value<'v>) -> Value<'v> {
+        if self.keys.iter().any(|k| k.eq_ignore_ascii_case(key)) {
+            return Value::Value(kv::Value::from(REDACTED));
+        }
+        #[cfg(feature = "redact")]
+        if !self.patterns.is_empty() {
+            let string = value.to_string();
+            if self
+                .patterns
+                .iter()
+                .any(|pattern| pattern.is_match(&string))
+            {
+                return Value::String(self.redact_str(string));
+            }
+        }
+        Value::Value(value)
+    }
+
+    /// Replace all matches of the patterns in `string`.
+    #[cfg(feature = "redact")]
+    fn redact_str(&self, mut string: String) -> String {
+        for pattern in &self.patterns {
+            if let std::borrow::Cow::Owned(s) = pattern.replace_all(&string, REDACTED) {
+                string = s;
+            }
+        }
+        string
+    }
+}
+
+/// Collects the key-values of a record.
+struct Collect<'kvs>(Vec<(kv::Key<'kvs>, kv::Value<'kvs>)>);
+
+impl<'kvs> kv::VisitSource<'kvs> for Collect<'kvs> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        self.0.push((key, value));
+        Ok(())
+    }
+}
+
+/// Value after redaction.
+enum Value<'v> {
+    /// Original or fully redacted value.
+    Value(kv::Value<'v>),
+    /// Value with parts redacted.
+    #[cfg(feature = "redact")]
+    String(String),
+}
+
+/// Redacted key-values of a record.
+struct KeyValues<'a>(Vec<(kv::Key<'a>, Value<'a>)>);
+
+impl<'a> kv::Source for KeyValues<'a> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in &self.0 {
+            let value = match value {
+                Value::Value(value) => value.to_value(),
+                #[cfg(feature = "redact")]
+                Value::String(value) => kv::Value::from(&**value),
+            };
+            visitor.visit_pair(key.clone(), value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,416 @@
+//! Configuration file, see [`Config::with_config_file`].
+//!
+//! The file uses TOML, see the [crate level documentation] for the settings.
+//!
+//! [crate level documentation]: crate#reload-feature
+//! [`Config::with_config_file`]: crate::Config::with_config_file
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, OnceLock, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use log::LevelFilter;
+use serde::Deserialize;
+
+use crate::{target_level, Targets};
+
+/// Configuration read from the file.
+#[derive(Debug, Default, Eq, PartialEq)]
+pub(crate) struct FileConfig {
+    /// `level = "..."`.
+    pub(crate) level: Option<LevelFilter>,
+    /// `targets = [...]`.
+    pub(crate) targets: Option<Targets>,
+    /// `format = "..."`, only used when the logger is initialised.
+    pub(crate) format: Option<Format>,
+    /// `[levels]` table, target pattern to level.
+    pub(crate) levels: Vec<(Box<str>, LevelFilter)>,
+    /// `[sinks]` table, file path to level.
+    pub(crate) sinks: Vec<(PathBuf, LevelFilter)>,
+}
+
+/// Format of the records, see [`Config::logfmt`], [`Config::json`] and
+/// [`Config::gcloud`].
+///
+/// [`Config::logfmt`]: crate::Config::logfmt
+/// [`Config::json`]: crate::Config::json
+/// [`Config::gcloud`]: crate::Config::gcloud
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Format {
+    Logfmt,
+    Json,
+    Gcloud,
+}
+
+/// Read the configuration file at `path`.
+pub(crate) fn read(path: &Path) -> io::Result<FileConfig> {
+    let input = fs::read_to_string(path)?;
+    parse(&input).map_err(|(line, msg)| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "invalid logging configuration in '{}' on line {line}: {msg}",
+                path.display()
+            ),
+        )
+    })
+}
+
+/// Parse the configuration in `input`, returning the line number and a
+/// description of the error if it's invalid.
+pub(crate) fn parse(input: &str) -> Result<FileConfig, (usize, String)> {
+    let file: File = toml::from_str(input).map_err(|err| {
+        let start = err.span().map_or(0, |span| span.start);
+        let line = input[..start].matches('\n').count() + 1;
+        (line, err.message().trim().replace('\n', ", "))
+    })?;
+    Ok(FileConfig {
+        level: file.level.map(|level| level.0),
+        targets: file.targets.map(|targets| {
+            if targets.is_empty() {
+                Targets::All
+            } else {
+                Targets::Only(targets.into_iter().map(Into::into).collect())
+            }
+        }),
+        format: file.format,
+        levels: file
+            .levels
+            .into_iter()
+            .map(|(target, level)| (target.into(), level.0))
+            .collect(),
+        sinks: file
+            .sinks
+            .into_iter()
+            .map(|(path, level)| (path, level.0))
+            .collect(),
+    })
+}
+
+/// Contents of the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct File {
+    level: Option<Level>,
+    targets: Option<Vec<String>>,
+    format: Option<Format>,
+    #[serde(default)]
+    levels: BTreeMap<String, Level>,
+    #[serde(default)]
+    sinks: BTreeMap<PathBuf, Level>,
+}
+
+/// Level in the configuration file, e.g. `"debug"`.
+#[derive(Deserialize)]
+#[serde(try_from = "String")]
+struct Level(LevelFilter);
+
+impl TryFrom<String> for Level {
+    type Error = String;
+
+    fn try_from(level: String) -> Result<Level, String> {
+        level
+            .parse()
+            .map(Level)
+            .map_err(|_| format!("invalid level `{level}`"))
+    }
+}
+
+/// Filters that can be changed by reloading the configuration file.
+#[derive(Debug)]
+struct Filters {
+    level: LevelFilter,
+    targets: Targets,
+    /// Levels per target pattern, see [`Reload::filter`].
+    levels: Box<[(Box<str>, LevelFilter)]>,
+}
+
+impl Filters {
+    /// Returns the most verbose level enabled for any target.
+    fn max_level(&self) -> LevelFilter {
+        self.levels
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+}
+
+/// Reloadable configuration of the logger.
+#[derive(Debug)]
+pub(crate) struct Reload {
+    /// Configuration file, `None` if only the admin socket (see
+    /// [`Config::with_admin_socket`]) is used.
+    ///
+    /// [`Config::with_admin_socket`]: crate::Config::with_admin_socket
+    path: Option<PathBuf>,
+    /// Level, targets and levels per target used if not set in the file, i.e.
+    /// from the `Config`. The levels in the file are added to the default
+    /// levels.
+    default_level: LevelFilter,
+    default_targets: Targets,
+    default_levels: Box<[(Box<str>, LevelFilter)]>,
+    /// Most verbose level of the sinks, used to set the maximum level.
+    sinks_level: LevelFilter,
+    filters: RwLock<Filters>,
+    /// Modification time of the file when it was last read.
+    modified: Mutex<Option<SystemTime>>,
+    /// Set when the logger is shut down, to stop the watch thread.
+    stop: AtomicBool,
+}
+
+impl Reload {
+    /// Create a new `Reload`, using the configuration `file` (if it was read
+    /// successfully).
+    pub(crate) fn new(
+        path: Option<PathBuf>,
+        default_level: LevelFilter,
+        default_targets: Targets,
+        default_levels: Box<[(Box<str>, LevelFilter)]>,
+        sinks_level: LevelFilter,
+        file: Option<&FileConfig>,
+    ) -> Reload {
+        let modified = path.as_deref().and_then(modified);
+        let reload = Reload {
+            path,
+            filters: RwLock::new(Filters {
+                level: default_level,
+                targets: default_targets.clone(),
+                levels: default_levels.clone(),
+            }),
+            default_level,
+            default_targets,
+            default_levels,
+            sinks_level,
+            modified: Mutex::new(modified),
+            stop: AtomicBool::new(false),
+        };
+        if let Some(file) = file {
+            reload.apply(file);
+        }
+        reload
+    }
+
+    /// Apply the configuration in `file`.
+    fn apply(&self, file: &FileConfig) {
+        let filters = Filters {
+            level: file.level.unwrap_or(self.default_level),
+            targets: file
+                .targets
+                .clone()
+                .unwrap_or_else(|| self.default_targets.clone()),
+            levels: self
+                .default_levels
+                .iter()
+                .chain(&file.levels)
+                .cloned()
+                .collect(),
+        };
+        *self.filters.write().unwrap_or_else(PoisonError::into_inner) = filters;
+    }
+
+    /// Returns the level filter for standard out and error for `target`.
+    ///
+    /// This uses the level of the longest pattern in the `[levels]` table that
+    /// matches `target`, or the `level` if none match.
+    pub(crate) fn filter(&self, target: &str) -> LevelFilter {
+        let filters = self.filters.read().unwrap_or_else(PoisonError::into_inner);
+        target_level(&filters.levels, target, filters.level)
+    }
+
+    /// Returns `true` if the `target` should be logged, see
+    /// [`Targets::should_log`].
+    pub(crate) fn should_log(&self, target: &str, always_log: &[Box<str>]) -> bool {
+        let filters = self.filters.read().unwrap_or_else(PoisonError::into_inner);
+        filters.targets.should_log(target, always_log)
+    }
+
+    /// Returns the most verbose level enabled for any output.
+    pub(crate) fn max_level(&self) -> LevelFilter {
+        let filters = self.filters.read().unwrap_or_else(PoisonError::into_inner);
+        filters.max_level().max(self.sinks_level)
+    }
+
+    /// Set the level of standard out and error to `level` for `target`
+    /// pattern, or for all targets if `target` is `None`.
+    #[cfg(feature = "admin")]
+    pub(crate) fn set_level(&self, target: Option<&str>, level: LevelFilter) {
+        let mut filters = self.filters.write().unwrap_or_else(PoisonError::into_inner);
+        match target {
+            None => filters.level = level,
+            Some(target) => match filters.levels.iter_mut().find(|(p, _)| &**p == target) {
+                Some((_, l)) => *l = level,
+                None => {
+                    let mut levels = std::mem::take(&mut filters.levels).into_vec();
+                    levels.push((target.into(), level));
+                    filters.levels = levels.into_boxed_slice();
+                }
+            },
+        }
+        drop(filters);
+        self.set_max_level();
+    }
+
+    /// Reset the levels and targets to the configuration file, or to the
+    /// configuration of the logger if there is no file.
+    #[cfg(feature = "admin")]
+    pub(crate) fn reset(&self) -> io::Result<()> {
+        if self.path.is_some() {
+            return self.reload();
+        }
+        *self.filters.write().unwrap_or_else(PoisonError::into_inner) = Filters {
+            level: self.default_level,
+            targets: self.default_targets.clone(),
+            levels: self.default_levels.clone(),
+        };
+        self.set_max_level();
+        Ok(())
+    }
+
+    /// Returns the current levels as directives, e.g. `info,my_crate=debug`.
+    #[cfg(feature = "admin")]
+    pub(crate) fn directives(&self) -> String {
+        use std::fmt::Write;
+
+        let filters = self.filters.read().unwrap_or_else(PoisonError::into_inner);
+        let mut directives = filters.level.as_str().to_ascii_lowercase();
+        for (pattern, level) in filters.levels.iter() {
+            let _ = write!(
+                directives,
+                ",{pattern}={}",
+                level.as_str().to_ascii_lowercase()
+            );
+        }
+        directives
+    }
+
+    /// Set the maximum level of the `log` crate to match the filters.
+    fn set_max_level(&self) {
+        // Audit records (logged at the info level) are always logged.
+        log::set_max_level(self.max_level().max(LevelFilter::Info));
+    }
+
+    /// Read the configuration file again.
+    fn reload(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Err(no_config_file());
+        };
+        *self.modified.lock().unwrap_or_else(PoisonError::into_inner) = modified(path);
+        let file = read(path)?;
+        self.apply(&file);
+        self.set_max_level();
+        log::info!(path = path.display().to_string(); "reloaded logging configuration");
+        Ok(())
+    }
+
+    /// Read the configuration file again if it was modified since it was last
+    /// read, logging an error if it's invalid.
+    fn reload_if_modified(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let modified = modified(path);
+        if modified == *self.modified.lock().unwrap_or_else(PoisonError::into_inner) {
+            return;
+        }
+        if let Err(err) = self.reload() {
+            log::error!("failed to reload logging configuration: {err}");
+        }
+    }
+}
+
+/// Returns the modification time of the file at `path`, if available.
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// The reloadable configuration of the logger, if any.
+static RELOAD: OnceLock<Arc<Reload>> = OnceLock::new();
+
+/// Thread watching the configuration file, see [`watch`].
+static WATCHER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+
+/// Set the reloadable configuration of the logger.
+pub(crate) fn set(reload: Arc<Reload>) {
+    let _ = RELOAD.set(reload);
+}
+
+/// Start a thread that reloads the configuration file every `interval` if it
+/// was modified, see [`Config::with_config_reload`].
+///
+/// [`Config::with_config_reload`]: crate::Config::with_config_reload
+pub(crate) fn watch(reload: Arc<Reload>, interval: Duration) -> io::Result<()> {
+    let handle = thread::Builder::new()
+        .name("std-logger-reload".into())
+        .spawn(move || {
+            while !reload.stop.load(Ordering::Relaxed) {
+                thread::park_timeout(interval);
+                if !reload.stop.load(Ordering::Relaxed) {
+                    reload.reload_if_modified();
+                }
+            }
+        })?;
+    *WATCHER.lock().unwrap_or_else(PoisonError::into_inner) = Some(handle);
+    Ok(())
+}
+
+/// Stop the thread watching the configuration file, if any.
+pub(crate) fn stop() {
+    let Some(reload) = RELOAD.get() else {
+        return;
+    };
+    reload.stop.store(true, Ordering::Relaxed);
+    let handle = WATCHER
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    if let Some(handle) = handle {
+        handle.thread().unpark();
+        let _ = handle.join();
+    }
+}
+
+/// Forget the thread watching the configuration file, without stopping it,
+/// as it doesn't exist in the child process after `fork`.
+#[cfg(unix)]
+pub(crate) fn forget_watcher() {
+    if let Ok(mut watcher) = WATCHER.try_lock() {
+        if let Some(handle) = watcher.take() {
+            std::mem::forget(handle);
+        }
+    }
+}
+
+/// Read the configuration file, set using [`Config::with_config_file`] or the
+/// `LOG_CONFIG` environment variable, again.
+///
+/// This only changes the levels and targets, the format and sinks are only
+/// read when the logger is initialised. If the file is invalid the current
+/// configuration is kept. This can be used to reload the configuration on a
+/// signal, e.g. `SIGHUP`, rather than watching the file using
+/// [`Config::with_config_reload`].
+///
+/// Returns an error if the logger wasn't initialised with a configuration file
+/// or if the file can't be read or is invalid.
+///
+/// [`Config::with_config_file`]: crate::Config::with_config_file
+/// [`Config::with_config_reload`]: crate::Config::with_config_reload
+pub fn reload_config() -> io::Result<()> {
+    match RELOAD.get() {
+        Some(reload) => reload.reload(),
+        None => Err(no_config_file()),
+    }
+}
+
+/// Error returned if the logger wasn't initialised with a configuration file.
+fn no_config_file() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        "logger not initialised with a configuration file",
+    )
+}
@@ -0,0 +1,138 @@
+//! In-memory ring buffer sink, see [`RingBufferSink`].
+
+use std::collections::VecDeque;
+use std::io::{self, IoSlice, Write};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+
+use crate::stderr;
+
+/// Sink that keeps the last records in memory, a "flight recorder".
+///
+/// Add the sink using [`Config::add_sink`] with a low filter, e.g.
+/// [`LevelFilter::Trace`], to keep verbose records of the recent past without
+/// writing them to standard error. Once something goes wrong the records can
+/// be written using [`RingBufferSink::dump`], or automatically when the thread
+/// panics, see [`RingBufferSink::dump_on_panic`].
+///
+/// The sink can be cloned, all clones share the same records.
+///
+/// [`Config::add_sink`]: crate::Config::add_sink
+/// [`LevelFilter::Trace`]: log::LevelFilter::Trace
+///
+/// # Examples
+///
+/// ```
+/// use log::{debug, LevelFilter};
+/// use std_logger::RingBufferSink;
+///
+/// let recorder = RingBufferSink::new(1000);
+/// std_logger::Config::logfmt()
+///     .add_sink(recorder.clone().dump_on_panic(), LevelFilter::Trace)
+///     .init();
+///
+/// debug!("not written to standard error, but kept in memory");
+/// assert_eq!(recorder.records().len(), 1);
+///
+/// // Write the records to standard error on demand.
+/// recorder.dump(std::io::stderr()).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct RingBufferSink {
+    records: Arc<Mutex<VecDeque<Box<[u8]>>>>,
+    capacity: usize,
+    dump_on_panic: bool,
+}
+
+impl RingBufferSink {
+    /// Create a new sink that keeps the last `capacity` records.
+    pub fn new(capacity: usize) -> RingBufferSink {
+        RingBufferSink {
+            records: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+            dump_on_panic: false,
+        }
+    }
+
+    /// Write the records to standard error when a record is written while the
+    /// thread is panicking, e.g. the panic itself (with the *log-panic*
+    /// feature).
+    ///
+    /// The records are written before the record written while panicking,
+    /// which itself isn't dumped as the logger writes it to standard error.
+    /// The records are removed once written, so that they're only written
+    /// once.
+    pub fn dump_on_panic(mut self) -> RingBufferSink {
+        self.dump_on_panic = true;
+        self
+    }
+
+    /// Returns a copy of the records kept, oldest first.
+    pub fn records(&self) -> Vec<Vec<u8>> {
+        self.lock().iter().map(|record| record.to_vec()).collect()
+    }
+
+    /// Write the records kept to `output`, oldest first.
+    pub fn dump<W: Write>(&self, mut output: W) -> io::Result<()> {
+        for record in self.lock().iter() {
+            output.write_all(record)?;
+        }
+        output.flush()
+    }
+
+    /// Remove all records.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, VecDeque<Box<[u8]>>> {
+        self.records.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn push(&self, record: Box<[u8]>) {
+        let mut records = self.lock();
+        if self.capacity == 0 {
+            return;
+        }
+        if records.len() == self.capacity {
+            drop(records.pop_front());
+        }
+        records.push_back(record);
+
+        if self.dump_on_panic && thread::panicking() {
+            // The logger writes the sinks before standard error while
+            // panicking, so this is written before the latest record, which is
+            // written to standard error by the logger.
+            let mut output = stderr();
+            let dumped = records.len() - 1;
+            for record in records.drain(..).take(dumped) {
+                // Nothing we can do about errors while panicking.
+                if output.write_all(&record).is_err() {
+                    break;
+                }
+            }
+        }
+    }
+}
+
+impl Write for RingBufferSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.push(buf.into());
+        Ok(buf.len())
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        // Keep the parts of a record together.
+        let mut record = Vec::with_capacity(bufs.iter().map(|buf| buf.len()).sum());
+        for buf in bufs {
+            record.extend_from_slice(buf);
+        }
+        let len = record.len();
+        self.push(record.into_boxed_slice());
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
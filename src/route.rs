@@ -0,0 +1,83 @@
+//! Routing of targets to their own output, see [`Config::route_target`].
+//!
+//! [`Config::route_target`]: crate::Config::route_target
+
+use std::fs::File;
+use std::io::{self, IoSlice};
+use std::sync::{Mutex, PoisonError};
+
+use crate::write_all;
+
+/// Output for the records of a target, see [`Config::route_target`].
+///
+/// [`Config::route_target`]: crate::Config::route_target
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Route {
+    /// Standard out, the same output as requests (see [`REQUEST_TARGET`]).
+    /// This includes the file descriptor set using
+    /// [`Config::with_request_fd`].
+    ///
+    /// [`REQUEST_TARGET`]: crate::REQUEST_TARGET
+    /// [`Config::with_request_fd`]: crate::Config::with_request_fd
+    Stdout,
+    /// Standard error.
+    Stderr,
+    /// A file, e.g. opened in append mode or a pipe.
+    File(File),
+}
+
+/// Outputs of the routed targets.
+#[derive(Debug)]
+pub(crate) struct Routes(Vec<(Box<str>, Output)>);
+
+/// Output of a routed target.
+#[derive(Debug)]
+pub(crate) enum Output {
+    Stdout,
+    Stderr,
+    File(Mutex<File>),
+}
+
+impl Routes {
+    pub(crate) const fn new() -> Routes {
+        Routes(Vec::new())
+    }
+
+    /// Route the records with `target` to `route`, replacing the existing
+    /// route for `target`, if any.
+    pub(crate) fn add(&mut self, target: Box<str>, route: Route) {
+        let output = match route {
+            Route::Stdout => Output::Stdout,
+            Route::Stderr => Output::Stderr,
+            Route::File(file) => Output::File(Mutex::new(file)),
+        };
+        self.0.retain(|(t, _)| *t != target);
+        self.0.push((target, output));
+    }
+
+    /// Returns the routed targets.
+    pub(crate) fn targets(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(target, _)| &**target)
+    }
+
+    /// Returns the output for `target`, if it's routed.
+    #[inline]
+    pub(crate) fn get(&self, target: &str) -> Option<&Output> {
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0
+            .iter()
+            .find(|(t, _)| &**t == target)
+            .map(|(_, output)| output)
+    }
+}
+
+/// Write `bufs` to `file`, records are not batched.
+pub(crate) fn write_file(file: &Mutex<File>, bufs: &[IoSlice]) -> io::Result<()> {
+    // A panic while holding the lock doesn't leave the file in an invalid
+    // state, so we can ignore the poisoning.
+    let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+    write_all(&mut *file, bufs)
+}
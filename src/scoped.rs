@@ -0,0 +1,75 @@
+//! Logger that adds a component to all records, see [`ScopedLogger`].
+
+use log::{kv, Log, Metadata, Record};
+
+/// [`Log`] implementation that adds a `component` key-value to all records and
+/// passes them to the global logger.
+///
+/// This can be used to attribute records to a whole subsystem, e.g. all
+/// records logged by the ingestion pipeline, without adding the key-value to
+/// every call site. It works with any logger set as global logger, but is
+/// most useful with the one of this crate.
+///
+/// # Examples
+///
+/// ```
+/// use log::info;
+/// use std_logger::ScopedLogger;
+///
+/// static INGEST: ScopedLogger = ScopedLogger::new("ingest");
+///
+/// # fn main() {
+/// std_logger::Config::logfmt().init();
+///
+/// // Logged as:
+/// // `lvl="INFO" msg="processed batch" target="..." module="..." component="ingest" records=100`.
+/// info!(logger: INGEST, records = 100; "processed batch");
+/// # }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct ScopedLogger {
+    component: &'static str,
+}
+
+impl ScopedLogger {
+    /// Create a new logger adding `component` to all records.
+    pub const fn new(component: &'static str) -> ScopedLogger {
+        ScopedLogger { component }
+    }
+
+    /// Returns the component.
+    pub const fn component(&self) -> &'static str {
+        self.component
+    }
+}
+
+impl Log for ScopedLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        log::logger().enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        let key_values = KeyValues {
+            component: self.component,
+            inner: record.key_values(),
+        };
+        log::logger().log(&record.to_builder().key_values(&key_values).build());
+    }
+
+    fn flush(&self) {
+        log::logger().flush();
+    }
+}
+
+/// The `component` followed by the key-values of the record.
+struct KeyValues<'a> {
+    component: &'static str,
+    inner: &'a dyn kv::Source,
+}
+
+impl<'a> kv::Source for KeyValues<'a> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        visitor.visit_pair(kv::Key::from("component"), kv::Value::from(self.component))?;
+        self.inner.visit(visitor)
+    }
+}
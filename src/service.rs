@@ -0,0 +1,254 @@
+//! Request logging for (HTTP) services, see the *service* feature.
+//!
+//! This module wires the pieces needed to log the requests of a service
+//! together:
+//!
+//!  * [`RequestId`]: a unique id for each request.
+//!  * [`Context`]: adds the id of the current request, as `request_id`, to all
+//!    records logged while handling the request (on the same thread), including
+//!    panics (with the *log-panic* feature).
+//!  * [`Request`]: the scope of a request, logs the access log (using the
+//!    [`request`] macro) once the request is finished or dropped.
+//!  * [`handle`]: middleware that wraps a request handler, catching panics and
+//!    turning them into a `500` response.
+//!
+//! The module is not tied to a specific HTTP library, the handler only needs
+//! to return the status code of the response.
+//!
+//! [`request`]: crate::request
+//!
+//! # Examples
+//!
+//! ```
+//! use log::warn;
+//! use std_logger::service;
+//!
+//! fn main() {
+//!     // Initialise the logger with `service::Context`.
+//!     service::init();
+//!
+//!     // In the request handling code of the HTTP server.
+//!     let status = service::handle("GET", "/", |_request| {
+//!         // Logged with the `request_id` of the request.
+//!         warn!("page not found");
+//!         404
+//!     });
+//!     // Logs the access log to standard out:
+//!     // `msg="GET /" target="request" module="std_logger::service" request_id=... method="GET" path="/" status=404 took="..."`.
+//!     assert_eq!(status, 404);
+//! }
+//! ```
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::marker::PhantomData;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use log::{kv, SetLoggerError};
+
+use crate::{request, value, Config};
+
+/// Initialise the logger using logfmt, adding the [`Context`] to all records.
+///
+/// # Panics
+///
+/// This will panic if the logger fails to initialise. Use [`try_init`] if you
+/// want to handle the error yourself.
+pub fn init() {
+    try_init().unwrap_or_else(|err| panic!("failed to initialise the logger: {err}"));
+}
+
+/// Try to initialise the logger using logfmt, adding the [`Context`] to all
+/// records.
+///
+/// Unlike [`init`] this doesn't panic when the logger fails to initialise.
+pub fn try_init() -> Result<(), SetLoggerError> {
+    Config::logfmt().with_kvs(Context).try_init()
+}
+
+/// Handle a request using `handler`, logging the access log once it's done.
+///
+/// The `handler` must return the status code of the response. If `handler`
+/// panics the panic is caught and the request is logged (and returned) with
+/// status code `500`. The panic itself is logged by the panic hook, e.g. the
+/// one installed by the *log-panic* feature.
+pub fn handle<F>(method: &str, path: &str, handler: F) -> u16
+where
+    F: FnOnce(&Request) -> u16,
+{
+    let request = Request::start(method, path);
+    let status = panic::catch_unwind(AssertUnwindSafe(|| handler(&request))).unwrap_or(500);
+    request.finish(status);
+    status
+}
+
+/// Unique id of a request.
+///
+/// Logged as an (unsigned) integer, e.g. `request_id=5740036914836717830`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RequestId(u64);
+
+impl RequestId {
+    /// Create a new id, unique within the process.
+    ///
+    /// If the output is deterministic (see
+    /// [`Config::with_deterministic_output`]) the ids are sequential, starting
+    /// at zero.
+    ///
+    /// [`Config::with_deterministic_output`]: crate::Config::with_deterministic_output
+    pub fn new() -> RequestId {
+        /// Random start of the ids, to make them unique between processes (with
+        /// a high probability).
+        static SEED: OnceLock<u64> = OnceLock::new();
+        static COUNT: AtomicU64 = AtomicU64::new(0);
+
+        let n = COUNT.fetch_add(1, Ordering::Relaxed);
+        if crate::is_deterministic() {
+            return RequestId(n);
+        }
+        let seed = *SEED.get_or_init(|| RandomState::new().build_hasher().finish());
+        // Multiplying by an odd number doesn't create duplicates, but does
+        // make the ids look less sequential.
+        RequestId(seed.wrapping_add(n.wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+    }
+
+    /// Returns the id of the request currently being handled on this thread, if
+    /// any.
+    pub fn current() -> Option<RequestId> {
+        CURRENT.with(Cell::get)
+    }
+}
+
+impl Default for RequestId {
+    fn default() -> RequestId {
+        RequestId::new()
+    }
+}
+
+impl fmt::Display for RequestId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl kv::ToValue for RequestId {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from(self.0)
+    }
+}
+
+thread_local! {
+    /// Id of the request currently being handled on this thread.
+    static CURRENT: Cell<Option<RequestId>> = const { Cell::new(None) };
+}
+
+/// Key-values of the current request, use with [`Config::with_kvs`].
+///
+/// Adds `request_id` to all records logged while a [`Request`] is handled on
+/// the same thread. Records logged outside of a request don't get any
+/// key-values.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Context;
+
+impl kv::Source for Context {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        match RequestId::current() {
+            Some(id) => visitor.visit_pair(kv::Key::from("request_id"), kv::Value::from(id.0)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Scope of a request being handled.
+///
+/// While the request is alive its id is available using
+/// [`RequestId::current`], which is used by [`Context`]. Once the request is
+/// [finished] the access log is logged, using the [`request`] macro, with the
+/// `request_id`, `method`, `path`, `status` and the time it `took` to handle
+/// the request. If the request is dropped without being finished, e.g. when
+/// the handler panics, it's logged with status code `500`.
+///
+/// Requests can be nested, after finishing the inner request the outer request
+/// is the current request again. Because the request id is stored per thread
+/// a `Request` can't be send to another thread.
+///
+/// [finished]: Request::finish
+/// [`request`]: crate::request
+#[derive(Debug)]
+#[must_use = "the request is logged once it's finished or dropped"]
+pub struct Request {
+    id: RequestId,
+    method: Box<str>,
+    path: Box<str>,
+    start: Instant,
+    /// Request that was current before this request started.
+    previous: Option<RequestId>,
+    finished: bool,
+    /// The request id is stored in a thread local, so the request must not
+    /// move to another thread.
+    not_send: PhantomData<*const ()>,
+}
+
+impl Request {
+    /// Start handling a request with a new [`RequestId`].
+    pub fn start(method: &str, path: &str) -> Request {
+        Request::with_id(RequestId::new(), method, path)
+    }
+
+    /// Start handling a request with `id`, e.g. from the `X-Request-Id` header
+    /// of an upstream proxy.
+    pub fn with_id(id: RequestId, method: &str, path: &str) -> Request {
+        let previous = CURRENT.with(|current| current.replace(Some(id)));
+        Request {
+            id,
+            method: method.into(),
+            path: path.into(),
+            start: Instant::now(),
+            previous,
+            finished: false,
+            not_send: PhantomData,
+        }
+    }
+
+    /// Returns the id of the request.
+    pub const fn id(&self) -> RequestId {
+        self.id
+    }
+
+    /// Finish the request with response `status` code and log it.
+    pub fn finish(mut self, status: u16) {
+        self.log(status);
+    }
+
+    fn log(&mut self, status: u16) {
+        self.finished = true;
+        let took = if crate::is_deterministic() {
+            Duration::ZERO
+        } else {
+            self.start.elapsed()
+        };
+        // Restore the context first to not log the `request_id` twice.
+        CURRENT.with(|current| current.set(self.previous));
+        request!(
+            request_id = self.id,
+            method = &*self.method,
+            path = &*self.path,
+            status = status,
+            took = value::duration(took);
+            "{} {}", self.method, self.path
+        );
+    }
+}
+
+impl Drop for Request {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.log(500);
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Asserting on the log output in tests, see the *test* feature.
+//!
+//! [`capture`] starts capturing the records logged on the current thread,
+//! which can then be inspected using [`Capture::records`]. Because the records
+//! are captured per thread, tests running in parallel (each on their own
+//! thread) don't see each other's records.
+//!
+//! The captured records are formatted using logfmt, regardless of the format
+//! of the logger, and parsed using the [std-logger-parser] crate.
+//!
+//! [std-logger-parser]: https://crates.io/crates/std-logger-parser
+//!
+//! # Examples
+//!
+//! ```
+//! use log::{debug, Level};
+//! use std_logger::test;
+//!
+//! fn add(a: u32, b: u32) -> u32 {
+//!     debug!(a = a, b = b; "adding");
+//!     a + b
+//! }
+//!
+//! # fn main() {
+//! let capture = test::capture();
+//! assert_eq!(add(1, 2), 3);
+//!
+//! let records = capture.records();
+//! assert_eq!(records.len(), 1);
+//! assert_eq!(records[0].level, Level::Debug);
+//! assert_eq!(records[0].msg, "adding");
+//! # }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::io::IoSlice;
+use std::marker::PhantomData;
+
+use log::{kv, LevelFilter, Record as LogRecord};
+pub use std_logger_parser::{Record, Value};
+
+use crate::format::{Buffer, Format, LogFmt, Options, BUFS_SIZE};
+use crate::Config;
+
+thread_local! {
+    /// Number of `Capture`s alive on this thread.
+    static ACTIVE: Cell<usize> = const { Cell::new(0) };
+    /// Records captured on this thread, formatted using logfmt.
+    static CAPTURED: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Start capturing the records logged on the current thread.
+///
+/// If no logger is set this initialises the logger using [`Config::logfmt`],
+/// ignoring errors. The maximum log level is set to trace, so that records of
+/// all levels are captured, but only the records accepted by the logger's own
+/// filter are written to standard error. Records of targets filtered out by the
+/// logger, e.g. using the `LOG_TARGET` environment variable, aren't captured.
+///
+/// Note that records are only captured if the logger is set by this crate.
+pub fn capture() -> Capture {
+    let _ = Config::logfmt().try_init();
+    log::set_max_level(LevelFilter::Trace);
+    if ACTIVE.with(|active| active.replace(active.get() + 1)) == 0 {
+        CAPTURED.with(|captured| captured.borrow_mut().clear());
+    }
+    Capture {
+        not_send: PhantomData,
+    }
+}
+
+/// Captured records of the current thread, see [`capture`].
+///
+/// Records are captured until the `Capture` is dropped. Multiple `Capture`s on
+/// the same thread share the same records.
+#[derive(Debug)]
+#[must_use = "records are only captured while the capture is alive"]
+pub struct Capture {
+    /// The records are captured per thread, so the capture must not move to
+    /// another thread.
+    not_send: PhantomData<*const ()>,
+}
+
+impl Capture {
+    /// Returns the records captured so far.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the records can't be parsed, which indicates a bug in
+    /// this crate.
+    pub fn records(&self) -> Vec<Record> {
+        CAPTURED.with(|captured| {
+            std_logger_parser::parse(&**captured.borrow())
+                .collect::<Result<Vec<Record>, _>>()
+                .unwrap_or_else(|err| panic!("failed to parse captured records: {err}"))
+        })
+    }
+
+    /// Remove the records captured so far.
+    pub fn clear(&self) {
+        CAPTURED.with(|captured| captured.borrow_mut().clear());
+    }
+}
+
+impl Drop for Capture {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| active.set(active.get() - 1));
+    }
+}
+
+/// Returns `true` if records are captured on this thread.
+pub(crate) fn is_capturing() -> bool {
+    ACTIVE.with(Cell::get) != 0
+}
+
+/// Capture `record`, with the key-values `kvs` of the logger.
+pub(crate) fn add<Kvs: kv::Source>(record: &LogRecord, kvs: &Kvs, options: &Options) {
+    let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
+    let mut buf = Buffer::new();
+    let bufs = LogFmt::format(&mut bufs, &mut buf, record, kvs, options);
+    CAPTURED.with(|captured| {
+        let mut captured = captured.borrow_mut();
+        for buf in bufs {
+            captured.extend_from_slice(buf);
+        }
+    });
+}
@@ -7,8 +7,8 @@ use std::{env, fmt, panic, str};
 use log::{debug, error, info, kv, trace, warn, Level, LevelFilter, Record};
 
 use crate::config::{get_log_targets, get_max_level, NoKvs};
-use crate::format::{self, Format, Gcloud, Json, LogFmt};
-use crate::{request, Targets, BUFS_SIZE, LOG_OUTPUT, PANIC_TARGET, REQUEST_TARGET};
+use crate::format::{self, Format, Gcloud, Json, LogFmt, Options};
+use crate::{request, Targets, TypeHint, BUFS_SIZE, LOG_OUTPUT, PANIC_TARGET, REQUEST_TARGET};
 
 /// Macro to create a group of sequential tests.
 macro_rules! sequential_tests {
@@ -124,6 +124,298 @@ sequential_tests! {
 
         assert_eq!(got_length, want.len(), "the number of log messages got differs from the amount of messages wanted");
     }
+
+    fn batch_output() {
+        LOG_OUTPUT.lock().unwrap().clear();
+
+        // Use a new thread to get new batches.
+        std::thread::spawn(|| {
+            let policy = crate::BatchPolicy::new(2, Duration::from_secs(3600));
+            let add = |target, record: &[u8]| {
+                assert!(crate::batch::add(target, target == REQUEST_TARGET, Level::Info, &[IoSlice::new(record)], policy, crate::FullPolicy::Block));
+            };
+            add("target", b"record1\n");
+            add(REQUEST_TARGET, b"request1\n");
+            assert!(LOG_OUTPUT.lock().unwrap().is_empty());
+            add("target", b"record2\n");
+            assert_eq!(*LOG_OUTPUT.lock().unwrap(), [b"record1\nrecord2\n"]);
+
+            // Panics are written directly, after the batches.
+            add("target", b"record3\n");
+            assert!(!crate::batch::add(PANIC_TARGET, false, Level::Error, &[IoSlice::new(b"panic\n")], policy, crate::FullPolicy::Block));
+            crate::batch::flush();
+            add(REQUEST_TARGET, b"request2\n");
+            // Written when the thread stops.
+        })
+        .join()
+        .unwrap();
+
+        let got = take(&mut *(LOG_OUTPUT.lock().unwrap()));
+        let want: [&[u8]; 4] = [
+            b"record1\nrecord2\n",
+            b"request1\n",
+            b"record3\n",
+            b"request2\n",
+        ];
+        assert_eq!(got, want);
+    }
+
+    fn batch_flush_level() {
+        LOG_OUTPUT.lock().unwrap().clear();
+
+        std::thread::spawn(|| {
+            let policy = crate::BatchPolicy::new(100, Duration::from_secs(3600))
+                .with_flush_level(LevelFilter::Warn);
+            let add = |target, level, record: &[u8]| {
+                assert!(crate::batch::add(target, target == REQUEST_TARGET, level, &[IoSlice::new(record)], policy, crate::FullPolicy::Block));
+            };
+            add("target", Level::Info, b"record1\n");
+            add(REQUEST_TARGET, Level::Info, b"request1\n");
+            assert!(LOG_OUTPUT.lock().unwrap().is_empty());
+            // Writes both batches, including the record itself.
+            add("target", Level::Warn, b"record2\n");
+            assert_eq!(*LOG_OUTPUT.lock().unwrap(), [&b"request1\n"[..], b"record1\nrecord2\n"]);
+
+            // Requests are handled the same way.
+            add(REQUEST_TARGET, Level::Error, b"request2\n");
+            assert_eq!(LOG_OUTPUT.lock().unwrap().len(), 3);
+
+            // Batch everything.
+            let policy = policy.with_flush_level(LevelFilter::Off);
+            assert!(crate::batch::add("target", false, Level::Error, &[IoSlice::new(b"record3\n")], policy, crate::FullPolicy::Block));
+            assert_eq!(LOG_OUTPUT.lock().unwrap().len(), 3);
+        })
+        .join()
+        .unwrap();
+
+        let got = take(&mut *(LOG_OUTPUT.lock().unwrap()));
+        let want: [&[u8]; 4] = [b"request1\n", b"record1\nrecord2\n", b"request2\n", b"record3\n"];
+        assert_eq!(got, want);
+    }
+
+    fn sink_output() {
+        use std::sync::Arc;
+
+        use log::Log;
+
+        #[derive(Clone, Default)]
+        struct Shared(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Shared {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        LOG_OUTPUT.lock().unwrap().clear();
+
+        let debug = Shared::default();
+        let error = Shared::default();
+        let logger = crate::Config::logfmt()
+            .with_call_location(false)
+            .add_sink(debug.clone(), LevelFilter::Debug)
+            .add_sink(error.clone(), LevelFilter::Error)
+            .into_logger();
+        assert_eq!(logger.filter, LevelFilter::Info);
+        assert_eq!(logger.max_level(), LevelFilter::Debug);
+
+        for (level, msg) in [(Level::Trace, "trace"), (Level::Debug, "debug"), (Level::Info, "info"), (Level::Error, "error")] {
+            logger.log(&Record::builder().args(format_args!("{msg}")).level(level).target("sink").build());
+        }
+
+        let lines = |output: Vec<u8>| -> Vec<String> {
+            let output = String::from_utf8(output).unwrap();
+            // Remove the timestamp.
+            output.lines().map(|line| line[line.find("lvl=").unwrap()..].to_owned()).collect()
+        };
+        let got: Vec<String> = take(&mut *(LOG_OUTPUT.lock().unwrap())).into_iter().flat_map(lines).collect();
+        assert_eq!(got, [
+            "lvl=\"INFO\" msg=\"info\" target=\"sink\" module=\"\"",
+            "lvl=\"ERROR\" msg=\"error\" target=\"sink\" module=\"\"",
+        ]);
+        assert_eq!(lines(take(&mut *debug.0.lock().unwrap())), [
+            "lvl=\"DEBUG\" msg=\"debug\" target=\"sink\" module=\"\"",
+            "lvl=\"INFO\" msg=\"info\" target=\"sink\" module=\"\"",
+            "lvl=\"ERROR\" msg=\"error\" target=\"sink\" module=\"\"",
+        ]);
+        assert_eq!(lines(take(&mut *error.0.lock().unwrap())), [
+            "lvl=\"ERROR\" msg=\"error\" target=\"sink\" module=\"\"",
+        ]);
+    }
+
+    fn should_get_log_level_and_targets_from_custom_env() {
+        use crate::EnvMeaning;
+
+        let vars = &[
+            ("TEST_APP_LEVEL", EnvMeaning::Level),
+            ("TEST_APP_VERBOSITY", EnvMeaning::Verbosity),
+            ("TEST_APP_QUIET", EnvMeaning::LevelIfSet(LevelFilter::Error)),
+            ("TEST_APP_TARGET", EnvMeaning::Targets),
+        ];
+        let config = || crate::Config::logfmt().with_env_vars(vars);
+
+        let tests = &[
+            ("TEST_APP_LEVEL", "warn", LevelFilter::Warn),
+            ("TEST_APP_LEVEL", "invalid", LevelFilter::Info),
+            ("TEST_APP_VERBOSITY", "0", LevelFilter::Error),
+            ("TEST_APP_VERBOSITY", "3", LevelFilter::Debug),
+            ("TEST_APP_VERBOSITY", "10", LevelFilter::Trace),
+            ("TEST_APP_QUIET", "", LevelFilter::Error),
+            // Not the default variables.
+            ("LOG", "trace", LevelFilter::Info),
+        ];
+        for (env_var, env_val, want) in tests {
+            env::set_var(env_var, env_val);
+            assert_eq!(config().into_logger().filter, *want, "{env_var}={env_val}");
+            env::remove_var(env_var);
+        }
+
+        // The first variable that is set is used.
+        env::set_var("TEST_APP_VERBOSITY", "4");
+        env::set_var("TEST_APP_QUIET", "1");
+        assert_eq!(config().into_logger().filter, LevelFilter::Trace);
+        env::remove_var("TEST_APP_VERBOSITY");
+        env::remove_var("TEST_APP_QUIET");
+
+        assert_eq!(config().into_logger().targets, Targets::All);
+        env::set_var("TEST_APP_TARGET", "crate1,crate2");
+        let want = Targets::Only(vec!["crate1".into(), "crate2".into()].into_boxed_slice());
+        assert_eq!(config().into_logger().targets, want);
+        env::remove_var("TEST_APP_TARGET");
+    }
+
+    fn should_get_filters_from_rust_log() {
+        use crate::config::get_log_levels;
+
+        env::set_var("RUST_LOG", "warn,crate1=debug,crate1::db=trace");
+        assert_eq!(get_max_level(), LevelFilter::Warn);
+        assert_eq!(get_log_targets(), Targets::All);
+        let logger = crate::Config::logfmt().into_logger();
+        assert_eq!(logger.filter("crate2"), LevelFilter::Warn);
+        assert_eq!(logger.filter("crate1"), LevelFilter::Debug);
+        assert_eq!(logger.filter("crate1::mod1"), LevelFilter::Debug);
+        assert_eq!(logger.filter("crate1::db"), LevelFilter::Trace);
+        assert_eq!(logger.max_level(), LevelFilter::Trace);
+
+        // Only the targets are logged if no level for all targets is set.
+        env::set_var("RUST_LOG", "crate1=debug,crate2");
+        assert_eq!(get_max_level(), LevelFilter::Info);
+        let want = Targets::Only(vec!["crate1".into(), "crate2".into()].into_boxed_slice());
+        assert_eq!(get_log_targets(), want);
+        assert_eq!(get_log_levels(), [("crate1".into(), LevelFilter::Debug), ("crate2".into(), LevelFilter::Trace)]);
+
+        // Only used if the other variables are not set.
+        env::set_var("LOG_TARGET", "crate3");
+        assert_eq!(get_max_level(), LevelFilter::Info);
+        assert_eq!(get_log_targets(), Targets::Only(vec!["crate3".into()].into_boxed_slice()));
+        assert!(get_log_levels().is_empty());
+        env::remove_var("LOG_TARGET");
+        env::set_var("LOG_LEVEL", "error");
+        assert_eq!(get_max_level(), LevelFilter::Error);
+        assert_eq!(get_log_targets(), Targets::All);
+        assert!(get_log_levels().is_empty());
+        env::remove_var("LOG_LEVEL");
+
+        env::remove_var("RUST_LOG");
+        assert_eq!(get_max_level(), LevelFilter::Info);
+        assert!(get_log_levels().is_empty());
+    }
+
+    fn should_get_log_level_from_verbosity() {
+        let level = |quiet, verbose| crate::Config::logfmt().with_verbosity(quiet, verbose).into_logger().filter;
+        let tests = &[
+            (0, 1, LevelFilter::Debug),
+            (0, 2, LevelFilter::Trace),
+            (0, u8::MAX, LevelFilter::Trace),
+            (1, 0, LevelFilter::Warn),
+            (2, 0, LevelFilter::Error),
+            (u8::MAX, 0, LevelFilter::Error),
+            (1, 1, LevelFilter::Info),
+            (1, 3, LevelFilter::Trace),
+        ];
+        for (quiet, verbose, want) in tests {
+            assert_eq!(level(*quiet, *verbose), *want, "quiet={quiet}, verbose={verbose}");
+        }
+
+        // Without flags the environment is used.
+        assert_eq!(level(0, 0), LevelFilter::Info);
+        env::set_var("LOG_LEVEL", "warn");
+        assert_eq!(level(0, 0), LevelFilter::Warn);
+        assert_eq!(level(0, 1), LevelFilter::Debug);
+        env::remove_var("LOG_LEVEL");
+    }
+
+    fn logger_guard() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+        panic::set_hook(Box::new(|_| {
+            let _ = CALLED.fetch_add(1, Ordering::Relaxed);
+        }));
+
+        let guard = crate::LoggerGuard::new(crate::config::PanicOptions::DEFAULT);
+        // With the log-panic feature the hook is replaced by the guard.
+        let _ = panic::catch_unwind(|| panic!("oops"));
+        let want = if cfg!(feature = "log-panic") { 0 } else { 1 };
+        assert_eq!(CALLED.load(Ordering::Relaxed), want);
+        LOG_OUTPUT.lock().unwrap().clear();
+
+        // Dropping the guard restores the previous hook.
+        drop(guard);
+        let _ = panic::catch_unwind(|| panic!("oops"));
+        assert_eq!(CALLED.load(Ordering::Relaxed), want + 1);
+        let _ = panic::take_hook();
+    }
+
+    fn full_policy() {
+        use crate::full::write;
+        use crate::test_instruments::OUTPUT_FULL;
+        use crate::{dropped_records, FullPolicy};
+
+        LOG_OUTPUT.lock().unwrap().clear();
+        let record = |msg: &'static [u8]| [IoSlice::new(msg), IoSlice::new(b"\n")];
+
+        OUTPUT_FULL.with(|full| full.set(true));
+        let dropped = dropped_records();
+        write(false, &record(b"dropped"), FullPolicy::Drop).unwrap();
+        assert_eq!(dropped_records(), dropped + 1);
+        // Fits in the buffer.
+        write(false, &record(b"buffered"), FullPolicy::Buffer(10)).unwrap();
+        // Doesn't fit in the buffer.
+        write(false, &record(b"dropped"), FullPolicy::Buffer(10)).unwrap();
+        assert_eq!(dropped_records(), dropped + 2);
+        assert!(LOG_OUTPUT.lock().unwrap().is_empty());
+
+        OUTPUT_FULL.with(|full| full.set(false));
+        write(false, &record(b"written"), FullPolicy::Buffer(10)).unwrap();
+        let got = take(&mut *(LOG_OUTPUT.lock().unwrap()));
+        assert_eq!(got, [&b"buffered\n"[..], b"written\n"]);
+    }
+
+    fn full_policy_flush() {
+        use crate::full::{flush, write};
+        use crate::test_instruments::OUTPUT_FULL;
+        use crate::FullPolicy;
+
+        LOG_OUTPUT.lock().unwrap().clear();
+        OUTPUT_FULL.with(|full| full.set(true));
+        let record = [IoSlice::new(b"buffered"), IoSlice::new(b"\n")];
+        write(false, &record, FullPolicy::Buffer(100)).unwrap();
+        assert!(LOG_OUTPUT.lock().unwrap().is_empty());
+
+        // Flushing blocks until the records are written, even if the output is
+        // full.
+        flush().unwrap();
+        OUTPUT_FULL.with(|full| full.set(false));
+        let got = take(&mut *(LOG_OUTPUT.lock().unwrap()));
+        assert_eq!(got, [&b"buffered\n"[..]]);
+        flush().unwrap();
+        assert!(LOG_OUTPUT.lock().unwrap().is_empty());
+    }
 }
 
 fn add_timestamp(message: String, timestamp: SystemTime, got: &str) -> String {
@@ -161,29 +453,72 @@ fn targets_should_log() {
         Targets::Only(vec!["crate1".into()].into_boxed_slice()),
         Targets::Only(vec!["crate1::mod1".into()].into_boxed_slice()),
         Targets::Only(vec!["crate1".into(), "crate2".into()].into_boxed_slice()),
+        Targets::Only(vec!["crate1::*::db".into()].into_boxed_slice()),
+        Targets::Only(vec!["*::db".into()].into_boxed_slice()),
+        Targets::Only(vec!["=crate1::mod1".into()].into_boxed_slice()),
     ];
+    let always_log: &[Box<str>] = &[REQUEST_TARGET.into(), PANIC_TARGET.into()];
 
     let tests = vec![
-        ("", vec![true, false, false, false]),
-        ("crate1", vec![true, true, false, true]),
-        ("crate1::mod1", vec![true, true, true, true]),
-        ("crate2", vec![true, false, false, true]),
-        ("crate2::mod2", vec![true, false, false, true]),
+        ("", vec![true, false, false, false, false, false, false]),
+        ("crate1", vec![true, true, false, true, false, false, false]),
+        (
+            "crate1::mod1",
+            vec![true, true, true, true, false, false, true],
+        ),
+        (
+            "crate1::mod1::db",
+            vec![true, true, true, true, true, false, false],
+        ),
+        (
+            "crate1::db",
+            vec![true, true, false, true, false, true, false],
+        ),
+        (
+            "crate1_extras",
+            vec![true, false, false, false, false, false, false],
+        ),
+        (
+            "crate1::mod10",
+            vec![true, true, false, true, false, false, false],
+        ),
+        (
+            "crate2",
+            vec![true, false, false, true, false, false, false],
+        ),
+        (
+            "crate2::mod2",
+            vec![true, false, false, true, false, false, false],
+        ),
+        (
+            "crate2::db::conn",
+            vec![true, false, false, true, false, true, false],
+        ),
         // Requests should always be logged.
-        (REQUEST_TARGET, vec![true, true, true, true]),
+        (
+            REQUEST_TARGET,
+            vec![true, true, true, true, true, true, true],
+        ),
         // Panics should always be logged.
-        (PANIC_TARGET, vec![true, true, true, true]),
+        (PANIC_TARGET, vec![true, true, true, true, true, true, true]),
     ];
 
     for (test_target, wanted) in tests {
         for (target, want) in targets.iter().zip(wanted) {
             assert_eq!(
-                target.should_log(test_target),
+                target.should_log(test_target, always_log),
                 want,
                 "targets to log: {target:?}, logging target: {test_target}",
             )
         }
     }
+
+    let target = &targets[1];
+    let always_log: &[Box<str>] = &["audit".into()];
+    assert!(target.should_log("audit", always_log));
+    assert!(!target.should_log("audit::sub", always_log));
+    assert!(!target.should_log(REQUEST_TARGET, always_log));
+    assert!(target.should_log("crate1", always_log));
 }
 
 struct MyDisplay;
@@ -199,7 +534,7 @@ fn format_logfmt() {
     format_test::<LogFmt, _>(&[
         "lvl=\"INFO\" msg=\"some\\r\\n\\t\\nmessage\" target=\"some_target1\" module=\"module_path1\" key1=\"value1\" file=\"file1:123\"\n",
         "lvl=\"INFO\" msg=\"some\\r\\n\\t\\nmessage\" target=\"some_target1\" module=\"module_path1\" key1=\"value1\"\n",
-        "lvl=\"WARN\" msg=\"arguments2 with \\\"quotes\\\"\" target=\"second_target\" module=\"module_path1\" key2a=\"value2\" key2b=123 key3c=-123 key3d=123.0 key2e=true key2f=false key2g=\"c\" key2\\\"g=\"MyDisplay\" file=\"file2:111\"\n",
+        "lvl=\"WARN\" msg=\"arguments2 with \\\"quotes\\\"\" target=\"second_target\" module=\"module_path1\" key2a=\"value2\" key2b=123 key3c=-123 key3d=123.0 key2e=true key2f=false key2g=\"c\" \"key2\\\"g\"=\"MyDisplay\" key2h= file=\"file2:111\"\n",
         "lvl=\"ERROR\" msg=\"panicking!\" target=\"panic\" module=\"\" file=\"??:0\"\n",
     ], add_timestamp);
 }
@@ -209,7 +544,7 @@ fn format_json() {
     format_test::<Json, _>(&[
         "{\"level\":\"INFO\",\"message\":\"some\\r\\n\\t\\nmessage\",\"target\":\"some_target1\",\"module\":\"module_path1\",\"key1\":\"value1\",\"file\":\"file1\",\"line\":\"123\"}\n",
         "{\"level\":\"INFO\",\"message\":\"some\\r\\n\\t\\nmessage\",\"target\":\"some_target1\",\"module\":\"module_path1\",\"key1\":\"value1\"}\n",
-        "{\"level\":\"WARN\",\"message\":\"arguments2 with \\\"quotes\\\"\",\"target\":\"second_target\",\"module\":\"module_path1\",\"key2a\":\"value2\",\"key2b\":123,\"key3c\":-123,\"key3d\":123.0,\"key2e\":true,\"key2f\":false,\"key2g\":\"c\",\"key2\\\"g\":\"MyDisplay\",\"file\":\"file2\",\"line\":\"111\"}\n",
+        "{\"level\":\"WARN\",\"message\":\"arguments2 with \\\"quotes\\\"\",\"target\":\"second_target\",\"module\":\"module_path1\",\"key2a\":\"value2\",\"key2b\":123,\"key3c\":-123,\"key3d\":123.0,\"key2e\":true,\"key2f\":false,\"key2g\":\"c\",\"key2\\\"g\":\"MyDisplay\",\"key2h\":null,\"file\":\"file2\",\"line\":\"111\"}\n",
         "{\"level\":\"ERROR\",\"message\":\"panicking!\",\"target\":\"panic\",\"module\":\"\",\"file\":\"??\",\"line\":\"0\"}\n",
     ], add_timestamp_json);
 }
@@ -219,11 +554,627 @@ fn format_gcloud() {
     format_test::<Gcloud, _>(&[
         "{\"severity\":\"INFO\",\"message\":\"some\\r\\n\\t\\nmessage\",\"target\":\"some_target1\",\"module\":\"module_path1\",\"key1\":\"value1\",\"sourceLocation\":{\"file\":\"file1\",\"line\":\"123\"}}\n",
         "{\"severity\":\"INFO\",\"message\":\"some\\r\\n\\t\\nmessage\",\"target\":\"some_target1\",\"module\":\"module_path1\",\"key1\":\"value1\"}\n",
-        "{\"severity\":\"WARNING\",\"message\":\"arguments2 with \\\"quotes\\\"\",\"target\":\"second_target\",\"module\":\"module_path1\",\"key2a\":\"value2\",\"key2b\":123,\"key3c\":-123,\"key3d\":123.0,\"key2e\":true,\"key2f\":false,\"key2g\":\"c\",\"key2\\\"g\":\"MyDisplay\",\"sourceLocation\":{\"file\":\"file2\",\"line\":\"111\"}}\n",
+        "{\"severity\":\"WARNING\",\"message\":\"arguments2 with \\\"quotes\\\"\",\"target\":\"second_target\",\"module\":\"module_path1\",\"key2a\":\"value2\",\"key2b\":123,\"key3c\":-123,\"key3d\":123.0,\"key2e\":true,\"key2f\":false,\"key2g\":\"c\",\"key2\\\"g\":\"MyDisplay\",\"key2h\":null,\"sourceLocation\":{\"file\":\"file2\",\"line\":\"111\"}}\n",
         "{\"severity\":\"CRITICAL\",\"message\":\"panicking!\",\"target\":\"panic\",\"module\":\"\",\"sourceLocation\":{\"file\":\"??\",\"line\":\"0\"}}\n",
     ], add_timestamp_json);
 }
 
+#[test]
+fn format_logfmt_bare_keys() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("canary", &true), ("cached", &false)];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("bare keys"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+
+    let options = Options {
+        bare_keys: true,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"bare keys\" target=\"target\" module=\"\" canary cached=false\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn format_logfmt_msg_last() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("key1", &"value1"), ("key2", &123)];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("msg \"last\" key3=value3"))
+        .level(Level::Info)
+        .target("target")
+        .module_path(Some("module"))
+        .file(Some("file"))
+        .line(Some(123))
+        .key_values(kvs)
+        .build();
+
+    let tests = [
+        (false, "lvl=\"INFO\" target=\"target\" module=\"module\" key1=\"value1\" key2=123 msg=\"msg \\\"last\\\" key3=value3\"\n"),
+        (true, "lvl=\"INFO\" target=\"target\" module=\"module\" key1=\"value1\" key2=123 file=\"file:123\" msg=\"msg \\\"last\\\" key3=value3\"\n"),
+    ];
+    for (add_loc, want) in tests {
+        let options = Options {
+            msg_last: true,
+            ..Options::new(add_loc)
+        };
+        let got = format_record::<LogFmt>(&record, &options);
+        #[cfg(feature = "timestamp")]
+        let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+        assert_eq!(got, *want);
+    }
+}
+
+#[test]
+fn format_logfmt_separate_line() {
+    let record = Record::builder()
+        .args(format_args!("separate line"))
+        .level(Level::Info)
+        .target("target")
+        .module_path(Some("module"))
+        .file(Some("file"))
+        .line(Some(123))
+        .build();
+
+    let tests = [
+        (false, "lvl=\"INFO\" msg=\"separate line\" target=\"target\" module=\"module\" file=\"file\" line=123\n"),
+        (true, "lvl=\"INFO\" target=\"target\" module=\"module\" file=\"file\" line=123 msg=\"separate line\"\n"),
+    ];
+    for (msg_last, want) in tests {
+        let options = Options {
+            separate_line: true,
+            msg_last,
+            ..Options::new(true)
+        };
+        let got = format_record::<LogFmt>(&record, &options);
+        #[cfg(feature = "timestamp")]
+        let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+        assert_eq!(got, *want);
+    }
+}
+
+#[test]
+fn fmt_write_record() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("key1", &"value1"), ("key2", &123)];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("some message"))
+        .level(Level::Info)
+        .target("target")
+        .module_path(Some("module"))
+        .key_values(kvs)
+        .build();
+    let options = Options::new(false);
+
+    let mut got = Vec::new();
+    crate::fmt::write_record(&mut got, &record, &options).unwrap();
+    let got = String::from_utf8(got).unwrap();
+    let want = "lvl=\"INFO\" msg=\"some message\" target=\"target\" module=\"module\" key1=\"value1\" key2=123\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let mut got = Vec::new();
+    crate::fmt::write_json_record(&mut got, &record, &options).unwrap();
+    let got = String::from_utf8(got).unwrap();
+    let want = "{\"level\":\"INFO\",\"message\":\"some message\",\"target\":\"target\",\"module\":\"module\",\"key1\":\"value1\",\"key2\":123}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn buffer_pool() {
+    let pool = format::BufferPool::new();
+    assert_eq!(
+        pool.with(|_| pool.with(|_| pool.with(|_| ()))),
+        Some(Some(None))
+    );
+    // Buffers are returned to the pool.
+    assert_eq!(pool.with(|_| pool.with(|_| ())), Some(Some(())));
+}
+
+#[test]
+fn fmt_nested_records() {
+    /// Formats a record with a message of `Nested(n - 1)` when displayed,
+    /// displaying the message of that record.
+    struct Nested(usize);
+
+    impl fmt::Display for Nested {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            if self.0 == 0 {
+                return f.write_str("end");
+            }
+            let nested = Nested(self.0 - 1);
+            let args = format_args!("{nested}");
+            let record = Record::builder().args(args).level(Level::Info).build();
+            let mut output = Vec::new();
+            match crate::fmt::write_record(&mut output, &record, &Options::new(false)) {
+                Ok(()) => {
+                    let output = str::from_utf8(&output).unwrap();
+                    let msg = output.split("msg=\"").nth(1).unwrap();
+                    f.write_str(&msg[..msg.find('"').unwrap()])
+                }
+                Err(err) => write!(f, "{err}"),
+            }
+        }
+    }
+
+    let mut got = Vec::new();
+    let nested = Nested(1);
+    let args = format_args!("{nested}");
+    let record = Record::builder().args(args).level(Level::Info).build();
+    crate::fmt::write_record(&mut got, &record, &Options::new(false)).unwrap();
+    let got = String::from_utf8(got).unwrap();
+    assert!(got.contains(" msg=\"end\" "), "{got}");
+
+    let mut got = Vec::new();
+    let nested = Nested(2);
+    let args = format_args!("{nested}");
+    let record = Record::builder().args(args).level(Level::Info).build();
+    crate::fmt::write_record(&mut got, &record, &Options::new(false)).unwrap();
+    let got = String::from_utf8(got).unwrap();
+    assert!(
+        got.contains(" msg=\"formatting records nested too deep\" "),
+        "{got}"
+    );
+}
+
+#[test]
+fn syslog_priority() {
+    let tests = [
+        (Level::Error, PANIC_TARGET, "<2>"),
+        (Level::Error, "target", "<3>"),
+        (Level::Warn, "target", "<4>"),
+        (Level::Info, "target", "<6>"),
+        (Level::Debug, "target", "<7>"),
+        (Level::Trace, "target", "<7>"),
+    ];
+    for (level, target, want) in tests {
+        let record = Record::builder().level(level).target(target).build();
+        assert_eq!(crate::syslog_priority(&record), want.as_bytes());
+    }
+}
+
+#[test]
+fn format_raw_mode() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("key1", &"value1"),
+        ("key2", &kv::Value::from_display(&"display")),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("raw message"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+    let options = Options {
+        raw: true,
+        ..Options::new(false)
+    };
+
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"raw message\" target=\"target\" module=\"\" key1=\"value1\" key2=\"display\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let got = format_record::<Json>(&record, &options);
+    let want = "{\"level\":\"INFO\",\"message\":\"raw message\",\"target\":\"target\",\"module\":\"\",\"key1\":\"value1\",\"key2\":\"display\"}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    // Panics are never logged in raw mode.
+    let record = Record::builder()
+        .args(format_args!("panicked:\n\"oops\""))
+        .level(Level::Error)
+        .target(PANIC_TARGET)
+        .build();
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"ERROR\" msg=\"panicked:\\n\\\"oops\\\"\" target=\"panic\" module=\"\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+#[cfg(debug_assertions)]
+#[should_panic = "std-logger: string logged in raw mode contains characters that need escaping"]
+fn format_raw_mode_invalid() {
+    let record = Record::builder()
+        .args(format_args!("message with a \"quote\""))
+        .level(Level::Info)
+        .target("target")
+        .build();
+    let options = Options {
+        raw: true,
+        ..Options::new(false)
+    };
+    let _ = format_record::<LogFmt>(&record, &options);
+}
+
+#[test]
+fn format_multi_line_values() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("key1", &"line one\nline \"two\""),
+        ("key2", &kv::Value::from_display(&"display\nvalue")),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("multi\nline"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+
+    // New lines are escaped by default.
+    let got = format_record::<LogFmt>(&record, &Options::new(false));
+    let want = "lvl=\"INFO\" msg=\"multi\\nline\" target=\"target\" module=\"\" key1=\"line one\\nline \\\"two\\\"\" key2=\"display\\nvalue\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let options = Options {
+        multi_line: true,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"multi\nline\" target=\"target\" module=\"\" key1=\"line one\nline \\\"two\\\"\" key2=\"display\nvalue\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn format_max_record_size() {
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("key1", &"abcdefgh"),
+        ("key2", &123),
+        ("key3", &kv::Value::from_display(&"display")),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("message"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+
+    // Fits exactly.
+    let options = Options {
+        max_size: 22,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"message\" target=\"target\" module=\"\" key1=\"abcdefgh\" key2=123 key3=\"display\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let options = Options {
+        max_size: 10,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"message\" target=\"target\" module=\"\" key1=\"abc\" key2=123 key3=\"\" truncated=true\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let got = format_record::<Json>(&record, &options);
+    let want = "{\"level\":\"INFO\",\"message\":\"message\",\"target\":\"target\",\"module\":\"\",\"key1\":\"abc\",\"key2\":123,\"key3\":\"\",\"truncated\":true}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    // Don't split characters.
+    let record = Record::builder()
+        .args(format_args!("h\u{e9}llo"))
+        .level(Level::Info)
+        .target("target")
+        .build();
+    let options = Options {
+        max_size: 2,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"h\" target=\"target\" module=\"\" truncated=true\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn format_large_record() {
+    // Larger than the inline storage of the buffer, forcing it to spill to the
+    // heap, after which smaller records should use the inline storage again.
+    let value = "a".repeat(5000);
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("key", &&*value)];
+    let kvs: &dyn kv::Source = &kvs;
+    let large = Record::builder()
+        .args(format_args!("large"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+    let small = Record::builder()
+        .args(format_args!("small"))
+        .level(Level::Info)
+        .target("target")
+        .build();
+
+    let mut buf = format::Buffer::new();
+    let options = Options::new(false);
+    for (record, want) in [
+        (
+            &small,
+            "lvl=\"INFO\" msg=\"small\" target=\"target\" module=\"\"\n".to_owned(),
+        ),
+        (
+            &large,
+            format!("lvl=\"INFO\" msg=\"large\" target=\"target\" module=\"\" key=\"{value}\"\n"),
+        ),
+        (
+            &small,
+            "lvl=\"INFO\" msg=\"small\" target=\"target\" module=\"\"\n".to_owned(),
+        ),
+    ] {
+        let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
+        let bufs = LogFmt::format(&mut bufs, &mut buf, record, &NoKvs, &options);
+        let mut got = Vec::new();
+        let _ = got.write_vectored(bufs).unwrap();
+        let got = String::from_utf8(got).unwrap();
+        #[cfg(feature = "timestamp")]
+        let want = add_timestamp(want, SystemTime::now(), &got);
+        assert_eq!(got, want);
+    }
+}
+
+#[test]
+fn format_lazy_kv() {
+    let calls = std::cell::Cell::new(0);
+    let value = crate::lazy_kv!({
+        calls.set(calls.get() + 1);
+        format!("expensive {}", 123)
+    });
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[("summary", &value)];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("lazy"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+    assert_eq!(calls.get(), 0);
+
+    let got = format_record::<LogFmt>(&record, &Options::new(false));
+    assert_eq!(calls.get(), 1);
+    let want =
+        "lvl=\"INFO\" msg=\"lazy\" target=\"target\" module=\"\" summary=\"expensive 123\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn format_type_hints() {
+    let timeout = Duration::from_secs(2);
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("user_id", &123),
+        ("order_id", &"00123"),
+        ("took_ms", &"12"),
+        (
+            "elapsed",
+            &crate::value::duration(Duration::from_micros(1500)),
+        ),
+        ("timeout", &kv::Value::from_debug(&timeout)),
+        ("size", &kv::Value::from_display(&1.5)),
+        ("padded", &"007"),
+        ("flag", &true),
+        ("other", &"12"),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("type hints"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+
+    let options = Options {
+        bare_keys: true,
+        type_hints: Box::new([
+            ("user_id".into(), TypeHint::String),
+            ("order_id".into(), TypeHint::String),
+            ("took_ms".into(), TypeHint::Number),
+            ("elapsed".into(), TypeHint::Duration),
+            ("timeout".into(), TypeHint::Duration),
+            ("size".into(), TypeHint::Number),
+            ("padded".into(), TypeHint::Number),
+            ("flag".into(), TypeHint::String),
+        ]),
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"type hints\" target=\"target\" module=\"\" user_id=\"123\" order_id=\"00123\" took_ms=12 elapsed=1.5 timeout=2000 size=1.5 padded=\"007\" flag=\"true\" other=\"12\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+    #[cfg(feature = "test")]
+    {
+        use std_logger_parser::Value;
+        let record = std_logger_parser::parse(got.as_bytes())
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(record.key_values.get("took_ms"), Some(&Value::Int(12)));
+        assert_eq!(record.key_values.get("elapsed"), Some(&Value::Float(1.5)));
+        assert_eq!(record.key_values.get("timeout"), Some(&Value::Int(2000)));
+    }
+
+    let got = format_record::<Json>(&record, &options);
+    let want = "{\"level\":\"INFO\",\"message\":\"type hints\",\"target\":\"target\",\"module\":\"\",\"user_id\":\"123\",\"order_id\":\"00123\",\"took_ms\":12,\"elapsed\":1.5,\"timeout\":2000,\"size\":1.5,\"padded\":\"007\",\"flag\":\"true\",\"other\":\"12\"}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+#[cfg(feature = "kv-serde")]
+fn structured_values() {
+    #[derive(serde::Serialize)]
+    struct User {
+        id: u32,
+        name: &'static str,
+        tags: Vec<&'static str>,
+    }
+
+    let user = User {
+        id: 7,
+        name: "bob",
+        tags: vec!["admin", "ops"],
+    };
+    let kvs: &[(&str, kv::Value)] = &[("user", kv::Value::from_serde(&user)), ("other", 1.into())];
+    let record = Record::builder()
+        .args(format_args!("structured"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(&kvs)
+        .build();
+
+    let options = Options {
+        structured: crate::StructuredValues::Flatten,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"structured\" target=\"target\" module=\"\" user.id=7 user.name=\"bob\" user.tags.0=\"admin\" user.tags.1=\"ops\" other=1\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let got = format_record::<Json>(&record, &options);
+    let want = "{\"level\":\"INFO\",\"message\":\"structured\",\"target\":\"target\",\"module\":\"\",\"user.id\":7,\"user.name\":\"bob\",\"user.tags.0\":\"admin\",\"user.tags.1\":\"ops\",\"other\":1}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let options = Options {
+        structured: crate::StructuredValues::Json,
+        ..Options::new(false)
+    };
+    let got = format_record::<LogFmt>(&record, &options);
+    let want = "lvl=\"INFO\" msg=\"structured\" target=\"target\" module=\"\" user=\"{\\\"id\\\":7,\\\"name\\\":\\\"bob\\\",\\\"tags\\\":[\\\"admin\\\",\\\"ops\\\"]}\" other=1\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+
+    let got = format_record::<Json>(&record, &options);
+    let want = "{\"level\":\"INFO\",\"message\":\"structured\",\"target\":\"target\",\"module\":\"\",\"user\":{\"id\":7,\"name\":\"bob\",\"tags\":[\"admin\",\"ops\"]},\"other\":1}\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp_json(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn normalize_query() {
+    let tests = &[
+        ("SELECT 1", "SELECT ?"),
+        (
+            "SELECT * FROM users WHERE name = 'Thomas' AND age >= 30",
+            "SELECT * FROM users WHERE name = ? AND age >= ?",
+        ),
+        (
+            "SELECT * FROM table1 WHERE id = $1",
+            "SELECT * FROM table1 WHERE id = $1",
+        ),
+        ("SELECT 'it''s', 'a\\'b', ''", "SELECT ?, ?, ?"),
+        ("SELECT 1.5, -2, 1e-10, 0xFF", "SELECT ?, -?, ?, ?"),
+        (
+            "SELECT \"col 1\", `col2` FROM t",
+            "SELECT \"col 1\", `col2` FROM t",
+        ),
+        (
+            "SELECT a -- 'comment' 1\nFROM t",
+            "SELECT a -- 'comment' 1\nFROM t",
+        ),
+        ("SELECT /* 1 */ a IN (1, 2)", "SELECT /* 1 */ a IN (?, ?)"),
+        ("SELECT 'unterminated", "SELECT ?"),
+        ("UPDATE t SET naïve1 = 'é'", "UPDATE t SET naïve1 = ?"),
+    ];
+    for (query, want) in tests {
+        let got = crate::normalize_query(query).to_string();
+        assert_eq!(got, *want, "query: {query}");
+    }
+}
+
+#[test]
+fn hex_dump() {
+    let bytes = (0..=40).collect::<Vec<u8>>();
+    let got = crate::hex_dump(&bytes).to_string();
+    let want = "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|
+00000010  10 11 12 13 14 15 16 17  18 19 1a 1b 1c 1d 1e 1f  |................|
+00000020  20 21 22 23 24 25 26 27  28                       | !\"#$%&'(|";
+    assert_eq!(got, want);
+
+    let got = crate::hex_dump(&bytes).limit(20).to_string();
+    let want = "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|
+00000010  10 11 12 13                                       |....|
+... 21 more bytes";
+    assert_eq!(got, want);
+
+    assert_eq!(crate::hex_dump(&[]).to_string(), "");
+    assert_eq!(
+        crate::hex_dump(&bytes).limit(0).to_string(),
+        "... 41 more bytes"
+    );
+}
+
+#[test]
+fn format_values() {
+    use crate::value;
+
+    let bytes = [0xde, 0xad, 0xbe, 0xef];
+    let kvs: &[(&str, &dyn kv::ToValue)] = &[
+        ("took", &value::duration(Duration::from_micros(12_500))),
+        ("secs", &value::duration(Duration::from_secs(2))),
+        ("nanos", &value::duration(Duration::from_nanos(10))),
+        #[cfg(feature = "timestamp")]
+        (
+            "at",
+            &value::timestamp(SystemTime::UNIX_EPOCH + Duration::new(1614086148, 624447123)),
+        ),
+        ("hex", &value::hex(&bytes)),
+        ("b64_1", &value::base64(b"h")),
+        ("b64_2", &value::base64(b"he")),
+        ("b64_3", &value::base64(b"hello")),
+        ("empty", &value::base64(b"")),
+    ];
+    let kvs: &dyn kv::Source = &kvs;
+    let record = Record::builder()
+        .args(format_args!("values"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(kvs)
+        .build();
+
+    let got = format_record::<LogFmt>(&record, &Options::new(false));
+    #[cfg(feature = "timestamp")]
+    let want = "lvl=\"INFO\" msg=\"values\" target=\"target\" module=\"\" took=\"12.5ms\" secs=\"2s\" nanos=\"10ns\" at=\"2021-02-23T13:15:48.624447Z\" hex=\"deadbeef\" b64_1=\"aA==\" b64_2=\"aGU=\" b64_3=\"aGVsbG8=\" empty=\"\"\n";
+    #[cfg(not(feature = "timestamp"))]
+    let want = "lvl=\"INFO\" msg=\"values\" target=\"target\" module=\"\" took=\"12.5ms\" secs=\"2s\" nanos=\"10ns\" hex=\"deadbeef\" b64_1=\"aA==\" b64_2=\"aGU=\" b64_3=\"aGVsbG8=\" empty=\"\"\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
 fn add_timestamp_json(want: String, timestamp: SystemTime, got: &str) -> String {
     let mut want = want.to_owned();
     let timestamp = add_timestamp(String::new(), timestamp, &got[10..]);
@@ -258,6 +1209,7 @@ where
             "key2\"g",
             &(&log::kv::Value::from_display(&MyDisplay) as &dyn kv::ToValue),
         ),
+        ("key2h", &None::<u64>),
     ];
     let kvs: &dyn kv::Source = &kvs;
     let record2 = Record::builder()
@@ -283,7 +1235,7 @@ where
     ];
 
     for ((record, debug), want) in tests.into_iter().zip(expected) {
-        let got = format_record::<F>(&record, debug);
+        let got = format_record::<F>(&record, &Options::new(debug));
         #[cfg(feature = "timestamp")]
         let want = add_timestamp(want.to_string(), SystemTime::now(), &got);
         assert_eq!(got, *want);
@@ -292,10 +1244,10 @@ where
     let _ = add_timestamp;
 }
 
-fn format_record<F: Format>(record: &Record, debug: bool) -> String {
+fn format_record<F: Format>(record: &Record, options: &Options) -> String {
     let mut bufs = [IoSlice::new(&[]); BUFS_SIZE];
     let mut buf = format::Buffer::new();
-    let bufs = F::format(&mut bufs, &mut buf, record, &NoKvs, debug);
+    let bufs = F::format(&mut bufs, &mut buf, record, &NoKvs, options);
     let mut output = Vec::new();
     let _ = output.write_vectored(bufs).unwrap();
     String::from_utf8(output).unwrap()
@@ -311,6 +1263,18 @@ fn timestamp() {
         SystemTime::UNIX_EPOCH + Duration::from_secs(41 * (365 * 24 * 60 * 60)),
         SystemTime::UNIX_EPOCH + Duration::from_secs(51 * (365 * 24 * 60 * 60)),
         SystemTime::UNIX_EPOCH + Duration::from_secs(101 * (365 * 24 * 60 * 60)),
+        // Before 2000-03-01, the epoch of the previous implementation.
+        SystemTime::UNIX_EPOCH,
+        SystemTime::UNIX_EPOCH + Duration::from_micros(1_500_000),
+        SystemTime::UNIX_EPOCH + Duration::from_secs(365 * 24 * 60 * 60),
+        // 1999-12-31T23:59:59Z.
+        SystemTime::UNIX_EPOCH + Duration::from_secs(946_684_799),
+        // 2000-02-29T12:00:00Z.
+        SystemTime::UNIX_EPOCH + Duration::from_secs(951_825_600),
+        // 2000-03-01T00:00:00Z.
+        SystemTime::UNIX_EPOCH + Duration::from_secs(951_868_800),
+        // 2100-03-01T00:00:00Z, not a leap year.
+        SystemTime::UNIX_EPOCH + Duration::from_secs(4_107_542_400),
     ];
 
     for time in tests {
@@ -344,3 +1308,564 @@ fn timestamp() {
         assert_eq!(got.micro, micros);
     }
 }
+
+#[test]
+fn parse_env_directives() {
+    use crate::config::{parse_env_directives, Directives};
+
+    type Levels = &'static [(&'static str, LevelFilter)];
+    let tests: &[(&str, Option<LevelFilter>, Levels)] = &[
+        ("", None, &[]),
+        ("info", Some(LevelFilter::Info), &[]),
+        ("OFF", Some(LevelFilter::Off), &[]),
+        ("my_crate", None, &[("my_crate", LevelFilter::Trace)]),
+        ("my_crate=warn", None, &[("my_crate", LevelFilter::Warn)]),
+        (
+            " error , my_crate::db = debug,hyper=off ",
+            Some(LevelFilter::Error),
+            &[
+                ("my_crate::db", LevelFilter::Debug),
+                ("hyper", LevelFilter::Off),
+            ],
+        ),
+        // The last level for all targets is used.
+        ("info,debug", Some(LevelFilter::Debug), &[]),
+        // Invalid directives are ignored.
+        ("info,my_crate=loud,=debug,,", Some(LevelFilter::Info), &[]),
+        // Regex filter is ignored.
+        (
+            "my_crate=debug/foo.*",
+            None,
+            &[("my_crate", LevelFilter::Debug)],
+        ),
+        ("info/a,b=debug", Some(LevelFilter::Info), &[]),
+    ];
+    for (input, level, levels) in tests {
+        let want = Directives {
+            level: *level,
+            levels: levels.iter().map(|(t, l)| ((*t).into(), *l)).collect(),
+        };
+        assert_eq!(parse_env_directives(input), want, "{input:?}");
+    }
+}
+
+#[test]
+fn features() {
+    let features = crate::features();
+    assert_eq!(features.contains(&"timestamp"), cfg!(feature = "timestamp"));
+    assert_eq!(features.contains(&"log-panic"), cfg!(feature = "log-panic"));
+    assert_eq!(features.contains(&"service"), cfg!(feature = "service"));
+    assert_eq!(features.contains(&"net"), cfg!(feature = "net"));
+    assert_eq!(features.contains(&"test"), cfg!(feature = "test"));
+    assert_eq!(features.contains(&"tracing"), cfg!(feature = "tracing"));
+    assert_eq!(features.contains(&"kv-serde"), cfg!(feature = "kv-serde"));
+    assert_eq!(features.contains(&"redact"), cfg!(feature = "redact"));
+    assert_eq!(features.contains(&"wasm"), cfg!(feature = "wasm"));
+    assert_eq!(features.contains(&"reload"), cfg!(feature = "reload"));
+    assert_eq!(features.contains(&"admin"), cfg!(feature = "admin"));
+    assert_eq!(features.contains(&"gzip"), cfg!(feature = "gzip"));
+    assert_eq!(features.contains(&"zstd"), cfg!(feature = "zstd"));
+    assert_eq!(features.contains(&"tokio"), cfg!(feature = "tokio"));
+    assert_eq!(features.contains(&"nightly"), cfg!(feature = "nightly"));
+}
+
+#[test]
+#[cfg(feature = "reload")]
+fn reload_parse() {
+    use crate::reload::{parse, FileConfig, Format};
+
+    let input = r#"
+# Comment.
+level = 'debug' # Trailing comment.
+format = "json"
+targets = [
+    "my_crate", # Comment in an array.
+    'other::*',
+]
+
+[levels]
+"my_crate::db" = "trace"
+hyper = "WARN"
+
+[ sinks ]
+'/var/log/#app.log' = "off"
+"#;
+    let got = parse(input).unwrap();
+    let want = FileConfig {
+        level: Some(LevelFilter::Debug),
+        targets: Some(Targets::Only(Box::new([
+            "my_crate".into(),
+            "other::*".into(),
+        ]))),
+        format: Some(Format::Json),
+        // Sorted by target.
+        levels: vec![
+            ("hyper".into(), LevelFilter::Warn),
+            ("my_crate::db".into(), LevelFilter::Trace),
+        ],
+        sinks: vec![("/var/log/#app.log".into(), LevelFilter::Off)],
+    };
+    assert_eq!(got, want);
+
+    // Inline tables and dotted keys.
+    let input = r#"
+levels = { "my_crate::db" = "trace", hyper = 'warn' }
+sinks."/var/log/app.log" = "info"
+"#;
+    let got = parse(input).unwrap();
+    let want = FileConfig {
+        levels: vec![
+            ("hyper".into(), LevelFilter::Warn),
+            ("my_crate::db".into(), LevelFilter::Trace),
+        ],
+        sinks: vec![("/var/log/app.log".into(), LevelFilter::Info)],
+        ..FileConfig::default()
+    };
+    assert_eq!(got, want);
+
+    assert_eq!(parse("targets = []").unwrap().targets, Some(Targets::All));
+    assert_eq!(
+        parse("format = \"logfmt\"").unwrap().format,
+        Some(Format::Logfmt)
+    );
+    assert_eq!(parse("").unwrap(), FileConfig::default());
+
+    let tests = [
+        ("level = \"loud\"", 1, "invalid level `loud`"),
+        ("\nformat = \"xml\"", 2, "unknown variant `xml`"),
+        ("\n\nfilter = \"info\"", 3, "unknown field `filter`"),
+        ("[filters]", 1, "unknown field `filters`"),
+        ("[levels]\nhyper = 1", 2, "invalid type: integer `1`"),
+        ("level = debug", 1, "invalid string"),
+        ("[levels", 1, "invalid table header"),
+        ("targets = [\"my_crate\"\n", 2, "invalid array"),
+    ];
+    for (input, want_line, want) in tests {
+        let (line, msg) = parse(input).unwrap_err();
+        assert_eq!(line, want_line, "{input}: {msg}");
+        assert!(msg.starts_with(want), "{input}: {msg}");
+    }
+}
+
+#[test]
+fn ring_buffer_sink() {
+    let recorder = crate::RingBufferSink::new(2);
+    let mut sink = recorder.clone();
+    crate::write_all(&mut sink, &[IoSlice::new(b"record"), IoSlice::new(b"1\n")]).unwrap();
+    assert_eq!(recorder.records(), [b"record1\n"]);
+
+    // Only the last two records are kept.
+    crate::write_all(&mut sink, &[IoSlice::new(b"record2\n")]).unwrap();
+    crate::write_all(&mut sink, &[IoSlice::new(b"record3\n")]).unwrap();
+    assert_eq!(recorder.records(), [b"record2\n", b"record3\n"]);
+
+    let mut output = Vec::new();
+    recorder.dump(&mut output).unwrap();
+    assert_eq!(output, b"record2\nrecord3\n");
+
+    recorder.clear();
+    assert!(recorder.records().is_empty());
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn net_tcp_sink() {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap().to_string();
+    let mut sink = crate::net::TcpSink::new(&addr);
+
+    // Read from `stream` until the records written by `sink` end with `end`.
+    let read_until =
+        |stream: &mut std::net::TcpStream, sink: &mut crate::net::TcpSink, end: &[u8]| {
+            stream
+                .set_read_timeout(Some(Duration::from_millis(10)))
+                .unwrap();
+            let mut got = Vec::new();
+            let mut buf = [0; 64];
+            while !got.ends_with(end) {
+                // Writes the buffered records once connected.
+                sink.flush().unwrap();
+                match stream.read(&mut buf) {
+                    Ok(n) => got.extend_from_slice(&buf[..n]),
+                    Err(err)
+                        if matches!(
+                            err.kind(),
+                            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                        ) => {}
+                    Err(err) => panic!("unexpected error: {err}"),
+                }
+            }
+            got
+        };
+
+    // Connects in the background, buffering the records until connected.
+    crate::write_all(&mut sink, &[IoSlice::new(b"record"), IoSlice::new(b"1\n")]).unwrap();
+    let (mut stream, _) = listener.accept().unwrap();
+    crate::write_all(&mut sink, &[IoSlice::new(b"record2\n")]).unwrap();
+    assert_eq!(
+        read_until(&mut stream, &mut sink, b"record2\n"),
+        b"record1\nrecord2\n"
+    );
+
+    // Writing fails once the connection is closed, without returning an
+    // error, after which the sink reconnects.
+    drop(stream);
+    listener.set_nonblocking(true).unwrap();
+    let mut stream = loop {
+        crate::write_all(&mut sink, &[IoSlice::new(b"record3\n")]).unwrap();
+        match listener.accept() {
+            Ok((stream, _)) => break stream,
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(10));
+            }
+            Err(err) => panic!("unexpected error: {err}"),
+        }
+    };
+    stream.set_nonblocking(false).unwrap();
+    crate::write_all(&mut sink, &[IoSlice::new(b"record4\n")]).unwrap();
+    let got = read_until(&mut stream, &mut sink, b"record4\n");
+    // Records written after the failure are buffered.
+    assert!(
+        got.starts_with(b"record3\n"),
+        "{}",
+        String::from_utf8_lossy(&got)
+    );
+}
+
+#[test]
+#[cfg(feature = "net")]
+fn net_udp_sink() {
+    use std::net::UdpSocket;
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let mut sink = crate::net::UdpSink::new(socket.local_addr().unwrap()).unwrap();
+
+    // All parts of a record are send in a single datagram.
+    let bufs = [
+        IoSlice::new(b"record"),
+        IoSlice::new(b"1"),
+        IoSlice::new(b"\n"),
+    ];
+    crate::write_all(&mut sink, &bufs).unwrap();
+    let mut buf = [0; 64];
+    let n = socket.recv(&mut buf).unwrap();
+    assert_eq!(&buf[..n], b"record1\n");
+
+    // Errors are ignored.
+    drop(socket);
+    crate::write_all(&mut sink, &bufs).unwrap();
+    crate::write_all(&mut sink, &bufs).unwrap();
+}
+
+#[test]
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+fn compressed_sink() {
+    use crate::{CompressedSink, Compression};
+
+    fn decompress(compression: Compression, input: &[u8]) -> Vec<u8> {
+        match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Read;
+                let mut output = Vec::new();
+                let _ = flate2::read::MultiGzDecoder::new(input)
+                    .read_to_end(&mut output)
+                    .unwrap();
+                output
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::decode_all(input).unwrap(),
+        }
+    }
+
+    let compressions = [
+        #[cfg(feature = "gzip")]
+        Compression::Gzip,
+        #[cfg(feature = "zstd")]
+        Compression::Zstd,
+    ];
+    for compression in compressions {
+        let mut sink = CompressedSink::new(Vec::new(), compression).with_block_size(16);
+        let bufs = [IoSlice::new(b"record"), IoSlice::new(b"1\n")];
+        crate::write_all(&mut sink, &bufs).unwrap();
+        // Block not yet full.
+        assert!(sink.get_ref().is_empty());
+        let bufs = [IoSlice::new(b"record"), IoSlice::new(b"2\n")];
+        crate::write_all(&mut sink, &bufs).unwrap();
+        let first_block = sink.get_ref().len();
+        assert!(first_block != 0);
+        assert_eq!(
+            decompress(compression, sink.get_ref()),
+            b"record1\nrecord2\n"
+        );
+
+        // Flushing writes the block, even if not full.
+        crate::write_all(&mut sink, &[IoSlice::new(b"record3\n")]).unwrap();
+        assert_eq!(sink.get_ref().len(), first_block);
+        sink.flush().unwrap();
+        assert_eq!(
+            decompress(compression, sink.get_ref()),
+            b"record1\nrecord2\nrecord3\n"
+        );
+    }
+}
+
+#[test]
+#[cfg(all(feature = "reload", any(feature = "gzip", feature = "zstd")))]
+fn compression_from_path() {
+    use std::path::Path;
+
+    use crate::Compression;
+
+    let tests = [
+        #[cfg(feature = "gzip")]
+        ("/var/log/app.log.gz", Some(Compression::Gzip)),
+        #[cfg(feature = "zstd")]
+        ("/var/log/app.log.zst", Some(Compression::Zstd)),
+        ("/var/log/app.log", None),
+    ];
+    for (path, want) in tests {
+        assert_eq!(Compression::from_path(Path::new(path)), want, "{path}");
+    }
+}
+
+#[test]
+fn synced_file_sink() {
+    use std::fs::{self, File};
+    use std::num::NonZeroU32;
+
+    use crate::fsync::SyncedFile;
+    use crate::{SyncPolicy, AUDIT_TARGET};
+
+    let every_two = SyncPolicy::Every(NonZeroU32::new(2).unwrap());
+    let tests = [
+        (SyncPolicy::Never, "app", 1, false),
+        (SyncPolicy::Never, AUDIT_TARGET, 100, false),
+        (every_two, "app", 1, false),
+        (every_two, "app", 2, true),
+        (every_two, AUDIT_TARGET, 1, false),
+        (SyncPolicy::Audit, "app", 100, false),
+        (SyncPolicy::Audit, AUDIT_TARGET, 1, true),
+    ];
+    for (policy, target, unsynced, want) in tests {
+        let got = policy.should_sync(target, unsynced);
+        assert_eq!(got, want, "{policy:?} {target} {unsynced}");
+    }
+    assert_eq!(SyncPolicy::default(), SyncPolicy::Never);
+
+    let path = env::temp_dir().join("std-logger-synced-file-sink.log");
+    for policy in [SyncPolicy::Never, every_two, SyncPolicy::Audit] {
+        let mut file = SyncedFile::new(File::create(&path).unwrap(), policy);
+        let bufs = [IoSlice::new(b"record"), IoSlice::new(b"1\n")];
+        file.write("app", &bufs).unwrap();
+        file.write(AUDIT_TARGET, &[IoSlice::new(b"audit\n")])
+            .unwrap();
+        file.write("app", &[IoSlice::new(b"record2\n")]).unwrap();
+        file.flush().unwrap();
+        let got = fs::read_to_string(&path).unwrap();
+        assert_eq!(got, "record1\naudit\nrecord2\n", "{policy:?}");
+    }
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+#[cfg(feature = "log-panic")]
+fn truncate_backtrace() {
+    use crate::config::truncate_backtrace;
+
+    let backtrace = "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:9
+   1: my_app::main
+             at ./src/main.rs:4:5
+   2: core::ops::function::FnOnce::call_once
+";
+    assert_eq!(truncate_backtrace(backtrace, usize::MAX), backtrace);
+    assert_eq!(truncate_backtrace(backtrace, 3), backtrace);
+    assert_eq!(
+        truncate_backtrace(backtrace, 2),
+        "   0: std::backtrace::Backtrace::force_capture
+             at /rustc/library/std/src/backtrace.rs:312:9
+   1: my_app::main
+             at ./src/main.rs:4:5
+"
+    );
+    assert_eq!(truncate_backtrace(backtrace, 0), "");
+}
+
+#[test]
+#[cfg(feature = "log-panic")]
+fn format_backtrace() {
+    use crate::config::{format_backtrace, PanicOptions};
+    use crate::BacktraceStyle;
+
+    let options = |backtrace_style| PanicOptions {
+        backtrace_style,
+        backtrace_depth: 2,
+    };
+    assert_eq!(format_backtrace(options(BacktraceStyle::Off)), None);
+    for style in [BacktraceStyle::Short, BacktraceStyle::Full] {
+        let backtrace = format_backtrace(options(style)).unwrap();
+        // At most two frames, with their locations.
+        assert!(backtrace.lines().count() <= 4, "{backtrace}");
+    }
+    assert!(format_backtrace(PanicOptions::DEFAULT).is_some());
+}
+
+#[test]
+fn error_policy() {
+    use crate::{log_failure, ErrorPolicy};
+
+    let err = || std::io::Error::from(std::io::ErrorKind::BrokenPipe);
+    ErrorPolicy::Ignore.set();
+    log_failure(err());
+    ErrorPolicy::FallbackRaw.set();
+    log_failure(err());
+    ErrorPolicy::Panic.set();
+}
+
+#[test]
+fn write_all_partial_writes() {
+    /// Writes at most 3 bytes per call, returning an interrupted error on every
+    /// other call.
+    struct Partial {
+        buf: Vec<u8>,
+        interrupt: bool,
+    }
+
+    impl Write for Partial {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(std::io::ErrorKind::Interrupted.into());
+            }
+            let n = buf.len().min(3);
+            self.buf.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let mut output = Partial {
+        buf: Vec::new(),
+        interrupt: false,
+    };
+    let bufs = [
+        IoSlice::new(b"lvl=\"INFO\""),
+        IoSlice::new(b""),
+        IoSlice::new(b" msg=\"hi\""),
+        IoSlice::new(b"\n"),
+    ];
+    crate::write_all(&mut output, &bufs).unwrap();
+    assert_eq!(output.buf, b"lvl=\"INFO\" msg=\"hi\"\n");
+}
+
+#[test]
+fn metrics() {
+    use std::time::Duration;
+
+    use crate::metrics::{add_bytes_written, add_record, add_write_error, should_log};
+
+    let before = crate::metrics();
+    add_record(log::Level::Warn);
+    add_bytes_written(10);
+    add_write_error();
+    let after = crate::metrics();
+    // Other tests can log at the same time.
+    assert!(after.records(log::Level::Warn) > before.records(log::Level::Warn));
+    assert!(after.total_records() > before.total_records());
+    assert!(after.bytes_written >= before.bytes_written + 10);
+    assert!(after.write_errors > before.write_errors);
+
+    assert!(should_log(Duration::ZERO));
+    assert!(!should_log(Duration::from_secs(3600)));
+}
+
+#[test]
+#[cfg(feature = "timestamp")]
+fn coarse_clock() {
+    let now = SystemTime::now();
+    let coarse = crate::clock::coarse_now();
+    // The coarse clock has a resolution of a few milliseconds.
+    let diff = now
+        .duration_since(coarse)
+        .unwrap_or_else(|err| err.duration());
+    assert!(diff < Duration::from_millis(100), "{diff:?}");
+}
+
+#[test]
+fn redact() {
+    let mut redact = crate::redact::Redact::new();
+    redact.keys.push("password".into());
+    #[cfg(feature = "redact")]
+    redact.patterns.push(regex::Regex::new(r"\d{16}").unwrap());
+
+    let kvs: &[(&str, kv::Value)] = &[
+        ("user", "bob".into()),
+        ("Password", "hunter2".into()),
+        ("card", "card 1234567812345678".into()),
+        ("n", 1.into()),
+    ];
+    let record = Record::builder()
+        .args(format_args!("paid with 1234567812345678"))
+        .level(Level::Info)
+        .target("target")
+        .key_values(&kvs)
+        .build();
+    let mut got = String::new();
+    redact.apply(&record, |record| {
+        got = format_record::<LogFmt>(record, &Options::new(false))
+    });
+    #[cfg(not(feature = "redact"))]
+    let want = "lvl=\"INFO\" msg=\"paid with 1234567812345678\" target=\"target\" module=\"\" user=\"bob\" Password=\"[REDACTED]\" card=\"card 1234567812345678\" n=1\n";
+    #[cfg(feature = "redact")]
+    let want = "lvl=\"INFO\" msg=\"paid with [REDACTED]\" target=\"target\" module=\"\" user=\"bob\" Password=\"[REDACTED]\" card=\"card [REDACTED]\" n=1\n";
+    #[cfg(feature = "timestamp")]
+    let want = add_timestamp(want.to_owned(), SystemTime::now(), &got);
+    assert_eq!(got, *want);
+}
+
+#[test]
+fn custom_format_nested_records() {
+    use std::sync::OnceLock;
+
+    use crate::format::CustomFormat;
+
+    /// Format that formats a record with a message of `n - 1` while
+    /// formatting a record with message `n`.
+    static FORMAT: OnceLock<CustomFormat> = OnceLock::new();
+    /// Records written by `FORMAT`.
+    static WRITTEN: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+    fn format(n: usize) {
+        let args = format_args!("{n}");
+        let record = Record::builder().args(args).level(Level::Info).build();
+        FORMAT.get().unwrap().format(&record, |bufs| {
+            let record = str::from_utf8(&bufs[0]).unwrap().to_owned();
+            WRITTEN.lock().unwrap().push(record);
+        });
+    }
+
+    let _ = FORMAT.get_or_init(|| {
+        CustomFormat::new(|buf, record| {
+            let n = record.args().to_string().parse::<usize>().unwrap();
+            if n != 0 {
+                format(n - 1);
+            }
+            write!(buf, "n={n}").unwrap();
+        })
+    });
+
+    format(1);
+    assert_eq!(*WRITTEN.lock().unwrap(), ["n=0", "n=1"]);
+    WRITTEN.lock().unwrap().clear();
+
+    // Only two buffers are available, the most nested record is dropped.
+    format(2);
+    assert_eq!(*WRITTEN.lock().unwrap(), ["n=1", "n=2"]);
+}
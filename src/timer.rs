@@ -0,0 +1,119 @@
+//! Timing a block of code, see the [`timer!`] macro.
+//!
+//! [`timer!`]: crate::timer
+
+use std::cell::Cell;
+use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+
+use log::kv::{self, ToValue};
+use log::{Level, Record};
+
+use crate::value::{self, FmtDuration};
+use crate::OwnedValue;
+
+thread_local! {
+    /// Name of the timer currently running on this thread, if any.
+    static CURRENT: Cell<Option<&'static str>> = const { Cell::new(None) };
+}
+
+/// Guard that logs the time elapsed since it was created once it's dropped,
+/// see the [`timer!`] macro.
+///
+/// [`timer!`]: crate::timer
+#[derive(Debug)]
+#[must_use = "the elapsed time is logged once the timer is dropped"]
+pub struct LogTimer {
+    name: &'static str,
+    level: Level,
+    module_path: &'static str,
+    file: &'static str,
+    line: u32,
+    key_values: Vec<(&'static str, OwnedValue)>,
+    start: Instant,
+    /// Timer that was running when this timer was started.
+    parent: Option<&'static str>,
+    /// The name of the timer is stored in a thread local, so the timer must
+    /// not move to another thread.
+    not_send: PhantomData<*const ()>,
+}
+
+impl LogTimer {
+    /// Not part of the API, use the [`timer!`] macro.
+    ///
+    /// [`timer!`]: crate::timer
+    #[doc(hidden)]
+    pub fn _new(
+        name: &'static str,
+        level: Level,
+        (module_path, file, line): (&'static str, &'static str, u32),
+        key_values: Vec<(&'static str, OwnedValue)>,
+    ) -> LogTimer {
+        let parent = CURRENT.with(|current| current.replace(Some(name)));
+        LogTimer {
+            name,
+            level,
+            module_path,
+            file,
+            line,
+            key_values,
+            start: Instant::now(),
+            parent,
+            not_send: PhantomData,
+        }
+    }
+
+    /// Returns the name of the timer.
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the time elapsed since the timer was started.
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for LogTimer {
+    fn drop(&mut self) {
+        let elapsed = if crate::is_deterministic() {
+            Duration::ZERO
+        } else {
+            self.start.elapsed()
+        };
+        CURRENT.with(|current| current.set(self.parent));
+        let key_values = KeyValues {
+            timer: self,
+            elapsed: value::duration(elapsed),
+        };
+        log::logger().log(
+            &Record::builder()
+                .args(format_args!("{}", self.name))
+                .level(self.level)
+                .target(self.module_path)
+                .module_path_static(Some(self.module_path))
+                .file_static(Some(self.file))
+                .line(Some(self.line))
+                .key_values(&key_values)
+                .build(),
+        );
+    }
+}
+
+/// Key-values logged by [`LogTimer`].
+struct KeyValues<'a> {
+    timer: &'a LogTimer,
+    elapsed: FmtDuration,
+}
+
+impl<'a> kv::Source for KeyValues<'a> {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in &self.timer.key_values {
+            visitor.visit_pair(kv::Key::from(*key), value.to_value())?;
+        }
+        if let Some(parent) = self.timer.parent {
+            visitor.visit_pair(kv::Key::from("parent"), kv::Value::from(parent))?;
+        }
+        visitor.visit_pair(kv::Key::from("elapsed"), self.elapsed.to_value())
+    }
+}
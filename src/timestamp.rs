@@ -13,82 +13,42 @@ pub(crate) struct Timestamp {
 
 #[cfg(feature = "timestamp")]
 impl Timestamp {
-    pub(crate) fn now() -> Timestamp {
-        Timestamp::from(SystemTime::now())
-    }
-
-    /// # Notes
-    ///
-    /// This only works for days later then 2001.
+    /// Times before the Unix epoch are clamped to the epoch.
     // NOTE: pub for testing.
     #[allow(
         clippy::cast_possible_truncation,
+        clippy::cast_possible_wrap,
         clippy::cast_sign_loss,
-        clippy::similar_names,
-        clippy::unreadable_literal
+        clippy::similar_names
     )]
     pub(crate) fn from(time: SystemTime) -> Timestamp {
-        // Ported from musl, original source:
-        // <https://git.musl-libc.org/cgit/musl/tree/src/time/__secs_to_tm.c>.
-
-        /// 2000-03-01 (mod 400 year, immediately after feb29).
-        const LEAPOCH: u64 = 946684800 + 86400 * (31 + 29);
-        const DAYS_PER_400Y: u64 = 365 * 400 + 97;
-        const DAYS_PER_100Y: u64 = 365 * 100 + 24;
-        const DAYS_PER_4Y: u64 = 365 * 4 + 1;
-        const DAYS_IN_MONTH: [u64; 12] = [31, 30, 31, 30, 31, 31, 30, 31, 30, 31, 31, 29];
-
         let diff = time
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap_or_else(|_| Duration::new(0, 0));
-        let secs_since_epoch = diff.as_secs();
-
-        let secs = secs_since_epoch - LEAPOCH;
-        let days = secs / 86400;
-        let remsecs = secs % 86400;
-
-        let qc_cycles = days / DAYS_PER_400Y;
-        let mut remdays = days % DAYS_PER_400Y;
-
-        let mut c_cycles = remdays / DAYS_PER_100Y;
-        if c_cycles == 4 {
-            c_cycles -= 1;
-        }
-        remdays -= c_cycles * DAYS_PER_100Y;
-
-        let mut q_cycles = remdays / DAYS_PER_4Y;
-        if q_cycles == 25 {
-            q_cycles -= 1;
-        }
-        remdays -= q_cycles * DAYS_PER_4Y;
-
-        let mut remyears = remdays / 365;
-        if remyears == 4 {
-            remyears -= 1;
-        }
-        remdays -= remyears * 365;
-
-        let mut year = remyears + (4 * q_cycles) + (100 * c_cycles) + (400 * qc_cycles);
-
-        // Determine the month of the year based on the remaining days
-        // (`remdays`).
-        let mut month = 0;
-        for days_in_month in DAYS_IN_MONTH {
-            if days_in_month > remdays {
-                break;
-            }
-            remdays -= days_in_month;
-            month += 1;
-        }
-        if month >= 10 {
-            month -= 12;
-            year += 1;
-        }
+        let secs_since_epoch = i64::try_from(diff.as_secs()).unwrap_or(i64::MAX);
+        let days = secs_since_epoch / 86_400;
+        let remsecs = secs_since_epoch % 86_400;
+
+        // Civil from days, see
+        // <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+        // Shift the epoch to 0000-03-01, the start of a 400 year era, so that
+        // the leap day is the last day of the (shifted) year.
+        let days = days + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days % 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        // Month starting in March.
+        let month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month + 2) / 5 + 1;
+        let month = if month < 10 { month + 3 } else { month - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
 
         Timestamp {
-            year: (year + 100 + 1900) as u16,
-            month: (month + 2 + 1) as u8,
-            day: (remdays + 1) as u8,
+            year: year.min(9999) as u16,
+            month: month as u8,
+            day: day as u8,
             hour: (remsecs / 3600) as u8,
             min: (remsecs / 60 % 60) as u8,
             sec: (remsecs % 60) as u8,
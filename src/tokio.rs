@@ -0,0 +1,97 @@
+//! Logging for async applications, see the *tokio* feature.
+//!
+//! Writing a record to standard error blocks the thread if the output is full,
+//! e.g. when the log collector reading it is throttled. In an async
+//! application, e.g. one using the [tokio] runtime, that blocks all tasks
+//! running on the same thread. The logger initialised by this module formats
+//! the records on the logging thread, but writes them on a dedicated writer
+//! thread (see [`Config::with_writer_thread`]), so logging never blocks.
+//!
+//! # Drop policy
+//!
+//! The records are queued for the writer thread, up to [`QUEUE_CAPACITY`]
+//! records. If the queue is full the record is dropped and counted in
+//! [`dropped_records`], rather than blocking the logging thread. Audit and
+//! panic records are never dropped, they're written directly.
+//!
+//! Queued records are written once the logger is flushed, so use
+//! [`init_with_guard`] (or call [`shutdown`]) to not lose the records queued
+//! when the application exits.
+//!
+//! [tokio]: https://crates.io/crates/tokio
+//! [`Config::with_writer_thread`]: crate::Config::with_writer_thread
+//! [`dropped_records`]: crate::dropped_records
+//! [`shutdown`]: crate::shutdown
+//!
+//! # Examples
+//!
+//! ```
+//! use log::info;
+//!
+//! # fn main() {
+//! // In the `main` function, before starting the runtime.
+//! let _guard = std_logger::tokio::init_with_guard();
+//!
+//! // Inside async tasks, the record is written on the writer thread.
+//! info!("handling request");
+//!
+//! // Dropping the guard writes the queued records.
+//! # }
+//! ```
+//!
+//! To use another format or other options use [`Config::with_writer_thread`]
+//! directly.
+//!
+//! ```
+//! use std_logger::Config;
+//!
+//! # fn main() {
+//! let _guard = Config::json()
+//!     .with_writer_thread(std_logger::tokio::QUEUE_CAPACITY)
+//!     .init_with_guard();
+//! # }
+//! ```
+
+use log::SetLoggerError;
+
+use crate::{Config, LoggerGuard};
+
+/// Number of records queued for the writer thread, see the [module
+/// documentation].
+///
+/// [module documentation]: crate::tokio
+pub const QUEUE_CAPACITY: usize = 8192;
+
+/// Initialise the logger using logfmt, writing the records on a dedicated
+/// thread.
+///
+/// # Panics
+///
+/// This will panic if the logger fails to initialise. Use [`try_init`] if you
+/// want to handle the error yourself.
+pub fn init() {
+    try_init().unwrap_or_else(|err| panic!("failed to initialise the logger: {err}"));
+}
+
+/// Try to initialise the logger using logfmt, writing the records on a
+/// dedicated thread.
+///
+/// Unlike [`init`] this doesn't panic when the logger fails to initialise.
+pub fn try_init() -> Result<(), SetLoggerError> {
+    Config::logfmt()
+        .with_writer_thread(QUEUE_CAPACITY)
+        .try_init()
+}
+
+/// Initialise the logger using logfmt, writing the records on a dedicated
+/// thread, returning a guard that writes the queued records and shuts the
+/// logger down when dropped, see [`LoggerGuard`].
+///
+/// # Panics
+///
+/// This will panic if the logger fails to initialise.
+pub fn init_with_guard() -> LoggerGuard {
+    Config::logfmt()
+        .with_writer_thread(QUEUE_CAPACITY)
+        .init_with_guard()
+}
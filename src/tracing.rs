@@ -0,0 +1,191 @@
+//! Integration with the [tracing] ecosystem, see the *tracing* feature.
+//!
+//! [`Layer`] is a [`tracing_subscriber::Layer`] that logs tracing events using
+//! the logger of this crate, so that crates using [log] and crates using
+//! tracing produce the same output. The fields of the event are logged as
+//! key-values, prefixed by the fields of the spans the event is in (from the
+//! root span to the current span).
+//!
+//! [tracing]: https://docs.rs/tracing
+//! [log]: mod@log
+//!
+//! # Examples
+//!
+//! ```
+//! use tracing_subscriber::layer::SubscriberExt;
+//!
+//! # fn main() {
+//! std_logger::Config::logfmt().init();
+//! let subscriber = tracing_subscriber::registry().with(std_logger::tracing::Layer::new());
+//! tracing_core::dispatcher::set_global_default(subscriber.into())
+//!     .expect("failed to set tracing subscriber");
+//!
+//! // Logged as:
+//! // `lvl="INFO" msg="request done" target="my_app" module="my_app" request_id=123 status=200`.
+//! tracing::info_span!("request", request_id = 123).in_scope(|| {
+//!     tracing::info!(status = 200, "request done");
+//! });
+//! # }
+//! ```
+
+use std::fmt::{self, Write};
+
+use log::kv;
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, Level, Subscriber};
+use tracing_subscriber::layer::{self, Context};
+use tracing_subscriber::registry::LookupSpan;
+
+/// [`tracing_subscriber::Layer`] that logs events using the logger of this
+/// crate, see the [module documentation].
+///
+/// [module documentation]: crate::tracing
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Layer {
+    _private: (),
+}
+
+impl Layer {
+    /// Create a new layer.
+    pub const fn new() -> Layer {
+        Layer { _private: () }
+    }
+}
+
+impl<S> layer::Layer<S> for Layer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(fields);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<Fields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let level = convert_level(*metadata.level());
+        if level > log::max_level() {
+            return;
+        }
+
+        let mut fields = Fields::default();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<Fields>() {
+                    fields.pairs.extend(span_fields.pairs.iter().cloned());
+                }
+            }
+        }
+        event.record(&mut fields);
+
+        let logger = log::logger();
+        logger.log(
+            &log::Record::builder()
+                .args(format_args!("{}", fields.message))
+                .level(level)
+                .target(metadata.target())
+                .module_path_static(metadata.module_path())
+                .file_static(metadata.file())
+                .line(metadata.line())
+                .key_values(&fields)
+                .build(),
+        );
+    }
+}
+
+/// Convert a tracing level into a log level.
+const fn convert_level(level: Level) -> log::Level {
+    match level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+/// Message and fields of an event or span.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    pairs: Vec<(&'static str, Value)>,
+}
+
+/// Value of a field.
+#[derive(Clone)]
+enum Value {
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    String(String),
+}
+
+impl Visit for Fields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.pairs.push((field.name(), Value::F64(value)));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.pairs.push((field.name(), Value::I64(value)));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.pairs.push((field.name(), Value::U64(value)));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.pairs.push((field.name(), Value::Bool(value)));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message.push_str(value);
+        } else {
+            self.pairs
+                .push((field.name(), Value::String(value.to_owned())));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            // The message is passed as `format_args!`, which formats the same
+            // using `Debug` as using `Display`.
+            let _ = write!(self.message, "{value:?}");
+        } else {
+            self.pairs
+                .push((field.name(), Value::String(format!("{value:?}"))));
+        }
+    }
+}
+
+impl kv::Source for Fields {
+    fn visit<'kvs>(&'kvs self, visitor: &mut dyn kv::VisitSource<'kvs>) -> Result<(), kv::Error> {
+        for (key, value) in &self.pairs {
+            let value = match value {
+                Value::Bool(value) => kv::Value::from(*value),
+                Value::I64(value) => kv::Value::from(*value),
+                Value::U64(value) => kv::Value::from(*value),
+                Value::F64(value) => kv::Value::from(*value),
+                Value::String(value) => kv::Value::from(&**value),
+            };
+            visitor.visit_pair(kv::Key::from(*key), value)?;
+        }
+        Ok(())
+    }
+}
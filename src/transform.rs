@@ -0,0 +1,105 @@
+//! Transforming records before they're written, see [`RecordTransform`].
+
+use std::fmt;
+
+use log::Record;
+
+use crate::OwnedRecord;
+
+/// Transformation of records before they're written, added using
+/// [`Config::add_transform`].
+///
+/// Transforms can enrich records (e.g. add key-values), change them (e.g.
+/// redact values) or drop them (e.g. sampling). They're applied in the order
+/// they're added, to all records that are enabled by the log level and
+/// targets.
+///
+/// Transforming requires copying the record into an [`OwnedRecord`], which
+/// allocates, so only use transforms if needed.
+///
+/// It's implemented for functions and closures with the same signature as
+/// [`RecordTransform::transform`].
+///
+/// [`Config::add_transform`]: crate::Config::add_transform
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::{Action, Config, OwnedRecord};
+///
+/// Config::logfmt()
+///     // Add the hostname to all records.
+///     .add_transform(|record: &mut OwnedRecord| {
+///         record.insert("host", "web-1");
+///         Action::Keep
+///     })
+///     // Drop health check records.
+///     .add_transform(|record: &mut OwnedRecord| {
+///         if record.msg.contains("/health") {
+///             Action::Drop
+///         } else {
+///             Action::Keep
+///         }
+///     })
+///     .init();
+/// ```
+pub trait RecordTransform: Send + Sync {
+    /// Transform `record`, returning whether to keep or drop it.
+    fn transform(&self, record: &mut OwnedRecord) -> Action;
+}
+
+impl<F> RecordTransform for F
+where
+    F: Fn(&mut OwnedRecord) -> Action + Send + Sync,
+{
+    fn transform(&self, record: &mut OwnedRecord) -> Action {
+        (self)(record)
+    }
+}
+
+/// Result of a [`RecordTransform`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum Action {
+    /// Keep the record, passing it to the next transform or writing it.
+    Keep,
+    /// Drop the record, it's not written and no other transforms are applied.
+    Drop,
+}
+
+/// Transforms added to the configuration.
+pub(crate) struct Transforms(Vec<Box<dyn RecordTransform>>);
+
+impl Transforms {
+    pub(crate) const fn new() -> Transforms {
+        Transforms(Vec::new())
+    }
+
+    pub(crate) fn push(&mut self, transform: Box<dyn RecordTransform>) {
+        self.0.push(transform);
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Apply all transforms to `record`, calling `f` with the transformed
+    /// record unless it's dropped.
+    pub(crate) fn apply<F: FnOnce(&Record)>(&self, record: &Record, f: F) {
+        let mut record = OwnedRecord::from(record);
+        for transform in &self.0 {
+            if transform.transform(&mut record) == Action::Drop {
+                return;
+            }
+        }
+        record.with_record(f);
+    }
+}
+
+impl fmt::Debug for Transforms {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transforms")
+            .field("len", &self.0.len())
+            .finish()
+    }
+}
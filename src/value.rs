@@ -0,0 +1,186 @@
+//! Consistent formatting of common types as the value of key-value pairs.
+//!
+//! The [log] crate doesn't support logging types such as [`Duration`] or byte
+//! slices as value directly. The functions in this module wrap these types,
+//! formatting them in a way that is human-friendly and can be parsed again
+//! (e.g. by the std-logger-parser crate).
+//!
+//! [log]: mod@log
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use log::info;
+//! use std_logger::value;
+//!
+//! # fn main() {
+//! let took = Duration::from_micros(12_500);
+//! let id = [0xde, 0xad, 0xbe, 0xef];
+//! // Logs: `took="12.5ms" id="deadbeef" token="aGVsbG8="`.
+//! info!(took = value::duration(took), id = value::hex(&id), token = value::base64(b"hello"); "request");
+//! # }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+#[cfg(feature = "timestamp")]
+use std::time::SystemTime;
+
+use log::kv;
+
+/// Format `duration` using the largest fitting unit, e.g. `12.5ms` or `2s`.
+///
+/// The supported units are `ns`, `µs`, `ms` and `s`.
+pub const fn duration(duration: Duration) -> FmtDuration {
+    FmtDuration(duration)
+}
+
+/// Formatted duration, see [`duration`].
+#[derive(Copy, Clone, Debug)]
+pub struct FmtDuration(Duration);
+
+impl fmt::Display for FmtDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // NOTE: the `Debug` implementation already uses the format we want.
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl kv::ToValue for FmtDuration {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+/// Format `timestamp` as RFC 3339 timestamp in UTC, in the same format as the
+/// timestamp of the log message, e.g. `2018-03-24T13:48:28.820588Z`.
+///
+/// Requires the *timestamp* feature.
+#[cfg(feature = "timestamp")]
+pub const fn timestamp(timestamp: SystemTime) -> FmtTimestamp {
+    FmtTimestamp(timestamp)
+}
+
+/// Formatted timestamp, see [`timestamp()`].
+#[cfg(feature = "timestamp")]
+#[derive(Copy, Clone, Debug)]
+pub struct FmtTimestamp(SystemTime);
+
+#[cfg(feature = "timestamp")]
+impl fmt::Display for FmtTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ts = crate::timestamp::Timestamp::from(self.0);
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+            ts.year, ts.month, ts.day, ts.hour, ts.min, ts.sec, ts.micro
+        )
+    }
+}
+
+#[cfg(feature = "timestamp")]
+impl kv::ToValue for FmtTimestamp {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+/// Format `bytes` as lowercase hexadecimal string, e.g. `deadbeef`.
+pub const fn hex(bytes: &[u8]) -> Hex<'_> {
+    Hex(bytes)
+}
+
+/// Hexadecimal formatted bytes, see [`hex`].
+#[derive(Copy, Clone, Debug)]
+pub struct Hex<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{b:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> kv::ToValue for Hex<'a> {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+/// Format `bytes` using (standard, padded) base64, e.g. `aGVsbG8=`.
+pub const fn base64(bytes: &[u8]) -> Base64<'_> {
+    Base64(bytes)
+}
+
+/// Base64 formatted bytes, see [`base64`].
+#[derive(Copy, Clone, Debug)]
+pub struct Base64<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Base64<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        for chunk in self.0.chunks(3) {
+            let n = (u32::from(chunk[0]) << 16)
+                | (u32::from(*chunk.get(1).unwrap_or(&0)) << 8)
+                | u32::from(*chunk.get(2).unwrap_or(&0));
+            let mut encoded = [b'='; 4];
+            for (i, c) in encoded.iter_mut().enumerate().take(chunk.len() + 1) {
+                *c = ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize];
+            }
+            for c in encoded {
+                fmt::Write::write_char(f, c as char)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> kv::ToValue for Base64<'a> {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
+
+/// Format the sources of `err`, i.e. what caused the error, separated by
+/// `: `, e.g. `failed to read config: permission denied`. Formats as an empty
+/// string if `err` has no source.
+///
+/// See the [`log_error!`] macro to log an error together with its sources.
+///
+/// [`log_error!`]: crate::log_error
+pub const fn error_chain<'a>(err: &'a (dyn Error + 'a)) -> ErrorChain<'a> {
+    ErrorChain(err)
+}
+
+/// Formatted sources of an error, see [`error_chain`].
+#[derive(Copy, Clone, Debug)]
+pub struct ErrorChain<'a>(&'a (dyn Error + 'a));
+
+impl<'a> fmt::Display for ErrorChain<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut source = self.0.source();
+        let mut first = true;
+        while let Some(err) = source {
+            if !first {
+                f.write_str(": ")?;
+            }
+            first = false;
+            fmt::Display::fmt(err, f)?;
+            source = err.source();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> kv::ToValue for ErrorChain<'a> {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
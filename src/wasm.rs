@@ -0,0 +1,39 @@
+//! Support for `wasm32-unknown-unknown`, see the *wasm* feature.
+//!
+//! The target doesn't support standard out/error or getting the system time,
+//! so we use JavaScript's `console` and `Date` instead.
+
+use std::io::IoSlice;
+use std::str;
+#[cfg(feature = "timestamp")]
+use std::time::{Duration, SystemTime};
+
+use log::Level;
+use wasm_bindgen::JsValue;
+use web_sys::console;
+
+/// Write the formatted record in `bufs` to the console, using the console
+/// function matching the `level`, e.g. `console.error` for errors.
+pub(crate) fn write(level: Level, bufs: &[IoSlice]) {
+    let mut record = String::with_capacity(bufs.iter().map(|buf| buf.len()).sum());
+    for buf in bufs {
+        // The formatted record is always valid UTF-8.
+        record.push_str(str::from_utf8(buf).unwrap_or_default());
+    }
+    // The console adds its own line ending.
+    let record = JsValue::from_str(record.trim_end_matches('\n'));
+    match level {
+        Level::Error => console::error_1(&record),
+        Level::Warn => console::warn_1(&record),
+        Level::Info => console::info_1(&record),
+        Level::Debug | Level::Trace => console::debug_1(&record),
+    }
+}
+
+/// Returns the current time using `Date.now()`, [`SystemTime::now`] panics on
+/// this target.
+#[cfg(feature = "timestamp")]
+pub(crate) fn now() -> SystemTime {
+    let millis = js_sys::Date::now();
+    SystemTime::UNIX_EPOCH + Duration::from_secs_f64(millis / 1000.0)
+}
@@ -0,0 +1,98 @@
+//! Support for logging byte buffers, see the [`hex_dump!`] macro.
+//!
+//! [`hex_dump!`]: crate::hex_dump
+
+use std::fmt;
+
+use log::kv;
+
+/// Default maximum number of bytes shown in a [`HexDump`].
+const DEFAULT_LIMIT: usize = 256;
+
+/// Number of bytes shown per line.
+const LINE_LEN: usize = 16;
+
+/// Format `bytes` as a hex dump, similar to `hexdump -C`.
+///
+/// By default at most 256 bytes are shown, see [`HexDump::limit`]. The
+/// formatting is done when the dump is formatted, so no work is done if the log
+/// message is filtered.
+///
+/// # Examples
+///
+/// ```
+/// use std_logger::hex_dump;
+///
+/// let dump = hex_dump(b"Hello world\n");
+/// assert_eq!(
+///     dump.to_string(),
+///     "00000000  48 65 6c 6c 6f 20 77 6f  72 6c 64 0a              |Hello world.|"
+/// );
+/// ```
+pub const fn hex_dump(bytes: &[u8]) -> HexDump<'_> {
+    HexDump {
+        bytes,
+        limit: DEFAULT_LIMIT,
+    }
+}
+
+/// Hex dump of a byte buffer, see [`hex_dump`].
+#[derive(Copy, Clone, Debug)]
+pub struct HexDump<'a> {
+    bytes: &'a [u8],
+    limit: usize,
+}
+
+impl<'a> HexDump<'a> {
+    /// Show at most `limit` bytes, the number of bytes not shown is noted on
+    /// the last line.
+    pub const fn limit(mut self, limit: usize) -> HexDump<'a> {
+        self.limit = limit;
+        self
+    }
+}
+
+impl<'a> fmt::Display for HexDump<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = &self.bytes[..self.bytes.len().min(self.limit)];
+        for (i, line) in shown.chunks(LINE_LEN).enumerate() {
+            if i != 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{:08x} ", i * LINE_LEN)?;
+            for j in 0..LINE_LEN {
+                if j % 8 == 0 {
+                    f.write_str(" ")?;
+                }
+                match line.get(j) {
+                    Some(b) => write!(f, "{b:02x} ")?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str(" |")?;
+            for b in line {
+                let c = if b.is_ascii_graphic() || *b == b' ' {
+                    *b as char
+                } else {
+                    '.'
+                };
+                fmt::Write::write_char(f, c)?;
+            }
+            f.write_str("|")?;
+        }
+        let left = self.bytes.len() - shown.len();
+        if left != 0 {
+            if !shown.is_empty() {
+                f.write_str("\n")?;
+            }
+            write!(f, "... {left} more bytes")?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> kv::ToValue for HexDump<'a> {
+    fn to_value(&self) -> kv::Value<'_> {
+        kv::Value::from_display(self)
+    }
+}
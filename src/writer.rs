@@ -0,0 +1,167 @@
+//! Writing records on a dedicated thread, see [`Config::with_writer_thread`].
+//!
+//! [`Config::with_writer_thread`]: crate::Config::with_writer_thread
+
+use std::io::{self, IoSlice};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+
+use log::{Level, Record};
+
+use crate::full;
+
+/// Whether or not the writer thread is running. Not set if spawning the
+/// thread failed, or in the child process after `fork`, where the thread
+/// doesn't exist, in which case records are written directly.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Handle of the writer thread and a sender to stop it, see [`stop`].
+static THREAD: Mutex<Option<(SyncSender<Message>, JoinHandle<()>)>> = Mutex::new(None);
+
+/// Sending side of the queue of the writer thread.
+#[derive(Debug)]
+pub(crate) struct Writer {
+    sender: SyncSender<Message>,
+}
+
+/// Message send to the writer thread.
+#[derive(Debug)]
+pub(crate) enum Message {
+    /// Formatted record to write.
+    Record {
+        level: Level,
+        target: Box<str>,
+        record: Vec<u8>,
+    },
+    /// Flush the outputs, sending a message on the channel once done.
+    Flush(SyncSender<()>),
+    /// Write the queued records and stop the thread.
+    Stop,
+}
+
+/// Create a new writer with a queue of `capacity` records, returning the
+/// receiving side of the queue to pass to [`spawn`].
+pub(crate) fn new(capacity: usize) -> (Writer, Receiver<Message>) {
+    let (sender, receiver) = mpsc::sync_channel(capacity);
+    (Writer { sender }, receiver)
+}
+
+impl Writer {
+    /// Returns `true` if the writer thread is running.
+    pub(crate) fn is_active(&self) -> bool {
+        ACTIVE.load(Ordering::Relaxed)
+    }
+
+    /// Send the formatted `record` in `bufs` to the writer thread, without
+    /// blocking. If the queue is full the record is dropped, see
+    /// [`dropped_records`]. Returns `false` if the writer thread is stopped, in
+    /// which case the record should be written directly.
+    ///
+    /// [`dropped_records`]: crate::dropped_records
+    pub(crate) fn send(&self, record: &Record, bufs: &[IoSlice]) -> bool {
+        let len = bufs.iter().map(|buf| buf.len()).sum();
+        let mut formatted = Vec::with_capacity(len);
+        for buf in bufs {
+            formatted.extend_from_slice(buf);
+        }
+        let message = Message::Record {
+            level: record.level(),
+            target: record.target().into(),
+            record: formatted,
+        };
+        match self.sender.try_send(message) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                full::add_dropped();
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+
+    /// Wait until all records send before this call are written and the
+    /// outputs are flushed.
+    pub(crate) fn flush(&self) {
+        let (sender, receiver) = mpsc::sync_channel(1);
+        if self.sender.send(Message::Flush(sender)).is_ok() {
+            let _ = receiver.recv();
+        }
+    }
+}
+
+/// Spawn the writer thread for `writer`, calling `write` for each record and
+/// `flush` when the outputs should be flushed.
+pub(crate) fn spawn<W, F>(
+    writer: &Writer,
+    receiver: Receiver<Message>,
+    write: W,
+    flush: F,
+) -> io::Result<()>
+where
+    W: Fn(&Record, &[IoSlice]) + Send + 'static,
+    F: Fn() + Send + 'static,
+{
+    let write = move |message| match message {
+        Message::Record {
+            level,
+            target,
+            record,
+        } => write(
+            &Record::builder().level(level).target(&target).build(),
+            &[IoSlice::new(&record)],
+        ),
+        Message::Flush(done) => {
+            flush();
+            let _ = done.send(());
+        }
+        Message::Stop => {}
+    };
+    let handle = thread::Builder::new()
+        .name("std-logger-writer".into())
+        .spawn(move || {
+            for message in receiver.iter() {
+                if let Message::Stop = message {
+                    break;
+                }
+                write(message);
+            }
+            // Records logged from now on are written directly. Write the
+            // records queued before that, records send after the receiver is
+            // dropped are written directly as well (see `Writer::send`).
+            ACTIVE.store(false, Ordering::Relaxed);
+            receiver.try_iter().for_each(write);
+        })?;
+    ACTIVE.store(true, Ordering::Relaxed);
+    *THREAD.lock().unwrap_or_else(PoisonError::into_inner) = Some((writer.sender.clone(), handle));
+    Ok(())
+}
+
+/// Stop the writer thread, blocking until the records queued before this call
+/// are written.
+pub(crate) fn stop() {
+    let thread = THREAD.lock().unwrap_or_else(PoisonError::into_inner).take();
+    if let Some((sender, handle)) = thread {
+        // Queued after the records already in the queue, blocking if the queue
+        // is full.
+        if sender.send(Message::Stop).is_ok() {
+            // The thread only panics if writing panics, which is already
+            // logged.
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Forget the writer thread, as it doesn't exist in the child process after
+/// `fork`. The records still in the queue are written by the parent process.
+#[cfg(unix)]
+pub(crate) fn forget() {
+    ACTIVE.store(false, Ordering::Relaxed);
+    // Can't block in the child process, see `reload::forget_watcher`.
+    if let Ok(mut thread) = THREAD.try_lock() {
+        if let Some(thread) = thread.take() {
+            std::mem::forget(thread);
+        }
+    }
+}
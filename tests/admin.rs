@@ -0,0 +1,63 @@
+//! Tests for `Config::with_admin_socket`.
+
+#![cfg(all(feature = "admin", unix))]
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+
+use log::{Level, Metadata};
+use std_logger::Config;
+
+/// Returns `true` if a record with `target` and `level` is enabled.
+fn enabled(target: &str, level: Level) -> bool {
+    let metadata = Metadata::builder().target(target).level(level).build();
+    log::logger().enabled(&metadata)
+}
+
+#[test]
+fn admin() {
+    let path = std::env::temp_dir().join(format!("std_logger_admin_{}.sock", std::process::id()));
+
+    Config::logfmt()
+        .with_call_location(false)
+        .with_admin_socket(&path)
+        .init();
+
+    let stream = UnixStream::connect(&path).unwrap();
+    let mut reader = BufReader::new(&stream);
+    let mut send = |command: &str| {
+        (&stream)
+            .write_all(format!("{command}\n").as_bytes())
+            .unwrap();
+        let mut response = String::new();
+        reader.read_line(&mut response).unwrap();
+        response.trim_end().to_owned()
+    };
+
+    assert!(!enabled("app::db", Level::Debug));
+    assert_eq!(send("warn, app::db=debug"), "ok");
+    assert!(enabled("app::db", Level::Debug));
+    assert!(enabled("app::db::pool", Level::Debug));
+    assert!(!enabled("app", Level::Info));
+    assert!(log::max_level() >= Level::Debug);
+    assert_eq!(send("show"), "warn,app::db=debug");
+
+    // Changing an existing pattern.
+    assert_eq!(send("app::db=trace"), "ok");
+    assert_eq!(send("show"), "warn,app::db=trace");
+
+    assert_eq!(send("app::db=loud"), "error: invalid level");
+    assert_eq!(send("=debug"), "error: missing target");
+    assert_eq!(send(""), "error: missing command");
+    assert_eq!(send("show"), "warn,app::db=trace");
+
+    assert_eq!(send("reset"), "ok");
+    assert!(!enabled("app::db", Level::Debug));
+    assert!(enabled("app", Level::Info));
+    assert_eq!(send("show"), "info");
+
+    // Stops the thread, even with an open connection, and removes the socket.
+    std_logger::shutdown();
+    assert!(fs::symlink_metadata(&path).is_err());
+}
@@ -0,0 +1,34 @@
+//! Tests for audit logging.
+
+use std::fs::{self, File};
+
+use log::info;
+use std_logger::{audit, AuditOutput, Config, EnvMeaning};
+
+#[test]
+fn audit_to_file() {
+    let path = std::env::temp_dir().join(format!("std_logger_audit_{}.log", std::process::id()));
+    let file = File::create(&path).unwrap();
+    // Only log errors.
+    std::env::set_var("AUDIT_TEST_LOG_LEVEL", "error");
+    Config::logfmt()
+        .with_env_vars(&[("AUDIT_TEST_LOG_LEVEL", EnvMeaning::Level)])
+        .with_call_location(false)
+        .with_audit_output(AuditOutput::File(file))
+        .with_audit_sync(true)
+        .init();
+    assert!(!log::log_enabled!(target: "app", log::Level::Info));
+
+    audit!(user_id = 123; "granted role");
+    info!(target: "app", "not logged");
+
+    let got = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(got.lines().count(), 1, "{got}");
+    assert!(
+        got.ends_with(
+            "lvl=\"INFO\" msg=\"granted role\" target=\"audit\" module=\"audit\" user_id=123\n"
+        ),
+        "{got}"
+    );
+}
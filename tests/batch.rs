@@ -0,0 +1,73 @@
+//! Tests for `Config::with_batch`.
+//!
+//! The logger writes to standard error, so the test runs itself in a child
+//! process and checks its output.
+
+#![cfg(unix)]
+
+use std::env;
+use std::process::{self, Command};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use log::info;
+use std_logger::{BatchPolicy, Config};
+
+/// Environment variable set in the child process.
+const CHILD: &str = "STD_LOGGER_BATCH_TEST_CHILD";
+
+#[test]
+fn batch() {
+    if env::var_os(CHILD).is_some() {
+        return log_batched();
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "batch", "--nocapture"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "info")
+        .env_remove("LOG_TARGET")
+        .output()
+        .expect("failed to run child process");
+    assert!(output.status.success(), "child failed: {output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // Ignore the output of the test harness and the timestamps.
+    let lines = stderr
+        .lines()
+        .filter(|line| !line.starts_with("running") && !line.is_empty())
+        .map(|line| line.find("lvl=").map_or(line, |i| &line[i..]))
+        .collect::<Vec<_>>();
+    let want = [
+        "lvl=\"INFO\" msg=\"idle thread\" target=\"batch\" module=\"batch\"",
+        "written after the maximum delay",
+        "lvl=\"INFO\" msg=\"before exit\" target=\"batch\" module=\"batch\"",
+    ];
+    assert_eq!(lines, want, "{stderr}");
+}
+
+fn log_batched() {
+    Config::logfmt()
+        .with_call_location(false)
+        .with_batch(BatchPolicy::new(100, Duration::from_millis(200)))
+        .init();
+
+    // The thread keeps running, but doesn't log until asked to.
+    let (log, logging) = mpsc::channel::<()>();
+    let (logged, done) = mpsc::channel::<()>();
+    let _ = thread::spawn(move || {
+        info!("idle thread");
+        logging.recv().unwrap();
+        info!("before exit");
+        logged.send(()).unwrap();
+        // Keep the thread, and its batch, alive until the process exits.
+        thread::park();
+    });
+    thread::sleep(Duration::from_secs(1));
+    eprintln!("written after the maximum delay");
+
+    // Written when the process exits.
+    log.send(()).unwrap();
+    done.recv().unwrap();
+    process::exit(0);
+}
@@ -0,0 +1,32 @@
+//! Tests for the `child` module.
+
+#![cfg(unix)]
+
+use std::process::Command;
+
+use log::LevelFilter;
+use std_logger::{child, Config, RingBufferSink};
+
+#[test]
+fn spawn() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    let child = child::spawn(Command::new("sh").args(["-c", "echo out; echo err >&2"])).unwrap();
+    let pid = child.id();
+    assert!(child.wait().unwrap().success());
+
+    let mut records: Vec<String> = sink
+        .records()
+        .into_iter()
+        .map(|record| String::from_utf8(record).unwrap())
+        .collect();
+    // Standard out and error are read by different threads.
+    records.sort_by_key(|record| record.contains("stderr"));
+    assert_eq!(records.len(), 2, "{records:?}");
+    assert!(records[0].ends_with(&format!("lvl=\"INFO\" msg=\"out\" target=\"child\" module=\"std_logger::child\" pid={pid} stream=\"stdout\"\n")), "{records:?}");
+    assert!(records[1].ends_with(&format!("lvl=\"WARN\" msg=\"err\" target=\"child\" module=\"std_logger::child\" pid={pid} stream=\"stderr\"\n")), "{records:?}");
+}
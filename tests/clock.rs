@@ -0,0 +1,35 @@
+//! Tests for `Config::with_clock`.
+
+#![cfg(feature = "timestamp")]
+
+use std::time::{Duration, SystemTime};
+
+use log::{info, LevelFilter};
+use std_logger::{Clock, Config, RingBufferSink};
+
+/// 2021-02-03T04:05:06.789012Z.
+fn fixed() -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_micros(1_612_325_106_789_012)
+}
+
+#[test]
+fn custom_clock() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_clock(Clock::Custom(fixed))
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    info!("first");
+    info!("second");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    for record in records {
+        assert!(
+            record.starts_with(b"ts=\"2021-02-03T04:05:06.789012Z\" "),
+            "{}",
+            String::from_utf8_lossy(&record)
+        );
+    }
+}
@@ -0,0 +1,32 @@
+//! Tests for `Config::with_clock` returning a time before 2000.
+
+#![cfg(feature = "timestamp")]
+
+use std::time::SystemTime;
+
+use log::{info, LevelFilter};
+use std_logger::{Clock, Config, RingBufferSink};
+
+fn epoch() -> SystemTime {
+    SystemTime::UNIX_EPOCH
+}
+
+#[test]
+fn clock_before_2000() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_clock(Clock::Custom(epoch))
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    info!("at the epoch");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = String::from_utf8_lossy(&records[0]);
+    assert!(
+        record.starts_with("ts=\"1970-01-01T00:00:00.000000Z\" "),
+        "{record}"
+    );
+}
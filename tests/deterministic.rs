@@ -0,0 +1,29 @@
+//! Tests for `Config::with_deterministic_output`.
+
+#![cfg(feature = "timestamp")]
+
+use log::{info, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+#[test]
+fn deterministic_output() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_deterministic_output()
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    info!(user_id = 1; "first");
+    info!("second");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        &*records[0],
+        b"ts=\"2001-01-01T00:00:00.000000Z\" lvl=\"INFO\" msg=\"first\" target=\"deterministic\" module=\"deterministic\" user_id=1\n"
+    );
+    assert_eq!(
+        &*records[1],
+        b"ts=\"2001-01-01T00:00:00.001000Z\" lvl=\"INFO\" msg=\"second\" target=\"deterministic\" module=\"deterministic\"\n"
+    );
+}
@@ -0,0 +1,51 @@
+//! Tests for initialising the logger more than once.
+
+use std::thread;
+
+use log::{info, LevelFilter};
+use std_logger::{is_initialised, Config, RingBufferSink};
+
+#[test]
+fn double_init() {
+    assert!(!is_initialised());
+
+    // Only one of the threads sets the logger.
+    let sinks: Vec<RingBufferSink> = (0..4).map(|_| RingBufferSink::new(10)).collect();
+    thread::scope(|s| {
+        for sink in &sinks {
+            let _ = s.spawn(move || {
+                Config::logfmt()
+                    .with_call_location(false)
+                    .add_sink(sink.clone(), LevelFilter::Info)
+                    .init();
+                assert!(is_initialised());
+            });
+        }
+    });
+    assert!(is_initialised());
+
+    // Initialising again does nothing.
+    let other = RingBufferSink::new(10);
+    Config::json()
+        .add_sink(other.clone(), LevelFilter::Info)
+        .init();
+    Config::logfmt().try_init().unwrap();
+    // Dropping the guard doesn't shut down the logger.
+    drop(Config::logfmt().init_with_guard());
+
+    info!("hello");
+    let records: Vec<Vec<Vec<u8>>> = sinks.iter().map(RingBufferSink::records).collect();
+    assert_eq!(
+        records.iter().filter(|records| !records.is_empty()).count(),
+        1
+    );
+    let record = records.into_iter().flatten().next().unwrap();
+    let record = String::from_utf8(record).unwrap();
+    assert!(
+        record.ends_with(
+            "lvl=\"INFO\" msg=\"hello\" target=\"double_init\" module=\"double_init\"\n"
+        ),
+        "{record}"
+    );
+    assert!(other.records().is_empty());
+}
@@ -0,0 +1,39 @@
+//! Tests for `Config::with_kvs_from_env`.
+
+use std::env;
+
+use log::{info, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+#[test]
+fn kvs_from_env() {
+    env::set_var("STD_LOGGER_TEST_POD_NAME", "my-pod-1");
+    env::set_var("STD_LOGGER_TEST_REGION", "eu west");
+    env::remove_var("STD_LOGGER_TEST_UNSET");
+
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .with_kvs_from_env(&[
+            "STD_LOGGER_TEST_POD_NAME",
+            "STD_LOGGER_TEST_UNSET",
+            "STD_LOGGER_TEST_REGION",
+        ])
+        .with_kvs(("app", "test"))
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    // Changing the variables after initialising has no effect.
+    env::set_var("STD_LOGGER_TEST_POD_NAME", "my-pod-2");
+
+    info!(user_id = 1; "hello");
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = String::from_utf8(records[0].clone()).unwrap();
+    assert!(
+        record.ends_with(
+            " user_id=1 app=\"test\" std_logger_test_pod_name=\"my-pod-1\" std_logger_test_region=\"eu west\"\n"
+        ),
+        "{record}"
+    );
+}
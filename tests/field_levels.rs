@@ -0,0 +1,30 @@
+//! Tests for `Config::with_field_level`.
+
+use log::{error, info, LevelFilter};
+use std_logger::{Config, Field, RingBufferSink};
+
+#[test]
+fn field_levels() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .with_field_level(Field::Timestamp, LevelFilter::Off)
+        .with_field_level(Field::Module, LevelFilter::Off)
+        .with_field_level(Field::File, LevelFilter::Error)
+        .init();
+
+    info!("hello");
+    let line = line!() + 1;
+    error!("oops");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        records[0],
+        b"lvl=\"INFO\" msg=\"hello\" target=\"field_levels\"\n"
+    );
+    let want = format!(
+        "lvl=\"ERROR\" msg=\"oops\" target=\"field_levels\" file=\"tests/field_levels.rs:{line}\"\n"
+    );
+    assert_eq!(String::from_utf8(records[1].clone()).unwrap(), want);
+}
@@ -0,0 +1,36 @@
+//! Tests for `Config::with_field_name` and `Config::with_field_order`.
+
+use log::{info, LevelFilter};
+use std_logger::{Config, Field, RingBufferSink};
+
+#[test]
+fn field_names_and_order() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .with_field_name(Field::Timestamp, "time")
+        .with_field_name(Field::Level, "severity")
+        .with_field_name(Field::Message, "message")
+        .with_field_order([Field::Level, Field::Message])
+        .init();
+
+    info!(user = 1; "hello");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = String::from_utf8(records[0].clone()).unwrap();
+    assert!(
+        record.starts_with("severity=\"INFO\" message=\"hello\" "),
+        "{record}"
+    );
+    assert!(
+        record.ends_with("target=\"fields\" module=\"fields\" user=1\n"),
+        "{record}"
+    );
+    assert_eq!(
+        record.contains(" time=\""),
+        cfg!(feature = "timestamp"),
+        "{record}"
+    );
+}
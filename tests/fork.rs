@@ -0,0 +1,65 @@
+//! Tests for logging after `fork(2)`.
+//!
+//! The logger writes to standard error, so the test runs itself in a child
+//! process and checks its output.
+
+#![cfg(unix)]
+
+use std::env;
+use std::process::Command;
+use std::time::Duration;
+
+use log::info;
+use std_logger::{BatchPolicy, Config};
+
+/// Environment variable set in the child process.
+const CHILD: &str = "STD_LOGGER_FORK_TEST_CHILD";
+
+#[test]
+fn fork() {
+    if env::var_os(CHILD).is_some() {
+        return fork_and_log();
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "fork", "--nocapture"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "info")
+        .env_remove("LOG_TARGET")
+        .output()
+        .expect("failed to run child process");
+    assert!(output.status.success(), "child failed: {output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    // The batched record is only written by the parent process.
+    assert_eq!(stderr.matches("msg=\"batched\"").count(), 1, "{stderr}");
+    assert_eq!(stderr.matches("msg=\"in child\"").count(), 1, "{stderr}");
+    assert_eq!(stderr.matches("msg=\"after fork\"").count(), 1, "{stderr}");
+}
+
+fn fork_and_log() {
+    Config::logfmt()
+        .with_call_location(false)
+        .with_batch(BatchPolicy::new(100, Duration::from_secs(3600)))
+        .init();
+
+    info!("batched");
+    // SAFETY: the child process only logs and exits.
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork failed"),
+        0 => {
+            info!("in child");
+            log::logger().flush();
+            // SAFETY: exiting without running the test harness' cleanup.
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            // SAFETY: `status` is valid for the duration of the call.
+            assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+            assert_eq!(status, 0);
+        }
+    }
+    info!("after fork");
+    log::logger().flush();
+}
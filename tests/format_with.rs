@@ -0,0 +1,30 @@
+//! Tests for `Config::format_with`.
+
+use std::io::Write;
+
+use log::{info, trace, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+#[test]
+fn format_with() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .format_with(|buf, record| {
+            let _ = writeln!(
+                buf,
+                "[{}] {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        })
+        .init();
+
+    trace!("not logged");
+    info!("hello {}", "world");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0], b"[INFO] format_with: hello world\n");
+}
@@ -0,0 +1,19 @@
+//! Tests for `init_for_tests`.
+
+use log::{debug, info, LevelFilter};
+
+#[test]
+fn init_for_tests() {
+    std_logger::init_for_tests();
+    // Can be called multiple times.
+    std_logger::init_for_tests();
+
+    if std::env::var_os("LOG").is_none()
+        && std::env::var_os("LOG_LEVEL").is_none()
+        && std::env::var_os("DEBUG").is_none()
+    {
+        assert_eq!(log::max_level(), LevelFilter::Trace);
+    }
+    info!("captured by the test harness");
+    debug!("also captured by the test harness");
+}
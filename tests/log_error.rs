@@ -0,0 +1,62 @@
+//! Tests for the `log_error!` macro.
+
+use std::error::Error;
+use std::{fmt, io};
+
+use log::LevelFilter;
+use std_logger::{log_error, value, Config, RingBufferSink};
+
+/// Error with an optional source.
+#[derive(Debug)]
+struct Wrapped(&'static str, Option<Box<dyn Error + 'static>>);
+
+impl fmt::Display for Wrapped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl Error for Wrapped {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.1.as_deref()
+    }
+}
+
+#[test]
+fn log_error() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    let cause = io::Error::new(io::ErrorKind::PermissionDenied, "permission denied");
+    let err = Wrapped(
+        "failed to start",
+        Some(Box::new(Wrapped("invalid config", Some(Box::new(cause))))),
+    );
+    assert_eq!(
+        value::error_chain(&err).to_string(),
+        "invalid config: permission denied"
+    );
+    log_error!(err, "oops");
+    let dyn_err: &dyn Error = &err;
+    log_error!(dyn_err, path = "app.toml", attempt = 2; "oops {}", 2);
+    log_error!(Wrapped("no source", None), "oops");
+
+    let records = sink.records();
+    let records = records
+        .iter()
+        .map(|record| {
+            let record = std::str::from_utf8(record).unwrap();
+            // Remove the timestamp, if any.
+            record[record.find("lvl=").unwrap()..].to_owned()
+        })
+        .collect::<Vec<_>>();
+    let want = [
+        "lvl=\"ERROR\" msg=\"oops\" target=\"log_error\" module=\"log_error\" error=\"failed to start\" error.chain=\"invalid config: permission denied\"\n",
+        "lvl=\"ERROR\" msg=\"oops 2\" target=\"log_error\" module=\"log_error\" error=\"failed to start\" error.chain=\"invalid config: permission denied\" path=\"app.toml\" attempt=2\n",
+        "lvl=\"ERROR\" msg=\"oops\" target=\"log_error\" module=\"log_error\" error=\"no source\" error.chain=\"\"\n",
+    ];
+    assert_eq!(records, want);
+}
@@ -0,0 +1,61 @@
+//! Tests for initialising the logger when another logger is set.
+
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use std_logger::{is_initialised, Config};
+
+/// Logger that isn't from this crate.
+struct OtherLogger;
+
+impl Log for OtherLogger {
+    fn enabled(&self, _: &Metadata) -> bool {
+        false
+    }
+
+    fn log(&self, _: &Record) {}
+
+    fn flush(&self) {}
+}
+
+/// Sink that sets `dropped` when it's dropped.
+struct DropSink {
+    dropped: Arc<AtomicBool>,
+}
+
+impl Write for DropSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for DropSink {
+    fn drop(&mut self) {
+        self.dropped.store(true, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn other_logger() {
+    log::set_logger(&OtherLogger).unwrap();
+
+    for _ in 0..2 {
+        let dropped = Arc::new(AtomicBool::new(false));
+        let sink = DropSink {
+            dropped: dropped.clone(),
+        };
+        let result = Config::logfmt()
+            .add_sink(sink, LevelFilter::Info)
+            .try_init();
+        assert!(result.is_err());
+        assert!(!is_initialised());
+        // The logger, including its sinks, isn't leaked.
+        assert!(dropped.load(Ordering::Relaxed));
+    }
+}
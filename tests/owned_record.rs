@@ -0,0 +1,49 @@
+//! Tests for `OwnedRecord`.
+
+#![cfg(feature = "timestamp")]
+
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{info, Level, LevelFilter, Record};
+use std_logger::{Config, OwnedRecord, OwnedValue, RingBufferSink};
+
+#[test]
+fn owned_record() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_deterministic_output()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    let key_values = [("user_id", 1)];
+    let record = OwnedRecord::from(
+        &Record::builder()
+            .args(format_args!("buffered"))
+            .level(Level::Info)
+            .target("owned_record")
+            .key_values(&key_values)
+            .build(),
+    );
+    // 2001-01-01T00:00:00Z.
+    let start = SystemTime::UNIX_EPOCH + Duration::from_secs(978_307_200);
+    assert_eq!(record.timestamp, Some(start));
+    assert_eq!(record.msg, "buffered");
+    assert_eq!(record.get("user_id"), Some(&OwnedValue::I64(1)));
+
+    info!("logged");
+    // Logged on another thread, using the original timestamp.
+    thread::spawn(move || record.log()).join().unwrap();
+
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(
+        &*records[0],
+        b"ts=\"2001-01-01T00:00:00.001000Z\" lvl=\"INFO\" msg=\"logged\" target=\"owned_record\" module=\"owned_record\"\n"
+    );
+    assert_eq!(
+        &*records[1],
+        b"ts=\"2001-01-01T00:00:00.000000Z\" lvl=\"INFO\" msg=\"buffered\" target=\"owned_record\" module=\"\" user_id=1\n"
+    );
+}
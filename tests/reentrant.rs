@@ -0,0 +1,71 @@
+//! Tests for logging while formatting a record, e.g. when a `fmt::Display`
+//! implementation panics or logs itself.
+
+#![cfg(feature = "log-panic")]
+
+use std::fmt;
+use std::panic;
+
+use log::{info, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+/// Panics when displayed.
+struct Panics;
+
+impl fmt::Display for Panics {
+    fn fmt(&self, _f: &mut fmt::Formatter) -> fmt::Result {
+        panic!("panic during formatting")
+    }
+}
+
+/// Logs itself when displayed.
+struct Recursive;
+
+impl fmt::Display for Recursive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        info!("{}", Recursive);
+        f.write_str("recursive")
+    }
+}
+
+#[test]
+fn reentrant() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    // The panic is logged using the second buffer, while the first is still
+    // borrowed by the record being formatted.
+    let result = panic::catch_unwind(|| info!("{}", Panics));
+    assert!(result.is_err());
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = String::from_utf8(records[0].clone()).unwrap();
+    assert!(record.contains("lvl=\"ERROR\""), "{record}");
+    assert!(record.contains("panic during formatting"), "{record}");
+    assert!(record.contains("target=\"panic\""), "{record}");
+
+    // Both buffers should be returned after the panic.
+    info!("after panic");
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    let record = String::from_utf8(records[1].clone()).unwrap();
+    assert!(
+        record.ends_with(
+            "lvl=\"INFO\" msg=\"after panic\" target=\"reentrant\" module=\"reentrant\"\n"
+        ),
+        "{record}"
+    );
+
+    // Logging itself only recurses as deep as the number of buffers, the
+    // innermost record is dropped.
+    info!("{}", Recursive);
+    let records = sink.records();
+    assert_eq!(records.len(), 4);
+    for record in &records[2..] {
+        let record = String::from_utf8(record.clone()).unwrap();
+        assert!(record.contains(" msg=\"recursive\" "), "{record}");
+    }
+}
@@ -0,0 +1,88 @@
+//! Tests for `Config::with_config_file` and `Config::with_config_reload`.
+
+#![cfg(feature = "reload")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use log::{error, Level, LevelFilter, Metadata};
+use std_logger::{reload_config, Config};
+
+/// Returns `true` if a record with `target` and `level` is enabled.
+fn enabled(target: &str, level: Level) -> bool {
+    let metadata = Metadata::builder().target(target).level(level).build();
+    log::logger().enabled(&metadata)
+}
+
+#[test]
+fn reload() {
+    let dir = std::env::temp_dir().join(format!("std_logger_reload_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("log.toml");
+    let sink_path = dir.join("app.log");
+    let _ = fs::remove_file(&sink_path);
+    let config = format!(
+        "level = \"warn\"\n\n[levels]\n\"app::db\" = \"debug\"\n\n[sinks]\n{:?} = \"error\"\n",
+        sink_path.display().to_string(),
+    );
+    fs::write(&path, config).unwrap();
+
+    Config::logfmt()
+        .with_call_location(false)
+        .with_config_file(&path)
+        .with_config_reload(Duration::from_millis(10))
+        .init();
+
+    assert!(enabled("app", Level::Warn));
+    assert!(!enabled("app", Level::Info));
+    assert!(enabled("app::db", Level::Debug));
+    assert!(enabled("app::db::pool", Level::Debug));
+    assert!(!enabled("app::db", Level::Trace));
+    assert!(!enabled("other", Level::Info));
+
+    error!("written to the file");
+    let got = fs::read_to_string(&sink_path).unwrap();
+    assert!(
+        got.ends_with(
+            "lvl=\"ERROR\" msg=\"written to the file\" target=\"reload\" module=\"reload\"\n"
+        ),
+        "{got}"
+    );
+
+    // Reload manually.
+    fs::write(&path, "level = \"info\"\n[levels]\napp = \"error\"\n").unwrap();
+    reload_config().unwrap();
+    assert!(enabled("other", Level::Info));
+    assert!(!enabled("app", Level::Warn));
+    assert!(!enabled("app::db", Level::Debug));
+
+    // Invalid files keep the current configuration.
+    fs::write(&path, "level = 'loud'\n").unwrap();
+    let err = reload_config().unwrap_err();
+    assert!(
+        err.to_string().contains("on line 1: invalid level `loud`"),
+        "{err}"
+    );
+    assert!(!enabled("app", Level::Warn));
+
+    // Reloaded by the watch thread.
+    fs::write(&path, "level = \"info\"\n[levels]\napp = \"trace\"\n").unwrap();
+    wait_for(|| enabled("app", Level::Trace));
+    assert!(enabled("app::db", Level::Trace));
+    assert!(!enabled("other", Level::Debug));
+    assert!(log::max_level() >= LevelFilter::Trace);
+
+    std_logger::shutdown();
+    let _ = fs::remove_dir_all(PathBuf::from(&dir));
+}
+
+/// Wait until `f` returns `true`, or panic after five seconds.
+fn wait_for<F: Fn() -> bool>(f: F) {
+    let start = Instant::now();
+    while !f() {
+        assert!(start.elapsed() < Duration::from_secs(5), "timed out");
+        sleep(Duration::from_millis(10));
+    }
+}
@@ -0,0 +1,31 @@
+//! Tests for the `format` setting of the configuration file, see
+//! `Config::with_config_file`.
+
+#![cfg(all(feature = "reload", feature = "timestamp"))]
+
+use std::fs;
+
+use log::{info, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+#[test]
+fn format_from_config_file() {
+    let dir = std::env::temp_dir().join(format!("std_logger_format_{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("log.toml");
+    fs::write(&path, "format = 'json' # Overwrites `Config::logfmt`.\n").unwrap();
+
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .with_deterministic_output()
+        .with_config_file(&path)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    info!("formatted as JSON");
+    let want = "{\"timestamp\":\"2001-01-01T00:00:00.000000Z\",\"level\":\"INFO\",\"message\":\"formatted as JSON\",\"target\":\"reload_format\",\"module\":\"reload_format\"}\n";
+    assert_eq!(sink.records(), [want.as_bytes()]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
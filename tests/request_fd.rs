@@ -0,0 +1,33 @@
+//! Tests for `Config::with_request_fd`.
+
+#![cfg(unix)]
+
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::os::unix::io::FromRawFd;
+
+use std_logger::{request, Config};
+
+#[test]
+fn request_fd() {
+    let mut fds = [0; 2];
+    // SAFETY: `fds` is valid for the duration of the call.
+    assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+    // SAFETY: we own the read end of the pipe.
+    let mut reader = BufReader::new(unsafe { File::from_raw_fd(fds[0]) });
+
+    Config::logfmt()
+        .with_call_location(false)
+        .with_request_fd(fds[1])
+        .init();
+    request!(status = 200; "GET /");
+
+    let mut line = String::new();
+    let _ = reader.read_line(&mut line).unwrap();
+    assert!(
+        line.ends_with(
+            "lvl=\"INFO\" msg=\"GET /\" target=\"request\" module=\"request_fd\" status=200\n"
+        ),
+        "{line}"
+    );
+}
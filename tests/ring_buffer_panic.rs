@@ -0,0 +1,61 @@
+//! Tests for `RingBufferSink::dump_on_panic`.
+//!
+//! The records are dumped to standard error, so the test runs itself in a
+//! child process and checks its output.
+
+#![cfg(all(unix, feature = "log-panic"))]
+
+use std::env;
+use std::process::Command;
+
+use log::{debug, LevelFilter};
+use std_logger::{Config, RingBufferSink};
+
+/// Environment variable set in the child process.
+const CHILD: &str = "STD_LOGGER_RING_BUFFER_PANIC_TEST_CHILD";
+
+#[test]
+fn dump_on_panic() {
+    if env::var_os(CHILD).is_some() {
+        return log_and_panic();
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "dump_on_panic", "--nocapture"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "info")
+        .env_remove("LOG_TARGET")
+        .env_remove("RUST_BACKTRACE")
+        .output()
+        .expect("failed to run child process");
+    assert!(!output.status.success(), "child didn't panic: {output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    // Ignore the output of the test harness, the timestamps and the location
+    // of the panic.
+    let lines = stderr
+        .lines()
+        .filter_map(|line| line.find("lvl=").map(|i| &line[i..]))
+        .map(|line| line.split(" target=").next().unwrap())
+        .map(|line| line.split(" panicked at").next().unwrap())
+        .collect::<Vec<_>>();
+    // The recorded records are written before the panic, which is written
+    // only once.
+    let want = [
+        "lvl=\"DEBUG\" msg=\"first\"",
+        "lvl=\"DEBUG\" msg=\"second\"",
+        "lvl=\"ERROR\" msg=\"thread 'dump_on_panic'",
+    ];
+    assert_eq!(lines, want, "{stderr}");
+}
+
+fn log_and_panic() {
+    let recorder = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(recorder.dump_on_panic(), LevelFilter::Trace)
+        .init();
+
+    debug!("first");
+    debug!("second");
+    panic!("oops");
+}
@@ -0,0 +1,82 @@
+//! Tests for `Config::route_target`.
+//!
+//! The logger writes to standard out and error, so the test runs itself in a
+//! child process and checks its output.
+
+use std::env;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::process::Command;
+
+use log::info;
+use std_logger::{request, Config, Route, REQUEST_TARGET};
+
+/// Environment variable set in the child process, to the path of the file.
+const CHILD: &str = "STD_LOGGER_ROUTE_TEST_CHILD";
+
+#[test]
+fn route_target() {
+    if let Some(path) = env::var_os(CHILD) {
+        return log_records(path.into());
+    }
+
+    let path = env::temp_dir().join(format!("std_logger_route_{}.log", std::process::id()));
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "route_target", "--nocapture"])
+        .env(CHILD, &path)
+        .env("LOG_LEVEL", "info")
+        // Routed targets are always logged.
+        .env("LOG_TARGET", "route")
+        .output()
+        .expect("failed to run child process");
+    assert!(output.status.success(), "child failed: {output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let file = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let lines = |output: &str| -> Vec<String> {
+        output
+            .lines()
+            .filter(|line| line.contains("lvl="))
+            .map(|line| line[line.find("lvl=").unwrap()..].to_owned())
+            .collect()
+    };
+    assert_eq!(
+        lines(&stdout),
+        [
+            "lvl=\"INFO\" msg=\"grpc call\" target=\"grpc-access\" module=\"route\"",
+            "lvl=\"INFO\" msg=\"http request\" target=\"request\" module=\"route\"",
+        ],
+        "{stdout}"
+    );
+    assert_eq!(
+        lines(&stderr),
+        ["lvl=\"INFO\" msg=\"other\" target=\"route\" module=\"route\""],
+        "{stderr}"
+    );
+    assert_eq!(
+        lines(&file),
+        ["lvl=\"INFO\" msg=\"admin request\" target=\"admin-access\" module=\"route\""],
+        "{file}"
+    );
+}
+
+fn log_records(path: PathBuf) {
+    let file = File::create(path).unwrap();
+    Config::logfmt()
+        .with_call_location(false)
+        .route_target("grpc-access", Route::Stderr)
+        // Replaces the previous route.
+        .route_target("grpc-access", Route::Stdout)
+        .route_target("admin-access", Route::File(file))
+        .route_target(REQUEST_TARGET, Route::Stdout)
+        .init();
+
+    info!(target: "grpc-access", "grpc call");
+    info!(target: "admin-access", "admin request");
+    request!("http request");
+    info!("other");
+    // Not routed or in `LOG_TARGET`.
+    info!(target: "not-logged", "not logged");
+}
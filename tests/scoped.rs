@@ -0,0 +1,30 @@
+//! Tests for `ScopedLogger`.
+
+use log::{info, LevelFilter};
+use std_logger::{Config, RingBufferSink, ScopedLogger};
+
+static INGEST: ScopedLogger = ScopedLogger::new("ingest");
+
+#[test]
+fn scoped_logger() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    info!(logger: INGEST, records = 100; "processed batch");
+    info!("not scoped");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 2);
+    let record = String::from_utf8(records[0].clone()).unwrap();
+    assert!(
+        record.ends_with(
+            "lvl=\"INFO\" msg=\"processed batch\" target=\"scoped\" module=\"scoped\" component=\"ingest\" records=100\n"
+        ),
+        "{record}"
+    );
+    let record = String::from_utf8(records[1].clone()).unwrap();
+    assert!(!record.contains("component"), "{record}");
+}
@@ -0,0 +1,134 @@
+//! Tests for the *service* feature.
+//!
+//! The logger can only be initialised once per process and logs to standard
+//! out/error, so the test runs itself in a child process and checks its
+//! output.
+
+#![cfg(feature = "service")]
+
+use std::env;
+use std::process::Command;
+
+use log::{info, warn};
+use std_logger::service::{self, Request, RequestId};
+
+/// Environment variable set in the child process.
+const CHILD: &str = "STD_LOGGER_SERVICE_TEST_CHILD";
+
+#[test]
+fn service() {
+    if env::var_os(CHILD).is_some() {
+        return handle_requests();
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "service", "--nocapture", "--test-threads", "1"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "info")
+        .env_remove("LOG_TARGET")
+        .output()
+        .expect("failed to run child process");
+    assert!(output.status.success(), "child failed: {output:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    // Access logs, all with a unique request id.
+    let requests: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.contains("target=\"request\""))
+        .collect();
+    assert_eq!(requests.len(), 5, "stdout: {stdout}");
+    let ids: Vec<&str> = requests
+        .iter()
+        .map(|line| value(line, "request_id"))
+        .collect();
+    for (i, id) in ids.iter().enumerate() {
+        assert!(id.parse::<u64>().is_ok(), "invalid request id: {id}");
+        assert!(!ids[i + 1..].contains(id), "duplicate request id: {id}");
+    }
+    let want = [
+        ("\"GET /\"", "\"/\"", "200"),
+        ("\"GET /missing\"", "\"/missing\"", "404"),
+        ("\"GET /panic\"", "\"/panic\"", "500"),
+        ("\"GET /\"", "\"/\"", "200"),
+        ("\"POST /dropped\"", "\"/dropped\"", "500"),
+    ];
+    for (line, (msg, path, status)) in requests.iter().zip(want) {
+        assert_eq!(value(line, "msg"), msg, "{line}");
+        assert_eq!(value(line, "path"), path, "{line}");
+        assert_eq!(value(line, "status"), status, "{line}");
+        assert!(line.contains(" took=\""), "{line}");
+    }
+
+    // Records logged outside of a request don't have a request id.
+    let started = find(&stderr, "msg=\"started\"");
+    assert!(!started.contains("request_id="), "{started}");
+
+    // Records logged while handling a request have its id.
+    let not_found = find(&stderr, "msg=\"page not found\"");
+    assert_eq!(value(not_found, "request_id"), ids[1]);
+    #[cfg(feature = "log-panic")]
+    {
+        let panic = find(&stderr, "target=\"panic\"");
+        assert!(panic.contains("oops"), "{panic}");
+        assert_eq!(value(panic, "request_id"), ids[2]);
+    }
+    let nested = find(&stderr, "msg=\"after nested\"");
+    assert_eq!(value(nested, "request_id"), ids[4]);
+}
+
+/// Run in the child process.
+fn handle_requests() {
+    service::init();
+
+    info!("started");
+    for (path, want) in [("/", 200), ("/missing", 404), ("/panic", 500)] {
+        let status = service::handle("GET", path, |request| {
+            assert_eq!(RequestId::current(), Some(request.id()));
+            match path {
+                "/" => 200,
+                "/panic" => panic!("oops"),
+                _ => {
+                    warn!("page not found");
+                    404
+                }
+            }
+        });
+        assert_eq!(status, want);
+    }
+    assert_eq!(RequestId::current(), None);
+
+    // Nested requests restore the outer request, requests that are dropped
+    // are logged with status code 500.
+    let outer = Request::start("POST", "/dropped");
+    let inner = Request::start("GET", "/");
+    assert_eq!(RequestId::current(), Some(inner.id()));
+    inner.finish(200);
+    assert_eq!(RequestId::current(), Some(outer.id()));
+    warn!("after nested");
+    drop(outer);
+    assert_eq!(RequestId::current(), None);
+}
+
+/// Find the line in `output` containing `pattern`.
+fn find<'a>(output: &'a str, pattern: &str) -> &'a str {
+    output
+        .lines()
+        .find(|line| line.contains(pattern))
+        .unwrap_or_else(|| panic!("missing `{pattern}` in output: {output}"))
+}
+
+/// Returns the value of `key` in the logfmt `line`.
+fn value<'a>(line: &'a str, key: &str) -> &'a str {
+    let start = line
+        .find(&format!(" {key}="))
+        .unwrap_or_else(|| panic!("missing key `{key}` in: {line}"))
+        + key.len()
+        + 2;
+    let value = &line[start..];
+    if let Some(quoted) = value.strip_prefix('"') {
+        &value[..quoted.find('"').unwrap() + 2]
+    } else {
+        value.split(' ').next().unwrap()
+    }
+}
@@ -0,0 +1,56 @@
+//! Tests for the *test* feature.
+
+#![cfg(feature = "test")]
+
+use std::thread;
+
+use log::{debug, info, Level};
+use std_logger::test::{self, Value};
+
+#[test]
+fn capture() {
+    let capture = test::capture();
+    info!(user_id = 123; "logged in");
+    debug!("checking permissions");
+
+    let records = capture.records();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].level, Level::Info);
+    assert_eq!(records[0].msg, "logged in");
+    assert_eq!(records[0].target, module_path!());
+    assert_eq!(records[0].key_values["user_id"], Value::Int(123));
+    assert_eq!(records[1].level, Level::Debug);
+    assert_eq!(records[1].msg, "checking permissions");
+
+    capture.clear();
+    assert!(capture.records().is_empty());
+}
+
+#[test]
+fn capture_per_thread() {
+    let capture = test::capture();
+    info!("main thread");
+    thread::spawn(|| {
+        let capture = test::capture();
+        info!("other thread");
+        let records = capture.records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].msg, "other thread");
+    })
+    .join()
+    .unwrap();
+
+    let records = capture.records();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].msg, "main thread");
+}
+
+#[test]
+fn not_captured_after_drop() {
+    let capture = test::capture();
+    drop(capture);
+    info!("not captured");
+
+    let capture = test::capture();
+    assert!(capture.records().is_empty());
+}
@@ -0,0 +1,27 @@
+//! Tests for the *test* feature in combination with the `LOG_TARGET`
+//! environment variable.
+
+#![cfg(feature = "test")]
+
+use std::env;
+
+use log::{debug, info};
+use std_logger::test;
+
+#[test]
+fn capture_filtered_targets() {
+    env::set_var("LOG_TARGET", "test_capture_targets");
+    let capture = test::capture();
+    info!("captured");
+    debug!("captured at debug level");
+    info!(target: "other", "not captured");
+    debug!(target: "test_capture_targets::module", "captured sub-module");
+    assert!(!log::logger().enabled(&log::Metadata::builder().target("other").build()));
+
+    let records = capture.records();
+    let got = records.iter().map(|r| r.msg.as_str()).collect::<Vec<_>>();
+    assert_eq!(
+        got,
+        ["captured", "captured at debug level", "captured sub-module"]
+    );
+}
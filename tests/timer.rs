@@ -0,0 +1,45 @@
+//! Tests for the `timer!` macro.
+
+use log::{Level, LevelFilter};
+use std_logger::{timer, Config, RingBufferSink};
+
+#[test]
+fn timer() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_deterministic_output()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .init();
+
+    let id = 123;
+    {
+        let outer = timer!("outer");
+        assert_eq!(outer.name(), "outer");
+        {
+            let _t = timer!("inner", query_id = id, kind = "select",);
+            let _t = timer!(level: Level::Debug, "filtered");
+        }
+        let _t = timer!(level: Level::Warn, "second");
+        assert!(sink.records().len() == 1);
+    }
+    let _t = timer!("after");
+    drop(_t);
+
+    let records = sink.records();
+    let records = records
+        .iter()
+        .map(|record| {
+            let record = std::str::from_utf8(record).unwrap();
+            // Remove the timestamp, if any.
+            record[record.find("lvl=").unwrap()..].to_owned()
+        })
+        .collect::<Vec<_>>();
+    let want = [
+        "lvl=\"INFO\" msg=\"inner\" target=\"timer\" module=\"timer\" query_id=123 kind=\"select\" parent=\"outer\" elapsed=\"0ns\"\n",
+        "lvl=\"WARN\" msg=\"second\" target=\"timer\" module=\"timer\" parent=\"outer\" elapsed=\"0ns\"\n",
+        "lvl=\"INFO\" msg=\"outer\" target=\"timer\" module=\"timer\" elapsed=\"0ns\"\n",
+        "lvl=\"INFO\" msg=\"after\" target=\"timer\" module=\"timer\" elapsed=\"0ns\"\n",
+    ];
+    assert_eq!(records, want);
+}
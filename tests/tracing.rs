@@ -0,0 +1,28 @@
+//! Tests for the *tracing* feature.
+
+#![cfg(all(feature = "tracing", feature = "test"))]
+
+use log::Level;
+use std_logger::test::{self, Value};
+use tracing_subscriber::layer::SubscriberExt;
+
+#[test]
+fn tracing_layer() {
+    let capture = test::capture();
+    let subscriber = tracing_subscriber::registry().with(std_logger::tracing::Layer::new());
+    tracing_core::dispatcher::with_default(&subscriber.into(), || {
+        tracing::info_span!("request", request_id = 123).in_scope(|| {
+            tracing::warn!(status = 404, path = "/", "page {} not found", 1);
+        });
+    });
+
+    let records = capture.records();
+    assert_eq!(records.len(), 1);
+    let record = &records[0];
+    assert_eq!(record.level, Level::Warn);
+    assert_eq!(record.msg, "page 1 not found");
+    assert_eq!(record.target, module_path!());
+    assert_eq!(record.key_values["request_id"], Value::Int(123));
+    assert_eq!(record.key_values["status"], Value::Int(404));
+    assert_eq!(record.key_values["path"], Value::String("/".into()));
+}
@@ -0,0 +1,38 @@
+//! Tests for `Config::add_transform`.
+
+use log::{info, LevelFilter};
+use std_logger::{Action, Config, OwnedRecord, OwnedValue, RingBufferSink};
+
+#[test]
+fn transforms() {
+    let sink = RingBufferSink::new(10);
+    Config::logfmt()
+        .with_call_location(false)
+        .add_sink(sink.clone(), LevelFilter::Info)
+        .add_transform(|record: &mut OwnedRecord| {
+            record.insert("host", "web-1");
+            Action::Keep
+        })
+        .add_transform(|record: &mut OwnedRecord| {
+            if record.get("path") == Some(&OwnedValue::from("/health")) {
+                return Action::Drop;
+            }
+            let _ = record.remove("secret");
+            record.msg.make_ascii_uppercase();
+            Action::Keep
+        })
+        .init();
+
+    info!(path = "/health"; "request");
+    info!(path = "/users", secret = "abc", status = 200; "request");
+
+    let records = sink.records();
+    assert_eq!(records.len(), 1);
+    let record = String::from_utf8(records[0].clone()).unwrap();
+    assert!(
+        record.ends_with(
+            "lvl=\"INFO\" msg=\"REQUEST\" target=\"transform\" module=\"transform\" path=\"/users\" status=200 host=\"web-1\"\n"
+        ),
+        "{record}"
+    );
+}
@@ -0,0 +1,65 @@
+//! Tests for `Config::with_unlocked_stderr`.
+//!
+//! The logger writes directly to standard error, so the test runs itself in a
+//! child process and checks its output.
+
+use std::env;
+use std::process::Command;
+use std::thread;
+
+use log::info;
+use std_logger::Config;
+
+/// Environment variable set in the child process.
+const CHILD: &str = "STD_LOGGER_UNLOCKED_STDERR_TEST_CHILD";
+
+const THREADS: usize = 8;
+const RECORDS: usize = 1_000;
+
+#[test]
+fn unlocked_stderr() {
+    if env::var_os(CHILD).is_some() {
+        return log_records();
+    }
+
+    let output = Command::new(env::current_exe().unwrap())
+        .args(["--exact", "unlocked_stderr", "--nocapture"])
+        .env(CHILD, "1")
+        .env("LOG_LEVEL", "info")
+        .env_remove("LOG_TARGET")
+        .output()
+        .expect("failed to run child process");
+    assert!(output.status.success(), "child failed: {output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    let records: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.contains("target=\"unlocked_stderr\""))
+        .collect();
+    assert_eq!(records.len(), THREADS * RECORDS, "stderr: {stderr}");
+    for line in records {
+        // Records must not be interleaved.
+        assert_eq!(line.matches("lvl=").count(), 1, "{line}");
+        assert!(
+            line.contains("lvl=\"INFO\" msg=\"unlocked record\" target=\"unlocked_stderr\""),
+            "{line}"
+        );
+    }
+}
+
+fn log_records() {
+    Config::logfmt()
+        .with_call_location(false)
+        .with_unlocked_stderr(true)
+        .init();
+
+    thread::scope(|s| {
+        for n in 0..THREADS {
+            let _ = s.spawn(move || {
+                for i in 0..RECORDS {
+                    info!(thread = n, iteration = i; "unlocked record");
+                }
+            });
+        }
+    });
+}
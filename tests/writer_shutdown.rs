@@ -0,0 +1,73 @@
+//! Tests for stopping the writer thread using `shutdown`, see
+//! `Config::with_writer_thread`.
+
+use std::io::{self, IoSlice, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use log::{info, LevelFilter};
+use std_logger::Config;
+
+/// Number of records logged before shutting down.
+const RECORDS: usize = 20;
+
+/// Slow sink collecting the records and the name of the thread that wrote
+/// them.
+struct SlowSink {
+    written: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Write for SlowSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        thread::sleep(Duration::from_millis(5));
+        let thread = thread::current().name().unwrap_or_default().to_owned();
+        let record = bufs.iter().map(|buf| std::str::from_utf8(buf).unwrap());
+        let record = record.collect::<String>();
+        let len = record.len();
+        self.written.lock().unwrap().push((thread, record));
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writer_shutdown() {
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let sink = SlowSink {
+        written: written.clone(),
+    };
+    Config::logfmt()
+        .with_call_location(false)
+        .with_deterministic_output()
+        .with_writer_thread(RECORDS)
+        .add_sink(sink, LevelFilter::Info)
+        .init();
+
+    for n in 0..RECORDS {
+        info!("record {n}");
+    }
+    // Most records are still queued, but all are written once this returns.
+    std_logger::shutdown();
+    assert_eq!(written.lock().unwrap().len(), RECORDS);
+
+    // The writer thread is stopped, so this is written directly.
+    info!("after shutdown");
+
+    let written = written.lock().unwrap().clone();
+    assert_eq!(std_logger::dropped_records(), 0);
+    for (n, (thread, record)) in written.iter().take(RECORDS).enumerate() {
+        assert_eq!(thread, "std-logger-writer");
+        assert!(record.contains(&format!("msg=\"record {n}\"")), "{record}");
+    }
+    let (thread, record) = &written[RECORDS];
+    assert_eq!(thread, "writer_shutdown");
+    assert!(record.contains("msg=\"after shutdown\""), "{record}");
+}
@@ -0,0 +1,98 @@
+//! Tests for writing records on a dedicated thread, see
+//! `Config::with_writer_thread`.
+
+#![cfg(feature = "timestamp")]
+
+use std::io::{self, IoSlice, Write};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use log::{info, LevelFilter};
+use std_logger::{audit, Config};
+
+/// Sink that blocks while `gate` is locked, collecting the records and the
+/// name of the thread that wrote them.
+struct BlockingSink {
+    gate: Arc<Mutex<()>>,
+    entered: Sender<()>,
+    written: Arc<Mutex<Vec<(String, String)>>>,
+}
+
+impl Write for BlockingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[IoSlice::new(buf)])
+    }
+
+    fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+        self.entered.send(()).unwrap();
+        let _gate = self.gate.lock().unwrap();
+        let thread = thread::current().name().unwrap_or_default().to_owned();
+        let record = bufs.iter().map(|buf| std::str::from_utf8(buf).unwrap());
+        let record = record.collect::<String>();
+        let len = record.len();
+        self.written.lock().unwrap().push((thread, record));
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn writer_thread() {
+    let gate = Arc::new(Mutex::new(()));
+    let (entered_sender, entered) = mpsc::channel();
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let sink = BlockingSink {
+        gate: gate.clone(),
+        entered: entered_sender,
+        written: written.clone(),
+    };
+    Config::logfmt()
+        .with_call_location(false)
+        .with_deterministic_output()
+        .with_writer_thread(1)
+        .add_sink(sink, LevelFilter::Info)
+        .init();
+
+    let blocked = gate.lock().unwrap();
+    info!("one");
+    // Wait until the writer thread is blocked writing the first record.
+    entered.recv().unwrap();
+    // Queued.
+    info!("two");
+    // Queue is full, dropped without blocking.
+    info!("three");
+    assert_eq!(std_logger::dropped_records(), 1);
+    drop(blocked);
+
+    // Waits until the queued records are written.
+    log::logger().flush();
+    // Audit records are written directly.
+    audit!("audited");
+
+    // Clone the records so the lock isn't held if the assertion fails, which
+    // logs the panic.
+    let written = written.lock().unwrap().clone();
+    let got = written
+        .iter()
+        .map(|(thread, record)| (thread.as_str(), record.as_str()))
+        .collect::<Vec<_>>();
+    let want = [
+        (
+            "std-logger-writer",
+            "ts=\"2001-01-01T00:00:00.000000Z\" lvl=\"INFO\" msg=\"one\" target=\"writer_thread\" module=\"writer_thread\"\n",
+        ),
+        (
+            "std-logger-writer",
+            "ts=\"2001-01-01T00:00:00.001000Z\" lvl=\"INFO\" msg=\"two\" target=\"writer_thread\" module=\"writer_thread\"\n",
+        ),
+        (
+            "writer_thread",
+            "ts=\"2001-01-01T00:00:00.003000Z\" lvl=\"INFO\" msg=\"audited\" target=\"audit\" module=\"writer_thread\"\n",
+        ),
+    ];
+    assert_eq!(got, want);
+}